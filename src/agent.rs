@@ -0,0 +1,692 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::json::{self, Value};
+use crate::{engine_error, ssh};
+use ipchk::{
+    Builtin, Deadline, Engine, Error, Outcome, Payload, PortState, Privileges, Probe, ProbeOptions,
+    ProbeOutcome, ProbeType, Reply, Rtt, Scan, Stats, Target,
+};
+
+/* -------------------- distributed sweeps: ipchk agent and --via -------------------- */
+
+// A sweep with --via keeps one connection per agent and hands each target
+// to one of them, which probes it from where it sits. --via-ssh does the
+// same over `ssh`, with `ipchk agent --stdio` at the far end. Both ends
+// speak JSON Lines:
+//
+//   -> {"agent":1,"probe":{..}}                   the probe options, once
+//   <- {"ready":"native"} or {"error":".."}       after the agent's preflight
+//   -> {"id":7,"addr":"10.0.0.5"}                 per target, many in flight
+//   <- {"id":7,"outcome":{..}} or {"id":7,"error":".."}   as each finishes
+
+pub const DEFAULT_PORT: u16 = 9901;
+const VERSION: &str = "1";
+
+// On top of the probe budget, for the round trip to the agent
+const GRACE: Duration = Duration::from_secs(5);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// "10.0.0.2", "10.0.0.2:9000", "agent.lan" or "[fd00::2]:9000"
+pub fn address(spec: &str) -> Result<SocketAddr, String> {
+    address_on(spec, DEFAULT_PORT)
+}
+
+// The same, with `port` where the spec has none
+pub fn address_on(spec: &str, port: u16) -> Result<SocketAddr, String> {
+    let with_port = match spec.parse::<IpAddr>() {
+        Ok(ip) => SocketAddr::from((ip, port)).to_string(),
+        Err(_) if spec.contains(':') => spec.to_string(),
+        Err(_) => format!("{spec}:{port}"),
+    };
+    with_port
+        .to_socket_addrs()
+        .map_err(|e| format!("{spec}: {e}"))?
+        .next()
+        .ok_or_else(|| format!("{spec}: no address"))
+}
+
+/* ---- agent side ---- */
+
+// Serves coordinators until killed, each connection on a thread of its own
+pub fn listen(spec: &str) -> Result<(), String> {
+    let addr = address(spec)?;
+    let listener = TcpListener::bind(addr).map_err(|e| format!("agent: {addr}: {e}"))?;
+    eprintln!("ipchk agent: listening on {addr}");
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map_or_else(|_| "?".to_string(), |a| a.to_string());
+            let Ok(writer) = stream.try_clone() else {
+                return;
+            };
+            match serve(BufReader::new(stream), writer) {
+                Ok(n) => eprintln!(
+                    "ipchk agent: {peer}: probed {n} target{}",
+                    if n == 1 { "" } else { "s" }
+                ),
+                Err(e) => eprintln!("ipchk agent: {peer}: {e}"),
+            }
+        });
+    }
+    Ok(())
+}
+
+// One coordinator on stdin and stdout, as started by --via-ssh
+pub fn stdio() -> Result<(), String> {
+    serve(io::stdin().lock(), io::stdout()).map(|_| ())
+}
+
+// One coordinator's session; returns how many targets it sent
+fn serve(input: impl BufRead, output: impl Write + Send) -> Result<usize, String> {
+    let out = Mutex::new(output);
+    let send = |v: Value| {
+        let mut w = out.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(w, "{}", v.to_json()).and_then(|()| w.flush());
+    };
+    let refuse = |e: String| {
+        send(Value::Object(vec![("error".into(), Value::Str(e.clone()))]));
+        e
+    };
+    let mut lines = input.lines();
+    let hello = match lines.next() {
+        Some(Ok(line)) => parse(&line).map_err(refuse)?,
+        _ => return Err("no request".into()),
+    };
+    if hello.get("agent").map(Value::text).as_deref() != Some(VERSION) {
+        return Err(refuse(format!(
+            "not an ipchk agent request (protocol {VERSION})"
+        )));
+    }
+    let opts = options(hello.get("probe").unwrap_or(&Value::Null)).map_err(refuse)?;
+    let probe = Builtin::new(opts.clone());
+    probe
+        .preflight()
+        .map_err(|e| refuse(engine_error(&e, &opts)))?;
+    let engine = match opts.engine {
+        Engine::Ping => "ping",
+        Engine::Native => "native",
+    };
+    send(Value::Object(vec![(
+        "ready".into(),
+        Value::Str(engine.into()),
+    )]));
+
+    let budget = Builtin::budget(&opts);
+    let mut probed = 0;
+    thread::scope(|s| {
+        for line in lines {
+            let Ok(line) = line else {
+                break;
+            };
+            let Ok(req) = parse(&line) else {
+                continue;
+            };
+            let (Some(id), Some(addr)) = (
+                req.get("id").cloned(),
+                req.get("addr")
+                    .and_then(|a| a.text().parse::<Ipv4Addr>().ok()),
+            ) else {
+                continue;
+            };
+            probed += 1;
+            let (probe, send) = (&probe, &send);
+            s.spawn(move || {
+                let answer = match probe.probe(&Target::new(addr), Deadline::after(budget)) {
+                    Ok(o) => ("outcome".to_string(), encode(&o)),
+                    Err(e) => ("error".to_string(), Value::Str(e.to_string())),
+                };
+                send(Value::Object(vec![("id".into(), id), answer]));
+            });
+        }
+    });
+    Ok(probed)
+}
+
+fn parse(line: &str) -> Result<Value, String> {
+    json::parse_all(line)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "empty request".into())
+}
+
+fn us(d: Duration) -> Value {
+    Value::Num(d.as_micros().to_string())
+}
+
+fn num(v: impl ToString) -> Value {
+    Value::Num(v.to_string())
+}
+
+fn opt(v: Option<Value>) -> Value {
+    v.unwrap_or(Value::Null)
+}
+
+// The request's probe options. Without an engine the agent picks its own,
+// as a local sweep would.
+fn options(v: &Value) -> Result<ProbeOptions, String> {
+    let ms = |k: &str| {
+        v.get(k)
+            .filter(|x| **x != Value::Null)
+            .map(|x| x.text().parse::<u64>().map(Duration::from_millis))
+            .transpose()
+            .map_err(|e| format!("probe.{k}: {e}"))
+    };
+    let text = |k: &str| v.get(k).map(Value::text).unwrap_or_default();
+    let engine = match text("engine").as_str() {
+        "" => Privileges::detect().best_engine(),
+        e => e.parse()?,
+    };
+    let payload = match v.get("payload") {
+        Some(p @ Value::Object(_)) => {
+            let hex = p.get("pattern").map(Value::text).unwrap_or_default();
+            Some(Payload {
+                pattern: match hex.as_str() {
+                    "" => Vec::new(),
+                    hex => ipchk::parse_hex(hex).map_err(|e| format!("probe.payload: {e}"))?,
+                },
+                size: p
+                    .get("size")
+                    .and_then(|s| s.text().parse().ok())
+                    .unwrap_or(Payload::DEFAULT_SIZE),
+            })
+        }
+        _ => None,
+    };
+    let ports = match v.get("ports") {
+        Some(Value::Array(ports)) => ports
+            .iter()
+            .map(|p| {
+                p.text()
+                    .parse::<u16>()
+                    .map_err(|e| format!("probe.ports: {e}"))
+            })
+            .collect::<Result<_, _>>()?,
+        _ => Vec::new(),
+    };
+    Ok(ProbeOptions {
+        timeout: ms("timeout_ms")?.ok_or("probe.timeout_ms: missing")?,
+        count: text("count")
+            .parse()
+            .map_err(|e| format!("probe.count: {e}"))?,
+        interval: ms("interval_ms")?,
+        engine,
+        probe: text("type").parse()?,
+        payload,
+        df: text("df") == "true",
+        ports,
+        scan: match text("scan").as_str() {
+            "" => Scan::default(),
+            s => s.parse()?,
+        },
+        ping_args: Vec::new(),
+        proxy: None,
+        connect_timeout: ms("connect_timeout_ms")?,
+        response_timeout: None,
+        // Which ping is the agent host's business
+        ping_path: std::env::var_os("IPCHK_PING").map(std::path::PathBuf::from),
+    })
+}
+
+fn encode_options(o: &ProbeOptions, engine: Option<Engine>) -> Value {
+    let probe = match o.probe {
+        ProbeType::Echo => "echo",
+        ProbeType::Timestamp => "icmp-timestamp",
+        ProbeType::Tcp => "tcp",
+    };
+    let scan = match o.scan {
+        Scan::Connect => "connect",
+        Scan::Syn => "syn",
+    };
+    let engine = engine.map(|e| {
+        Value::Str(match e {
+            Engine::Ping => "ping".into(),
+            Engine::Native => "native".into(),
+        })
+    });
+    let payload = o.payload.as_ref().map(|p| {
+        let hex: String = p.pattern.iter().map(|b| format!("{b:02x}")).collect();
+        Value::Object(vec![
+            ("pattern".into(), Value::Str(hex)),
+            ("size".into(), num(p.size)),
+        ])
+    });
+    Value::Object(vec![
+        ("type".into(), Value::Str(probe.into())),
+        ("timeout_ms".into(), num(o.timeout.as_millis())),
+        ("count".into(), num(o.count)),
+        (
+            "interval_ms".into(),
+            opt(o.interval.map(|i| num(i.as_millis()))),
+        ),
+        ("engine".into(), opt(engine)),
+        ("payload".into(), opt(payload)),
+        ("df".into(), Value::Bool(o.df)),
+        (
+            "ports".into(),
+            Value::Array(o.ports.iter().map(num).collect()),
+        ),
+        ("scan".into(), Value::Str(scan.into())),
+        (
+            "connect_timeout_ms".into(),
+            opt(o.connect_timeout.map(|t| num(t.as_millis()))),
+        ),
+    ])
+}
+
+// Durations in microseconds, send times in microseconds since the epoch
+fn encode(o: &Outcome) -> Value {
+    let replies = o
+        .replies
+        .iter()
+        .map(|r| {
+            let sent = r
+                .sent_at
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(us);
+            Value::Object(vec![
+                ("seq".into(), num(r.seq)),
+                ("bytes".into(), num(r.bytes)),
+                ("rtt_us".into(), us(r.rtt)),
+                ("sent_us".into(), opt(sent)),
+                ("clock_offset_ms".into(), opt(r.clock_offset_ms.map(num))),
+            ])
+        })
+        .collect();
+    let rtt = o.stats.rtt.map(|r| {
+        Value::Object(vec![
+            ("min_us".into(), us(r.min)),
+            ("avg_us".into(), us(r.avg)),
+            ("max_us".into(), us(r.max)),
+            ("mdev_us".into(), us(r.mdev)),
+        ])
+    });
+    let ports = o
+        .ports
+        .iter()
+        .map(|(port, state)| Value::Array(vec![num(port), Value::Str(state.to_string())]))
+        .collect();
+    Value::Object(vec![
+        ("up".into(), Value::Bool(o.up)),
+        ("sent".into(), num(o.sent)),
+        ("replies".into(), Value::Array(replies)),
+        ("transmitted".into(), num(o.stats.transmitted)),
+        ("received".into(), num(o.stats.received)),
+        ("loss".into(), num(o.stats.loss)),
+        ("rtt".into(), opt(rtt)),
+        (
+            "icmp".into(),
+            opt(o
+                .icmp
+                .map(|(id, seq)| Value::Array(vec![num(id), num(seq)]))),
+        ),
+        ("corrupted".into(), num(o.corrupted)),
+        ("frag_needed".into(), num(o.frag_needed)),
+        ("next_hop_mtu".into(), opt(o.next_hop_mtu.map(num))),
+        ("ports".into(), Value::Array(ports)),
+    ])
+}
+
+fn decode(v: &Value) -> Option<Outcome> {
+    fn n<T: std::str::FromStr>(v: &Value, k: &str) -> Option<T> {
+        v.get(k)?.text().parse().ok()
+    }
+    let dur = |v: &Value, k: &str| n(v, k).map(Duration::from_micros);
+    let list = |k: &str| match v.get(k) {
+        Some(Value::Array(items)) => items.as_slice(),
+        _ => &[],
+    };
+    let replies = list("replies")
+        .iter()
+        .map(|r| {
+            Some(Reply {
+                seq: n(r, "seq")?,
+                bytes: n(r, "bytes")?,
+                rtt: dur(r, "rtt_us")?,
+                sent_at: dur(r, "sent_us").map(|d| UNIX_EPOCH + d),
+                clock_offset_ms: n(r, "clock_offset_ms"),
+            })
+        })
+        .collect::<Option<_>>()?;
+    let rtt = match v.get("rtt") {
+        Some(r @ Value::Object(_)) => Some(Rtt {
+            min: dur(r, "min_us")?,
+            avg: dur(r, "avg_us")?,
+            max: dur(r, "max_us")?,
+            mdev: dur(r, "mdev_us")?,
+        }),
+        _ => None,
+    };
+    let icmp = match v.get("icmp") {
+        Some(Value::Array(pair)) => match pair.as_slice() {
+            [id, seq] => Some((id.text().parse().ok()?, seq.text().parse().ok()?)),
+            _ => None,
+        },
+        _ => None,
+    };
+    let ports = list("ports")
+        .iter()
+        .map(|p| match p {
+            Value::Array(pair) => match pair.as_slice() {
+                [port, state] => Some((
+                    port.text().parse().ok()?,
+                    match state.text().as_str() {
+                        "open" => PortState::Open,
+                        "closed" => PortState::Closed,
+                        _ => PortState::Filtered,
+                    },
+                )),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    Some(Outcome {
+        up: v.get("up") == Some(&Value::Bool(true)),
+        sent: n(v, "sent")?,
+        replies,
+        stats: Stats {
+            transmitted: n(v, "transmitted")?,
+            received: n(v, "received")?,
+            loss: n(v, "loss")?,
+            rtt,
+        },
+        icmp,
+        corrupted: n(v, "corrupted").unwrap_or(0),
+        frag_needed: n(v, "frag_needed").unwrap_or(0),
+        next_hop_mtu: n(v, "next_hop_mtu"),
+        ports,
+    })
+}
+
+/* ---- coordinator side ---- */
+
+// Where --via and --via-ssh probe from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Agent(String), // host[:port] of an ipchk agent
+    Ssh(String),   // [user@]host for ssh
+}
+
+// What a link speaks: an agent's JSON Lines, or the shell loop --via-ssh
+// falls back to, which takes "7 10.0.0.5" and answers with the ping's
+// output as "7 <line>" and then "7 exit <status>"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Agent,
+    Shell { count: u32 },
+}
+
+type Waiter = mpsc::Sender<Result<Outcome, String>>;
+type Waiting = Arc<Mutex<Option<HashMap<u64, Waiter>>>>; // None once the agent is gone
+
+// Sends the hello and reads the agent's answer: true once it's ready,
+// false if it hung up without one
+pub fn greet(
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+    hello: &str,
+) -> Result<bool, String> {
+    if writeln!(writer, "{hello}")
+        .and_then(|()| writer.flush())
+        .is_err()
+    {
+        return Ok(false);
+    }
+    let mut line = String::new();
+    if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+        return Ok(false);
+    }
+    let ready = parse(&line).map_err(|_| "not an ipchk agent".to_string())?;
+    if let Some(e) = ready.get("error") {
+        return Err(e.text());
+    }
+    if ready.get("ready").is_none() {
+        return Err("not an ipchk agent".into());
+    }
+    Ok(true)
+}
+
+// A connection to an agent: requests go out under the lock, answers are
+// matched to their callers by id on a reader thread
+pub struct Link {
+    agent: String,
+    dialect: Dialect,
+    writer: Mutex<Box<dyn Write + Send>>,
+    waiting: Waiting,
+    next_id: AtomicU64,
+}
+
+impl Link {
+    fn connect(agent: &str, hello: &str) -> Result<Link, String> {
+        let addr = address(agent)?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+            .map_err(|e| format!("{agent}: {e}"))?;
+        let _ = stream.set_nodelay(true);
+        let mut reader = BufReader::new(stream.try_clone().map_err(|e| format!("{agent}: {e}"))?);
+        let mut writer = stream;
+
+        // The agent's preflight is quick; a silent peer isn't an agent
+        let _ = writer.set_read_timeout(Some(CONNECT_TIMEOUT * 2));
+        match greet(&mut reader, &mut writer, hello) {
+            Ok(true) => {}
+            Ok(false) => return Err(format!("{agent}: hung up without answering")),
+            Err(e) => return Err(format!("{agent}: {e}")),
+        }
+        let _ = writer.set_read_timeout(None);
+        Ok(Link::start(agent, Dialect::Agent, reader, writer))
+    }
+
+    // A link over a connection that's already past its handshake
+    pub fn start(
+        agent: &str,
+        dialect: Dialect,
+        reader: impl BufRead + Send + 'static,
+        writer: impl Write + Send + 'static,
+    ) -> Link {
+        let waiting: Waiting = Arc::new(Mutex::new(Some(HashMap::new())));
+        let shared = Arc::clone(&waiting);
+        thread::spawn(move || {
+            let answer = |id: u64, result: Result<Outcome, String>| {
+                let mut waiting = shared.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some(tx) = waiting.as_mut().and_then(|w| w.remove(&id)) {
+                    let _ = tx.send(result);
+                }
+            };
+            let mut output: HashMap<u64, String> = HashMap::new();
+            for line in reader.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                match dialect {
+                    Dialect::Agent => {
+                        let Ok(reply) = parse(&line) else {
+                            continue;
+                        };
+                        let Some(id) = reply.get("id").and_then(|i| i.text().parse().ok()) else {
+                            continue;
+                        };
+                        answer(
+                            id,
+                            match (reply.get("outcome"), reply.get("error")) {
+                                (Some(o), _) => {
+                                    decode(o).ok_or_else(|| "unreadable outcome".into())
+                                }
+                                (None, Some(e)) => Err(e.text()),
+                                (None, None) => Err("empty answer".into()),
+                            },
+                        );
+                    }
+                    Dialect::Shell { count } => {
+                        let Some((id, rest)) = line
+                            .split_once(' ')
+                            .and_then(|(id, rest)| Some((id.parse::<u64>().ok()?, rest)))
+                        else {
+                            continue;
+                        };
+                        let Some(status) = rest.strip_prefix("exit ") else {
+                            let text = output.entry(id).or_default();
+                            text.push_str(rest);
+                            text.push('\n');
+                            continue;
+                        };
+                        let text = output.remove(&id).unwrap_or_default();
+                        answer(
+                            id,
+                            match status.trim().parse::<i32>() {
+                                Ok(127) => Err("no ping on the remote host".into()),
+                                Ok(code) => Ok(ipchk::parse_ping(&text, count, code == 0)),
+                                Err(_) => Err("unreadable exit status".into()),
+                            },
+                        );
+                    }
+                }
+            }
+            // Dropping the senders fails everything still in flight
+            shared.lock().unwrap_or_else(|e| e.into_inner()).take();
+        });
+        Link {
+            agent: agent.to_string(),
+            dialect,
+            writer: Mutex::new(Box::new(writer)),
+            waiting,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    fn ask(&self, addr: Ipv4Addr, wait: Duration) -> Result<Outcome, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::channel();
+        match self
+            .waiting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_mut()
+        {
+            Some(w) => w.insert(id, tx),
+            None => return Err("connection lost".into()),
+        };
+        let request = match self.dialect {
+            Dialect::Agent => Value::Object(vec![
+                ("id".into(), num(id)),
+                ("addr".into(), Value::Str(addr.to_string())),
+            ])
+            .to_json(),
+            Dialect::Shell { .. } => format!("{id} {addr}"),
+        };
+        let sent = {
+            let mut w = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+            writeln!(w, "{request}").and_then(|()| w.flush())
+        };
+        let forget = || {
+            if let Some(w) = self
+                .waiting
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .as_mut()
+            {
+                w.remove(&id);
+            }
+        };
+        if let Err(e) = sent {
+            forget();
+            return Err(e.to_string());
+        }
+        match rx.recv_timeout(wait) {
+            Ok(result) => result,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                forget();
+                Err("no answer in time".into())
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err("connection lost".into()),
+        }
+    }
+}
+
+// The Probe behind --via and --via-ssh. Each target always goes to the
+// same endpoint, with the addresses spread evenly between them.
+pub struct Remote {
+    endpoints: Vec<Endpoint>,
+    hello: String,
+    opts: ProbeOptions,
+    engine: Option<Engine>,
+    links: OnceLock<Vec<Link>>,
+}
+
+impl Remote {
+    // `engine` is --engine as given; None lets every agent pick its own
+    pub fn new(endpoints: &[Endpoint], opts: &ProbeOptions, engine: Option<Engine>) -> Self {
+        let hello = Value::Object(vec![
+            ("agent".into(), Value::Num(VERSION.into())),
+            ("probe".into(), encode_options(opts, engine)),
+        ]);
+        Remote {
+            endpoints: endpoints.to_vec(),
+            hello: hello.to_json(),
+            opts: opts.clone(),
+            engine,
+            links: OnceLock::new(),
+        }
+    }
+
+    fn open(&self, endpoint: &Endpoint) -> Result<Link, String> {
+        match endpoint {
+            Endpoint::Agent(agent) => {
+                Link::connect(agent, &self.hello).map_err(|e| format!("--via {e}"))
+            }
+            Endpoint::Ssh(dest) => ssh::open(dest, &self.hello, &self.opts, self.engine)
+                .map_err(|e| format!("--via-ssh {e}")),
+        }
+    }
+}
+
+impl Probe for Remote {
+    fn name(&self) -> &str {
+        "via"
+    }
+
+    // Connects to every endpoint, each of which runs its own preflight
+    fn preflight(&self) -> Result<(), Error> {
+        let links: Vec<Result<Link, String>> = thread::scope(|s| {
+            let handles: Vec<_> = self
+                .endpoints
+                .iter()
+                .map(|e| s.spawn(|| self.open(e)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|_| Err("connect panicked".into())))
+                .collect()
+        });
+        let links = links
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::Io(io::Error::other(e)))?;
+        let _ = self.links.set(links);
+        Ok(())
+    }
+
+    fn probe(&self, target: &Target, deadline: Deadline) -> ProbeOutcome {
+        let links = self
+            .links
+            .get()
+            .ok_or_else(|| Error::Io(io::Error::other("--via: not connected")))?;
+        let link = &links[u32::from(target.addr) as usize % links.len()];
+        link.ask(target.addr, deadline.remaining() + GRACE)
+            .map_err(|e| Error::Io(io::Error::other(format!("via {}: {e}", link.agent))))
+    }
+}
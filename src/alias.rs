@@ -0,0 +1,81 @@
+use crate::{
+    PingResult,
+    input::{self, Fields},
+};
+use std::{collections::HashMap, net::Ipv4Addr};
+
+/* -------------------- alias detection (--aliases) -------------------- */
+
+// Gives every up host its MAC, from the input's `mac` column or else the
+// ARP cache the sweep just filled (or an ARP request, where the cache has
+// lost it), and flags addresses sharing one as aliases of the first
+// (lowest) of them. `results` must be sorted.
+pub fn mark(results: &mut [PingResult], fields: &Fields, raw: bool) {
+    let arp: HashMap<Ipv4Addr, String> = match input::arp_entries() {
+        Ok(entries) => entries.into_iter().map(|(ip, mac, _)| (ip, mac)).collect(),
+        Err(e) => {
+            eprintln!("--aliases: {e}");
+            HashMap::new()
+        }
+    };
+    for r in results.iter_mut().filter(|r| r.up == Some(true)) {
+        let addr = r.addr.or_else(|| r.ip.parse().ok());
+        let listed = fields
+            .row(&r.ip)
+            .and_then(|row| row.into_iter().find(|(k, _)| *k == "mac"))
+            .and_then(|(_, mac)| input::normalise_mac(mac));
+        r.mac = listed.or_else(|| input::normalise_mac(arp.get(&addr?)?));
+    }
+    let missing: Vec<Ipv4Addr> = results
+        .iter()
+        .filter(|r| r.up == Some(true) && r.mac.is_none())
+        .filter_map(|r| r.addr.or_else(|| r.ip.parse().ok()))
+        .collect();
+    if !missing.is_empty() {
+        let asked = input::resolve_macs(&missing);
+        for r in results
+            .iter_mut()
+            .filter(|r| r.up == Some(true) && r.mac.is_none())
+        {
+            let addr = r.addr.or_else(|| r.ip.parse().ok());
+            r.mac = addr.and_then(|a| input::normalise_mac(asked.get(&a)?));
+        }
+    }
+
+    let mut devices: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, r) in results.iter().enumerate() {
+        if let Some(mac) = &r.mac {
+            devices.entry(mac.clone()).or_default().push(i);
+        }
+    }
+    for (mac, members) in devices.into_iter().filter(|(_, m)| m.len() > 1) {
+        let device = shown(&results[members[0]]);
+        let note = format!(" ({mac}, {} addresses)", members.len());
+        results[members[0]].msg.push_str(&dim(&note, raw));
+        for &i in &members[1..] {
+            let note = format!(" (alias of {device})");
+            results[i].msg.push_str(&dim(&note, raw));
+            results[i].alias_of = Some(device.clone());
+        }
+    }
+}
+
+// The number of distinct devices behind the up addresses with a known MAC
+pub fn devices(results: &[PingResult]) -> usize {
+    let mut macs: Vec<&str> = results.iter().filter_map(|r| r.mac.as_deref()).collect();
+    macs.sort();
+    macs.dedup();
+    macs.len()
+}
+
+fn shown(r: &PingResult) -> String {
+    r.addr.map_or_else(|| r.ip.clone(), |a| a.to_string())
+}
+
+fn dim(note: &str, raw: bool) -> String {
+    if raw {
+        note.to_string()
+    } else {
+        format!("\x1b[2m{note}\x1b[0m")
+    }
+}
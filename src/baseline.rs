@@ -0,0 +1,266 @@
+use crate::{Latency, PingResult, daemon::fmt_secs, parse_rfc3339, rfc3339_utc};
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/* -------------------- known-host baselines (--baseline) -------------------- */
+
+// One known host per line, its address (or target name) first, so the
+// output of `--output fping` makes one: "10.0.0.5", "10.0.0.6 (core-switch)".
+// Blank lines and "#" comments are skipped. --baseline-update keeps
+// "# rtt 1.234 # seen 2024-05-01T12:34:56Z" comments at the end of each
+// line that answered: its usual RTT in milliseconds, and when it was last
+// seen.
+#[derive(Debug)]
+pub struct Baseline {
+    pub path: PathBuf,
+    lines: Vec<String>,
+    known: HashMap<String, Known>,
+    factor: Option<f64>, // --latency-factor
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Known {
+    seen: Option<SystemTime>, // when it last answered
+    rtt: Option<f64>,         // its usual average RTT, ms
+}
+
+const SEEN: &str = "# seen ";
+const RTT: &str = "# rtt ";
+
+// Weight of a sweep's RTT in the recorded one, as the daemon smooths it
+const SMOOTHING: f64 = 0.125;
+
+// How a result stands against the baseline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    New,                         // up, and not in the baseline
+    Missing(Option<SystemTime>), // in the baseline, down; when it was last seen
+    Slower(f64),                 // up, past --latency-factor times this usual RTT
+}
+
+// The host a line lists, and what is known of it
+fn entry(line: &str) -> Option<(&str, Known)> {
+    let host = line.split('#').next()?.split_whitespace().next()?;
+    let seen = line
+        .rfind(SEEN)
+        .and_then(|i| parse_rfc3339(line[i + SEEN.len()..].trim()));
+    let rtt = line.rfind(RTT).and_then(|i| {
+        let rest = &line[i + RTT.len()..];
+        rest.split('#').next()?.trim().parse().ok()
+    });
+    Some((host, Known { seen, rtt }))
+}
+
+impl Baseline {
+    pub fn load(path: PathBuf, factor: Option<f64>) -> Result<Self, String> {
+        let text = fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let lines: Vec<String> = text.lines().map(String::from).collect();
+        let known = lines
+            .iter()
+            .filter_map(|l| entry(l))
+            .map(|(host, known)| (host.to_string(), known))
+            .collect();
+        Ok(Baseline {
+            path,
+            lines,
+            known,
+            factor,
+        })
+    }
+
+    // --latency-factor: how slow `target` may be before it counts as degraded
+    pub fn slow_rtt(&self, target: &str) -> Option<Duration> {
+        let usual = self.known.get(target)?.rtt?;
+        Some(Duration::from_secs_f64(usual * self.factor? / 1000.0))
+    }
+
+    // The entry for `r`, by target or by probed address
+    fn find(&self, r: &PingResult) -> Option<(&str, Known)> {
+        let addr = r.addr.map(|a| a.to_string());
+        [Some(&r.ip), addr.as_ref()]
+            .into_iter()
+            .flatten()
+            .find_map(|k| self.known.get_key_value(k.as_str()))
+            .map(|(k, known)| (k.as_str(), *known))
+    }
+
+    // Flags up hosts the baseline doesn't list, listed ones that are down
+    // and, with --latency-factor, up ones that are that much slower than
+    // usual, which then count as degraded
+    pub fn mark(&self, results: &mut [PingResult], raw: bool) {
+        let now = SystemTime::now();
+        for r in results.iter_mut() {
+            let rtt = r
+                .outcome
+                .as_ref()
+                .and_then(|o| o.stats.rtt)
+                .map(|s| ms(s.avg));
+            let (change, note) = match (r.up, self.find(r)) {
+                (Some(true), None) => (Change::New, "new: not in baseline".to_string()),
+                (
+                    Some(true),
+                    Some((
+                        _,
+                        Known {
+                            rtt: Some(usual), ..
+                        },
+                    )),
+                ) if self.factor.zip(rtt).is_some_and(|(f, rtt)| rtt > usual * f) => {
+                    // Probed here it is degraded already; not from a cache
+                    // or an agent
+                    if r.latency == Latency::Ok {
+                        r.latency = Latency::Warn;
+                    }
+                    let times = rtt.unwrap_or_default() / usual.max(0.001);
+                    (
+                        Change::Slower(usual),
+                        format!("{times:.1}x its baseline of {usual:.1} ms"),
+                    )
+                }
+                (Some(false), Some((_, Known { seen, .. }))) => (
+                    Change::Missing(seen),
+                    match seen.and_then(|t| now.duration_since(t).ok()) {
+                        Some(gone) => {
+                            format!("in baseline, missing for {}", fmt_secs(gone.as_secs()))
+                        }
+                        None => "in baseline, missing".to_string(),
+                    },
+                ),
+                _ => continue,
+            };
+            r.baseline = Some(change);
+            let note = format!(" ({note})");
+            r.msg.push_str(&if raw {
+                note
+            } else {
+                format!("\x1b[1m\x1b[33m{note}\x1b[0m")
+            });
+        }
+    }
+
+    // "ipchk: 2 new hosts not in known.txt: 10.0.0.7, 10.0.0.9" and the
+    // like for missing ones on stderr, after the results, so they're the
+    // last thing on a terminal
+    pub fn report(&self, results: &[PingResult]) {
+        let shown = |r: &PingResult| r.addr.map_or_else(|| r.ip.clone(), |a| a.to_string());
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        let new: Vec<String> = results
+            .iter()
+            .filter(|r| r.baseline == Some(Change::New))
+            .map(shown)
+            .collect();
+        if !new.is_empty() {
+            eprintln!(
+                "ipchk: {} new host{} not in {}: {}",
+                new.len(),
+                plural(new.len()),
+                self.path.display(),
+                new.join(", ")
+            );
+        }
+        let now = SystemTime::now();
+        let missing: Vec<String> = results
+            .iter()
+            .filter_map(|r| match r.baseline {
+                Some(Change::Missing(seen)) => {
+                    Some(match seen.and_then(|t| now.duration_since(t).ok()) {
+                        Some(gone) => format!("{} (for {})", shown(r), fmt_secs(gone.as_secs())),
+                        None => shown(r),
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+        if !missing.is_empty() {
+            eprintln!(
+                "ipchk: {} host{} in {} no longer answer{}: {}",
+                missing.len(),
+                plural(missing.len()),
+                self.path.display(),
+                if missing.len() == 1 { "s" } else { "" },
+                missing.join(", ")
+            );
+        }
+        let slower: Vec<String> = results
+            .iter()
+            .filter_map(|r| match r.baseline {
+                Some(Change::Slower(usual)) => {
+                    let rtt = r.outcome.as_ref()?.stats.rtt?;
+                    Some(format!(
+                        "{} ({:.1} ms, usually {usual:.1})",
+                        shown(r),
+                        ms(rtt.avg)
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        if !slower.is_empty() {
+            eprintln!(
+                "ipchk: {} host{} in {} answer{} more than {}x slower than usual: {}",
+                slower.len(),
+                plural(slower.len()),
+                self.path.display(),
+                if slower.len() == 1 { "s" } else { "" },
+                self.factor.unwrap_or_default(),
+                slower.join(", ")
+            );
+        }
+    }
+
+    // --baseline-update: stamps the lines of hosts that answered, and
+    // moves their usual RTT an eighth of the way to this sweep's (unless
+    // it was flagged slower, which shouldn't become the new normal). Other
+    // lines, and hosts missing from the file, are left as they are.
+    pub fn update(&self, results: &[PingResult]) -> Result<(), String> {
+        let mut seen: HashMap<&str, Known> = HashMap::new();
+        for r in results.iter().filter(|r| r.up == Some(true)) {
+            if let Some((host, known)) = self.find(r) {
+                let at = seen.entry(host).or_insert(Known {
+                    seen: Some(r.at),
+                    rtt: known.rtt,
+                });
+                at.seen = at.seen.max(Some(r.at));
+                let rtt = r.outcome.as_ref().and_then(|o| o.stats.rtt);
+                if let Some(rtt) = rtt.filter(|_| !matches!(r.baseline, Some(Change::Slower(_)))) {
+                    let ms = ms(rtt.avg);
+                    at.rtt = Some(at.rtt.map_or(ms, |usual| usual + SMOOTHING * (ms - usual)));
+                }
+            }
+        }
+        let mut text = String::new();
+        for line in &self.lines {
+            match entry(line).and_then(|(host, _)| seen.get(host)) {
+                Some(known) => {
+                    let end = [line.find(RTT), line.rfind(SEEN)]
+                        .into_iter()
+                        .flatten()
+                        .min()
+                        .unwrap_or(line.len());
+                    text.push_str(line[..end].trim_end());
+                    if let Some(rtt) = known.rtt {
+                        text.push_str(&format!(" {RTT}{rtt:.3}"));
+                    }
+                    if let Some(at) = known.seen {
+                        text.push_str(&format!(" {SEEN}{}", rfc3339_utc(at)));
+                    }
+                }
+                None => text.push_str(line),
+            }
+            text.push('\n');
+        }
+        // Replaced in one step, so an interrupted write can't lose the list
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, text)
+            .and_then(|()| fs::rename(&tmp, &self.path))
+            .map_err(|e| format!("{}: {e}", self.path.display()))
+    }
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
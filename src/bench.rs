@@ -0,0 +1,260 @@
+use crate::json::Value;
+use crate::{engine_error, output};
+use ipchk::{Builtin, Engine, Probe, ProbeOptions, ProbeType, Scan, Target};
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+/* -------------------- ipchk bench: engines side by side -------------------- */
+
+// Every engine that can run the chosen probe type sweeps the same targets,
+// one after another in the order listed, with the same options.
+
+pub struct Request {
+    pub targets: Vec<Ipv4Addr>,
+    pub probe: ProbeOptions, // timeout, count, probe type...; the engine is varied
+    pub concurrency: usize,  // -c
+    pub output: output::Format,
+    pub raw: bool,
+}
+
+struct Run {
+    label: &'static str,
+    // Err when the engine can't run here, with the reason
+    result: Result<Sweep, String>,
+}
+
+struct Sweep {
+    elapsed: Duration,
+    verdicts: Vec<Option<bool>>, // per target; None for a probe error
+    cpu: Option<(Duration, Duration)>, // user, system, children included
+}
+
+// The variants worth comparing for a probe type
+fn variants(opts: &ProbeOptions) -> Vec<(&'static str, ProbeOptions)> {
+    let with = |engine, scan| ProbeOptions {
+        engine,
+        scan,
+        ..opts.clone()
+    };
+    match opts.probe {
+        ProbeType::Tcp => vec![
+            ("connect", with(opts.engine, Scan::Connect)),
+            ("syn", with(opts.engine, Scan::Syn)),
+        ],
+        // Both engines send timestamps through the same raw socket
+        ProbeType::Timestamp => vec![("native", with(Engine::Native, opts.scan))],
+        #[cfg(windows)]
+        ProbeType::Echo => vec![("IcmpSendEcho", with(Engine::Ping, opts.scan))],
+        #[cfg(not(windows))]
+        ProbeType::Echo => vec![
+            ("ping", with(Engine::Ping, opts.scan)),
+            ("native", with(Engine::Native, opts.scan)),
+        ],
+    }
+}
+
+pub fn run(req: &Request) -> Result<(), String> {
+    if !matches!(req.output, output::Format::Text | output::Format::Json) {
+        return Err("bench: --output must be text or json".into());
+    }
+    let runs: Vec<Run> = variants(&req.probe)
+        .into_iter()
+        .map(|(label, opts)| {
+            if !req.raw && req.output == output::Format::Text {
+                eprintln!("\x1b[2mbench: {label}...\x1b[0m");
+            }
+            let probe = Builtin::new(opts.clone());
+            let result = probe
+                .preflight()
+                .map_err(|e| engine_error(&e, &opts))
+                .map(|()| sweep(req, &probe, Builtin::budget(&opts)));
+            Run { label, result }
+        })
+        .collect();
+    match req.output {
+        output::Format::Json => println!("{}", to_json(req, &runs)),
+        _ => print_text(req, &runs),
+    }
+    Ok(())
+}
+
+fn sweep(req: &Request, probe: &dyn Probe, budget: Duration) -> Sweep {
+    let targets: Vec<Target> = req.targets.iter().map(|&ip| Target::new(ip)).collect();
+    let cpu_before = cpu_time();
+    let started = Instant::now();
+    let verdicts = ipchk::sweep(probe, &targets, req.concurrency, budget)
+        .into_iter()
+        .map(|r| r.ok().map(|o| o.up))
+        .collect();
+    let elapsed = started.elapsed();
+    let cpu = cpu_before
+        .zip(cpu_time())
+        .map(|((u0, s0), (u1, s1))| (u1.saturating_sub(u0), s1.saturating_sub(s0)));
+    Sweep {
+        elapsed,
+        verdicts,
+        cpu,
+    }
+}
+
+// User and system CPU time of this process and its reaped children (the
+// system ping). None where it isn't measured.
+#[cfg(unix)]
+fn cpu_time() -> Option<(Duration, Duration)> {
+    let tv = |t: libc::timeval| {
+        Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+    };
+    let mut total = (Duration::ZERO, Duration::ZERO);
+    for who in [libc::RUSAGE_SELF, libc::RUSAGE_CHILDREN] {
+        let mut ru: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(who, &mut ru) } != 0 {
+            return None;
+        }
+        total.0 += tv(ru.ru_utime);
+        total.1 += tv(ru.ru_stime);
+    }
+    Some(total)
+}
+
+#[cfg(not(unix))]
+fn cpu_time() -> Option<(Duration, Duration)> {
+    None
+}
+
+fn state(v: Option<bool>) -> &'static str {
+    match v {
+        Some(true) => "up",
+        Some(false) => "down",
+        None => "error",
+    }
+}
+
+// A target and each engine's verdict on it
+type Verdicts<'a> = (Ipv4Addr, Vec<(&'a str, Option<bool>)>);
+
+// Targets on which the engines that ran disagree
+fn disagreements<'a>(req: &Request, runs: &'a [Run]) -> Vec<Verdicts<'a>> {
+    let ran: Vec<(&str, &Sweep)> = runs
+        .iter()
+        .filter_map(|r| Some((r.label, r.result.as_ref().ok()?)))
+        .collect();
+    req.targets
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &ip)| {
+            let verdicts: Vec<(&str, Option<bool>)> = ran
+                .iter()
+                .map(|(label, s)| (*label, s.verdicts[i]))
+                .collect();
+            let first = verdicts.first()?.1;
+            verdicts
+                .iter()
+                .any(|(_, v)| *v != first)
+                .then_some((ip, verdicts))
+        })
+        .collect()
+}
+
+fn print_text(req: &Request, runs: &[Run]) {
+    let n = req.targets.len();
+    let width = runs.iter().map(|r| r.label.len()).max().unwrap_or(0).max(6);
+    let bold = |s: &str| {
+        if req.raw {
+            s.to_string()
+        } else {
+            format!("\x1b[1m{s}\x1b[0m")
+        }
+    };
+    println!(
+        "{}",
+        bold(&format!(
+            "{:<width$}  {:>9}  {:>9}  {:>5}  {:>6}  {:>15}",
+            "engine", "time", "hosts/s", "up", "errors", "cpu user/sys"
+        ))
+    );
+    for r in runs {
+        match &r.result {
+            Ok(s) => {
+                let secs = s.elapsed.as_secs_f64();
+                let up = s.verdicts.iter().filter(|v| **v == Some(true)).count();
+                let errors = s.verdicts.iter().filter(|v| v.is_none()).count();
+                let cpu = s.cpu.map_or("-".to_string(), |(u, sys)| {
+                    format!("{:.2}/{:.2} s", u.as_secs_f64(), sys.as_secs_f64())
+                });
+                println!(
+                    "{:<width$}  {:>7.2} s  {:>9.1}  {up:>5}  {errors:>6}  {cpu:>15}",
+                    r.label,
+                    secs,
+                    n as f64 / secs.max(f64::EPSILON)
+                );
+            }
+            Err(e) => println!("{:<width$}  unavailable: {e}", r.label),
+        }
+    }
+    let ran = runs.iter().filter(|r| r.result.is_ok()).count();
+    if ran < 2 {
+        return;
+    }
+    let differ = disagreements(req, runs);
+    println!();
+    println!(
+        "{}/{n} targets got the same verdict from every engine",
+        n - differ.len()
+    );
+    for (ip, verdicts) in differ {
+        let each: Vec<String> = verdicts
+            .iter()
+            .map(|(label, v)| format!("{label} {}", state(*v)))
+            .collect();
+        println!("  {ip}: {}", each.join(", "));
+    }
+}
+
+fn to_json(req: &Request, runs: &[Run]) -> String {
+    let num = |x: f64| Value::Num(format!("{x:.3}"));
+    let engines = runs
+        .iter()
+        .map(|r| {
+            let mut m = vec![("engine".to_string(), Value::Str(r.label.into()))];
+            match &r.result {
+                Ok(s) => {
+                    let secs = s.elapsed.as_secs_f64();
+                    let count = |f: &dyn Fn(&Option<bool>) -> bool| {
+                        Value::Num(s.verdicts.iter().filter(|v| f(v)).count().to_string())
+                    };
+                    m.push(("seconds".into(), num(secs)));
+                    m.push((
+                        "per_second".into(),
+                        num(req.targets.len() as f64 / secs.max(f64::EPSILON)),
+                    ));
+                    m.push(("up".into(), count(&|v| *v == Some(true))));
+                    m.push(("errors".into(), count(&|v| v.is_none())));
+                    if let Some((user, sys)) = s.cpu {
+                        m.push(("cpu_user_s".into(), num(user.as_secs_f64())));
+                        m.push(("cpu_system_s".into(), num(sys.as_secs_f64())));
+                    }
+                }
+                Err(e) => m.push(("error".into(), Value::Str(e.clone()))),
+            }
+            Value::Object(m)
+        })
+        .collect();
+    let differ = disagreements(req, runs)
+        .into_iter()
+        .map(|(ip, verdicts)| {
+            let mut m = vec![("ip".to_string(), Value::Str(ip.to_string()))];
+            m.extend(
+                verdicts
+                    .into_iter()
+                    .map(|(label, v)| (label.to_string(), Value::Str(state(v).into()))),
+            );
+            Value::Object(m)
+        })
+        .collect();
+    Value::Object(vec![
+        ("targets".into(), Value::Num(req.targets.len().to_string())),
+        ("engines".into(), Value::Array(engines)),
+        ("disagreements".into(), Value::Array(differ)),
+    ])
+    .to_json()
+}
@@ -0,0 +1,130 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, Write},
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/* -------------------- on-disk result cache (--cache) -------------------- */
+
+// Entries older than this are dropped on write regardless of the caller's TTL,
+// so runs with a long --cache don't lose lines to runs with a short one.
+const KEEP_SECS: u64 = 24 * 60 * 60;
+
+// One line per target and probe setup:
+// "<target>@<setup hash>\t<unix seconds>\t<up|down>"
+pub struct Cache {
+    path: PathBuf,
+    ttl: Duration,
+    entries: HashMap<String, (u64, bool)>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// $IPCHK_CACHE, else the platform's per-user cache directory
+fn cache_path() -> Option<PathBuf> {
+    if let Some(p) = env::var_os("IPCHK_CACHE") {
+        return Some(PathBuf::from(p));
+    }
+
+    #[cfg(windows)]
+    let base = env::var_os("LOCALAPPDATA").map(PathBuf::from);
+
+    #[cfg(not(windows))]
+    let base = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")));
+
+    base.map(|b| b.join("ipchk").join("results"))
+}
+
+fn read_entries(path: &PathBuf) -> HashMap<String, (u64, bool)> {
+    let mut entries = HashMap::new();
+    let Ok(text) = fs::read_to_string(path) else {
+        return entries;
+    };
+    for line in text.lines() {
+        let mut cols = line.split('\t');
+        let (Some(target), Some(ts), Some(state)) = (cols.next(), cols.next(), cols.next()) else {
+            continue;
+        };
+        let Ok(ts) = ts.parse::<u64>() else {
+            continue;
+        };
+        let up = match state {
+            "up" => true,
+            "down" => false,
+            _ => continue,
+        };
+        entries.insert(target.to_string(), (ts, up));
+    }
+    entries
+}
+
+// The cache key for `target` probed as `setup` describes: a verdict only
+// stands for the same probe, ports, timeouts and so on. FNV-1a, so the key
+// stays the same from one build to the next.
+pub fn key(target: &str, setup: &str) -> String {
+    let hash = setup.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{target}@{hash:016x}")
+}
+
+impl Cache {
+    pub fn load(ttl: Duration) -> Self {
+        let path = cache_path().unwrap_or_else(|| env::temp_dir().join("ipchk-results"));
+        let entries = read_entries(&path);
+        Cache { path, ttl, entries }
+    }
+
+    // Cached verdict under `key` and when it was reached, if that was
+    // within the TTL (whole seconds, as --cache takes no less)
+    pub fn lookup(&self, key: &str) -> Option<(bool, SystemTime)> {
+        let &(ts, up) = self.entries.get(key)?;
+        let age = now_secs().saturating_sub(ts);
+        (age < self.ttl.as_secs()).then(|| (up, UNIX_EPOCH + Duration::from_secs(ts)))
+    }
+
+    // The last verdict under `key`, however old, while the file keeps it
+    pub fn previous(&self, key: &str) -> Option<bool> {
+        self.entries.get(key).map(|&(_, up)| up)
+    }
+
+    // Merge fresh verdicts into whatever is on disk now (other ipchk runs may
+    // have written in the meantime), drop stale lines, and replace the file.
+    pub fn store(&self, fresh: &[(String, bool)]) -> io::Result<()> {
+        if fresh.is_empty() {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let now = now_secs();
+        let mut entries = read_entries(&self.path);
+        let keep = self.ttl.as_secs().max(KEEP_SECS);
+        entries.retain(|_, (ts, _)| now.saturating_sub(*ts) < keep);
+        for (target, up) in fresh {
+            entries.insert(target.clone(), (now, *up));
+        }
+
+        let tmp = self
+            .path
+            .with_extension(format!("tmp{}", std::process::id()));
+        {
+            let mut f = io::BufWriter::new(fs::File::create(&tmp)?);
+            for (target, (ts, up)) in &entries {
+                writeln!(f, "{target}\t{ts}\t{}", if *up { "up" } else { "down" })?;
+            }
+            f.flush()?;
+        }
+        fs::rename(&tmp, &self.path)
+    }
+}
@@ -0,0 +1,116 @@
+use ipchk::{Cidr, parse_cidr, parse_ipv4, range_cidrs};
+use std::net::Ipv4Addr;
+
+/* -------------------- ipchk calc: subnet calculator -------------------- */
+
+pub struct Request {
+    pub args: Vec<String>,                   // <prefix> or <start> <end>
+    pub range: Option<(Ipv4Addr, Ipv4Addr)>, // -r start end
+    pub split: Option<u32>,                  // --split <n>: list the /n subnets
+    pub supernet: Option<u32>,               // --supernet <n>: the enclosing /n
+}
+
+pub const USAGE: &str =
+    "Usage: ipchk calc <prefix>|<start> <end> [--split <len>] [--supernet <len>]";
+
+// "24" or "/24"
+pub fn parse_len(s: &str) -> Result<u32, String> {
+    match s.trim_start_matches('/').parse::<u32>() {
+        Ok(n) if n <= 32 => Ok(n),
+        _ => Err(format!("invalid prefix length {s:?} (use 0-32)")),
+    }
+}
+
+pub fn run(req: &Request) -> Result<(), String> {
+    let range = match (req.range, req.args.as_slice()) {
+        (Some(r), _) => Some(r),
+        (None, [start, end]) => Some((
+            parse_ipv4(start).map_err(|e| e.to_string())?,
+            parse_ipv4(end).map_err(|e| e.to_string())?,
+        )),
+        (None, [_]) => None,
+        _ => return Err(USAGE.into()),
+    };
+    if let Some((start, end)) = range {
+        if req.split.is_some() {
+            return Err("calc: --split needs a single prefix, not a range".into());
+        }
+        print_range(start, end, req.supernet);
+        return Ok(());
+    }
+
+    let arg = &req.args[0];
+    let block = parse_cidr(arg).map_err(|e| e.to_string())?;
+    let addr: Ipv4Addr = arg
+        .split('/')
+        .next()
+        .unwrap_or("")
+        .parse()
+        .unwrap_or(block.net);
+    if let Some(len) = req.supernet.filter(|&len| len > block.prefix) {
+        return Err(format!(
+            "calc: --supernet /{len} is longer than /{}",
+            block.prefix
+        ));
+    }
+    if let Some(len) = req.split.filter(|&len| len < block.prefix) {
+        return Err(format!(
+            "calc: --split /{len} is shorter than /{}",
+            block.prefix
+        ));
+    }
+
+    if addr != block.net {
+        row("Address", addr);
+    }
+    print_block(block);
+    if let Some(len) = req.supernet {
+        row("Supernet", Cidr::new(block.net, len));
+    }
+    if let Some(len) = req.split {
+        println!();
+        println!("{} subnets of /{len}:", 1u64 << (len - block.prefix));
+        for sub in block.split(len) {
+            let (first, last) = sub.host_range();
+            println!("  {:<18} {first} - {last}", sub.to_string());
+        }
+    }
+    Ok(())
+}
+
+// "Network:   10.0.0.0/24"; an empty label continues the previous row
+fn row(what: &str, value: impl std::fmt::Display) {
+    let label = if what.is_empty() {
+        String::new()
+    } else {
+        format!("{what}:")
+    };
+    println!("{label:<10} {value}");
+}
+
+fn print_block(block: Cidr) {
+    let wildcard = Ipv4Addr::from(!u32::from(block.mask()));
+    row("Network", block);
+    row("Netmask", format!("{} = {}", block.mask(), block.prefix));
+    row("Wildcard", wildcard);
+    row("Broadcast", block.broadcast());
+    let (first, last) = block.host_range();
+    row("HostMin", first);
+    row("HostMax", last);
+    row("Hosts", block.host_count());
+}
+
+// The prefixes that make up an arbitrary range, and its smallest supernet
+fn print_range(start: Ipv4Addr, end: Ipv4Addr, supernet: Option<u32>) {
+    let (lo, hi) = (start.min(end), start.max(end));
+    let size = u64::from(u32::from(hi)) - u64::from(u32::from(lo)) + 1;
+    row("Range", format!("{lo} - {hi} ({size} addresses)"));
+    for (i, block) in range_cidrs(lo, hi).into_iter().enumerate() {
+        row(if i == 0 { "Prefixes" } else { "" }, block);
+    }
+    let common = (u32::from(lo) ^ u32::from(hi)).leading_zeros();
+    row(
+        "Supernet",
+        Cidr::new(lo, supernet.map_or(common, |s| s.min(common))),
+    );
+}
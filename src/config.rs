@@ -0,0 +1,249 @@
+use std::{env, ffi::OsString, fs, path::PathBuf};
+
+/* -------------------- config file profiles (--profile) -------------------- */
+
+// Named bundles of probe settings, so "fast local sweep" and "gentle
+// internet check" are a flag each:
+//
+//   [profile.wan]
+//   timeout = 3000
+//   count = 2
+//   concurrency = 16
+//   probe = "tcp"
+//   ports = "443"
+//
+// A setting stands for the flag of that name, and flags given on the
+// command line win over the profile's. ipchk has no rate limit of its own,
+// so `rate` (probes a second to each host) stands for the --interval
+// between them: `rate = 2` is `interval = 500`.
+
+// Settings that only mean something for the profile's own probe
+const PROBE_SETTINGS: &[&str] = &["ports", "scan", "connect-timeout", "response-timeout"];
+
+// The settings a profile may hold, with their short flags
+const SETTINGS: &[(&str, Option<&str>)] = &[
+    ("timeout", Some("-t")),
+    ("count", Some("-n")),
+    ("interval", Some("-i")),
+    ("concurrency", Some("-c")),
+    ("rate", None),
+    ("require", None),
+    ("engine", None),
+    ("probe", None),
+    ("ports", Some("-p")),
+    ("scan", None),
+    ("connect-timeout", None),
+    ("response-timeout", None),
+];
+
+// $IPCHK_CONFIG, else config.toml in the platform's per-user config directory
+pub fn path() -> Option<PathBuf> {
+    if let Some(p) = env::var_os("IPCHK_CONFIG") {
+        return Some(PathBuf::from(p));
+    }
+
+    #[cfg(windows)]
+    let base = env::var_os("APPDATA").map(PathBuf::from);
+
+    #[cfg(not(windows))]
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")));
+
+    base.map(|b| b.join("ipchk").join("config.toml"))
+}
+
+// `args` (without the program) with "--profile <name>" (or
+// "--profile=<name>") replaced by the profile's settings, except those the
+// command line sets itself
+pub fn with_profile(mut args: Vec<OsString>) -> Result<Vec<OsString>, String> {
+    let Some(at) = args
+        .iter()
+        .position(|a| a == "--profile" || a.to_string_lossy().starts_with("--profile="))
+    else {
+        return Ok(args);
+    };
+    let name = match args[at].to_string_lossy().strip_prefix("--profile=") {
+        Some(name) => {
+            let name = name.to_string();
+            args.remove(at);
+            name
+        }
+        None => {
+            let Some(name) = args.get(at + 1).map(|n| n.to_string_lossy().into_owned()) else {
+                return Err("--profile: a profile name is needed".into());
+            };
+            args.drain(at..at + 2);
+            name
+        }
+    };
+    if name.is_empty() {
+        return Err("--profile: a profile name is needed".into());
+    }
+    let path = path().ok_or("--profile: no config directory (set $IPCHK_CONFIG)")?;
+    let text =
+        fs::read_to_string(&path).map_err(|e| format!("--profile: {}: {e}", path.display()))?;
+    let profiles = parse(&text).map_err(|e| format!("--profile: {}:{e}", path.display()))?;
+    let Some((_, settings)) = profiles.iter().find(|(n, _)| *n == name) else {
+        let names: Vec<&str> = profiles.iter().map(|(n, _)| n.as_str()).collect();
+        return Err(format!(
+            "--profile: no [profile.{name}] in {} (it has {})",
+            path.display(),
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        ));
+    };
+    let given = |args: &[OsString], key: &str| {
+        let short = SETTINGS
+            .iter()
+            .find(|(k, _)| *k == key)
+            .and_then(|(_, s)| *s);
+        let long = format!("--{key}");
+        args.iter().any(|a| {
+            let a = a.to_string_lossy();
+            a == long || Some(&*a) == short || a.starts_with(&format!("{long}="))
+        })
+    };
+    // Another probe on the command line leaves the profile's ports and
+    // such behind with its probe
+    let own_probe = given(&args, "probe");
+    let mut extra = Vec::new();
+    for (key, value) in settings {
+        if given(&args, key) || (own_probe && PROBE_SETTINGS.contains(&key.as_str())) {
+            continue;
+        }
+        extra.push(OsString::from(format!("--{key}")));
+        extra.push(value.into());
+    }
+    args.extend(extra);
+    Ok(args)
+}
+
+// A profile's name and its settings, in the order written
+type Profile = (String, Vec<(String, String)>);
+
+// Every [profile.<name>] of the file. Errors start with the line number.
+fn parse(text: &str) -> Result<Vec<Profile>, String> {
+    let mut profiles: Vec<Profile> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let bad = |what: &str| format!("{}: {what}", i + 1);
+        let line = line.split_once('#').map_or(line, |(l, _)| l).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let Some(name) = section.trim().strip_prefix("profile.") else {
+                return Err(bad(&format!(
+                    "unknown section [{section}] (use [profile.<name>])"
+                )));
+            };
+            profiles.push((name.trim().to_string(), Vec::new()));
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(bad("use key = value"));
+        };
+        let (key, value) = (key.trim(), value.trim());
+        if !SETTINGS.iter().any(|(k, _)| *k == key) {
+            let known: Vec<&str> = SETTINGS.iter().map(|(k, _)| *k).collect();
+            return Err(bad(&format!(
+                "unknown setting {key:?} (use {})",
+                known.join(", ")
+            )));
+        }
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        let Some((_, settings)) = profiles.last_mut() else {
+            return Err(bad("settings go under a [profile.<name>] section"));
+        };
+        let (key, value) = match key {
+            "rate" => ("interval", rate_interval(value).map_err(|e| bad(&e))?),
+            _ => (key, value.to_string()),
+        };
+        if key == "interval" && settings.iter().any(|(k, _)| k == "interval") {
+            return Err(bad("rate and interval both space the probes; keep one"));
+        }
+        settings.push((key.to_string(), value));
+    }
+    Ok(profiles)
+}
+
+// `rate` probes a second as the --interval between them, in whole ms
+fn rate_interval(rate: &str) -> Result<String, String> {
+    match rate.parse::<f64>() {
+        Ok(r) if r.is_finite() && r > 0.0 => Ok(format!("{}", (1000.0 / r).round().max(1.0))),
+        _ => Err(format!("rate {rate:?}: probes a second, a number above 0")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(profile: &Profile) -> Vec<(&str, &str)> {
+        profile
+            .1
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn profiles_in_order() {
+        let text = "# mine\n\n[profile.lan]\ntimeout = 300\ncount=1 # one is enough\n\n[ profile.wan ]\nprobe = \"tcp\"\nports = \"22,443\"\n";
+        let profiles = parse(text).unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].0, "lan");
+        assert_eq!(settings(&profiles[0]), [("timeout", "300"), ("count", "1")]);
+        assert_eq!(profiles[1].0, "wan");
+        assert_eq!(
+            settings(&profiles[1]),
+            [("probe", "tcp"), ("ports", "22,443")]
+        );
+    }
+
+    #[test]
+    fn empty_file_has_no_profiles() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("# nothing yet\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn unknown_setting_names_its_line() {
+        let e = parse("[profile.lan]\ntimeout = 300\nretries = 10\n").unwrap_err();
+        assert!(e.starts_with("3: unknown setting \"retries\""), "{e}");
+    }
+
+    #[test]
+    fn rate_is_an_interval() {
+        let profiles = parse("[profile.wan]\nrate = 2\n[profile.lan]\nrate = 0.5\n").unwrap();
+        assert_eq!(settings(&profiles[0]), [("interval", "500")]);
+        assert_eq!(settings(&profiles[1]), [("interval", "2000")]);
+        let e = parse("[profile.wan]\ninterval = 100\nrate = 2\n").unwrap_err();
+        assert_eq!(e, "3: rate and interval both space the probes; keep one");
+        assert!(parse("[profile.wan]\nrate = 0\n").is_err());
+    }
+
+    #[test]
+    fn unknown_section() {
+        let e = parse("[defaults]\ncount = 1\n").unwrap_err();
+        assert!(e.starts_with("1: unknown section [defaults]"), "{e}");
+    }
+
+    #[test]
+    fn setting_before_any_section() {
+        let e = parse("count = 1\n[profile.lan]\n").unwrap_err();
+        assert_eq!(e, "1: settings go under a [profile.<name>] section");
+    }
+
+    #[test]
+    fn line_without_equals() {
+        let e = parse("[profile.lan]\ntimeout 300\n").unwrap_err();
+        assert_eq!(e, "2: use key = value");
+    }
+}
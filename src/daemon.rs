@@ -0,0 +1,699 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    io::Write,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    Style, engine_error,
+    eventlog::EventLog,
+    format_verdict,
+    journald::Journal,
+    notify::{Change, Notifiers},
+    reconfigure, rfc3339_utc, statsd, v6_engine_error,
+};
+use ipchk::{ProbeOptions, ProbeType, parse_target, preflight, preflight_v6, probe, probe_v6};
+
+/* -------------------- daemon mode: monitor + query socket -------------------- */
+
+pub struct Config {
+    pub targets: Vec<String>,
+    pub every: Duration,
+    pub probe: ProbeOptions,
+    pub require: u32, // replies needed to count as up
+    pub concurrency: usize,
+    pub style: Style,
+    pub socket: PathBuf,
+    pub log: Option<PathBuf>,
+    pub notify: Notifiers,
+    pub argv: Option<Vec<OsString>>, // parsed again on SIGHUP; None for targets from stdin
+    pub statsd: Option<statsd::Client>,
+    pub journal: Option<Journal>, // --journald: state changes as journal entries
+    pub eventlog: Option<EventLog>, // --eventlog: ... and as Windows events
+    pub flapping: Option<Flapping>,
+}
+
+// --flap-threshold: a host that changes state more than `threshold` times
+// within `window` is flapping, and its notifications are held until it has
+// kept one state for a whole window
+#[derive(Debug, Clone, Copy)]
+pub struct Flapping {
+    pub threshold: usize,
+    pub window: Duration,
+}
+
+// Raised by SIGHUP, taken by the monitor loop
+static RELOAD: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_hangup(_: libc::c_int) {
+    RELOAD.store(true, Ordering::Relaxed);
+}
+
+#[derive(Clone, Default)]
+struct Host {
+    up: Option<bool>,               // None until the first sweep reaches it
+    checked: u64,                   // unix seconds of the last probe
+    changed: u64,                   // unix seconds of the last up/down transition
+    rtt: Option<f64>,               // ms, the last sweep's average; None unless it answered
+    smoothed: Option<f64>,          // ms, the EWMA of `rtt` across sweeps
+    flips: Vec<u64>,                // unix seconds of the transitions within the flap window
+    flapping: Option<bool>,         // while flapping, the state last notified
+    history: VecDeque<Option<f64>>, // the last HISTORY sweeps' `rtt`, oldest first
+}
+
+impl Host {
+    // Counts a transition at `now`; whether it starts the host flapping
+    fn flip(&mut self, now: u64, f: &Flapping) -> bool {
+        self.flips.push(now);
+        self.flips
+            .retain(|&t| now.saturating_sub(t) < f.window.as_secs());
+        self.flapping.is_none() && self.flips.len() > f.threshold
+    }
+}
+
+// Weight of each new RTT in the smoothed one, as TCP's SRTT (RFC 6298)
+const SMOOTHING: f64 = 0.125;
+
+// Sweeps of RTTs kept for `ipchk status`'s sparklines
+const HISTORY: usize = 20;
+
+type State = Arc<Mutex<HashMap<String, Host>>>;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Compact "1d2h", "2h13m", "5m3s", "42s"
+pub fn fmt_secs(secs: u64) -> String {
+    let (d, h, m, s) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if d > 0 {
+        format!("{d}d{h}h")
+    } else if h > 0 {
+        format!("{h}h{m}m")
+    } else if m > 0 {
+        format!("{m}m{s}s")
+    } else {
+        format!("{s}s")
+    }
+}
+
+pub fn default_socket() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR") {
+        return PathBuf::from(dir).join("ipchk.sock");
+    }
+    let user = env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_default();
+    if user.is_empty() {
+        env::temp_dir().join("ipchk.sock")
+    } else {
+        env::temp_dir().join(format!("ipchk-{user}.sock"))
+    }
+}
+
+fn sweep(
+    cfg: &Config,
+    addrs: &[(String, IpAddr)],
+    state: &State,
+    log: &mut Option<File>,
+    stop: &AtomicBool,
+) {
+    let mut changes = Vec::new();
+    let started = Instant::now();
+    let mut metrics = Vec::new();
+    let mut discovered = false;
+    for chunk in addrs.chunks(cfg.concurrency) {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        let verdicts: Vec<(&str, bool, Option<Duration>)> = thread::scope(|s| {
+            let handles: Vec<_> = chunk
+                .iter()
+                .map(|(name, ip)| {
+                    s.spawn(move || {
+                        let outcome = match *ip {
+                            IpAddr::V4(v4) => probe(v4, &cfg.probe),
+                            IpAddr::V6(v6) => probe_v6(v6, &cfg.probe),
+                        };
+                        // A probe that can't run counts as down, but say why
+                        let (up, rtt) = outcome.map_or_else(
+                            |e| {
+                                eprintln!("daemon: {e}");
+                                (false, None)
+                            },
+                            |o| {
+                                let o = o.require(cfg.require);
+                                (o.up, o.stats.rtt.map(|r| r.avg))
+                            },
+                        );
+                        (name.as_str(), up, rtt)
+                    })
+                })
+                .collect();
+            handles.into_iter().filter_map(|h| h.join().ok()).collect()
+        });
+        metrics.extend(verdicts.iter().map(|&(name, up, rtt)| statsd::Host {
+            name,
+            label: None,
+            up: Some(up),
+            rtt,
+        }));
+
+        let now = now_secs();
+        let mut hosts = state.lock().unwrap_or_else(|e| e.into_inner());
+        for (name, up, rtt) in verdicts {
+            let host = hosts.entry(name.to_string()).or_default();
+            if host.up != Some(up) {
+                // Back up: how long it was down, which is what gets asked first
+                let downtime =
+                    (host.up == Some(false) && up).then(|| now.saturating_sub(host.changed));
+                let after = downtime
+                    .map(|s| format!(" (down for {})", fmt_secs(s)))
+                    .unwrap_or_default();
+                let mut began = None;
+                // The first verdict is a discovery, not a transition
+                if host.up.is_some() {
+                    if let Some(f) = &cfg.flapping
+                        && host.flip(now, f)
+                    {
+                        host.flapping = Some(!up);
+                        began = Some(format!(
+                            "{name} is flapping ({} changes in {}); holding its notifications",
+                            host.flips.len(),
+                            fmt_secs(f.window.as_secs())
+                        ));
+                    }
+                    if host.flapping.is_none() {
+                        announce(cfg, name, up, downtime, &mut changes);
+                    }
+                } else {
+                    discovered = true;
+                }
+                host.changed = now;
+                println!("{}{after}", format_verdict(name, up, cfg.style, false));
+                if let Some(f) = log.as_mut() {
+                    let _ = writeln!(
+                        f,
+                        "{} {}{after}",
+                        rfc3339_utc(SystemTime::now()),
+                        format_verdict(name, up, Style::PLAIN, false)
+                    );
+                }
+                if let Some(journal) = &cfg.journal {
+                    let message =
+                        format!("{}{after}", format_verdict(name, up, Style::PLAIN, false));
+                    let change = journal.change(name, up, host.up, rtt, downtime, &message);
+                    if let Err(e) = change {
+                        eprintln!("daemon: --journald: {e}");
+                    }
+                }
+                if let Some(line) = began {
+                    note(log, &line);
+                }
+            }
+            host.up = Some(up);
+            host.checked = now;
+            // Settled: what the held notifications would have ended on, if
+            // that differs from the last one sent
+            if let (Some(notified), Some(f)) = (host.flapping, &cfg.flapping)
+                && now.saturating_sub(host.changed) >= f.window.as_secs()
+            {
+                host.flapping = None;
+                host.flips.clear();
+                let state = if up { "up" } else { "down" };
+                note(log, &format!("{name} stopped flapping ({state})"));
+                if notified != up {
+                    announce(cfg, name, up, None, &mut changes);
+                }
+            }
+            // Down sweeps leave the smoothed RTT where it was
+            host.rtt = rtt.filter(|_| up).map(|d| d.as_secs_f64() * 1000.0);
+            if let Some(ms) = host.rtt {
+                host.smoothed = Some(host.smoothed.map_or(ms, |s| s + SMOOTHING * (ms - s)));
+            }
+            if host.history.len() == HISTORY {
+                host.history.pop_front();
+            }
+            host.history.push_back(host.rtt);
+        }
+    }
+    cfg.notify.changes_done(&changes);
+    // A summary for the first verdicts, and whenever something changed
+    if let Some(events) = &cfg.eventlog
+        && (discovered || !changes.is_empty())
+    {
+        let up = metrics.iter().filter(|h| h.up == Some(true)).count();
+        if let Err(e) = events.daemon_sweep(up, metrics.len(), &changes) {
+            eprintln!("daemon: --eventlog: {e}");
+        }
+    }
+    if let Some(client) = &cfg.statsd
+        && let Err(e) = client.report(&metrics, started.elapsed())
+    {
+        eprintln!("daemon: --statsd: {e}");
+    }
+}
+
+// Tell the notifiers and the event log about a transition
+fn announce(cfg: &Config, name: &str, up: bool, downtime: Option<u64>, changes: &mut Vec<Change>) {
+    let change = Change {
+        target: name.to_string(),
+        up,
+        downtime,
+    };
+    cfg.notify.state_changed(&change);
+    if let Some(Err(e)) = cfg.eventlog.as_ref().map(|l| l.change(&change)) {
+        eprintln!("daemon: --eventlog: {e}");
+    }
+    changes.push(change);
+}
+
+// A line about the daemon's view of a host, on stdout and in the --log
+fn note(log: &mut Option<File>, line: &str) {
+    println!("{line}");
+    if let Some(f) = log.as_mut() {
+        let _ = writeln!(f, "{} {line}", rfc3339_utc(SystemTime::now()));
+    }
+}
+
+// Monitor until `stop` is raised (never, for the console daemon)
+pub fn run(mut cfg: Config, stop: &AtomicBool) -> Result<(), String> {
+    let mut addrs = addresses(&cfg.targets).map_err(|e| format!("daemon: {e}"))?;
+    if addrs.is_empty() {
+        return Err("daemon: no targets to monitor".into());
+    }
+    check_engines(&cfg.probe, &addrs).map_err(|e| format!("daemon: {e}"))?;
+
+    let state: State = Arc::new(Mutex::new(
+        addrs
+            .iter()
+            .map(|(name, _)| (name.clone(), Host::default()))
+            .collect(),
+    ));
+
+    let mut log = open_log(cfg.log.as_deref()).map_err(|e| format!("daemon: {e}"))?;
+
+    serve(&cfg.socket, Arc::clone(&state))?;
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGHUP, on_hangup as *const () as libc::sighandler_t);
+    }
+
+    while !stop.load(Ordering::Relaxed) {
+        if RELOAD.swap(false, Ordering::Relaxed) {
+            reload(&mut cfg, &mut addrs, &state, &mut log);
+        }
+        sweep(&cfg, &addrs, &state, &mut log, stop);
+
+        // Sleep in short steps so a stop or reload request is honoured promptly
+        let mut left = cfg.every;
+        while !left.is_zero() && !stop.load(Ordering::Relaxed) && !RELOAD.load(Ordering::Relaxed) {
+            let step = left.min(Duration::from_millis(250));
+            thread::sleep(step);
+            left -= step;
+        }
+    }
+    Ok(())
+}
+
+// The targets as the monitor probes them, each once
+fn addresses(targets: &[String]) -> Result<Vec<(String, IpAddr)>, String> {
+    let mut addrs: Vec<(String, IpAddr)> = Vec::with_capacity(targets.len());
+    for t in targets {
+        if !addrs.iter().any(|(name, _)| name == t) {
+            addrs.push((t.clone(), parse_target(t).map_err(|e| e.to_string())?));
+        }
+    }
+    Ok(addrs)
+}
+
+// The engine works, and so does the system ping when IPv6 targets need it.
+// Those are only pinged for now, so other probes would fail every sweep.
+fn check_engines(opts: &ProbeOptions, addrs: &[(String, IpAddr)]) -> Result<(), String> {
+    preflight(opts).map_err(|e| engine_error(&e, opts))?;
+    if addrs.iter().any(|(_, ip)| ip.is_ipv6()) {
+        if cfg!(windows) {
+            return Err("IPv6 targets aren't supported on Windows yet".into());
+        }
+        if opts.probe != ProbeType::Echo {
+            return Err("IPv6 targets only take echo probes for now; drop --probe".into());
+        }
+        preflight_v6(opts).map_err(|e| v6_engine_error(&e, opts))?;
+    }
+    Ok(())
+}
+
+fn open_log(path: Option<&Path>) -> Result<Option<File>, String> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map(Some)
+        .map_err(|e| format!("--log {}: {e}", path.display()))
+}
+
+// After a SIGHUP: the daemon's command line parsed again, with the config
+// file's profile and the --input files as they are now, swapped in for the
+// old settings. Hosts still listed keep their state, new ones start pending
+// and the others are dropped. Any failure leaves everything as it was. The
+// query socket is already being served, so a new --socket waits for a
+// restart.
+fn reload(
+    cfg: &mut Config,
+    addrs: &mut Vec<(String, IpAddr)>,
+    state: &State,
+    log: &mut Option<File>,
+) {
+    let Some(argv) = &cfg.argv else {
+        eprintln!(
+            "daemon: SIGHUP: the targets came from stdin, which can't be reread; nothing reloaded"
+        );
+        return;
+    };
+    let fresh = reconfigure(argv).and_then(|new| {
+        let fresh = addresses(&new.targets)?;
+        check_engines(&new.probe, &fresh)?;
+        let opened = match new.log != cfg.log {
+            true => Some(open_log(new.log.as_deref())?),
+            false => None,
+        };
+        Ok((new, fresh, opened))
+    });
+    let (mut new, fresh, opened) = match fresh {
+        Ok(fresh) => fresh,
+        // The full usage text, for a target list that is now empty
+        Err(e) if e.starts_with("Usage:") => {
+            eprintln!("daemon: reload: no targets listed; configuration unchanged");
+            return;
+        }
+        Err(e) => {
+            eprintln!("daemon: reload: {e}; configuration unchanged");
+            return;
+        }
+    };
+    if new.socket != cfg.socket {
+        eprintln!(
+            "daemon: reload: --socket {} needs a restart; still serving {}",
+            new.socket.display(),
+            cfg.socket.display()
+        );
+        new.socket = cfg.socket.clone();
+    }
+    if let Some(opened) = opened {
+        *log = opened;
+    }
+    let mut hosts = state.lock().unwrap_or_else(|e| e.into_inner());
+    let (added, dropped) = retarget(&mut hosts, &fresh);
+    eprintln!(
+        "daemon: reloaded {} targets ({added} new, {dropped} dropped) and the settings",
+        fresh.len()
+    );
+    *addrs = fresh;
+    *cfg = new;
+}
+
+// Hosts still in `fresh` keep their state, the others go; (new, dropped)
+fn retarget(hosts: &mut HashMap<String, Host>, fresh: &[(String, IpAddr)]) -> (usize, usize) {
+    let before = hosts.len();
+    hosts.retain(|name, _| fresh.iter().any(|(n, _)| n == name));
+    let kept = hosts.len();
+    for (name, _) in fresh {
+        hosts.entry(name.clone()).or_default();
+    }
+    (fresh.len() - kept, before - kept)
+}
+
+// One reply line per target:
+// "<target>\t<up|down|pending|unknown>\t<checked>\t<changed>\t<rtt>\t<smoothed>\t<flapping>\t<history>"
+// where checked and changed are unix seconds (0 when not applicable), the
+// RTTs milliseconds (empty when there is none), flapping "flapping" or
+// empty, and history the recent sweeps' RTTs, oldest first, as
+// "0.412,0.398,-,0.501" with "-" for a sweep it didn't answer.
+#[cfg(unix)]
+fn answer(request: &str, state: &State) -> String {
+    let hosts = state.lock().unwrap_or_else(|e| e.into_inner());
+    let mut wanted: Vec<&str> = request.split_whitespace().collect();
+    if wanted.is_empty() {
+        wanted = hosts.keys().map(String::as_str).collect();
+        wanted.sort_by_key(|k| k.parse::<IpAddr>().ok());
+    }
+
+    let mut out = String::new();
+    for t in wanted {
+        let line = match hosts.get(t) {
+            Some(Host {
+                up: Some(up),
+                checked,
+                changed,
+                rtt,
+                smoothed,
+                flapping,
+                history,
+                ..
+            }) => {
+                let ms = |v: &Option<f64>| v.map(|ms| format!("{ms:.3}")).unwrap_or_default();
+                let history: Vec<String> = history
+                    .iter()
+                    .map(|v| v.map_or("-".into(), |ms| format!("{ms:.3}")))
+                    .collect();
+                format!(
+                    "{t}\t{}\t{checked}\t{changed}\t{}\t{}\t{}\t{}\n",
+                    if *up { "up" } else { "down" },
+                    ms(rtt),
+                    ms(smoothed),
+                    if flapping.is_some() { "flapping" } else { "" },
+                    history.join(",")
+                )
+            }
+            Some(_) => format!("{t}\tpending\t0\t0\t\t\t\t\n"),
+            None => format!("{t}\tunknown\t0\t0\t\t\t\t\n"),
+        };
+        out.push_str(&line);
+    }
+    out
+}
+
+#[cfg(unix)]
+fn serve(path: &Path, state: State) -> Result<(), String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let listener = match UnixListener::bind(path) {
+        Ok(l) => l,
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            if UnixStream::connect(path).is_ok() {
+                return Err(format!(
+                    "daemon: another daemon is already listening on {}",
+                    path.display()
+                ));
+            }
+            // Stale socket left behind by a daemon that didn't exit cleanly
+            let _ = std::fs::remove_file(path);
+            UnixListener::bind(path).map_err(|e| format!("daemon: {}: {e}", path.display()))?
+        }
+        Err(e) => return Err(format!("daemon: {}: {e}", path.display())),
+    };
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+            let mut request = String::new();
+            let mut reader = BufReader::new(&stream);
+            if reader.read_line(&mut request).is_err() {
+                continue;
+            }
+            let _ = (&stream).write_all(answer(&request, &state).as_bytes());
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve(path: &Path, _state: State) -> Result<(), String> {
+    eprintln!(
+        "daemon: query socket {} unavailable on this platform; monitoring only",
+        path.display()
+    );
+    Ok(())
+}
+
+// "0.412" as "0.41", "12.750" as "12.8": three significant figures or so
+#[cfg(unix)]
+fn short_ms(ms: &str) -> String {
+    match ms.parse::<f64>() {
+        Ok(v) if v < 10.0 => format!("{v:.2}"),
+        Ok(v) if v < 100.0 => format!("{v:.1}"),
+        Ok(v) => format!("{v:.0}"),
+        Err(_) => ms.to_string(),
+    }
+}
+
+// "▁▂▁▃█▂", each sweep's RTT scaled between the lowest and highest shown,
+// and a gap for a sweep without a reply; under --ascii "_.-:=+*#" instead
+#[cfg(unix)]
+fn sparkline(history: &str, ascii: bool) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    const ASCII: [char; 8] = ['_', '.', '-', ':', '=', '+', '*', '#'];
+    let samples: Vec<Option<f64>> = history.split(',').map(|s| s.parse().ok()).collect();
+    let known = samples.iter().flatten();
+    let lo = known.clone().copied().fold(f64::INFINITY, f64::min);
+    let hi = known.copied().fold(0.0, f64::max);
+    let levels = if ascii { &ASCII } else { &BLOCKS };
+    samples
+        .iter()
+        .map(|s| match s {
+            Some(ms) if hi > lo => {
+                let i = ((ms - lo) / (hi - lo) * 7.0).round() as usize;
+                levels[i.min(7)]
+            }
+            Some(_) => levels[0],
+            None => ' ',
+        })
+        .collect()
+}
+
+// Ask a running daemon about `targets` (all tracked hosts when empty) and
+// print its answers. Returns whether every reported host is up.
+#[cfg(unix)]
+pub fn query(path: &Path, targets: &[String], style: Style) -> Result<bool, String> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(path)
+        .map_err(|e| format!("status: no daemon on {}: {e}", path.display()))?;
+    writeln!(stream, "{}", targets.join(" ")).map_err(|e| format!("status: {e}"))?;
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .map_err(|e| format!("status: {e}"))?;
+
+    let now = now_secs();
+    let mut all_up = true;
+    for line in reply.lines() {
+        let cols: Vec<&str> = line.split('\t').collect();
+        // Daemons from before RTT tracking send four columns, from before
+        // flap detection six, and from before sparklines seven
+        let [target, state, checked, changed, ref rest @ ..] = cols[..] else {
+            continue;
+        };
+        let ago = |ts: &str| fmt_secs(now.saturating_sub(ts.parse().unwrap_or(now)));
+        match state {
+            "up" | "down" => {
+                let up = state == "up";
+                all_up &= up;
+                // "rtt 4.1 ms, smoothed 1.2 ms": this sweep's, and the trend
+                let mut rtt = String::new();
+                if let [now, smoothed, ..] = rest {
+                    if !now.is_empty() {
+                        rtt.push_str(&format!(", rtt {} ms", short_ms(now)));
+                    }
+                    if !smoothed.is_empty() {
+                        rtt.push_str(&format!(", smoothed {} ms", short_ms(smoothed)));
+                    }
+                }
+                let flapping = if rest.get(2) == Some(&"flapping") {
+                    "flapping, "
+                } else {
+                    ""
+                };
+                // Two sweeps or more make a trend
+                let trend = match rest.get(3) {
+                    Some(history) if history.contains(',') => sparkline(history, style.raw),
+                    _ => String::new(),
+                };
+                let trend = match trend.trim_end() {
+                    "" => String::new(),
+                    line => format!(" {line}"),
+                };
+                println!(
+                    "{} ({flapping}for {}, checked {} ago{rtt}){trend}",
+                    format_verdict(target, up, style, false),
+                    ago(changed),
+                    ago(checked)
+                );
+            }
+            "pending" => {
+                all_up = false;
+                println!("{target} is pending (not probed yet)");
+            }
+            _ => {
+                all_up = false;
+                println!("{target} is not monitored by this daemon");
+            }
+        }
+    }
+    Ok(all_up)
+}
+
+#[cfg(not(unix))]
+pub fn query(path: &Path, _targets: &[String], _style: Style) -> Result<bool, String> {
+    Err(format!(
+        "status: query socket {} unavailable on this platform",
+        path.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listed(names: &[&str]) -> Vec<(String, IpAddr)> {
+        names
+            .iter()
+            .map(|n| (n.to_string(), n.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn reloaded_hosts_keep_their_state() {
+        let mut hosts = HashMap::new();
+        let seen = Host {
+            up: Some(false),
+            checked: 200,
+            changed: 100,
+            smoothed: Some(1.5),
+            history: VecDeque::from([Some(1.5), None]),
+            ..Host::default()
+        };
+        hosts.insert("10.0.0.1".to_string(), seen);
+        hosts.insert("10.0.0.2".to_string(), Host::default());
+
+        let (added, dropped) = retarget(&mut hosts, &listed(&["10.0.0.1", "10.0.0.3"]));
+        assert_eq!((added, dropped), (1, 1));
+        assert_eq!(hosts.len(), 2);
+        let kept = &hosts["10.0.0.1"];
+        assert_eq!(kept.up, Some(false));
+        assert_eq!((kept.checked, kept.changed), (200, 100));
+        assert_eq!(kept.smoothed, Some(1.5));
+        assert_eq!(kept.history, [Some(1.5), None]);
+        assert_eq!(hosts["10.0.0.3"].up, None);
+        assert!(!hosts.contains_key("10.0.0.2"));
+    }
+
+    #[test]
+    fn addresses_are_listed_once() {
+        let targets = ["10.0.0.1", "2001:db8::1", "10.0.0.2", "10.0.0.1"].map(String::from);
+        assert_eq!(
+            addresses(&targets).unwrap(),
+            listed(&["10.0.0.1", "2001:db8::1", "10.0.0.2"])
+        );
+        assert!(addresses(&["10.0.0.300".to_string()]).is_err());
+    }
+}
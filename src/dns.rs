@@ -0,0 +1,520 @@
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::Error;
+
+/* -------------------- minimal stub resolver (--dns-server) -------------------- */
+
+// Plain UDP queries with recursion desired; no TCP fallback for truncated
+// answers, no caching, no EDNS.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resolver {
+    pub server: SocketAddr,
+    pub timeout: Duration,
+}
+
+impl Resolver {
+    pub fn new(server: SocketAddr) -> Self {
+        Resolver {
+            server,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    // First `nameserver` line of /etc/resolv.conf, for reverse lookups when
+    // no --dns-server was given
+    pub fn system() -> Option<Self> {
+        let conf = fs::read_to_string("/etc/resolv.conf").ok()?;
+        conf.lines()
+            .filter_map(|l| l.trim().strip_prefix("nameserver"))
+            .find_map(|v| v.trim().split('%').next()?.parse::<IpAddr>().ok())
+            .map(|ip| Resolver::new(SocketAddr::new(ip, 53)))
+    }
+
+    // A and AAAA records for `host`, IPv4 first
+    pub fn lookup(&self, host: &str) -> Result<Vec<IpAddr>, Error> {
+        let mut addrs = Vec::new();
+        for qtype in [TYPE_A, TYPE_AAAA] {
+            for rdata in self.query(host, qtype)? {
+                match rdata.len() {
+                    4 => addrs.push(IpAddr::V4(Ipv4Addr::new(
+                        rdata[0], rdata[1], rdata[2], rdata[3],
+                    ))),
+                    16 => {
+                        let octets: [u8; 16] = rdata[..].try_into().unwrap_or_default();
+                        addrs.push(IpAddr::V6(Ipv6Addr::from(octets)));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if addrs.is_empty() {
+            return Err(resolve_error(host, "no address records"));
+        }
+        Ok(addrs)
+    }
+
+    // PTR name for `ip`, or None when the zone has none
+    pub fn reverse(&self, ip: IpAddr) -> Result<Option<String>, Error> {
+        let name = match ip {
+            IpAddr::V4(v4) => {
+                let o = v4.octets();
+                format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+            }
+            IpAddr::V6(v6) => {
+                let mut name = String::new();
+                for b in v6.octets().iter().rev() {
+                    name.push_str(&format!("{:x}.{:x}.", b & 0xf, b >> 4));
+                }
+                name + "ip6.arpa"
+            }
+        };
+        match self.query_raw(&name, TYPE_PTR) {
+            Ok((msg, answers)) => Ok(answers
+                .first()
+                .and_then(|&(start, _)| read_name(&msg, start).map(|(n, _)| n))),
+            Err(Rcode::NxDomain) => Ok(None),
+            Err(e) => Err(resolve_error(&ip.to_string(), &e.to_string())),
+        }
+    }
+
+    // TXT records of `name`, each record's strings joined; empty when none
+    pub fn txt(&self, name: &str) -> Result<Vec<String>, Error> {
+        let (msg, answers) = match self.query_raw(name, TYPE_TXT) {
+            Ok(r) => r,
+            Err(Rcode::NxDomain) => return Ok(Vec::new()),
+            Err(e) => return Err(resolve_error(name, &e.to_string())),
+        };
+        Ok(answers
+            .into_iter()
+            .map(|(start, len)| {
+                let rdata = &msg[start..start + len];
+                let mut text = String::new();
+                let mut rest = rdata;
+                while let Some((&len, tail)) = rest.split_first() {
+                    let len = (len as usize).min(tail.len());
+                    text.push_str(&String::from_utf8_lossy(&tail[..len]));
+                    rest = &tail[len..];
+                }
+                text
+            })
+            .collect())
+    }
+
+    fn query(&self, host: &str, qtype: u16) -> Result<Vec<Vec<u8>>, Error> {
+        match self.query_raw(host, qtype) {
+            Ok((msg, answers)) => Ok(answers
+                .into_iter()
+                .map(|(start, len)| msg[start..start + len].to_vec())
+                .collect()),
+            Err(Rcode::NxDomain) => Err(resolve_error(host, "no such host")),
+            Err(e) => Err(resolve_error(host, &e.to_string())),
+        }
+    }
+
+    fn query_raw(&self, name: &str, qtype: u16) -> Result<Response, Rcode> {
+        let id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos() as u16 ^ (d.as_secs() as u16));
+        let packet = encode_query(id, name, qtype).ok_or(Rcode::BadName)?;
+
+        let bind: SocketAddr = if self.server.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let sock = UdpSocket::bind(bind).map_err(|e| Rcode::Io(e.to_string()))?;
+        sock.connect(self.server)
+            .map_err(|e| Rcode::Io(e.to_string()))?;
+        sock.send(&packet).map_err(|e| Rcode::Io(e.to_string()))?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut buf = vec![0u8; 4096];
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(Rcode::Timeout);
+            }
+            let _ = sock.set_read_timeout(Some(left));
+            let n = match sock.recv(&mut buf) {
+                Ok(n) => n,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Err(Rcode::Timeout);
+                }
+                Err(e) => return Err(Rcode::Io(e.to_string())),
+            };
+            // Ignore stray datagrams that don't answer this query
+            if n < 12 || u16::from_be_bytes([buf[0], buf[1]]) != id || buf[2] & 0x80 == 0 {
+                continue;
+            }
+            let msg = buf[..n].to_vec();
+            return match msg[3] & 0x0f {
+                0 => parse_answers(&msg, qtype)
+                    .map(|a| (msg, a))
+                    .ok_or(Rcode::Malformed),
+                3 => Err(Rcode::NxDomain),
+                2 => Err(Rcode::ServFail),
+                5 => Err(Rcode::Refused),
+                _ => Err(Rcode::Malformed),
+            };
+        }
+    }
+}
+
+// The response and the (offset, length) of each answer's rdata of the query type
+type Response = (Vec<u8>, Vec<(usize, usize)>);
+
+// Why a query failed
+enum Rcode {
+    NxDomain,
+    ServFail,
+    Refused,
+    Timeout,
+    Malformed,
+    BadName,
+    Io(String),
+}
+
+impl std::fmt::Display for Rcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rcode::NxDomain => f.write_str("no such host"),
+            Rcode::ServFail => f.write_str("server failure"),
+            Rcode::Refused => f.write_str("query refused"),
+            Rcode::Timeout => f.write_str("timed out"),
+            Rcode::Malformed => f.write_str("malformed response"),
+            Rcode::BadName => f.write_str("not a valid hostname"),
+            Rcode::Io(e) => f.write_str(e),
+        }
+    }
+}
+
+fn resolve_error(host: &str, reason: &str) -> Error {
+    Error::Resolve {
+        host: host.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+// "10.0.0.53", "10.0.0.53:5353", "fd00::53" or "[fd00::53]:5353"
+pub fn parse_server(s: &str) -> Result<SocketAddr, Error> {
+    s.parse::<SocketAddr>()
+        .or_else(|_| s.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| Error::Parse {
+            input: s.to_string(),
+            expected: "DNS server (ip[:port])",
+        })
+}
+
+// Letters, digits, '-', '_' and dots, with sane label lengths once any
+// Unicode labels are in punycode; labels can't start or end with '-' (which
+// also keeps stray flags out)
+pub fn is_hostname(s: &str) -> bool {
+    let Some(s) = to_ascii(s) else {
+        return false;
+    };
+    let s = s.strip_suffix('.').unwrap_or(&s);
+    !s.is_empty()
+        && s.len() <= 253
+        && s.split('.').all(|l| {
+            !l.is_empty()
+                && l.len() <= 63
+                && !l.starts_with('-')
+                && !l.ends_with('-')
+                && l.bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        })
+        && !s.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+}
+
+// Addresses for `host`, IPv4 first: via `resolver` if given (which carries
+// its own timeout), else the system resolver, abandoned after `timeout`
+pub fn resolve(
+    host: &str,
+    resolver: Option<&Resolver>,
+    timeout: Duration,
+) -> Result<Vec<IpAddr>, Error> {
+    if let Some(r) = resolver {
+        return r.lookup(host);
+    }
+    // getaddrinfo can't be cancelled; a stuck lookup thread is left behind
+    let (tx, rx) = mpsc::channel();
+    let name = to_ascii(host).ok_or_else(|| resolve_error(host, &Rcode::BadName.to_string()))?;
+    thread::spawn(move || {
+        let _ = tx.send(
+            (name.as_str(), 0)
+                .to_socket_addrs()
+                .map(|a| a.collect::<Vec<_>>()),
+        );
+    });
+    let mut addrs: Vec<IpAddr> = match rx.recv_timeout(timeout) {
+        Ok(Ok(sas)) => sas.into_iter().map(|sa| sa.ip()).collect(),
+        Ok(Err(e)) => return Err(resolve_error(host, &e.to_string())),
+        Err(_) => return Err(resolve_error(host, "timed out")),
+    };
+    // One entry per address, not per socket type
+    let mut seen = Vec::with_capacity(addrs.len());
+    addrs.retain(|ip| {
+        !seen.contains(ip) && {
+            seen.push(*ip);
+            true
+        }
+    });
+    addrs.sort_by_key(|ip| ip.is_ipv6());
+    if addrs.is_empty() {
+        return Err(resolve_error(host, "no address records"));
+    }
+    Ok(addrs)
+}
+
+/* -------------------- internationalized names (IDNA) -------------------- */
+
+// Unicode labels go on the wire as "xn--" and their punycode (RFC 3492), so
+// "münchen-gw.example" is looked up as "xn--mnchen-gw-9db.example"; labels
+// are lowercased first, without the rest of nameprep's mappings.
+
+const BASE: u32 = 36;
+const TMIN: u32 = 1;
+const TMAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+// `name` with its Unicode labels in punycode (and the ideographic full stops
+// as dots); None when a label can't be encoded
+pub fn to_ascii(name: &str) -> Option<String> {
+    if name.is_ascii() {
+        return Some(name.to_string());
+    }
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '\u{3002}' | '\u{ff0e}' | '\u{ff61}' => '.',
+            c => c,
+        })
+        .collect();
+    let labels: Option<Vec<String>> = name
+        .split('.')
+        .map(|l| {
+            if l.is_ascii() {
+                Some(l.to_string())
+            } else if l.starts_with('-') || l.ends_with('-') {
+                None
+            } else {
+                punycode_encode(&l.to_lowercase()).map(|p| format!("xn--{p}"))
+            }
+        })
+        .collect();
+    Some(labels?.join("."))
+}
+
+// `name` as people read it: "xn--" labels decoded, anything else as it is
+pub fn to_unicode(name: &str) -> String {
+    if !name.contains("xn--") && !name.contains("XN--") {
+        return name.to_string();
+    }
+    name.split('.')
+        .map(|l| {
+            l.get(..4)
+                .filter(|p| p.eq_ignore_ascii_case("xn--"))
+                .and_then(|_| punycode_decode(&l[4..]))
+                .unwrap_or_else(|| l.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn adapt(delta: u32, points: u32, first: bool) -> u32 {
+    let mut delta = if first { delta / DAMP } else { delta / 2 };
+    delta += delta / points;
+    let mut k = 0;
+    while delta > ((BASE - TMIN) * TMAX) / 2 {
+        delta /= BASE - TMIN;
+        k += BASE;
+    }
+    k + (BASE - TMIN + 1) * delta / (delta + SKEW)
+}
+
+// The threshold for digit position `k`
+fn threshold(k: u32, bias: u32) -> u32 {
+    if k <= bias {
+        TMIN
+    } else if k >= bias + TMAX {
+        TMAX
+    } else {
+        k - bias
+    }
+}
+
+fn punycode_encode(label: &str) -> Option<String> {
+    let input: Vec<u32> = label.chars().map(u32::from).collect();
+    let mut out: String = label.chars().filter(char::is_ascii).collect();
+    let basic = out.len() as u32;
+    if basic > 0 {
+        out.push('-');
+    }
+    let digit = |d: u32| {
+        (if d < 26 {
+            b'a' + d as u8
+        } else {
+            b'0' + (d - 26) as u8
+        }) as char
+    };
+    let (mut n, mut delta, mut bias, mut h) = (INITIAL_N, 0u32, INITIAL_BIAS, basic);
+    while (h as usize) < input.len() {
+        let m = input.iter().copied().filter(|&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(h + 1)?)?;
+        n = m;
+        for &c in &input {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+            if c == n {
+                let (mut q, mut k) = (delta, BASE);
+                loop {
+                    let t = threshold(k, bias);
+                    if q < t {
+                        break;
+                    }
+                    out.push(digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                out.push(digit(q));
+                bias = adapt(delta, h + 1, h == basic);
+                delta = 0;
+                h += 1;
+            }
+        }
+        delta += 1;
+        n += 1;
+    }
+    Some(out)
+}
+
+fn punycode_decode(encoded: &str) -> Option<String> {
+    let (basic, digits) = match encoded.rfind('-') {
+        Some(i) => (&encoded[..i], &encoded[i + 1..]),
+        None => ("", encoded),
+    };
+    if !basic.is_ascii() {
+        return None;
+    }
+    let mut out: Vec<char> = basic.chars().collect();
+    let (mut n, mut i, mut bias) = (INITIAL_N, 0u32, INITIAL_BIAS);
+    let mut digits = digits.bytes().peekable();
+    while digits.peek().is_some() {
+        let (old, mut w, mut k) = (i, 1u32, BASE);
+        loop {
+            let d = match digits.next()? {
+                b @ b'a'..=b'z' => b - b'a',
+                b @ b'A'..=b'Z' => b - b'A',
+                b @ b'0'..=b'9' => b - b'0' + 26,
+                _ => return None,
+            } as u32;
+            i = i.checked_add(d.checked_mul(w)?)?;
+            let t = threshold(k, bias);
+            if d < t {
+                break;
+            }
+            w = w.checked_mul(BASE - t)?;
+            k += BASE;
+        }
+        let len = out.len() as u32 + 1;
+        bias = adapt(i - old, len, old == 0);
+        n = n.checked_add(i / len)?;
+        i %= len;
+        out.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+    }
+    Some(out.into_iter().collect())
+}
+
+/* -------------------- wire format -------------------- */
+
+fn encode_query(id: u16, name: &str, qtype: u16) -> Option<Vec<u8>> {
+    let name = to_ascii(name)?;
+    let mut p = Vec::with_capacity(name.len() + 18);
+    p.extend_from_slice(&id.to_be_bytes());
+    p.extend_from_slice(&[0x01, 0x00]); // RD
+    p.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // QDCOUNT=1
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return None;
+        }
+        p.push(label.len() as u8);
+        p.extend_from_slice(label.as_bytes());
+    }
+    p.push(0);
+    p.extend_from_slice(&qtype.to_be_bytes());
+    p.extend_from_slice(&[0, 1]); // IN
+    Some(p)
+}
+
+// Decode a possibly compressed name at `pos`; returns it and the offset just
+// past it in the original (uncompressed) position
+fn read_name(msg: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut name = String::new();
+    let mut end = None;
+    for _ in 0..128 {
+        let len = *msg.get(pos)? as usize;
+        if len & 0xc0 == 0xc0 {
+            let ptr = (len & 0x3f) << 8 | *msg.get(pos + 1)? as usize;
+            end.get_or_insert(pos + 2);
+            pos = ptr;
+            continue;
+        }
+        if len == 0 {
+            return Some((name, end.unwrap_or(pos + 1)));
+        }
+        let label = msg.get(pos + 1..pos + 1 + len)?;
+        if !name.is_empty() {
+            name.push('.');
+        }
+        name.push_str(&String::from_utf8_lossy(label));
+        pos += 1 + len;
+    }
+    None // pointer loop
+}
+
+fn parse_answers(msg: &[u8], qtype: u16) -> Option<Vec<(usize, usize)>> {
+    let count = |i: usize| u16::from_be_bytes([msg[i], msg[i + 1]]) as usize;
+    let (qd, an) = (count(4), count(6));
+    let mut pos = 12;
+    for _ in 0..qd {
+        pos = read_name(msg, pos)?.1 + 4;
+    }
+    let mut out = Vec::new();
+    for _ in 0..an {
+        pos = read_name(msg, pos)?.1;
+        let head = msg.get(pos..pos + 10)?;
+        let rtype = u16::from_be_bytes([head[0], head[1]]);
+        let len = u16::from_be_bytes([head[8], head[9]]) as usize;
+        pos += 10;
+        msg.get(pos..pos + len)?;
+        // CNAMEs in front of the records are followed implicitly
+        if rtype == qtype {
+            out.push((pos, len));
+        }
+        pos += len;
+    }
+    Some(out)
+}
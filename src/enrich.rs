@@ -0,0 +1,107 @@
+use crate::geoip;
+use ipchk::dns::Resolver;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/* -------------------- per-address annotations (--rdns, --asn, --geoip, --zone-file, labels) -------------------- */
+
+#[derive(Debug, Default, Clone)]
+pub struct Enrich {
+    pub rdns: Option<Resolver>,                        // --rdns: PTR names
+    pub asn: Option<Resolver>, // --asn: origin AS via Team Cymru's DNS interface
+    pub geoip: Option<Arc<geoip::Db>>, // --geoip: country/city from a local MaxMind DB
+    pub names: Option<Arc<HashMap<Ipv4Addr, String>>>, // --zone-file owner names
+    pub labels: Option<Arc<HashMap<String, String>>>, // target=label and label columns, by target
+}
+
+impl Enrich {
+    // Name to show for `ip`: its zone-file owner(s), else its PTR record.
+    // A failed lookup just means no name to show.
+    pub fn name(&self, ip: Ipv4Addr) -> Option<String> {
+        if let Some(name) = self.names.as_ref().and_then(|n| n.get(&ip)) {
+            return Some(name.clone());
+        }
+        self.rdns?.reverse(IpAddr::V4(ip)).ok().flatten()
+    }
+
+    // The label `target` was given, if any
+    pub fn label(&self, target: &str) -> Option<String> {
+        self.labels.as_ref()?.get(target).cloned()
+    }
+
+    // Short notes to append to a verdict, e.g. "AS13335 CLOUDFLARENET, US"
+    pub fn notes(&self, ip: Ipv4Addr) -> Vec<String> {
+        let mut notes = Vec::new();
+        if !is_public(ip) {
+            return notes;
+        }
+        if let Some(r) = &self.asn
+            && let Some(asn) = origin_as(r, ip)
+        {
+            notes.push(asn);
+        }
+        if let Some(place) = self.geoip.as_ref().and_then(|db| db.describe(ip)) {
+            notes.push(place);
+        }
+        notes
+    }
+}
+
+// Ipv4Addr::is_global is still unstable
+pub fn is_public(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || (a == 100 && (64..128).contains(&b)) // CGNAT
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (18..20).contains(&b)) // benchmarking
+        || a >= 240)
+}
+
+// "13335 | 1.1.1.0/24 | AU | apnic | 2011-08-11" from
+// 1.1.1.1 -> 1.1.1.1.origin.asn.cymru.com
+fn origin_as(r: &Resolver, ip: Ipv4Addr) -> Option<String> {
+    let o = ip.octets();
+    let query = format!("{}.{}.{}.{}.origin.asn.cymru.com", o[3], o[2], o[1], o[0]);
+    let txt = r.txt(&query).ok()?;
+    // Prefixes announced by several origins list them space-separated
+    let asn = txt
+        .first()?
+        .split('|')
+        .next()?
+        .split_whitespace()
+        .next()?
+        .to_string();
+    Some(match as_name(r, &asn) {
+        Some(name) => format!("AS{asn} {name}"),
+        None => format!("AS{asn}"),
+    })
+}
+
+// "13335 | US | arin | 2010-07-14 | CLOUDFLARENET, US" from AS13335.asn.cymru.com,
+// remembered per process since a sweep usually hits few distinct ASes
+fn as_name(r: &Resolver, asn: &str) -> Option<String> {
+    static NAMES: OnceLock<Mutex<HashMap<String, Option<String>>>> = OnceLock::new();
+    let names = NAMES.get_or_init(Default::default);
+    if let Some(name) = names.lock().unwrap_or_else(|e| e.into_inner()).get(asn) {
+        return name.clone();
+    }
+    let name = r
+        .txt(&format!("AS{asn}.asn.cymru.com"))
+        .ok()
+        .and_then(|txt| Some(txt.first()?.rsplit('|').next()?.trim().to_string()))
+        .filter(|n| !n.is_empty());
+    names
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(asn.to_string(), name.clone());
+    name
+}
@@ -0,0 +1,173 @@
+use crate::{PingResult, notify::Change};
+
+/* -------------------- Windows Event Log (--eventlog) -------------------- */
+
+// State changes and sweep summaries as events from source "ipchk" in the
+// Application log, each kind with an event ID of its own for monitoring
+// tools and GPO-driven forwarding to match on. The text is the event's one
+// insertion string, so Event Viewer shows it as it is once the source is
+// registered (New-EventLog -LogName Application -Source ipchk).
+
+pub const SUMMARY: u32 = 1000; // a sweep's (or a daemon sweep with news) totals
+pub const HOST_UP: u32 = 1001; // daemon: a host came (back) up
+pub const HOST_DOWN: u32 = 1002; // daemon: a host went down
+
+// How many addresses a summary lists by name
+const LISTED: usize = 50;
+
+#[derive(Debug)]
+pub struct EventLog {
+    #[cfg(windows)]
+    handle: windows_sys::Win32::Foundation::HANDLE,
+}
+
+// An event source handle may be used from any thread
+#[cfg(windows)]
+unsafe impl Send for EventLog {}
+#[cfg(windows)]
+unsafe impl Sync for EventLog {}
+
+#[derive(Clone, Copy)]
+enum Level {
+    Information,
+    Warning,
+}
+
+impl EventLog {
+    #[cfg(windows)]
+    pub fn open() -> Result<Self, String> {
+        use windows_sys::Win32::System::EventLog::RegisterEventSourceW;
+        let source: Vec<u16> = "ipchk".encode_utf16().chain([0]).collect();
+        let handle = unsafe { RegisterEventSourceW(std::ptr::null(), source.as_ptr()) };
+        if handle.is_null() {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        Ok(EventLog { handle })
+    }
+
+    #[cfg(not(windows))]
+    pub fn open() -> Result<Self, String> {
+        Err("the Windows Event Log is only on Windows".into())
+    }
+
+    // A sweep's totals, naming the hosts that were down or not probed
+    pub fn sweep(&self, results: &[PingResult]) -> Result<(), String> {
+        let up = results.iter().filter(|r| r.up == Some(true)).count();
+        let down: Vec<&str> = results
+            .iter()
+            .filter(|r| r.up == Some(false))
+            .map(|r| r.ip.as_str())
+            .collect();
+        let failed: Vec<&str> = results
+            .iter()
+            .filter(|r| r.up.is_none())
+            .map(|r| r.ip.as_str())
+            .collect();
+        let mut text = format!(
+            "Sweep of {} targets: {up} up, {} down, {} not probed",
+            results.len(),
+            down.len(),
+            failed.len()
+        );
+        for (what, list) in [("Down", &down), ("Not probed", &failed)] {
+            if !list.is_empty() {
+                text.push_str(&format!("\r\n{what}: {}", listed(list)));
+            }
+        }
+        let level = if down.is_empty() && failed.is_empty() {
+            Level::Information
+        } else {
+            Level::Warning
+        };
+        self.report(level, SUMMARY, &text)
+    }
+
+    // The daemon's totals after a sweep that changed something
+    pub fn daemon_sweep(&self, up: usize, total: usize, changes: &[Change]) -> Result<(), String> {
+        let mut text = format!("{up} of {total} hosts up");
+        for (what, state) in [("Went down", false), ("Came up", true)] {
+            let described: Vec<String> = changes
+                .iter()
+                .filter(|c| c.up == state)
+                .map(|c| format!("{}{}", c.target, c.downtime_note()))
+                .collect();
+            let list: Vec<&str> = described.iter().map(String::as_str).collect();
+            if !list.is_empty() {
+                text.push_str(&format!("\r\n{what}: {}", listed(&list)));
+            }
+        }
+        let level = if up == total {
+            Level::Information
+        } else {
+            Level::Warning
+        };
+        self.report(level, SUMMARY, &text)
+    }
+
+    // A daemon state change (not a host's first verdict)
+    pub fn change(&self, change: &Change) -> Result<(), String> {
+        let target = &change.target;
+        if change.up {
+            self.report(
+                Level::Information,
+                HOST_UP,
+                &format!("{target} is up again{}", change.downtime_note()),
+            )
+        } else {
+            self.report(Level::Warning, HOST_DOWN, &format!("{target} went down"))
+        }
+    }
+
+    #[cfg(windows)]
+    fn report(&self, level: Level, id: u32, text: &str) -> Result<(), String> {
+        use windows_sys::Win32::System::EventLog::{
+            EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE, ReportEventW,
+        };
+        let kind = match level {
+            Level::Information => EVENTLOG_INFORMATION_TYPE,
+            Level::Warning => EVENTLOG_WARNING_TYPE,
+        };
+        let wide: Vec<u16> = text.encode_utf16().chain([0]).collect();
+        let strings = [wide.as_ptr()];
+        let ok = unsafe {
+            ReportEventW(
+                self.handle,
+                kind,
+                0,
+                id,
+                std::ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                std::ptr::null(),
+            )
+        };
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    fn report(&self, _: Level, _: u32, _: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        unsafe {
+            windows_sys::Win32::System::EventLog::DeregisterEventSource(self.handle);
+        }
+    }
+}
+
+// "10.0.0.5, 10.0.0.9 and 3 more"
+fn listed(names: &[&str]) -> String {
+    let shown = names[..names.len().min(LISTED)].join(", ");
+    match names.len().saturating_sub(LISTED) {
+        0 => shown,
+        more => format!("{shown} and {more} more"),
+    }
+}
@@ -0,0 +1,231 @@
+use std::{fs, net::Ipv4Addr, path::Path};
+
+/* -------------------- MaxMind DB reader (--geoip) -------------------- */
+
+// Just enough of the MaxMind DB format to read country/city out of
+// GeoLite2/GeoIP2 City and Country databases; the whole file is read into
+// memory once at startup.
+const METADATA_MARKER: &[u8] = b"\xab\xcd\xefMaxMind.com";
+
+#[derive(Debug)]
+pub struct Db {
+    buf: Vec<u8>,
+    node_count: usize,
+    record_size: usize, // bits: 24, 28 or 32
+    data_start: usize,
+    ipv4_start: usize, // node after 96 zero bits in an IPv6 tree
+}
+
+#[derive(Debug)]
+enum Value {
+    Str(String),
+    Uint(u128),
+    Map(Vec<(String, Value)>),
+    Other,
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(m) => m.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn text(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn uint(&self) -> Option<usize> {
+        match self {
+            Value::Uint(n) => usize::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl Db {
+    pub fn open(path: &Path) -> Result<Db, String> {
+        let buf = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let bad = || format!("{}: not a MaxMind DB file", path.display());
+
+        let meta_at = buf
+            .windows(METADATA_MARKER.len())
+            .rposition(|w| w == METADATA_MARKER)
+            .ok_or_else(bad)?
+            + METADATA_MARKER.len();
+        let mut db = Db {
+            buf,
+            node_count: 0,
+            record_size: 0,
+            data_start: 0,
+            ipv4_start: 0,
+        };
+        let (meta, _) = db.decode(meta_at, meta_at).ok_or_else(bad)?;
+        db.node_count = meta
+            .get("node_count")
+            .and_then(Value::uint)
+            .ok_or_else(bad)?;
+        db.record_size = meta
+            .get("record_size")
+            .and_then(Value::uint)
+            .ok_or_else(bad)?;
+        if ![24, 28, 32].contains(&db.record_size) {
+            return Err(format!(
+                "{}: unsupported record size {}",
+                path.display(),
+                db.record_size
+            ));
+        }
+        db.data_start = db.record_size * 2 / 8 * db.node_count + 16;
+        if db.data_start > meta_at {
+            return Err(bad());
+        }
+
+        if meta.get("ip_version").and_then(Value::uint) == Some(6) {
+            let mut node = 0;
+            for _ in 0..96 {
+                if node >= db.node_count {
+                    break;
+                }
+                node = db.record(node, 0).ok_or_else(bad)?;
+            }
+            db.ipv4_start = node;
+        }
+        Ok(db)
+    }
+
+    // "US, Mountain View", "AU", or None when the address isn't listed
+    pub fn describe(&self, ip: Ipv4Addr) -> Option<String> {
+        let rec = self.lookup(ip)?;
+        let country = rec
+            .get("country")
+            .and_then(|c| c.get("iso_code"))
+            .and_then(Value::text);
+        let city = rec
+            .get("city")
+            .and_then(|c| c.get("names"))
+            .and_then(|n| n.get("en"))
+            .and_then(Value::text);
+        match (country, city) {
+            (Some(c), Some(city)) => Some(format!("{c}, {city}")),
+            (Some(c), None) => Some(c.to_string()),
+            (None, Some(city)) => Some(city.to_string()),
+            (None, None) => None,
+        }
+    }
+
+    fn lookup(&self, ip: Ipv4Addr) -> Option<Value> {
+        let bits = u32::from(ip);
+        let mut node = self.ipv4_start;
+        for i in 0..32 {
+            if node >= self.node_count {
+                break;
+            }
+            node = self.record(node, (bits >> (31 - i)) & 1)?;
+        }
+        if node <= self.node_count {
+            return None; // == node_count: no data for this address
+        }
+        let at = self.data_start + (node - self.node_count - 16);
+        self.decode(at, self.data_start).map(|(v, _)| v)
+    }
+
+    // Left (0) or right (1) record of `node`
+    fn record(&self, node: usize, bit: u32) -> Option<usize> {
+        let size = self.record_size * 2 / 8;
+        let n = self.buf.get(node * size..node * size + size)?;
+        let be = |b: &[u8]| b.iter().fold(0usize, |acc, &x| acc << 8 | x as usize);
+        Some(match (self.record_size, bit) {
+            (24, 0) => be(&n[..3]),
+            (24, _) => be(&n[3..]),
+            (28, 0) => (n[3] as usize >> 4) << 24 | be(&n[..3]),
+            (28, _) => (n[3] as usize & 0x0f) << 24 | be(&n[4..]),
+            (_, 0) => be(&n[..4]),
+            (_, _) => be(&n[4..]),
+        })
+    }
+
+    // Decode the value at `at`; pointers are relative to `base`. Returns the
+    // value and the offset just past it.
+    fn decode(&self, mut at: usize, base: usize) -> Option<(Value, usize)> {
+        let b = &self.buf;
+        let ctrl = *b.get(at)?;
+        at += 1;
+        let mut kind = ctrl >> 5;
+
+        if kind == 1 {
+            // Pointer: follow it, but continue after the pointer itself
+            let ss = (ctrl >> 3 & 3) as usize;
+            let v = (ctrl & 7) as usize;
+            let p = b.get(at..at + ss + 1)?;
+            let be = p.iter().fold(0usize, |acc, &x| acc << 8 | x as usize);
+            let target = match ss {
+                0 => v << 8 | be,
+                1 => (v << 16 | be) + 2048,
+                2 => (v << 24 | be) + 526_336,
+                _ => be,
+            };
+            let (value, _) = self.decode(base + target, base)?;
+            return Some((value, at + ss + 1));
+        }
+        if kind == 0 {
+            kind = 7 + *b.get(at)?;
+            at += 1;
+        }
+
+        let mut size = (ctrl & 0x1f) as usize;
+        if size >= 29 {
+            let extra = size - 28;
+            let be = b
+                .get(at..at + extra)?
+                .iter()
+                .fold(0usize, |acc, &x| acc << 8 | x as usize);
+            size = match extra {
+                1 => 29 + be,
+                2 => 285 + be,
+                _ => 65_821 + be,
+            };
+            at += extra;
+        }
+
+        match kind {
+            2 => {
+                let s = String::from_utf8_lossy(b.get(at..at + size)?).into_owned();
+                Some((Value::Str(s), at + size))
+            }
+            5 | 6 | 9 | 10 => {
+                let n = b
+                    .get(at..at + size)?
+                    .iter()
+                    .fold(0u128, |acc, &x| acc << 8 | x as u128);
+                Some((Value::Uint(n), at + size))
+            }
+            7 => {
+                let mut map = Vec::with_capacity(size);
+                for _ in 0..size {
+                    let (key, next) = self.decode(at, base)?;
+                    let (value, next) = self.decode(next, base)?;
+                    if let Value::Str(key) = key {
+                        map.push((key, value));
+                    }
+                    at = next;
+                }
+                Some((Value::Map(map), at))
+            }
+            11 => {
+                for _ in 0..size {
+                    at = self.decode(at, base)?.1;
+                }
+                Some((Value::Other, at))
+            }
+            3 => Some((Value::Other, at + 8)),
+            15 => Some((Value::Other, at + 4)),
+            14 => Some((Value::Other, at)), // boolean: the size is the value
+            _ => Some((Value::Other, at + size)),
+        }
+    }
+}
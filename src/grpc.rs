@@ -0,0 +1,493 @@
+use std::{
+    io,
+    net::{Ipv4Addr, TcpListener},
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::h2::{self, Request, Responder};
+use crate::{DEFAULT_TCP_PORT, agent, engine_error, rfc3339_utc, rfc3339_utc_ms};
+use ipchk::{Builtin, MAX_EXPANSION, ProbeOptions, ProbeOutcome, ProbeType, Target};
+
+/* -------------------- ipchk grpc: sweeps as a gRPC service -------------------- */
+
+// The Scanner service of proto/ipchk.proto over cleartext HTTP/2. StartScan
+// runs a sweep in the background; StreamResults sends its results as each
+// batch finishes and GetStatus says how far it has got. Messages are
+// encoded and decoded by hand, as the few fields there are need no more.
+
+pub const DEFAULT_PORT: u16 = 50051;
+
+// Scans probing at once, and scans kept for StreamResults and GetStatus
+// (finished ones go oldest first)
+const MAX_RUNNING: usize = 4;
+const MAX_KEPT: usize = 64;
+
+const INVALID_ARGUMENT: u32 = 3;
+const NOT_FOUND: u32 = 5;
+const RESOURCE_EXHAUSTED: u32 = 8;
+const FAILED_PRECONDITION: u32 = 9;
+const UNIMPLEMENTED: u32 = 12;
+const INTERNAL: u32 = 13;
+
+// A call that failed: the gRPC status code and message
+type Failure = (u32, String);
+
+pub struct Config {
+    pub probe: ProbeOptions, // what a ScanRequest doesn't override
+    pub require: u32,
+    pub concurrency: usize, // and the most a ScanRequest may ask for
+}
+
+struct Server {
+    cfg: Config,
+    scans: Mutex<Vec<Arc<Scan>>>,
+    next: AtomicU64,
+}
+
+struct Scan {
+    id: String,
+    targets: Vec<Ipv4Addr>,
+    started: SystemTime,
+    clock: Instant,
+    progress: Mutex<Progress>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct Progress {
+    results: Vec<Found>,
+    finished: Option<Duration>, // how long it took, once it has
+}
+
+// One target's HostResult, encoded as it will be sent
+struct Found {
+    up: bool,
+    message: Vec<u8>,
+}
+
+impl Scan {
+    fn progress(&self) -> std::sync::MutexGuard<'_, Progress> {
+        self.progress.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Serves until killed, each connection on a thread of its own
+pub fn listen(spec: &str, cfg: Config) -> Result<(), String> {
+    let addr = agent::address_on(spec, DEFAULT_PORT)?;
+    let listener = TcpListener::bind(addr).map_err(|e| format!("grpc: {addr}: {e}"))?;
+    eprintln!("ipchk grpc: listening on {addr}");
+    let server = Arc::new(Server {
+        cfg,
+        scans: Mutex::new(Vec::new()),
+        next: AtomicU64::new(1),
+    });
+    let handle = Arc::new(move |req: Request, out: Responder| {
+        // Failing to answer means the client has gone
+        let _ = server.call(&req, &out);
+    });
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let handle = Arc::clone(&handle);
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map_or_else(|_| "?".to_string(), |a| a.to_string());
+            if let Err(e) = h2::serve(stream, handle) {
+                eprintln!("ipchk grpc: {peer}: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+impl Server {
+    fn call(&self, req: &Request, out: &Responder) -> io::Result<()> {
+        let grpc = req
+            .header("content-type")
+            .is_some_and(|t| t == "application/grpc" || t.starts_with("application/grpc+"));
+        if req.header(":method") != Some("POST") || !grpc {
+            return out.headers(&[(":status", "415")], true);
+        }
+        let path = req.header(":path").unwrap_or_default();
+        let message = match unframe(&req.body) {
+            Ok(m) => m,
+            Err(failure) => return fail(out, failure),
+        };
+        let answer = match path {
+            "/ipchk.v1.Scanner/StartScan" => self.start(message).map(|id| handle(&id)),
+            "/ipchk.v1.Scanner/GetStatus" => self.find(message).map(|scan| status(&scan)),
+            "/ipchk.v1.Scanner/StreamResults" => {
+                return match self.find(message) {
+                    Ok(scan) => stream(&scan, out),
+                    Err(failure) => fail(out, failure),
+                };
+            }
+            _ => Err((UNIMPLEMENTED, format!("unknown method {path}"))),
+        };
+        match answer {
+            Ok(message) => {
+                out.headers(RESPONSE, false)?;
+                out.data(&frame(&message))?;
+                out.headers(&[("grpc-status", "0")], true)
+            }
+            Err(failure) => fail(out, failure),
+        }
+    }
+
+    // StartScan: checks the request against the server's options and
+    // starts probing; returns the scan's id
+    fn start(&self, message: &[u8]) -> Result<String, Failure> {
+        let invalid = |e: String| (INVALID_ARGUMENT, e);
+        let mut opts = self.cfg.probe.clone();
+        let mut concurrency = self.cfg.concurrency;
+        let (mut targets, mut ports) = (Vec::new(), Vec::new());
+        for (field, value) in fields(message)? {
+            match (field, value) {
+                (1, Value::Bytes(b)) => targets.push(text(b)?),
+                (2, Value::Varint(n)) if n > 0 => opts.count = number(n, "count")?,
+                (3, Value::Varint(n)) if n > 0 => opts.timeout = Duration::from_millis(n),
+                (4, Value::Varint(n)) if n > 0 => {
+                    concurrency = concurrency.min(number(n, "concurrency")?);
+                }
+                (5, Value::Bytes(b)) if !b.is_empty() => {
+                    opts.probe = text(b)?.parse::<ProbeType>().map_err(invalid)?;
+                }
+                (6, Value::Varint(n)) => ports.push(n),
+                // Repeated numbers come packed from proto3 encoders
+                (6, Value::Bytes(mut b)) => {
+                    while !b.is_empty() {
+                        ports.push(varint(&mut b)?);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !ports.is_empty() {
+            if opts.probe != ProbeType::Tcp {
+                return Err(invalid("ports only apply to the tcp probe".into()));
+            }
+            opts.ports = ports
+                .into_iter()
+                .map(|p| u16::try_from(p).ok().filter(|&p| p > 0))
+                .collect::<Option<_>>()
+                .ok_or_else(|| invalid("ports: 1 to 65535".into()))?;
+        } else if opts.probe == ProbeType::Tcp && opts.ports.is_empty() {
+            opts.ports = vec![DEFAULT_TCP_PORT];
+        }
+        let targets = expand(&targets).map_err(invalid)?;
+        ipchk::preflight(&opts).map_err(|e| (FAILED_PRECONDITION, engine_error(&e, &opts)))?;
+
+        let scan = {
+            let mut scans = self.scans.lock().unwrap_or_else(|e| e.into_inner());
+            let running = scans.iter().filter(|s| s.progress().finished.is_none());
+            if running.count() >= MAX_RUNNING {
+                return Err((
+                    RESOURCE_EXHAUSTED,
+                    format!("{MAX_RUNNING} scans are already running"),
+                ));
+            }
+            if scans.len() >= MAX_KEPT
+                && let Some(i) = scans.iter().position(|s| s.progress().finished.is_some())
+            {
+                scans.remove(i);
+            }
+            let scan = Arc::new(Scan {
+                id: self.next.fetch_add(1, Ordering::Relaxed).to_string(),
+                targets,
+                started: SystemTime::now(),
+                clock: Instant::now(),
+                progress: Mutex::new(Progress::default()),
+                changed: Condvar::new(),
+            });
+            scans.push(Arc::clone(&scan));
+            scan
+        };
+        let id = scan.id.clone();
+        let require = self.cfg.require;
+        thread::spawn(move || run(&scan, &opts, concurrency, require));
+        Ok(id)
+    }
+
+    // The scan a ScanHandle names
+    fn find(&self, message: &[u8]) -> Result<Arc<Scan>, Failure> {
+        let mut id = String::new();
+        for (field, value) in fields(message)? {
+            if let (1, Value::Bytes(b)) = (field, value) {
+                id = text(b)?;
+            }
+        }
+        let scans = self.scans.lock().unwrap_or_else(|e| e.into_inner());
+        scans
+            .iter()
+            .find(|s| s.id == id)
+            .cloned()
+            .ok_or_else(|| (NOT_FOUND, format!("no scan {id:?}")))
+    }
+}
+
+fn run(scan: &Scan, opts: &ProbeOptions, concurrency: usize, require: u32) {
+    let probe = Builtin::new(opts.clone());
+    let budget = Builtin::budget(opts);
+    for chunk in scan.targets.chunks(concurrency.max(1)) {
+        let targets: Vec<Target> = chunk.iter().map(|&a| Target::new(a)).collect();
+        let outcomes = ipchk::sweep(&probe, &targets, concurrency, budget);
+        let at = SystemTime::now();
+        let found: Vec<Found> = chunk
+            .iter()
+            .zip(outcomes)
+            .map(|(&addr, o)| result(addr, o.map(|o| o.require(require)), at))
+            .collect();
+        scan.progress().results.extend(found);
+        scan.changed.notify_all();
+    }
+    scan.progress().finished = Some(scan.clock.elapsed());
+    scan.changed.notify_all();
+}
+
+// Addresses and prefixes ("10.0.0.0/24"), IPv4 only
+fn expand(targets: &[String]) -> Result<Vec<Ipv4Addr>, String> {
+    let mut addrs = Vec::new();
+    for t in targets {
+        if t.contains('/') {
+            let cidr = ipchk::parse_cidr(t).map_err(|e| format!("{t}: {e}"))?;
+            addrs.extend(cidr.hosts().take(MAX_EXPANSION + 1));
+        } else {
+            addrs.push(
+                t.parse()
+                    .map_err(|_| format!("{t}: not an IPv4 address or prefix"))?,
+            );
+        }
+        if addrs.len() > MAX_EXPANSION {
+            return Err(format!("more than {MAX_EXPANSION} targets"));
+        }
+    }
+    if addrs.is_empty() {
+        return Err("no targets".into());
+    }
+    Ok(addrs)
+}
+
+/* ---- responses ---- */
+
+const RESPONSE: &[(&str, &str)] = &[(":status", "200"), ("content-type", "application/grpc")];
+
+// A trailers-only response: the status, without any messages
+fn fail(out: &Responder, (code, message): Failure) -> io::Result<()> {
+    let code = code.to_string();
+    let message = percent_encoded(&message);
+    let mut fields = RESPONSE.to_vec();
+    fields.extend([("grpc-status", code.as_str()), ("grpc-message", &message)]);
+    out.headers(&fields, true)
+}
+
+// StreamResults: every result so far, then the rest as they come
+fn stream(scan: &Scan, out: &Responder) -> io::Result<()> {
+    out.headers(RESPONSE, false)?;
+    let mut sent = 0;
+    loop {
+        let (batch, finished) = {
+            let mut p = scan.progress();
+            while p.results.len() == sent && p.finished.is_none() {
+                p = scan.changed.wait(p).unwrap_or_else(|e| e.into_inner());
+            }
+            let batch: Vec<u8> = p.results[sent..]
+                .iter()
+                .flat_map(|r| frame(&r.message))
+                .collect();
+            sent = p.results.len();
+            (batch, p.finished.is_some())
+        };
+        out.data(&batch)?;
+        if finished {
+            break;
+        }
+    }
+    out.headers(&[("grpc-status", "0")], true)
+}
+
+fn handle(id: &str) -> Vec<u8> {
+    let mut m = Vec::new();
+    put_str(&mut m, 1, id);
+    m
+}
+
+fn status(scan: &Scan) -> Vec<u8> {
+    let p = scan.progress();
+    let elapsed = p.finished.unwrap_or_else(|| scan.clock.elapsed());
+    let up = p.results.iter().filter(|r| r.up).count();
+    let mut m = Vec::new();
+    put_str(&mut m, 1, &scan.id);
+    put_str(
+        &mut m,
+        2,
+        if p.finished.is_some() {
+            "done"
+        } else {
+            "running"
+        },
+    );
+    put_varint(&mut m, 3, scan.targets.len() as u64);
+    put_varint(&mut m, 4, p.results.len() as u64);
+    put_varint(&mut m, 5, up as u64);
+    put_str(&mut m, 6, &rfc3339_utc(scan.started));
+    put_varint(&mut m, 7, elapsed.as_millis() as u64);
+    m
+}
+
+// A HostResult
+fn result(addr: Ipv4Addr, outcome: ProbeOutcome, at: SystemTime) -> Found {
+    let mut m = Vec::new();
+    put_str(&mut m, 1, &addr.to_string());
+    let up = match &outcome {
+        Ok(o) => {
+            put_str(&mut m, 2, if o.up { "up" } else { "down" });
+            put_varint(&mut m, 3, u64::from(o.stats.transmitted));
+            put_varint(&mut m, 4, u64::from(o.stats.received));
+            put_double(&mut m, 5, o.stats.loss);
+            if let Some(rtt) = o.stats.rtt {
+                put_double(&mut m, 6, rtt.avg.as_secs_f64() * 1000.0);
+            }
+            o.up
+        }
+        Err(e) => {
+            put_str(&mut m, 2, "error");
+            put_str(&mut m, 7, &e.to_string());
+            false
+        }
+    };
+    put_str(&mut m, 8, &rfc3339_utc_ms(at));
+    Found { up, message: m }
+}
+
+// grpc-message is percent-encoded, past printable ASCII
+fn percent_encoded(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b' '..=b'~' if b != b'%' => char::from(b).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/* ---- gRPC framing and protobuf ---- */
+
+// A length-prefixed message
+fn frame(message: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(5 + message.len());
+    out.push(0);
+    out.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    out.extend_from_slice(message);
+    out
+}
+
+// The one message of a unary request body
+fn unframe(body: &[u8]) -> Result<&[u8], Failure> {
+    let bad = || (INTERNAL, "malformed gRPC message".to_string());
+    let (head, rest) = body.split_at_checked(5).ok_or_else(bad)?;
+    if head[0] != 0 {
+        return Err((UNIMPLEMENTED, "compressed messages aren't supported".into()));
+    }
+    let len = u32::from_be_bytes([head[1], head[2], head[3], head[4]]) as usize;
+    if rest.len() != len {
+        return Err(bad());
+    }
+    Ok(rest)
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed, // 32 or 64 bits, which no request field is
+}
+
+// Every field of a message, in order
+fn fields(mut buf: &[u8]) -> Result<Vec<(u64, Value<'_>)>, Failure> {
+    let mut out = Vec::new();
+    while !buf.is_empty() {
+        let key = varint(&mut buf)?;
+        let value = match key & 7 {
+            0 => Value::Varint(varint(&mut buf)?),
+            1 | 5 => {
+                let n = if key & 7 == 1 { 8 } else { 4 };
+                buf = buf.get(n..).ok_or_else(malformed)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = usize::try_from(varint(&mut buf)?).map_err(|_| malformed())?;
+                let (bytes, rest) = buf.split_at_checked(len).ok_or_else(malformed)?;
+                buf = rest;
+                Value::Bytes(bytes)
+            }
+            _ => return Err(malformed()),
+        };
+        out.push((key >> 3, value));
+    }
+    Ok(out)
+}
+
+fn malformed() -> Failure {
+    (INTERNAL, "malformed protobuf message".into())
+}
+
+fn varint(buf: &mut &[u8]) -> Result<u64, Failure> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&b, rest) = buf.split_first().ok_or_else(malformed)?;
+        *buf = rest;
+        n |= u64::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(malformed())
+}
+
+fn text(b: &[u8]) -> Result<String, Failure> {
+    String::from_utf8(b.to_vec()).map_err(|_| malformed())
+}
+
+fn number<T: TryFrom<u64>>(n: u64, what: &str) -> Result<T, Failure> {
+    T::try_from(n).map_err(|_| (INVALID_ARGUMENT, format!("{what}: {n} is too large")))
+}
+
+fn put_key(m: &mut Vec<u8>, field: u64, wire: u64) {
+    put_raw_varint(m, field << 3 | wire);
+}
+
+fn put_raw_varint(m: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        m.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    m.push(n as u8);
+}
+
+// proto3 leaves out fields at their defaults
+fn put_varint(m: &mut Vec<u8>, field: u64, n: u64) {
+    if n != 0 {
+        put_key(m, field, 0);
+        put_raw_varint(m, n);
+    }
+}
+
+fn put_double(m: &mut Vec<u8>, field: u64, x: f64) {
+    if x != 0.0 {
+        put_key(m, field, 1);
+        m.extend_from_slice(&x.to_le_bytes());
+    }
+}
+
+fn put_str(m: &mut Vec<u8>, field: u64, s: &str) {
+    if !s.is_empty() {
+        put_key(m, field, 2);
+        put_raw_varint(m, s.len() as u64);
+        m.extend_from_slice(s.as_bytes());
+    }
+}
@@ -0,0 +1,653 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read, Write},
+    net::TcpStream,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+/* -------------------- cleartext HTTP/2, for the gRPC server -------------------- */
+
+// What gRPC needs of HTTP/2 with prior knowledge (h2c): each stream's
+// request headers and body, and a response of HEADERS, DATA and trailing
+// HEADERS frames within the client's flow-control windows. No TLS, server
+// push or priorities; responses only use HPACK literals, so there is no
+// dynamic table of our own to keep.
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY: u8 = 0x20;
+
+const SETTINGS_HEADER_TABLE_SIZE: u16 = 0x1;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+
+const PROTOCOL_ERROR: u32 = 0x1;
+const FLOW_CONTROL_ERROR: u32 = 0x3;
+const FRAME_SIZE_ERROR: u32 = 0x6;
+const COMPRESSION_ERROR: u32 = 0x9;
+
+// The defaults, which we never raise: frames up to 16 KiB, 64 KiB windows
+const MAX_FRAME: usize = 16_384;
+const INITIAL_WINDOW: i64 = 65_535;
+const TABLE_SIZE: usize = 4096;
+
+// gRPC requests here are a few hundred bytes
+const MAX_REQUEST: usize = 1 << 20;
+
+pub struct Request {
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// The writing half of a connection, shared by its streams
+struct Conn {
+    out: Mutex<TcpStream>,
+    windows: Mutex<Windows>,
+    changed: Condvar,
+}
+
+// How much DATA the client will take, on the connection and per open
+// stream; a stream that is gone (reset, or finished) has no entry
+struct Windows {
+    conn: i64,
+    streams: HashMap<u32, i64>,
+    initial: i64,
+    closed: bool,
+}
+
+impl Conn {
+    fn frame(&self, kind: u8, flags: u8, stream: u32, payload: &[u8]) -> io::Result<()> {
+        let len = payload.len() as u32;
+        let mut buf = Vec::with_capacity(9 + payload.len());
+        buf.extend_from_slice(&len.to_be_bytes()[1..]);
+        buf.extend_from_slice(&[kind, flags]);
+        buf.extend_from_slice(&stream.to_be_bytes());
+        buf.extend_from_slice(payload);
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        out.write_all(&buf)
+    }
+
+    fn windows(&self) -> std::sync::MutexGuard<'_, Windows> {
+        self.windows.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn gone() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionReset, "stream reset by the client")
+}
+
+// Writes the response on one stream
+pub struct Responder {
+    conn: Arc<Conn>,
+    stream: u32,
+}
+
+impl Responder {
+    // A HEADERS frame; `end` closes the stream, as trailers do
+    pub fn headers(&self, fields: &[(&str, &str)], end: bool) -> io::Result<()> {
+        if !self.conn.windows().streams.contains_key(&self.stream) {
+            return Err(gone());
+        }
+        let block = encode(fields);
+        let flags = END_HEADERS | if end { END_STREAM } else { 0 };
+        self.conn.frame(HEADERS, flags, self.stream, &block)?;
+        if end {
+            self.conn.windows().streams.remove(&self.stream);
+        }
+        Ok(())
+    }
+
+    // DATA frames, each waiting for room in both windows
+    pub fn data(&self, mut bytes: &[u8]) -> io::Result<()> {
+        while !bytes.is_empty() {
+            let n = {
+                let mut w = self.conn.windows();
+                loop {
+                    let Some(&stream) = w.streams.get(&self.stream) else {
+                        return Err(gone());
+                    };
+                    if w.closed {
+                        return Err(gone());
+                    }
+                    if w.conn > 0 && stream > 0 {
+                        break;
+                    }
+                    w = self.conn.changed.wait(w).unwrap_or_else(|e| e.into_inner());
+                }
+                let stream = w.streams[&self.stream];
+                let n = (bytes.len() as i64)
+                    .min(w.conn)
+                    .min(stream)
+                    .min(MAX_FRAME as i64);
+                w.conn -= n;
+                w.streams.insert(self.stream, stream - n);
+                n as usize
+            };
+            self.conn.frame(DATA, 0, self.stream, &bytes[..n])?;
+            bytes = &bytes[n..];
+        }
+        Ok(())
+    }
+}
+
+// A stream whose request is still coming in
+#[derive(Default)]
+struct Incoming {
+    block: Vec<u8>, // header block, until END_HEADERS
+    headers: Option<Vec<(String, String)>>,
+    body: Vec<u8>,
+}
+
+// Serves one client connection until it closes, handing each complete
+// request to `handle` on a thread of its own
+pub fn serve<F>(stream: TcpStream, handle: Arc<F>) -> Result<(), String>
+where
+    F: Fn(Request, Responder) + Send + Sync + 'static,
+{
+    let mut input = stream.try_clone().map_err(|e| e.to_string())?;
+    let conn = Arc::new(Conn {
+        out: Mutex::new(stream),
+        windows: Mutex::new(Windows {
+            conn: INITIAL_WINDOW,
+            streams: HashMap::new(),
+            initial: INITIAL_WINDOW,
+            closed: false,
+        }),
+        changed: Condvar::new(),
+    });
+    let result = read_loop(&mut input, &conn, &handle);
+    conn.windows().closed = true;
+    conn.changed.notify_all();
+    result
+}
+
+fn read_loop<F>(input: &mut TcpStream, conn: &Arc<Conn>, handle: &Arc<F>) -> Result<(), String>
+where
+    F: Fn(Request, Responder) + Send + Sync + 'static,
+{
+    let mut preface = [0u8; PREFACE.len()];
+    input
+        .read_exact(&mut preface)
+        .map_err(|e| format!("no HTTP/2 preface: {e}"))?;
+    if preface != PREFACE {
+        return Err("not HTTP/2 with prior knowledge (h2c)".into());
+    }
+    conn.frame(SETTINGS, 0, 0, &[]).map_err(|e| e.to_string())?;
+
+    let mut decoder = Decoder::new();
+    let mut incoming: HashMap<u32, Incoming> = HashMap::new();
+    let mut continuing: Option<(u32, bool)> = None; // stream, END_STREAM seen
+    let goaway = |code: u32, why: &str| {
+        let mut payload = 0u32.to_be_bytes().to_vec();
+        payload.extend_from_slice(&code.to_be_bytes());
+        let _ = conn.frame(GOAWAY, 0, 0, &payload);
+        Err(why.to_string())
+    };
+    loop {
+        let mut head = [0u8; 9];
+        match input.read_exact(&mut head) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        }
+        let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+        let (kind, flags) = (head[3], head[4]);
+        let id = u32::from_be_bytes([head[5], head[6], head[7], head[8]]) & 0x7fff_ffff;
+        if len > MAX_FRAME {
+            return goaway(FRAME_SIZE_ERROR, "frame larger than allowed");
+        }
+        let mut payload = vec![0u8; len];
+        input.read_exact(&mut payload).map_err(|e| e.to_string())?;
+        if continuing.is_some_and(|(s, _)| kind != CONTINUATION || id != s) {
+            return goaway(PROTOCOL_ERROR, "header block interrupted");
+        }
+
+        match kind {
+            SETTINGS if flags & ACK == 0 => {
+                if !payload.len().is_multiple_of(6) {
+                    return goaway(FRAME_SIZE_ERROR, "bad SETTINGS frame");
+                }
+                for s in payload.chunks(6) {
+                    let value = u32::from_be_bytes([s[2], s[3], s[4], s[5]]);
+                    match u16::from_be_bytes([s[0], s[1]]) {
+                        SETTINGS_INITIAL_WINDOW_SIZE if value > i32::MAX as u32 => {
+                            return goaway(FLOW_CONTROL_ERROR, "window too large");
+                        }
+                        SETTINGS_INITIAL_WINDOW_SIZE => {
+                            let mut w = conn.windows();
+                            let delta = i64::from(value) - w.initial;
+                            w.initial = i64::from(value);
+                            for window in w.streams.values_mut() {
+                                *window += delta;
+                            }
+                        }
+                        // Only sizes our encoder's table, which it doesn't use
+                        SETTINGS_HEADER_TABLE_SIZE => {}
+                        _ => {}
+                    }
+                }
+                conn.changed.notify_all();
+                conn.frame(SETTINGS, ACK, 0, &[])
+                    .map_err(|e| e.to_string())?;
+            }
+            PING if flags & ACK == 0 => {
+                conn.frame(PING, ACK, 0, &payload)
+                    .map_err(|e| e.to_string())?;
+            }
+            WINDOW_UPDATE if payload.len() == 4 => {
+                let inc = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]);
+                let inc = i64::from(inc & 0x7fff_ffff);
+                let mut w = conn.windows();
+                if id == 0 {
+                    w.conn += inc;
+                } else if let Some(window) = w.streams.get_mut(&id) {
+                    *window += inc;
+                }
+                drop(w);
+                conn.changed.notify_all();
+            }
+            RST_STREAM => {
+                incoming.remove(&id);
+                conn.windows().streams.remove(&id);
+                conn.changed.notify_all();
+            }
+            GOAWAY => return Ok(()),
+            HEADERS | CONTINUATION | DATA if id == 0 => {
+                return goaway(PROTOCOL_ERROR, "stream frame on stream 0");
+            }
+            HEADERS => {
+                let Some(mut block) = unpad(&payload, flags) else {
+                    return goaway(PROTOCOL_ERROR, "bad padding");
+                };
+                if flags & PRIORITY != 0 {
+                    if block.len() < 5 {
+                        return goaway(PROTOCOL_ERROR, "bad HEADERS frame");
+                    }
+                    block = &block[5..];
+                }
+                let end = flags & END_STREAM != 0;
+                let s = incoming.entry(id).or_default();
+                s.block.extend_from_slice(block);
+                continuing = Some((id, end));
+            }
+            CONTINUATION => {
+                let Some(s) = incoming.get_mut(&id) else {
+                    return goaway(PROTOCOL_ERROR, "CONTINUATION without HEADERS");
+                };
+                s.block.extend_from_slice(&payload);
+            }
+            DATA => {
+                if !payload.is_empty() {
+                    // Taken as it comes, so the client's windows back up at once
+                    let inc = (payload.len() as u32).to_be_bytes();
+                    let _ = conn.frame(WINDOW_UPDATE, 0, 0, &inc);
+                    if flags & END_STREAM == 0 {
+                        let _ = conn.frame(WINDOW_UPDATE, 0, id, &inc);
+                    }
+                }
+                let Some(body) = unpad(&payload, flags) else {
+                    return goaway(PROTOCOL_ERROR, "bad padding");
+                };
+                if let Some(s) = incoming.get_mut(&id) {
+                    if s.body.len() + body.len() > MAX_REQUEST {
+                        incoming.remove(&id);
+                        let _ = conn.frame(RST_STREAM, 0, id, &PROTOCOL_ERROR.to_be_bytes());
+                        continue;
+                    }
+                    s.body.extend_from_slice(body);
+                }
+                if flags & END_STREAM != 0 {
+                    dispatch(conn, handle, &mut incoming, id);
+                }
+            }
+            _ => {}
+        }
+
+        // A header block is complete; without END_STREAM a body follows
+        if matches!(kind, HEADERS | CONTINUATION)
+            && flags & END_HEADERS != 0
+            && let Some((id, end)) = continuing.take()
+        {
+            let Some(s) = incoming.get_mut(&id) else {
+                continue;
+            };
+            match decoder.decode(&s.block) {
+                Some(headers) => s.headers = Some(headers),
+                None => return goaway(COMPRESSION_ERROR, "undecodable header block"),
+            }
+            s.block.clear();
+            if end {
+                dispatch(conn, handle, &mut incoming, id);
+            }
+        }
+    }
+}
+
+fn dispatch<F>(conn: &Arc<Conn>, handle: &Arc<F>, incoming: &mut HashMap<u32, Incoming>, id: u32)
+where
+    F: Fn(Request, Responder) + Send + Sync + 'static,
+{
+    let Some(Incoming {
+        headers: Some(headers),
+        body,
+        ..
+    }) = incoming.remove(&id)
+    else {
+        return;
+    };
+    {
+        let mut w = conn.windows();
+        let initial = w.initial;
+        w.streams.insert(id, initial);
+    }
+    let responder = Responder {
+        conn: Arc::clone(conn),
+        stream: id,
+    };
+    let handle = Arc::clone(handle);
+    thread::spawn(move || handle(Request { headers, body }, responder));
+}
+
+// A frame's payload without its padding
+fn unpad(payload: &[u8], flags: u8) -> Option<&[u8]> {
+    if flags & PADDED == 0 {
+        return Some(payload);
+    }
+    let (&pad, rest) = payload.split_first()?;
+    rest.len()
+        .checked_sub(usize::from(pad))
+        .map(|end| &rest[..end])
+}
+
+/* ---- HPACK (RFC 7541) ---- */
+
+const STATIC: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+// Header block for `fields`: literals without indexing, names and values
+// as they are
+fn encode(fields: &[(&str, &str)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (name, value) in fields {
+        out.push(0);
+        for s in [name, value] {
+            integer(&mut out, 0, 7, s.len());
+            out.extend_from_slice(s.as_bytes());
+        }
+    }
+    out
+}
+
+fn integer(out: &mut Vec<u8>, first: u8, prefix: u32, mut n: usize) {
+    let max = (1usize << prefix) - 1;
+    if n < max {
+        out.push(first | n as u8);
+        return;
+    }
+    out.push(first | max as u8);
+    n -= max;
+    while n >= 128 {
+        out.push((n % 128) as u8 | 0x80);
+        n /= 128;
+    }
+    out.push(n as u8);
+}
+
+struct Decoder {
+    table: VecDeque<(String, String)>, // newest first
+    size: usize,
+    max: usize,
+}
+
+impl Decoder {
+    fn new() -> Self {
+        Decoder {
+            table: VecDeque::new(),
+            size: 0,
+            max: TABLE_SIZE,
+        }
+    }
+
+    // None is a COMPRESSION_ERROR: the connection can't go on
+    fn decode(&mut self, block: &[u8]) -> Option<Vec<(String, String)>> {
+        let mut headers = Vec::new();
+        let mut at = 0;
+        while at < block.len() {
+            let b = block[at];
+            if b & 0x80 != 0 {
+                let i = read_integer(block, &mut at, 7)?;
+                headers.push(self.entry(i)?);
+            } else if b & 0x40 != 0 {
+                let field = self.literal(block, &mut at, 6)?;
+                self.insert(field.clone());
+                headers.push(field);
+            } else if b & 0x20 != 0 {
+                let max = read_integer(block, &mut at, 5)?;
+                if max > TABLE_SIZE {
+                    return None;
+                }
+                self.max = max;
+                self.evict(0);
+            } else {
+                // Without indexing and never indexed alike: 4-bit prefix
+                headers.push(self.literal(block, &mut at, 4)?);
+            }
+        }
+        Some(headers)
+    }
+
+    fn entry(&self, i: usize) -> Option<(String, String)> {
+        match i {
+            0 => None,
+            1..=61 => {
+                let (n, v) = STATIC[i - 1];
+                Some((n.to_string(), v.to_string()))
+            }
+            _ => self.table.get(i - 62).cloned(),
+        }
+    }
+
+    fn literal(&self, block: &[u8], at: &mut usize, prefix: u32) -> Option<(String, String)> {
+        let i = read_integer(block, at, prefix)?;
+        let name = match i {
+            0 => read_string(block, at)?,
+            _ => self.entry(i)?.0,
+        };
+        Some((name, read_string(block, at)?))
+    }
+
+    fn insert(&mut self, field: (String, String)) {
+        let size = field.0.len() + field.1.len() + 32;
+        if size > self.max {
+            self.table.clear();
+            self.size = 0;
+            return;
+        }
+        self.evict(size);
+        self.size += size;
+        self.table.push_front(field);
+    }
+
+    // Drops the oldest entries until `room` more fits
+    fn evict(&mut self, room: usize) {
+        while self.size + room > self.max {
+            let Some((n, v)) = self.table.pop_back() else {
+                break;
+            };
+            self.size -= n.len() + v.len() + 32;
+        }
+    }
+}
+
+fn read_integer(block: &[u8], at: &mut usize, prefix: u32) -> Option<usize> {
+    let max = (1usize << prefix) - 1;
+    let mut n = usize::from(*block.get(*at)?) & max;
+    *at += 1;
+    if n < max {
+        return Some(n);
+    }
+    let mut shift = 0;
+    loop {
+        let b = *block.get(*at)?;
+        *at += 1;
+        n = n.checked_add(usize::from(b & 0x7f).checked_shl(shift)?)?;
+        if b & 0x80 == 0 {
+            return Some(n);
+        }
+        shift += 7;
+        if shift > 28 {
+            return None;
+        }
+    }
+}
+
+fn read_string(block: &[u8], at: &mut usize) -> Option<String> {
+    let huffman = *block.get(*at)? & 0x80 != 0;
+    let len = read_integer(block, at, 7)?;
+    let bytes = block.get(*at..at.checked_add(len)?)?;
+    *at += len;
+    let bytes = if huffman {
+        huffman_decode(bytes)?
+    } else {
+        bytes.to_vec()
+    };
+    String::from_utf8(bytes).ok()
+}
+
+// The HPACK code is canonical: codes of each length follow on from the
+// shorter ones, in symbol order, so the symbols by length are the whole
+// table. These are every code up to 15 bits, which covers NUL and all of
+// printable ASCII. The rest (control and 8-bit bytes, 19 to 30 bits)
+// start with 15 ones; gRPC keeps header values printable, with binary
+// ones in base64, so meeting one fails the block.
+const HUFFMAN: [(u32, &[u8]); 10] = [
+    (5, b"012aceiost"),
+    (6, b" %-./3456789=A_bdfghlmnpru"),
+    (7, b":BCDEFGHIJKLMNOPQRSTUVWYjkqvwxyz"),
+    (8, b"&*,;XZ"),
+    (10, b"!\"()?"),
+    (11, b"'+|"),
+    (12, b"#>"),
+    (13, b"\0$@[]~"),
+    (14, b"^}"),
+    (15, b"<`{"),
+];
+
+fn huffman_decode(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut code, mut len) = (0u32, 0u32);
+    for bit in bytes
+        .iter()
+        .flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1))
+    {
+        code = code << 1 | u32::from(bit);
+        len += 1;
+        // The first code of each length, walking up to this one
+        let mut first = 0u32;
+        let mut at = 0u32;
+        for &(l, symbols) in &HUFFMAN {
+            first <<= l - at;
+            at = l;
+            if l == len {
+                if let Some(&s) = code
+                    .checked_sub(first)
+                    .and_then(|i| symbols.get(i as usize))
+                {
+                    out.push(s);
+                    code = 0;
+                    len = 0;
+                }
+                break;
+            }
+            first += symbols.len() as u32;
+        }
+        if len >= 15 {
+            return None;
+        }
+    }
+    // Padding: fewer than 8 bits, all ones (the start of EOS)
+    (len < 8 && code == (1 << len) - 1).then_some(out)
+}
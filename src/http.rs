@@ -0,0 +1,178 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+/* -------------------- HTTP(S) via the system curl -------------------- */
+
+// ipchk carries no TLS stack; like probing defers to the system ping,
+// HTTPS defers to curl (shipped with Windows 10+, macOS and most distros).
+const CURL_TIMEOUT_SECS: u32 = 10;
+
+pub fn post_json(url: &str, body: &str) -> Result<(), String> {
+    let mut child = Command::new("curl")
+        .args(["-fsS", "-o"])
+        .arg(if cfg!(windows) { "NUL" } else { "/dev/null" })
+        .arg("-m")
+        .arg(CURL_TIMEOUT_SECS.to_string())
+        .args(["-X", "POST", "-H", "Content-Type: application/json"])
+        .args(["--data-binary", "@-", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("curl: {e}"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(body.as_bytes())
+            .map_err(|e| format!("curl: {e}"))?;
+    }
+    let out = child.wait_with_output().map_err(|e| format!("curl: {e}"))?;
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        return Err(format!("{url}: {}", err.trim()));
+    }
+    Ok(())
+}
+
+// The body of a GET, e.g. an echo service's one-line answer; `args` go to
+// curl as they are (-4/-6)
+pub fn get_text(url: &str, args: &[&str]) -> Result<String, String> {
+    let out = Command::new("curl")
+        .arg("-fsS")
+        .arg("-m")
+        .arg(CURL_TIMEOUT_SECS.to_string())
+        .args(args)
+        .arg(url)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("curl: {e}"))?;
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        return Err(format!("{url}: {}", err.trim()));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+// A request with headers, returning the body; a body is JSON unless
+// `headers` say otherwise. Everything goes to curl as a config on stdin, so
+// tokens in `headers` stay out of the process list.
+pub fn request(
+    method: &str,
+    url: &str,
+    headers: &[String],
+    body: Option<&str>,
+) -> Result<String, String> {
+    // Double-quoted curl config values take \\, \" and \n escapes, one
+    // value to a line
+    let quoted = |v: &str| {
+        let v = v.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("\"{}\"", v.replace('\n', "\\n"))
+    };
+    // The status code goes on a line of its own after the body
+    let mut config = format!(
+        "url = {}\nrequest = {}\nwrite-out = \"\\n%{{http_code}}\"\n",
+        quoted(url),
+        quoted(method)
+    );
+    for h in headers {
+        config.push_str(&format!("header = {}\n", quoted(h)));
+    }
+    let typed = headers
+        .iter()
+        .any(|h| h.to_ascii_lowercase().starts_with("content-type:"));
+    if let Some(body) = body {
+        if !typed {
+            config.push_str("header = \"Content-Type: application/json\"\n");
+        }
+        config.push_str(&format!("data-binary = {}\n", quoted(body)));
+    }
+    let mut child = Command::new("curl")
+        .args(["-sS", "-m"])
+        .arg(CURL_TIMEOUT_SECS.to_string())
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("curl: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(config.as_bytes())
+            .map_err(|e| format!("curl: {e}"))?;
+    }
+    let out = child.wait_with_output().map_err(|e| format!("curl: {e}"))?;
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        return Err(format!("{url}: {}", err.trim()));
+    }
+    let text = String::from_utf8_lossy(&out.stdout);
+    let (body, code) = text.rsplit_once('\n').unwrap_or(("", &text));
+    match code.trim().parse::<u16>() {
+        Ok(c) if c < 400 => Ok(body.trim().to_string()),
+        _ => Err(format!("{url}: HTTP {}: {}", code.trim(), body.trim())),
+    }
+}
+
+/* -------------------- plain HTTP GET (captive portal check) -------------------- */
+
+// Clear text on purpose: portals can only tamper with what they can read.
+// Enough of the body is kept to tell a portal page from the expected answer.
+const MAX_BODY: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub location: Option<String>, // redirect target
+    pub body: Vec<u8>,
+}
+
+pub fn get(
+    addr: SocketAddr,
+    host: &str,
+    path: &str,
+    timeout: Duration,
+) -> Result<Response, String> {
+    let mut stream =
+        TcpStream::connect_timeout(&addr, timeout).map_err(|e| format!("{host}: {e}"))?;
+    let _ = stream.set_read_timeout(Some(timeout));
+    let _ = stream.set_write_timeout(Some(timeout));
+    let request = format!(
+        "GET {path} HTTP/1.1\r\nHost: {host}\r\nUser-Agent: ipchk/{}\r\nConnection: close\r\n\r\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("{host}: {e}"))?;
+    let mut raw = Vec::new();
+    stream
+        .take(MAX_BODY as u64)
+        .read_to_end(&mut raw)
+        .map_err(|e| format!("{host}: {e}"))?;
+
+    let end = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| format!("{host}: not an HTTP response"))?;
+    let head = String::from_utf8_lossy(&raw[..end]);
+    let mut lines = head.split("\r\n");
+    // "HTTP/1.1 204 No Content"
+    let status = lines
+        .next()
+        .filter(|l| l.starts_with("HTTP/"))
+        .and_then(|l| l.split_whitespace().nth(1)?.parse().ok())
+        .ok_or_else(|| format!("{host}: not an HTTP response"))?;
+    let location = lines.find_map(|l| {
+        let (name, value) = l.split_once(':')?;
+        name.eq_ignore_ascii_case("location")
+            .then(|| value.trim().to_string())
+    });
+    Ok(Response {
+        status,
+        location,
+        body: raw[end + 4..].to_vec(),
+    })
+}
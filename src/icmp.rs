@@ -0,0 +1,440 @@
+use crate::{Error, Outcome, ProbeErrorKind, ProbeOptions, ProbeType, Reply, Stats};
+use std::{
+    io, mem,
+    net::Ipv4Addr,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::atomic::{AtomicU16, Ordering},
+    time::{Instant, SystemTime},
+};
+
+/* -------------------- native ICMP echo engine (--engine native) -------------------- */
+
+// Unprivileged ICMP datagram sockets where the OS allows them (macOS, Linux
+// within net.ipv4.ping_group_range), raw sockets otherwise.
+
+const ECHO_REPLY: u8 = 0;
+const DEST_UNREACHABLE: u8 = 3;
+const FRAG_NEEDED: u8 = 4; // code of DEST_UNREACHABLE
+
+// The socket option and value that set DF on everything sent (--df). Linux
+// also refuses, with EMSGSIZE, sends larger than the path MTU it has learnt.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const DONT_FRAGMENT: Option<(libc::c_int, libc::c_int)> =
+    Some((libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_DO));
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+const DONT_FRAGMENT: Option<(libc::c_int, libc::c_int)> = Some((libc::IP_DONTFRAG, 1));
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+)))]
+const DONT_FRAGMENT: Option<(libc::c_int, libc::c_int)> = None;
+const ECHO_REQUEST: u8 = 8;
+const TIMESTAMP_REQUEST: u8 = 13;
+const TIMESTAMP_REPLY: u8 = 14;
+const MS_PER_DAY: i64 = 86_400_000;
+
+// Every probe gets its own identifier and a run of sequence numbers no other
+// probe in this process uses, so a reply (or a late one to an earlier probe)
+// can only ever match the echo it answers.
+static NEXT_ID: AtomicU16 = AtomicU16::new(0);
+static NEXT_SEQ: AtomicU16 = AtomicU16::new(0);
+
+pub(crate) struct Socket {
+    fd: OwnedFd,
+    dgram: bool, // SOCK_DGRAM: no IP header on Linux, kernel-owned identifier
+}
+
+fn last_error() -> io::Error {
+    io::Error::last_os_error()
+}
+
+fn sockaddr(ip: Ipv4Addr, port: u16) -> libc::sockaddr_in {
+    // SAFETY: sockaddr_in is plain old data; all-zero is a valid value
+    let mut sa: libc::sockaddr_in = unsafe { mem::zeroed() };
+    sa.sin_family = libc::AF_INET as libc::sa_family_t;
+    sa.sin_port = port.to_be();
+    sa.sin_addr.s_addr = u32::from(ip).to_be();
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly"
+    ))]
+    {
+        sa.sin_len = mem::size_of::<libc::sockaddr_in>() as u8;
+    }
+    sa
+}
+
+// A raw socket for `protocol`, e.g. IPPROTO_TCP for SYN scans
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn raw(protocol: libc::c_int) -> Result<Socket, Error> {
+    // SAFETY: plain socket(2) call; the fd is owned from here on
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_RAW, protocol) };
+    if fd < 0 {
+        return Err(socket_error(last_error()));
+    }
+    // SAFETY: fd was just returned by socket(2) and is not shared
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    Ok(Socket { fd, dgram: false })
+}
+
+// Linux datagram sockets carry echo requests only
+fn open(probe: ProbeType) -> Result<Socket, Error> {
+    let dgram_ok =
+        probe == ProbeType::Echo || !cfg!(any(target_os = "linux", target_os = "android"));
+    let kinds = [(libc::SOCK_DGRAM, true), (libc::SOCK_RAW, false)];
+    for (kind, dgram) in kinds.into_iter().filter(|&(_, d)| dgram_ok || !d) {
+        // SAFETY: plain socket(2) call; the fd is owned from here on
+        let fd = unsafe { libc::socket(libc::AF_INET, kind, libc::IPPROTO_ICMP) };
+        if fd >= 0 {
+            // SAFETY: fd was just returned by socket(2) and is not shared
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            return Ok(Socket { fd, dgram });
+        }
+    }
+    Err(socket_error(last_error()))
+}
+
+fn socket_error(e: io::Error) -> Error {
+    match e.raw_os_error() {
+        Some(libc::EPERM) | Some(libc::EACCES) => Error::Privilege {
+            what: format!("socket: {e}"),
+        },
+        _ => Error::Io(e),
+    }
+}
+
+impl Socket {
+    // The identifier replies will carry. On Linux the kernel owns a datagram
+    // socket's identifier: binding asks for one, and fails if another socket
+    // already has it, in which case the kernel picks.
+    fn claim(&self) -> io::Result<u16> {
+        let next = || NEXT_ID.fetch_add(1, Ordering::Relaxed) ^ std::process::id() as u16;
+        if !(self.dgram && cfg!(any(target_os = "linux", target_os = "android"))) {
+            return Ok(next());
+        }
+        let bind = |id: u16| {
+            let sa = sockaddr(Ipv4Addr::UNSPECIFIED, id);
+            // SAFETY: sa outlives the call and the length matches its type
+            unsafe {
+                libc::bind(
+                    self.fd.as_raw_fd(),
+                    (&sa as *const libc::sockaddr_in).cast(),
+                    mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                ) == 0
+            }
+        };
+        if (0..16).any(|_| bind(next())) || bind(0) {
+            // SAFETY: sockaddr_in is plain old data
+            let mut sa: libc::sockaddr_in = unsafe { mem::zeroed() };
+            let mut len = mem::size_of::<libc::sockaddr_in>() as libc::socklen_t;
+            // SAFETY: sa and len outlive the call and the length matches
+            let rc = unsafe {
+                libc::getsockname(
+                    self.fd.as_raw_fd(),
+                    (&mut sa as *mut libc::sockaddr_in).cast(),
+                    &mut len,
+                )
+            };
+            if rc == 0 {
+                return Ok(u16::from_be(sa.sin_port));
+            }
+        }
+        Err(last_error())
+    }
+
+    fn dont_fragment(&self) -> Result<(), Error> {
+        let Some((name, value)) = DONT_FRAGMENT else {
+            return Err(Error::Probe {
+                target: "--df".into(),
+                kind: ProbeErrorKind::Unsupported,
+            });
+        };
+        // SAFETY: value outlives the call and the length matches its type
+        let rc = unsafe {
+            libc::setsockopt(
+                self.fd.as_raw_fd(),
+                libc::IPPROTO_IP,
+                name,
+                (&value as *const libc::c_int).cast(),
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if rc == 0 {
+            Ok(())
+        } else {
+            Err(Error::Io(last_error()))
+        }
+    }
+
+    pub(crate) fn send(&self, dest: Ipv4Addr, packet: &[u8]) -> io::Result<()> {
+        let sa = sockaddr(dest, 0);
+        // SAFETY: packet and sa outlive the call and the lengths match
+        let n = unsafe {
+            libc::sendto(
+                self.fd.as_raw_fd(),
+                packet.as_ptr().cast(),
+                packet.len(),
+                0,
+                (&sa as *const libc::sockaddr_in).cast(),
+                mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+            )
+        };
+        if n < 0 { Err(last_error()) } else { Ok(()) }
+    }
+
+    // One datagram and its source, or None once `deadline` passes
+    pub(crate) fn recv(
+        &self,
+        buf: &mut [u8],
+        deadline: Instant,
+    ) -> io::Result<Option<(usize, Ipv4Addr)>> {
+        let wait = deadline.saturating_duration_since(Instant::now());
+        if wait.is_zero() {
+            return Ok(None);
+        }
+        let mut pfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ms = wait.as_millis().clamp(1, i32::MAX as u128) as libc::c_int;
+        // SAFETY: one valid pollfd
+        match unsafe { libc::poll(&mut pfd, 1, ms) } {
+            0 => return Ok(None),
+            n if n < 0 => {
+                let e = last_error();
+                return if e.kind() == io::ErrorKind::Interrupted {
+                    self.recv(buf, deadline)
+                } else {
+                    Err(e)
+                };
+            }
+            _ => {}
+        }
+        // SAFETY: sockaddr_in is plain old data
+        let mut sa: libc::sockaddr_in = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::sockaddr_in>() as libc::socklen_t;
+        // SAFETY: buf, sa and len outlive the call and the lengths match
+        let n = unsafe {
+            libc::recvfrom(
+                self.fd.as_raw_fd(),
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+                0,
+                (&mut sa as *mut libc::sockaddr_in).cast(),
+                &mut len,
+            )
+        };
+        if n < 0 {
+            return Err(last_error());
+        }
+        Ok(Some((
+            n as usize,
+            Ipv4Addr::from(u32::from_be(sa.sin_addr.s_addr)),
+        )))
+    }
+}
+
+// RFC 1071 ones'-complement sum
+pub(crate) fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|c| u32::from(u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+// Echo (8) or timestamp (13) request; the kernel fills in nothing but, on
+// Linux datagram sockets, the identifier
+fn request(kind: u8, id: u16, seq: u16, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![kind, 0, 0, 0];
+    packet.extend(id.to_be_bytes());
+    packet.extend(seq.to_be_bytes());
+    packet.extend(body);
+    let sum = checksum(&packet);
+    packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    packet
+}
+
+// The ICMP message in a received datagram: raw sockets (and datagram sockets
+// outside Linux) deliver the IP header too
+fn icmp_message(datagram: &[u8]) -> Option<&[u8]> {
+    let first = *datagram.first()?;
+    if first >> 4 == 4 {
+        datagram.get(usize::from(first & 0x0f) * 4..)
+    } else {
+        Some(datagram)
+    }
+}
+
+// (type, identifier, sequence, body) of a code-0 query reply
+fn parse_reply(msg: &[u8]) -> Option<(u8, u16, u16, &[u8])> {
+    if msg.len() < 8 || msg[1] != 0 {
+        return None;
+    }
+    let id = u16::from_be_bytes([msg[4], msg[5]]);
+    let seq = u16::from_be_bytes([msg[6], msg[7]]);
+    Some((msg[0], id, seq, &msg[8..]))
+}
+
+// Next-hop MTU (0 if the router didn't say) and the header of the datagram
+// a "fragmentation needed" error is about, if `msg` is one
+fn parse_frag_needed(msg: &[u8]) -> Option<(u16, &[u8])> {
+    if msg.len() < 8 || msg[0] != DEST_UNREACHABLE || msg[1] != FRAG_NEEDED {
+        return None;
+    }
+    Some((u16::from_be_bytes([msg[6], msg[7]]), &msg[8..]))
+}
+
+// RFC 792 timestamps: milliseconds since midnight UT
+fn ms_since_midnight(t: SystemTime) -> u32 {
+    let ms = t
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    (ms % MS_PER_DAY as u128) as u32
+}
+
+// Remote clock minus ours from the four timestamps of an exchange, taking
+// the path as symmetric. None for non-standard times (high bit set).
+fn clock_offset(originate: u32, receive: u32, transmit: u32, back: u32) -> Option<i64> {
+    if (receive | transmit) & 0x8000_0000 != 0 {
+        return None;
+    }
+    // Differences wrap at midnight; keep them within half a day
+    let diff = |a: u32, b: u32| {
+        let d = (i64::from(a) - i64::from(b)).rem_euclid(MS_PER_DAY);
+        if d > MS_PER_DAY / 2 {
+            d - MS_PER_DAY
+        } else {
+            d
+        }
+    };
+    Some((diff(receive, originate) + diff(transmit, back)) / 2)
+}
+
+pub(crate) fn preflight(opts: &ProbeOptions) -> Result<(), Error> {
+    let sock = open(opts.probe)?;
+    if opts.df {
+        sock.dont_fragment()?;
+    }
+    Ok(())
+}
+
+pub(crate) fn ping(ip: Ipv4Addr, opts: &ProbeOptions) -> Result<Outcome, Error> {
+    let sock = open(opts.probe)?;
+    if opts.df {
+        sock.dont_fragment()?;
+    }
+    let tries = opts.count.max(1);
+    let id = sock.claim()?;
+    let first_seq = NEXT_SEQ.fetch_add(tries as u16, Ordering::Relaxed);
+    let payload = opts.payload.clone().unwrap_or_default().bytes();
+    // IP header, ICMP header and payload
+    let mut buf = vec![0u8; payload.len() + 128];
+    let mut replies = Vec::new();
+    let mut corrupted = 0;
+    let timestamp = opts.probe == ProbeType::Timestamp;
+    let (mut frag_needed, mut next_hop_mtu) = (0, None);
+
+    for attempt in 0..tries {
+        if let Some(i) = opts.interval.filter(|_| attempt > 0) {
+            std::thread::sleep(i);
+        }
+        let seq = first_seq.wrapping_add(attempt as u16);
+        let (sent_at, start) = (SystemTime::now(), Instant::now());
+        let originate = ms_since_midnight(sent_at);
+        let packet = if timestamp {
+            let mut body = originate.to_be_bytes().to_vec();
+            body.resize(12, 0);
+            request(TIMESTAMP_REQUEST, id, seq, &body)
+        } else {
+            request(ECHO_REQUEST, id, seq, &payload)
+        };
+        match sock.send(ip, &packet) {
+            // Bigger than the route's MTU, so it was never sent
+            Err(e) if opts.df && e.raw_os_error() == Some(libc::EMSGSIZE) => {
+                frag_needed += 1;
+                continue;
+            }
+            r => r?,
+        }
+        let deadline = start + opts.timeout;
+        // Anything else on the socket (other probes' replies on a raw socket,
+        // our own requests on loopback, stragglers) is skipped
+        while let Some((n, from)) = sock.recv(&mut buf, deadline)? {
+            let Some(msg) = icmp_message(&buf[..n]) else {
+                continue;
+            };
+            // A router that couldn't forward this probe without fragmenting
+            // it; raw sockets see these, quoting our IP and ICMP headers
+            if let Some((mtu, quoted)) = parse_frag_needed(msg)
+                && let Some(inner) = icmp_message(quoted)
+                && quoted.get(16..20) == Some(&ip.octets()[..])
+                && inner.get(4..8) == Some(&packet[4..8])
+            {
+                frag_needed += 1;
+                next_hop_mtu = Some(mtu).filter(|&m| m > 0).or(next_hop_mtu);
+                break;
+            }
+            let Some((kind, rid, rseq, body)) = parse_reply(msg) else {
+                continue;
+            };
+            let expected = if timestamp {
+                TIMESTAMP_REPLY
+            } else {
+                ECHO_REPLY
+            };
+            if kind != expected || from != ip || rid != id || rseq != seq {
+                continue;
+            }
+            let rtt = start.elapsed();
+            let mut offset = None;
+            if timestamp {
+                let field = |i: usize| {
+                    body.get(i..i + 4)
+                        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+                };
+                let (Some(orig), Some(recv), Some(xmit)) = (field(0), field(4), field(8)) else {
+                    continue;
+                };
+                if orig != originate {
+                    continue;
+                }
+                offset = clock_offset(orig, recv, xmit, ms_since_midnight(SystemTime::now()));
+            } else if body != payload.as_slice() {
+                // The answer to this echo, but not with what was sent
+                corrupted += 1;
+                break;
+            }
+            replies.push(Reply {
+                seq: attempt,
+                bytes: (8 + body.len()) as u32,
+                rtt,
+                sent_at: Some(sent_at),
+                clock_offset_ms: offset,
+            });
+            break;
+        }
+    }
+
+    Ok(Outcome {
+        up: !replies.is_empty(),
+        sent: tries,
+        stats: Stats::from_replies(tries, &replies),
+        replies,
+        icmp: Some((id, first_seq)),
+        corrupted,
+        frag_needed,
+        next_hop_mtu,
+        ports: Vec::new(),
+    })
+}
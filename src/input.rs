@@ -0,0 +1,1238 @@
+use crate::{
+    json::{self, Value},
+    output,
+};
+use ipchk::{Cidr, ProbeOptions, ProbeType};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
+    time::Duration,
+};
+
+/* -------------------- target sources (--input, --zone-file) -------------------- */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,     // header row with an `ip` column; other columns are carried through
+    Json,    // array or JSON Lines of objects, as printed by --output json/jsonl
+    NmapXml, // `nmap -oX` scan results
+    List,    // a target per line, then its own key=value settings
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "nmap-xml" => Ok(Format::NmapXml),
+            "list" => Ok(Format::List),
+            _ => Err(format!(
+                "unknown format {s:?} (use csv, json, nmap-xml or list)"
+            )),
+        }
+    }
+}
+
+// A target read from an input file, with the rest of its row
+#[derive(Debug)]
+pub struct Target {
+    pub ip: String,
+    pub fields: Vec<(String, String)>,
+    pub options: Overrides, // --input list only
+}
+
+impl Target {
+    // The row's `label` column, which is shown with the target rather than
+    // carried through with the other columns
+    pub fn take_label(&mut self) -> Option<String> {
+        let i = self.fields.iter().position(|(k, _)| k == "label")?;
+        Some(self.fields.remove(i).1).filter(|l| !l.is_empty())
+    }
+}
+
+// A target's own probe settings, over the flags'
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Overrides {
+    pub timeout: Option<Duration>,
+    pub count: Option<u32>,
+    pub interval: Option<Duration>,
+    pub probe: Option<ProbeType>,
+    pub ports: Option<Vec<u16>>,
+}
+
+impl Overrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Overrides::default()
+    }
+
+    pub fn apply(&self, opts: &ProbeOptions) -> ProbeOptions {
+        let mut opts = opts.clone();
+        if let Some(t) = self.timeout {
+            opts.timeout = t;
+        }
+        if let Some(n) = self.count {
+            opts.count = n;
+        }
+        if self.interval.is_some() {
+            opts.interval = self.interval;
+        }
+        if let Some(p) = self.probe {
+            opts.probe = p;
+        }
+        if let Some(ports) = &self.ports {
+            opts.ports = ports.clone();
+        }
+        opts
+    }
+
+    // "timeout=5000", "probe=tcp:8443"
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let ms = |v: &str| {
+            v.parse::<u64>()
+                .map(Duration::from_millis)
+                .map_err(|e| format!("{key}: {e}"))
+        };
+        let ports = |v: &str| ipchk::parse_ports(v).map_err(|e| format!("{key}: {e}"));
+        match key {
+            "timeout" => self.timeout = Some(ms(value)?),
+            "interval" => self.interval = Some(ms(value)?),
+            "count" => {
+                let n = value.parse::<u32>().map_err(|e| format!("{key}: {e}"))?;
+                self.count = Some(n.max(1));
+            }
+            "probe" => {
+                let (probe, list) = match value.split_once(':') {
+                    Some(("tcp", list)) => ("tcp", Some(list)),
+                    _ => (value, None),
+                };
+                let probe = probe.parse().map_err(|e| format!("{key}: {e}"))?;
+                if let Some(list) = list {
+                    self.ports = Some(ports(list)?);
+                }
+                self.probe = Some(probe);
+            }
+            "ports" => self.ports = Some(ports(value)?),
+            _ => {
+                return Err(format!(
+                    "unknown setting {key:?} (use timeout, count, interval, probe, ports or label)"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Inventory columns of every --input target, for structured output
+#[derive(Debug, Default)]
+pub struct Fields {
+    pub columns: Vec<String>,               // first-seen order across all files
+    pub rows: HashMap<String, Vec<String>>, // target -> values in `columns` order
+}
+
+impl Fields {
+    // Adds `t`'s columns; a target listed twice keeps its first row
+    pub fn add(&mut self, t: &Target) -> bool {
+        if self.rows.contains_key(&t.ip) {
+            return false;
+        }
+        for (k, _) in &t.fields {
+            if !self.columns.contains(k) {
+                self.columns.push(k.clone());
+            }
+        }
+        let row = self
+            .columns
+            .iter()
+            .map(|c| {
+                t.fields
+                    .iter()
+                    .find(|(k, _)| k == c)
+                    .map(|(_, v)| v.clone())
+                    .unwrap_or_default()
+            })
+            .collect();
+        self.rows.insert(t.ip.clone(), row);
+        true
+    }
+
+    // `target`'s value for every column, "" where its file had none
+    pub fn row(&self, target: &str) -> Option<Vec<(&str, &str)>> {
+        let row = self.rows.get(target)?;
+        Some(
+            self.columns
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (c.as_str(), row.get(i).map_or("", String::as_str)))
+                .collect(),
+        )
+    }
+}
+
+// `path` ("-" for stdin) parsed as `format`
+pub fn read(format: Format, path: &Path) -> Result<Vec<Target>, String> {
+    let text = if path == Path::new("-") {
+        let mut s = String::new();
+        io::stdin()
+            .read_to_string(&mut s)
+            .map_err(|e| format!("stdin: {e}"))?;
+        s
+    } else {
+        fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?
+    };
+    match format {
+        Format::Csv => csv(&text, path),
+        Format::Json => json(&text, path),
+        Format::NmapXml => nmap_xml(&text, path),
+        Format::List => list(&text, path),
+    }
+}
+
+// Every <host> of an `nmap -oX` report, by its IPv4/IPv6 address. Its first
+// hostname, MAC address/vendor and the state nmap saw are carried through.
+fn nmap_xml(text: &str, path: &Path) -> Result<Vec<Target>, String> {
+    if !text.contains("<nmaprun") {
+        return Err(format!("{}: not an nmap XML report", path.display()));
+    }
+    let mut out = Vec::new();
+    let mut host: Option<Target> = None; // ip stays empty until an address is seen
+    for (name, attrs) in xml_tags(text) {
+        let attr = |k: &str| attrs.iter().find(|(a, _)| a == k).map(|(_, v)| v.clone());
+        match (name.as_str(), host.as_mut()) {
+            ("host", _) => {
+                host = Some(Target {
+                    ip: String::new(),
+                    fields: Vec::new(),
+                    options: Overrides::default(),
+                })
+            }
+            ("/host", Some(_)) => out.extend(host.take().filter(|t| !t.ip.is_empty())),
+            ("status", Some(t)) => {
+                if let Some(state) = attr("state") {
+                    t.fields.push(("nmap_state".into(), state));
+                }
+            }
+            ("address", Some(t)) => match attr("addrtype").as_deref() {
+                Some("ipv4" | "ipv6") if t.ip.is_empty() => t.ip = attr("addr").unwrap_or_default(),
+                Some("mac") => {
+                    t.fields
+                        .extend(attr("addr").map(|a| ("mac".to_string(), a)));
+                    t.fields
+                        .extend(attr("vendor").map(|v| ("vendor".to_string(), v)));
+                }
+                _ => {}
+            },
+            ("hostname", Some(t)) if !t.fields.iter().any(|(k, _)| k == "hostname") => {
+                t.fields
+                    .extend(attr("name").map(|n| ("hostname".to_string(), n)));
+            }
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+// Element tags in document order as (name, attributes); closing tags are
+// named "/name". Enough XML for machine-written reports: comments,
+// declarations and text are skipped, entities in values decoded.
+fn xml_tags(text: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut out = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        if let Some(after) = rest.strip_prefix("!--") {
+            rest = after.find("-->").map_or("", |end| &after[end + 3..]);
+            continue;
+        }
+        // '>' can't appear unescaped inside attribute values nmap writes
+        let Some(close) = rest.find('>') else {
+            break;
+        };
+        let tag = rest[..close].trim_end_matches('/');
+        rest = &rest[close + 1..];
+        if tag.starts_with(['?', '!']) {
+            continue;
+        }
+        let (name, mut body) = tag.split_once([' ', '\t', '\n', '\r']).unwrap_or((tag, ""));
+        let mut attrs = Vec::new();
+        while let Some(eq) = body.find('=') {
+            let key = body[..eq].trim().to_string();
+            let after = body[eq + 1..].trim_start();
+            let Some(q) = after.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+                break;
+            };
+            let Some(end) = after[1..].find(q) else {
+                break;
+            };
+            attrs.push((key, xml_unescape(&after[1..1 + end])));
+            body = &after[end + 2..];
+        }
+        out.push((name.to_string(), attrs));
+    }
+    out
+}
+
+fn xml_unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else {
+            break;
+        };
+        let c = match &rest[1..semi] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            e => e
+                .strip_prefix("#x")
+                .map(|h| u32::from_str_radix(h, 16))
+                .or_else(|| e.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// Verdict keys of --output json; re-fed results are checked afresh
+const RESULT_KEYS: &[&str] = &[
+    "address",
+    "name",
+    "status",
+    "cached",
+    "sent",
+    "received",
+    "loss_pct",
+    "rtt_ms",
+    "error",
+    "time",
+    "mac",
+    "alias_of",
+    "baseline",
+    "last_seen",
+    "next_hop_mtu",
+    "clock_offset_ms",
+    "probes",
+    "ports",
+    "schema_version",
+];
+
+// Target objects (or bare address strings), either as one array or one per
+// line. `fields` members and any keys ipchk doesn't emit itself are carried
+// through.
+fn json(text: &str, path: &Path) -> Result<Vec<Target>, String> {
+    let items = json::parse_all(text)
+        .and_then(output::results_of)
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut out = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let bad = |what: &str| format!("{}: target {}: {what}", path.display(), i + 1);
+        let ip_key = ["ip", "address", "host"]
+            .into_iter()
+            .find(|k| matches!(item.get(k), Some(Value::Str(_))));
+        let (ip, members) = match (item, ip_key) {
+            (Value::Str(ip), _) => (ip.clone(), &[][..]),
+            (Value::Object(m), Some(k)) => {
+                (item.get(k).map(Value::text).unwrap_or_default(), &m[..])
+            }
+            (Value::Object(_), None) => return Err(bad("no \"ip\" string")),
+            _ => return Err(bad("expected an object or a string")),
+        };
+        if ip.trim().is_empty() {
+            return Err(bad("empty ip"));
+        }
+        let mut fields = Vec::new();
+        for (k, v) in members {
+            match (k.as_str(), v) {
+                ("fields", Value::Object(inner)) => {
+                    fields.extend(inner.iter().map(|(k, v)| (k.clone(), v.text())));
+                }
+                (k, _) if Some(k) == ip_key || RESULT_KEYS.contains(&k) => {}
+                (k, v) => fields.push((k.to_string(), v.text())),
+            }
+        }
+        out.push(Target {
+            ip: ip.trim().to_string(),
+            fields,
+            options: Overrides::default(),
+        });
+    }
+    Ok(out)
+}
+
+// "10.0.0.9 timeout=5000 probe=tcp:8443 label=sat-modem": a target, then
+// settings of its own for the flags to give way to. `#` starts a comment.
+fn list(text: &str, path: &Path) -> Result<Vec<Target>, String> {
+    let mut out = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split_once('#').map_or(line, |(l, _)| l);
+        let mut words = line.split_whitespace();
+        let Some(ip) = words.next() else {
+            continue;
+        };
+        let mut target = Target {
+            ip: ip.to_string(),
+            fields: Vec::new(),
+            options: Overrides::default(),
+        };
+        for word in words {
+            let Some((key, value)) = word.split_once('=') else {
+                return Err(bad(path, i + 1, &format!("{word}: use key=value")));
+            };
+            if key == "label" {
+                target.fields.push((key.into(), value.into()));
+                continue;
+            }
+            target
+                .options
+                .set(key, value)
+                .map_err(|e| bad(path, i + 1, &e))?;
+        }
+        out.push(target);
+    }
+    Ok(out)
+}
+
+// RFC 4180: quoted fields may hold commas, newlines and "" escapes. The
+// address column is `ip` (or `address`/`host`), matched case-insensitively.
+fn csv(text: &str, path: &Path) -> Result<Vec<Target>, String> {
+    let mut records = csv_records(text).into_iter();
+    let Some((_, header)) = records.next() else {
+        return Ok(Vec::new());
+    };
+    let header: Vec<String> = header.iter().map(|h| h.trim().to_string()).collect();
+    let ip_col = ["ip", "address", "host"]
+        .iter()
+        .find_map(|want| header.iter().position(|h| h.eq_ignore_ascii_case(want)))
+        .ok_or_else(|| bad(path, 1, "no ip column in header"))?;
+
+    let mut out = Vec::new();
+    for (lineno, record) in records {
+        if record.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let ip = record.get(ip_col).map_or("", |s| s.trim());
+        if ip.is_empty() {
+            return Err(bad(path, lineno, "empty ip column"));
+        }
+        let fields = header
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != ip_col)
+            .map(|(i, h)| (h.clone(), record.get(i).cloned().unwrap_or_default()))
+            .collect();
+        out.push(Target {
+            ip: ip.to_string(),
+            fields,
+            options: Overrides::default(),
+        });
+    }
+    Ok(out)
+}
+
+// Records as fields, each tagged with the line it started on
+fn csv_records(text: &str) -> Vec<(usize, Vec<String>)> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut out = Vec::new();
+    let (mut record, mut field) = (Vec::new(), String::new());
+    let (mut quoted, mut line, mut start) = (false, 1, 1);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                out.push((start, std::mem::take(&mut record)));
+                line += 1;
+                start = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        out.push((start, record));
+    }
+    out
+}
+
+// A and AAAA records of a BIND zone file as (owner name, address), in file
+// order. Handles $ORIGIN, $TTL, comments, parenthesised continuations and
+// blank owners; $INCLUDE and $GENERATE are skipped with a warning.
+pub fn zone_file(path: &Path) -> Result<Vec<(String, IpAddr)>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut origin = String::new();
+    let mut owner = String::new();
+    let mut out = Vec::new();
+
+    for (lineno, entry) in logical_lines(&text) {
+        let indented = entry.starts_with([' ', '\t']);
+        let mut tokens: Vec<&str> = entry.split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+        match tokens[0].to_ascii_uppercase().as_str() {
+            "$ORIGIN" => {
+                let name = tokens
+                    .get(1)
+                    .ok_or_else(|| bad(path, lineno, "$ORIGIN needs a name"))?;
+                origin = qualify(name, &origin);
+                continue;
+            }
+            "$TTL" => continue,
+            d if d.starts_with('$') => {
+                eprintln!(
+                    "--zone-file: {}:{lineno}: {d} not supported, skipped",
+                    path.display()
+                );
+                continue;
+            }
+            _ => {}
+        }
+
+        if !indented {
+            owner = qualify(tokens.remove(0), &origin);
+        }
+        // [ttl] [class] or [class] [ttl] before the type
+        while let Some(t) = tokens.first() {
+            let upper = t.to_ascii_uppercase();
+            let is_class = matches!(upper.as_str(), "IN" | "CH" | "HS" | "CS");
+            let is_ttl = t.starts_with(|c: char| c.is_ascii_digit());
+            if !(is_class || is_ttl) {
+                break;
+            }
+            tokens.remove(0);
+        }
+        let (Some(rtype), Some(rdata)) = (tokens.first(), tokens.get(1)) else {
+            continue;
+        };
+        let addr = match rtype.to_ascii_uppercase().as_str() {
+            "A" => rdata.parse::<Ipv4Addr>().map(IpAddr::V4).ok(),
+            "AAAA" => rdata.parse::<Ipv6Addr>().map(IpAddr::V6).ok(),
+            _ => continue,
+        };
+        match addr {
+            Some(a) => out.push((owner.clone(), a)),
+            None => return Err(bad(path, lineno, &format!("bad {rtype} record {rdata:?}"))),
+        }
+    }
+    Ok(out)
+}
+
+fn bad(path: &Path, lineno: usize, what: &str) -> String {
+    format!("{}:{lineno}: {what}", path.display())
+}
+
+// "@" is the origin, "www" is relative to it, "www.example.com." is absolute
+fn qualify(name: &str, origin: &str) -> String {
+    if name == "@" {
+        origin.to_string()
+    } else if let Some(abs) = name.strip_suffix('.') {
+        abs.to_string()
+    } else if origin.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name}.{origin}")
+    }
+}
+
+// Comment-stripped entries with "( ... )" continuations joined, each tagged
+// with the line it started on. Leading whitespace is kept: it means "same
+// owner as the previous record".
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let (mut start, mut depth) = (0, 0);
+    for (i, line) in text.lines().enumerate() {
+        let mut quoted = false;
+        let mut kept = String::with_capacity(line.len());
+        for c in line.chars() {
+            match c {
+                '"' => quoted = !quoted,
+                ';' if !quoted => break,
+                '(' if !quoted => {
+                    depth += 1;
+                    kept.push(' ');
+                    continue;
+                }
+                ')' if !quoted => {
+                    depth -= 1;
+                    kept.push(' ');
+                    continue;
+                }
+                _ => {}
+            }
+            kept.push(c);
+        }
+        if cur.is_empty() {
+            start = i + 1;
+            cur = kept;
+        } else {
+            cur.push(' ');
+            cur.push_str(&kept);
+        }
+        if depth <= 0 {
+            depth = 0;
+            out.push((start, std::mem::take(&mut cur)));
+        }
+    }
+    if !cur.is_empty() {
+        out.push((start, cur));
+    }
+    out
+}
+
+// "0:1a:2B:3c:4d:5e" (BSD arp), "00-1A-2B-3C-4D-5E" (Windows) and the usual
+// form all compare as "00:1a:2b:3c:4d:5e". None for anything else, and for
+// the all-zero address of an incomplete entry.
+pub fn normalise_mac(mac: &str) -> Option<String> {
+    let octets: Vec<u8> = mac
+        .split([':', '-'])
+        .map(|o| {
+            u8::from_str_radix(o, 16)
+                .ok()
+                .filter(|_| (1..=2).contains(&o.len()))
+        })
+        .collect::<Option<_>>()?;
+    if octets.len() != 6 || octets.iter().all(|&o| o == 0) {
+        return None;
+    }
+    let hex: Vec<String> = octets.iter().map(|o| format!("{o:02x}")).collect();
+    Some(hex.join(":"))
+}
+
+// --from-arp-cache: IPv4 neighbours the kernel has a link-layer address
+// for, with that MAC and the interface. IPv6 (NDP) entries are left out
+// while the probe engines are IPv4-only.
+pub fn arp_cache() -> Result<Vec<Target>, String> {
+    let neighbours = arp_entries()?;
+    Ok(neighbours
+        .into_iter()
+        .map(|(ip, mac, dev)| Target {
+            ip: ip.to_string(),
+            fields: vec![("mac".into(), mac), ("interface".into(), dev)],
+            options: Overrides::default(),
+        })
+        .collect())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn arp_entries() -> Result<Vec<(Ipv4Addr, String, String)>, String> {
+    crate::netlink::neighbours()
+}
+
+// `arp -an`: "? (10.0.0.1) at 0:1a:2b:3c:4d:5e on en0 ifscope [ethernet]"
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+pub fn arp_entries() -> Result<Vec<(Ipv4Addr, String, String)>, String> {
+    let out = std::process::Command::new("arp")
+        .arg("-an")
+        .output()
+        .map_err(|e| format!("arp -an: {e}"))?;
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|line| {
+            let ip = line.split_once('(')?.1.split_once(')')?.0.parse().ok()?;
+            let mut words = line.split_whitespace();
+            let mac = words.by_ref().skip_while(|w| *w != "at").nth(1)?;
+            if mac.starts_with('(') {
+                return None; // "(incomplete)"
+            }
+            let dev = words.skip_while(|w| *w != "on").nth(1).unwrap_or("");
+            Some((ip, mac.to_string(), dev.to_string()))
+        })
+        .collect())
+}
+
+// The IPv4 neighbour table, with interfaces by name ("Ethernet", "Wi-Fi").
+// Multicast and broadcast entries are left out, as Linux has none.
+#[cfg(windows)]
+pub fn arp_entries() -> Result<Vec<(Ipv4Addr, String, String)>, String> {
+    use windows_sys::Win32::Foundation::NO_ERROR;
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        ConvertInterfaceLuidToAlias, FreeMibTable, GetIpNetTable2, MIB_IPNET_ROW2, MIB_IPNET_TABLE2,
+    };
+    use windows_sys::Win32::Networking::WinSock::{AF_INET, NlnsIncomplete, NlnsUnreachable};
+
+    let mut table: *mut MIB_IPNET_TABLE2 = std::ptr::null_mut();
+    let ret = unsafe { GetIpNetTable2(AF_INET, &mut table) };
+    if ret != NO_ERROR {
+        return Err(format!("GetIpNetTable2 failed (error {ret})"));
+    }
+    let rows: &[MIB_IPNET_ROW2] = unsafe {
+        std::slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize)
+    };
+    let mut names: HashMap<u32, String> = HashMap::new();
+    let entries = rows
+        .iter()
+        .filter(|r| r.State != NlnsIncomplete && r.State != NlnsUnreachable)
+        .filter_map(|r| {
+            let ip = Ipv4Addr::from(unsafe { r.Address.Ipv4.sin_addr.S_un.S_addr }.to_ne_bytes());
+            let len = (r.PhysicalAddressLength as usize).min(r.PhysicalAddress.len());
+            let mac = &r.PhysicalAddress[..len];
+            if len == 0 || ip.is_multicast() || ip.is_broadcast() || mac.iter().all(|&b| b == 0xff)
+            {
+                return None;
+            }
+            let dev = names.entry(r.InterfaceIndex).or_insert_with(|| {
+                let mut alias = [0u16; 257];
+                let ret = unsafe {
+                    ConvertInterfaceLuidToAlias(&r.InterfaceLuid, alias.as_mut_ptr(), alias.len())
+                };
+                let end = alias.iter().position(|&c| c == 0).unwrap_or(alias.len());
+                match ret {
+                    NO_ERROR if end > 0 => String::from_utf16_lossy(&alias[..end]),
+                    _ => r.InterfaceIndex.to_string(),
+                }
+            });
+            Some((ip, hex_mac(mac), dev.clone()))
+        })
+        .collect();
+    unsafe { FreeMibTable(table.cast()) };
+    Ok(entries)
+}
+
+// "00:1a:2b:3c:4d:5e"
+#[cfg(windows)]
+fn hex_mac(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    hex.join(":")
+}
+
+// MACs of neighbours the ARP cache has forgotten, e.g. hosts whose verdict
+// came from --cache, found by ARPing for them. Only addresses in directly
+// connected subnets are tried.
+pub fn resolve_macs(addrs: &[Ipv4Addr]) -> HashMap<Ipv4Addr, String> {
+    let subnets: Vec<Cidr> = match connected_routes() {
+        Ok(routes) => routes
+            .into_iter()
+            .map(|(net, prefix, _)| Cidr::new(net, prefix))
+            .collect(),
+        Err(_) => return HashMap::new(),
+    };
+    let local: Vec<Ipv4Addr> = addrs
+        .iter()
+        .copied()
+        .filter(|&ip| subnets.iter().any(|s| s.prefix < 32 && s.contains(ip)))
+        .collect();
+    if local.is_empty() {
+        return HashMap::new();
+    }
+    ask_macs(&local)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn ask_macs(addrs: &[Ipv4Addr]) -> HashMap<Ipv4Addr, String> {
+    crate::netlink::solicit(addrs)
+}
+
+// The BSDs and macOS would need a BPF device; the cache is all there is
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn ask_macs(_: &[Ipv4Addr]) -> HashMap<Ipv4Addr, String> {
+    HashMap::new()
+}
+
+#[cfg(windows)]
+fn ask_macs(addrs: &[Ipv4Addr]) -> HashMap<Ipv4Addr, String> {
+    use windows_sys::Win32::NetworkManagement::IpHelper::SendARP;
+
+    // SendARP blocks until the answer or its own timeout of about a second
+    let mut out = HashMap::new();
+    for batch in addrs.chunks(64) {
+        std::thread::scope(|s| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|&ip| {
+                    s.spawn(move || {
+                        let mut mac = [0u8; 8];
+                        let mut len = mac.len() as u32;
+                        let dest = u32::from_ne_bytes(ip.octets());
+                        let ret = unsafe { SendARP(dest, 0, mac.as_mut_ptr().cast(), &mut len) };
+                        let len = (len as usize).min(mac.len());
+                        (ret == 0 && len > 0).then(|| (ip, hex_mac(&mac[..len])))
+                    })
+                })
+                .collect();
+            out.extend(handles.into_iter().filter_map(|h| h.join().ok().flatten()));
+        });
+    }
+    out
+}
+
+// MAC targets: where each of `macs` (normalised) is now, with the
+// interface. The ARP cache answers first; for the rest every address of the
+// directly connected subnets (up to a /16) is ARPed for.
+pub fn locate_macs(macs: &[String]) -> Result<HashMap<String, (Ipv4Addr, String)>, String> {
+    let mut found = HashMap::new();
+    for (ip, mac, dev) in arp_entries()? {
+        if let Some(mac) = normalise_mac(&mac).filter(|m| macs.contains(m)) {
+            found.entry(mac).or_insert((ip, dev));
+        }
+    }
+    if found.len() == macs.len() {
+        return Ok(found);
+    }
+    let mut hosts = Vec::new();
+    let mut devs: Vec<(Cidr, String)> = Vec::new();
+    for (net, prefix, dev) in connected_routes()? {
+        let block = Cidr::new(net, prefix);
+        if block.prefix == 32
+            || block.prefix < MIN_ROUTE_PREFIX
+            || block.net.is_loopback()
+            || devs.iter().any(|(b, _)| *b == block)
+        {
+            continue;
+        }
+        hosts.extend(block.hosts());
+        devs.push((block, dev));
+    }
+    for (ip, mac) in ask_macs(&hosts) {
+        let Some(mac) = normalise_mac(&mac).filter(|m| macs.contains(m)) else {
+            continue;
+        };
+        let dev = devs
+            .iter()
+            .find(|(b, _)| b.contains(ip))
+            .map_or_else(String::new, |(_, d)| d.clone());
+        found.entry(mac).or_insert((ip, dev));
+    }
+    Ok(found)
+}
+
+// Connected subnets bigger than this are skipped by --from-routes
+const MIN_ROUTE_PREFIX: u32 = 16;
+
+// --from-routes: every host address of each directly connected IPv4 subnet
+// (no gateway), tagged with the subnet and interface. Loopback, host routes
+// and subnets wider than a /16 are left out.
+pub fn routes() -> Result<Vec<Target>, String> {
+    subnet_hosts("--from-routes", |_| true)
+}
+
+// -I/--interface: the hosts of each of `names`' connected subnets, as
+// --from-routes has them, taken from the interfaces in turn so that each
+// batch of the sweep covers all of them rather than one after the other
+pub fn interfaces(names: &[String]) -> Result<Vec<Target>, String> {
+    let mut unique: Vec<&str> = Vec::with_capacity(names.len());
+    for name in names {
+        if !unique.contains(&name.as_str()) {
+            unique.push(name);
+        }
+    }
+    let names = unique;
+    let hosts = subnet_hosts("--interface", |dev| names.contains(&dev))?;
+    let total = hosts.len();
+    let mut on: Vec<Vec<Target>> = names.iter().map(|_| Vec::new()).collect();
+    for target in hosts {
+        let dev = target.fields.iter().find(|(k, _)| k == "interface");
+        if let Some(i) = dev.and_then(|(_, d)| names.iter().position(|n| n == d)) {
+            on[i].push(target);
+        }
+    }
+    if let Some(i) = on.iter().position(Vec::is_empty) {
+        return Err(format!(
+            "{} has no directly connected IPv4 subnet",
+            names[i]
+        ));
+    }
+    let mut queues: Vec<_> = on.into_iter().map(Vec::into_iter).collect();
+    let mut out = Vec::with_capacity(total);
+    while out.len() < total {
+        out.extend(queues.iter_mut().filter_map(Iterator::next));
+    }
+    Ok(out)
+}
+
+// The host addresses of the connected subnets on the interfaces `keep`
+// picks, tagged with the subnet and interface; `flag` heads the warnings
+fn subnet_hosts(flag: &str, keep: impl Fn(&str) -> bool) -> Result<Vec<Target>, String> {
+    let mut out = Vec::new();
+    let mut seen = Vec::new();
+    for (net, prefix, dev) in connected_routes()? {
+        if !keep(&dev) {
+            continue;
+        }
+        let block = Cidr::new(net, prefix);
+        if block.prefix == 32 || block.net.is_loopback() || block.net.is_multicast() {
+            continue;
+        }
+        if block.prefix < MIN_ROUTE_PREFIX {
+            eprintln!("{flag}: {block} on {dev} is wider than /{MIN_ROUTE_PREFIX}, skipped");
+            continue;
+        }
+        if seen.contains(&block) {
+            continue;
+        }
+        for ip in block.hosts() {
+            out.push(Target {
+                ip: ip.to_string(),
+                fields: vec![
+                    ("subnet".into(), block.to_string()),
+                    ("interface".into(), dev.clone()),
+                ],
+                options: Overrides::default(),
+            });
+        }
+        seen.push(block);
+    }
+    Ok(out)
+}
+
+// Iface Destination Gateway Flags RefCnt Use Metric Mask ..., with
+// addresses as little-endian hex
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn connected_routes() -> Result<Vec<(Ipv4Addr, u32, String)>, String> {
+    const RTF_UP: u32 = 0x1;
+    const RTF_GATEWAY: u32 = 0x2;
+    // Android keeps a routing table per network apart from the main one
+    // /proc/net/route shows, and keeps apps out of /proc/net besides
+    if cfg!(target_os = "android") {
+        return interface_subnets();
+    }
+    let text =
+        fs::read_to_string("/proc/net/route").map_err(|e| format!("/proc/net/route: {e}"))?;
+    let hex = |s: &str| u32::from_str_radix(s, 16).ok();
+    Ok(text
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            let (dest, flags, mask) = (hex(cols.get(1)?)?, hex(cols.get(3)?)?, hex(cols.get(7)?)?);
+            if flags & RTF_UP == 0 || flags & RTF_GATEWAY != 0 || mask == 0 {
+                return None;
+            }
+            let dest = Ipv4Addr::from(dest.to_le_bytes());
+            Some((dest, mask.count_ones(), cols[0].to_string()))
+        })
+        .collect())
+}
+
+// The subnet of each IPv4 address on an interface that is up, from
+// getifaddrs(3), which any app may call
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn interface_subnets() -> Result<Vec<(Ipv4Addr, u32, String)>, String> {
+    let v4 = |sa: *const libc::sockaddr| {
+        if sa.is_null() || unsafe { (*sa).sa_family } != libc::AF_INET as libc::sa_family_t {
+            return None;
+        }
+        let sin = unsafe { &*sa.cast::<libc::sockaddr_in>() };
+        Some(u32::from_be(sin.sin_addr.s_addr))
+    };
+    let mut list: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut list) } != 0 {
+        return Err(format!("getifaddrs: {}", io::Error::last_os_error()));
+    }
+    let mut out = Vec::new();
+    let mut next = list;
+    while let Some(ifa) = unsafe { next.as_ref() } {
+        next = ifa.ifa_next;
+        if ifa.ifa_flags & libc::IFF_UP as libc::c_uint == 0 {
+            continue;
+        }
+        let (Some(addr), Some(mask)) = (v4(ifa.ifa_addr), v4(ifa.ifa_netmask)) else {
+            continue;
+        };
+        let dev = unsafe { std::ffi::CStr::from_ptr(ifa.ifa_name) };
+        out.push((
+            Ipv4Addr::from(addr & mask),
+            mask.count_ones(),
+            dev.to_string_lossy().into_owned(),
+        ));
+    }
+    unsafe { libc::freeifaddrs(list) };
+    Ok(out)
+}
+
+// `netstat -rn -f inet`: "192.168.1  link#4  UCS  en0" or "10.8/16 ...",
+// where connected networks have a link#N gateway and no G/H flag
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn connected_routes() -> Result<Vec<(Ipv4Addr, u32, String)>, String> {
+    let out = std::process::Command::new("netstat")
+        .args(["-rn", "-f", "inet"])
+        .output()
+        .map_err(|e| format!("netstat -rn: {e}"))?;
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            let (dest, gateway, flags) = (cols.first()?, cols.get(1)?, cols.get(2)?);
+            if !gateway.starts_with("link#") || flags.contains(['G', 'H']) {
+                return None;
+            }
+            let dev = cols.get(3)?.to_string();
+            let (addr, prefix) = match dest.split_once('/') {
+                Some((a, p)) => (a, Some(p.parse().ok()?)),
+                None => (*dest, None),
+            };
+            // Trailing zero octets are dropped: "10/8", "192.168.1"
+            let mut octets = [0u8; 4];
+            let parts: Vec<&str> = addr.split('.').collect();
+            if parts.len() > 4 {
+                return None;
+            }
+            for (o, p) in octets.iter_mut().zip(&parts) {
+                *o = p.parse().ok()?;
+            }
+            let prefix = prefix.unwrap_or(8 * parts.len() as u32);
+            Some((Ipv4Addr::from(octets), prefix, dev))
+        })
+        .collect())
+}
+
+#[cfg(windows)]
+fn connected_routes() -> Result<Vec<(Ipv4Addr, u32, String)>, String> {
+    use windows_sys::Win32::Foundation::{ERROR_INSUFFICIENT_BUFFER, NO_ERROR};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        GetIpForwardTable, MIB_IPFORWARDROW, MIB_IPFORWARDTABLE, MIB_IPROUTE_TYPE_DIRECT,
+    };
+
+    let mut size = 0u32;
+    let ret = unsafe { GetIpForwardTable(std::ptr::null_mut(), &mut size, 0) };
+    if ret != ERROR_INSUFFICIENT_BUFFER && ret != NO_ERROR {
+        return Err(format!("GetIpForwardTable failed (error {ret})"));
+    }
+    let mut buf = vec![0u64; (size as usize).div_ceil(8).max(1)];
+    let table = buf.as_mut_ptr() as *mut MIB_IPFORWARDTABLE;
+    let ret = unsafe { GetIpForwardTable(table, &mut size, 1) };
+    if ret != NO_ERROR {
+        return Err(format!("GetIpForwardTable failed (error {ret})"));
+    }
+    let rows: &[MIB_IPFORWARDROW] = unsafe {
+        std::slice::from_raw_parts((*table).table.as_ptr(), (*table).dwNumEntries as usize)
+    };
+    Ok(rows
+        .iter()
+        .filter(|r| unsafe { r.Anonymous1.ForwardType } == MIB_IPROUTE_TYPE_DIRECT)
+        .filter(|r| r.dwForwardMask != 0)
+        .map(|r| {
+            (
+                Ipv4Addr::from(r.dwForwardDest.to_ne_bytes()),
+                r.dwForwardMask.count_ones(),
+                r.dwForwardIfIndex.to_string(),
+            )
+        })
+        .collect())
+}
+
+// --check-gateway: the next hop of the IPv4 default route with the lowest
+// metric, and its interface. None without a default route.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn default_gateway() -> Result<Option<(Ipv4Addr, String)>, String> {
+    const RTF_UP: u32 = 0x1;
+    const RTF_GATEWAY: u32 = 0x2;
+    if cfg!(target_os = "android") {
+        return table_gateway();
+    }
+    let text =
+        fs::read_to_string("/proc/net/route").map_err(|e| format!("/proc/net/route: {e}"))?;
+    let hex = |s: &str| u32::from_str_radix(s, 16).ok();
+    Ok(text
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            let (dest, gateway, flags) =
+                (hex(cols.get(1)?)?, hex(cols.get(2)?)?, hex(cols.get(3)?)?);
+            let (metric, mask) = (cols.get(6)?.parse::<u32>().ok()?, hex(cols.get(7)?)?);
+            if dest != 0 || mask != 0 || flags & (RTF_UP | RTF_GATEWAY) != RTF_UP | RTF_GATEWAY {
+                return None;
+            }
+            let gateway = Ipv4Addr::from(gateway.to_le_bytes());
+            Some((metric, gateway, cols[0].to_string()))
+        })
+        .min_by_key(|r| r.0)
+        .map(|(_, gateway, dev)| (gateway, dev)))
+}
+
+// `ip -4 route show table all`: "default via 192.168.1.1 dev wlan0 table
+// wlan0 proto static", from Android's tables per network; the first one
+// listed is the one in use
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn table_gateway() -> Result<Option<(Ipv4Addr, String)>, String> {
+    let out = std::process::Command::new("ip")
+        .args(["-4", "route", "show", "table", "all"])
+        .output()
+        .map_err(|e| format!("ip route: {e}"))?;
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        return Err(format!(
+            "ip route: {}",
+            err.lines().next().unwrap_or("failed").trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .find_map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.first() != Some(&"default") {
+                return None;
+            }
+            let after = |key| {
+                words
+                    .iter()
+                    .position(|w| *w == key)
+                    .and_then(|i| words.get(i + 1))
+            };
+            let gateway = after("via")?.parse().ok()?;
+            Some((gateway, after("dev").unwrap_or(&"").to_string()))
+        }))
+}
+
+// `netstat -rn -f inet`: "default  192.168.1.1  UGScg  en0"; the first
+// one listed is the one in use
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+pub fn default_gateway() -> Result<Option<(Ipv4Addr, String)>, String> {
+    let out = std::process::Command::new("netstat")
+        .args(["-rn", "-f", "inet"])
+        .output()
+        .map_err(|e| format!("netstat -rn: {e}"))?;
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .find_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if !matches!(cols.first(), Some(&("default" | "0.0.0.0/0"))) {
+                return None;
+            }
+            let gateway = cols.get(1)?.parse().ok()?;
+            Some((gateway, cols.get(3).unwrap_or(&"").to_string()))
+        }))
+}
+
+#[cfg(windows)]
+pub fn default_gateway() -> Result<Option<(Ipv4Addr, String)>, String> {
+    use windows_sys::Win32::Foundation::{ERROR_INSUFFICIENT_BUFFER, NO_ERROR};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        GetIpForwardTable, MIB_IPFORWARDROW, MIB_IPFORWARDTABLE,
+    };
+
+    let mut size = 0u32;
+    let ret = unsafe { GetIpForwardTable(std::ptr::null_mut(), &mut size, 0) };
+    if ret != ERROR_INSUFFICIENT_BUFFER && ret != NO_ERROR {
+        return Err(format!("GetIpForwardTable failed (error {ret})"));
+    }
+    let mut buf = vec![0u64; (size as usize).div_ceil(8).max(1)];
+    let table = buf.as_mut_ptr() as *mut MIB_IPFORWARDTABLE;
+    let ret = unsafe { GetIpForwardTable(table, &mut size, 1) };
+    if ret != NO_ERROR {
+        return Err(format!("GetIpForwardTable failed (error {ret})"));
+    }
+    let rows: &[MIB_IPFORWARDROW] = unsafe {
+        std::slice::from_raw_parts((*table).table.as_ptr(), (*table).dwNumEntries as usize)
+    };
+    Ok(rows
+        .iter()
+        .filter(|r| r.dwForwardDest == 0 && r.dwForwardMask == 0 && r.dwForwardNextHop != 0)
+        .min_by_key(|r| r.dwForwardMetric1)
+        .map(|r| {
+            (
+                Ipv4Addr::from(r.dwForwardNextHop.to_ne_bytes()),
+                r.dwForwardIfIndex.to_string(),
+            )
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(text: &str) -> Result<Vec<Target>, String> {
+        list(text, Path::new("hosts.txt"))
+    }
+
+    #[test]
+    fn list_reads_targets_and_settings() {
+        let text = "# lab\n10.0.0.1\n\n10.0.0.9 timeout=5000 probe=tcp:8443 label=rig-7 # sat\n";
+        let targets = parsed(text).unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].ip, "10.0.0.1");
+        assert!(targets[0].options.is_empty());
+        assert_eq!(targets[1].ip, "10.0.0.9");
+        assert_eq!(
+            targets[1].fields,
+            [("label".to_string(), "rig-7".to_string())]
+        );
+        assert_eq!(
+            targets[1].options,
+            Overrides {
+                timeout: Some(Duration::from_millis(5000)),
+                probe: Some(ProbeType::Tcp),
+                ports: Some(vec![8443]),
+                ..Overrides::default()
+            }
+        );
+    }
+
+    #[test]
+    fn list_errors_name_the_line() {
+        let e = parsed("10.0.0.1\n10.0.0.2 timeout\n").unwrap_err();
+        assert_eq!(e, "hosts.txt:2: timeout: use key=value");
+        let e = parsed("10.0.0.1 rate=10\n").unwrap_err();
+        assert!(
+            e.starts_with("hosts.txt:1: unknown setting \"rate\""),
+            "{e}"
+        );
+    }
+
+    #[test]
+    fn set_reads_each_setting() {
+        let mut own = Overrides::default();
+        own.set("timeout", "250").unwrap();
+        own.set("interval", "100").unwrap();
+        own.set("count", "0").unwrap();
+        own.set("probe", "icmp-timestamp").unwrap();
+        own.set("ports", "22,80-81").unwrap();
+        assert_eq!(
+            own,
+            Overrides {
+                timeout: Some(Duration::from_millis(250)),
+                count: Some(1),
+                interval: Some(Duration::from_millis(100)),
+                probe: Some(ProbeType::Timestamp),
+                ports: Some(vec![22, 80, 81]),
+            }
+        );
+    }
+
+    #[test]
+    fn set_rejects_bad_values() {
+        let mut own = Overrides::default();
+        assert!(
+            own.set("timeout", "5s")
+                .unwrap_err()
+                .starts_with("timeout: ")
+        );
+        assert!(own.set("count", "-1").unwrap_err().starts_with("count: "));
+        assert!(own.set("probe", "udp").unwrap_err().starts_with("probe: "));
+        assert!(own.set("probe", "tcp:").unwrap_err().starts_with("probe: "));
+        assert!(own.is_empty());
+    }
+}
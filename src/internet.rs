@@ -0,0 +1,339 @@
+use crate::json::Value;
+use crate::{http, input, output};
+use ipchk::dns::{self, Resolver};
+use ipchk::{Outcome, ProbeOptions, ProbeType, Scan, probe};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/* -------------------- ipchk internet: is the uplink working? -------------------- */
+
+// Anycast resolvers, each reachable from almost anywhere
+const ANYCAST: [(Ipv4Addr, &str); 3] = [
+    (Ipv4Addr::new(1, 1, 1, 1), "Cloudflare"),
+    (Ipv4Addr::new(8, 8, 8, 8), "Google"),
+    (Ipv4Addr::new(9, 9, 9, 9), "Quad9"),
+];
+// Tried when no anycast address answers pings, for networks that drop ICMP
+const WAN_TCP_PORT: u16 = 443;
+pub const DEFAULT_NAME: &str = "example.com";
+// Answers 204 with an empty body; anything else came from something in
+// between, usually a portal's login page or redirect
+const CHECK_HOST: &str = "connectivitycheck.gstatic.com";
+const CHECK_PATH: &str = "/generate_204";
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub const USAGE: &str = "Usage: ipchk internet [<name to resolve>] [OPTIONS]";
+
+pub struct Request {
+    pub probe: ProbeOptions,    // engine, timeout and count for every probe
+    pub name: String,           // resolved for the DNS layer
+    pub dns: Option<Resolver>,  // --dns-server, else the system resolver
+    pub dns_timeout: Duration,  // --dns-timeout
+    pub output: output::Format, // text or json
+    pub raw: bool,              // -a/--raw
+}
+
+// One line of the verdict
+struct Layer {
+    name: &'static str,
+    ok: bool,
+    detail: String,
+    rtt: Option<Duration>,
+    portal: bool, // answered, but by a captive portal
+}
+
+pub enum Captive {
+    Online,
+    Portal(String), // what gave it away
+    Failed(String), // the fetch didn't get an answer at all
+}
+
+// The sweep's options, as a plain echo request
+pub fn echo(opts: &ProbeOptions) -> ProbeOptions {
+    ProbeOptions {
+        probe: ProbeType::Echo,
+        payload: None,
+        df: false,
+        ports: Vec::new(),
+        ..opts.clone()
+    }
+}
+
+// The default gateway's address, interface and RTT, or why it can't be
+// reached. Also behind --check-gateway.
+pub fn gateway(opts: &ProbeOptions) -> Result<(Ipv4Addr, String, Duration), String> {
+    let (gateway, dev) = match input::default_gateway() {
+        Ok(Some(route)) => route,
+        Ok(None) => return Err("there is no IPv4 default route".into()),
+        Err(e) => return Err(format!("can't read the default route: {e}")),
+    };
+    let on = if dev.is_empty() {
+        String::new()
+    } else {
+        format!(" on {dev}")
+    };
+    match probe(gateway, &echo(opts)) {
+        Ok(o) if o.up => Ok((gateway, dev, rtt(&o).unwrap_or_default())),
+        Ok(_) => Err(format!("the default gateway {gateway}{on} isn't answering")),
+        Err(e) => Err(format!(
+            "can't probe the default gateway {gateway}{on}: {e}"
+        )),
+    }
+}
+
+fn rtt(o: &Outcome) -> Option<Duration> {
+    o.stats.rtt.map(|r| r.avg)
+}
+
+fn ms(d: Duration) -> String {
+    format!("{:.1} ms", d.as_secs_f64() * 1000.0)
+}
+
+// Fetches the check URL in clear text. Also behind --captive-check.
+pub fn captive(dns: Option<&Resolver>, dns_timeout: Duration) -> Captive {
+    let addrs = match dns::resolve(CHECK_HOST, dns, dns_timeout) {
+        Ok(addrs) => addrs,
+        Err(e) => return Captive::Failed(e.to_string()),
+    };
+    match http::get(
+        SocketAddr::new(addrs[0], 80),
+        CHECK_HOST,
+        CHECK_PATH,
+        CHECK_TIMEOUT,
+    ) {
+        Ok(r) if r.status == 204 && r.body.is_empty() => Captive::Online,
+        Ok(r) => Captive::Portal(match r.location {
+            Some(to) if (300..400).contains(&r.status) => format!("redirected to {to}"),
+            _ => format!(
+                "{CHECK_HOST} answered {} with {} bytes instead of 204",
+                r.status,
+                r.body.len()
+            ),
+        }),
+        Err(e) => Captive::Failed(e),
+    }
+}
+
+// All four layers are checked at once, so a dead uplink costs one timeout
+pub fn run(req: &Request) -> Result<bool, String> {
+    if !matches!(req.output, output::Format::Text | output::Format::Json) {
+        return Err("internet: --output must be text or json".into());
+    }
+    let mut layers = thread::scope(|s| {
+        let handles = [
+            s.spawn(|| lan(req)),
+            s.spawn(|| wan(req)),
+            s.spawn(|| dns(req)),
+            s.spawn(|| web(req)),
+        ];
+        handles
+            .map(|h| {
+                h.join()
+                    .map_err(|_| "internet: a check panicked".to_string())
+            })
+            .into_iter()
+            .collect::<Result<Vec<Layer>, String>>()
+    })?;
+    // A gateway that drops pings still forwards traffic
+    if !layers[0].ok && layers[1].ok {
+        layers[0]
+            .detail
+            .push_str(" (but traffic gets through, so it may just drop pings)");
+    }
+    let all_ok = layers.iter().all(|l| l.ok);
+    match req.output {
+        output::Format::Json => println!("{}", to_json(&layers, all_ok)),
+        _ => {
+            for l in &layers {
+                let (state, colour) = match (l.ok, l.portal) {
+                    (true, _) => ("ok", "32"),
+                    (false, true) => ("PORTAL", "33"),
+                    (false, false) => ("FAIL", "31"),
+                };
+                let pad = " ".repeat(6 - state.len());
+                if req.raw {
+                    println!("{:<4} {state}{pad}  {}", l.name, l.detail);
+                } else {
+                    println!(
+                        "{:<4} \x1b[1m\x1b[{colour}m{state}\x1b[0m{pad}  {}",
+                        l.name, l.detail
+                    );
+                }
+            }
+        }
+    }
+    Ok(all_ok)
+}
+
+fn lan(req: &Request) -> Layer {
+    match gateway(&req.probe) {
+        Ok((gateway, dev, rtt)) => {
+            let on = if dev.is_empty() {
+                String::new()
+            } else {
+                format!(" on {dev}")
+            };
+            Layer {
+                name: "LAN",
+                ok: true,
+                detail: format!("gateway {gateway}{on}, {}", ms(rtt)),
+                rtt: Some(rtt),
+                portal: false,
+            }
+        }
+        Err(problem) => Layer {
+            name: "LAN",
+            ok: false,
+            detail: problem,
+            rtt: None,
+            portal: false,
+        },
+    }
+}
+
+fn wan(req: &Request) -> Layer {
+    let ping = echo(&req.probe);
+    let tcp = ProbeOptions {
+        probe: ProbeType::Tcp,
+        ports: vec![WAN_TCP_PORT],
+        scan: Scan::Connect,
+        ..ping.clone()
+    };
+    let sweep = |opts: &ProbeOptions| -> Vec<Result<Outcome, String>> {
+        thread::scope(|s| {
+            let handles: Vec<_> = ANYCAST
+                .iter()
+                .map(|&(ip, _)| s.spawn(move || probe(ip, opts).map_err(|e| e.to_string())))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|_| Err("panicked".into())))
+                .collect()
+        })
+    };
+    let mut results = sweep(&ping);
+    let mut via = "";
+    if !results.iter().any(|r| matches!(r, Ok(o) if o.up)) {
+        let over_tcp = sweep(&tcp);
+        if over_tcp.iter().any(|r| matches!(r, Ok(o) if o.up)) {
+            results = over_tcp;
+            via = " (TCP 443 only; pings are dropped)";
+        }
+    }
+    let mut best: Option<Duration> = None;
+    let parts: Vec<String> = ANYCAST
+        .iter()
+        .zip(&results)
+        .map(|(&(ip, who), r)| match r {
+            Ok(o) if o.up => {
+                let t = rtt(o).unwrap_or_default();
+                best = Some(best.map_or(t, |b| b.min(t)));
+                format!("{ip} ({who}) {}", ms(t))
+            }
+            Ok(_) => format!("{ip} ({who}) down"),
+            Err(e) => format!("{ip} ({who}) {e}"),
+        })
+        .collect();
+    Layer {
+        name: "WAN",
+        ok: best.is_some(),
+        detail: format!("{}{via}", parts.join(", ")),
+        rtt: best,
+        portal: false,
+    }
+}
+
+fn dns(req: &Request) -> Layer {
+    let started = Instant::now();
+    let result = dns::resolve(&req.name, req.dns.as_ref(), req.dns_timeout);
+    let took = started.elapsed();
+    let server = req.dns.map_or_else(
+        || "the system resolver".to_string(),
+        |r| r.server.to_string(),
+    );
+    match result {
+        Ok(addrs) => {
+            let shown: Vec<String> = addrs.iter().take(2).map(IpAddr::to_string).collect();
+            Layer {
+                name: "DNS",
+                ok: true,
+                detail: format!(
+                    "{} is {} via {server}, {}",
+                    req.name,
+                    shown.join(", "),
+                    ms(took)
+                ),
+                rtt: Some(took),
+                portal: false,
+            }
+        }
+        Err(e) => {
+            let mut detail = format!("{server} failed: {e}");
+            // Tells a broken local resolver apart from DNS blocked outright
+            let direct = Resolver {
+                timeout: req.dns_timeout,
+                ..Resolver::new(SocketAddr::new(ANYCAST[0].0.into(), 53))
+            };
+            if req.dns.is_none_or(|r| r.server != direct.server) && direct.lookup(&req.name).is_ok()
+            {
+                detail.push_str(&format!(" ({} answers directly)", direct.server.ip()));
+            }
+            Layer {
+                name: "DNS",
+                ok: false,
+                detail,
+                rtt: None,
+                portal: false,
+            }
+        }
+    }
+}
+
+fn web(req: &Request) -> Layer {
+    let started = Instant::now();
+    let (ok, portal, detail) = match captive(req.dns.as_ref(), req.dns_timeout) {
+        Captive::Online => (
+            true,
+            false,
+            format!("http://{CHECK_HOST}{CHECK_PATH} answered as expected"),
+        ),
+        Captive::Portal(what) => (
+            false,
+            true,
+            format!("online, but behind a captive portal: {what}"),
+        ),
+        Captive::Failed(e) => (
+            false,
+            false,
+            format!("can't fetch http://{CHECK_HOST}{CHECK_PATH}: {e}"),
+        ),
+    };
+    Layer {
+        name: "HTTP",
+        ok,
+        detail,
+        rtt: ok.then(|| started.elapsed()),
+        portal,
+    }
+}
+
+// {"ok":true,"lan":{"ok":true,"detail":"...","rtt_ms":0.4},"wan":{...},...}
+fn to_json(layers: &[Layer], all_ok: bool) -> String {
+    let mut members = vec![("ok".to_string(), Value::Bool(all_ok))];
+    for l in layers {
+        let mut layer = vec![
+            ("ok".to_string(), Value::Bool(l.ok)),
+            ("detail".to_string(), Value::Str(l.detail.clone())),
+        ];
+        if l.portal {
+            layer.push(("portal".to_string(), Value::Bool(true)));
+        }
+        if let Some(t) = l.rtt {
+            let ms = format!("{:.3}", t.as_secs_f64() * 1000.0);
+            layer.push(("rtt_ms".to_string(), Value::Num(ms)));
+        }
+        members.push((l.name.to_lowercase(), Value::Object(layer)));
+    }
+    Value::Object(members).to_json()
+}
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+
+use crate::{PingResult, output};
+
+/* -------------------- systemd journal entries (--journald) -------------------- */
+
+// Results and state changes as structured entries, through journald's
+// native protocol: one datagram per entry, its fields as KEY=value lines.
+// SYSLOG_IDENTIFIER is ipchk, so `journalctl -t ipchk IPCHK_STATUS=down`
+// finds the downs.
+
+#[cfg(unix)]
+const SOCKET: &str = "/run/systemd/journal/socket";
+
+// syslog priorities
+const ERR: u8 = 3;
+const WARNING: u8 = 4;
+const NOTICE: u8 = 5;
+const INFO: u8 = 6;
+
+#[derive(Debug)]
+pub struct Journal {
+    #[cfg(unix)]
+    sock: UnixDatagram,
+}
+
+impl Journal {
+    #[cfg(unix)]
+    pub fn open() -> Result<Self, String> {
+        let sock = UnixDatagram::unbound().map_err(|e| e.to_string())?;
+        sock.connect(SOCKET)
+            .map_err(|e| format!("no systemd journal here ({SOCKET}: {e})"))?;
+        Ok(Journal { sock })
+    }
+
+    #[cfg(not(unix))]
+    pub fn open() -> Result<Self, String> {
+        Err("no systemd journal here (it is Linux-only)".into())
+    }
+
+    // A sweep's verdict on one target
+    pub fn result(&self, r: &PingResult) -> Result<(), String> {
+        let status = output::status(r);
+        let mut fields = vec![
+            ("IPCHK_EVENT", "result".to_string()),
+            ("IPCHK_TARGET", r.ip.clone()),
+            ("IPCHK_STATUS", status.to_string()),
+        ];
+        if let Some(addr) = r.addr.filter(|a| a.to_string() != r.ip) {
+            fields.push(("IPCHK_ADDRESS", addr.to_string()));
+        }
+        if let Some(name) = &r.name {
+            fields.push(("IPCHK_NAME", name.clone()));
+        }
+        if let Some(label) = &r.label {
+            fields.push(("IPCHK_LABEL", label.clone()));
+        }
+        if let Some(o) = &r.outcome {
+            if let Some(rtt) = o.stats.rtt {
+                fields.push(("IPCHK_RTT", ms(rtt.avg)));
+            }
+            fields.push(("IPCHK_LOSS", format!("{}", o.stats.loss)));
+        }
+        let message = match &r.error {
+            Some(e) => {
+                fields.push(("IPCHK_ERROR", e.to_string()));
+                format!("{} is {status}: {e}", r.ip)
+            }
+            None => format!("{} is {status}", r.ip),
+        };
+        let priority = match r.up {
+            Some(true) => INFO,
+            Some(false) => WARNING,
+            None => ERR,
+        };
+        self.send(priority, &message, &fields)
+    }
+
+    // The daemon's verdict on a host, when it differs from `previous`
+    // (None for the first one); `downtime` is how long in seconds a host
+    // back up was down
+    pub fn change(
+        &self,
+        target: &str,
+        up: bool,
+        previous: Option<bool>,
+        rtt: Option<Duration>,
+        downtime: Option<u64>,
+        message: &str,
+    ) -> Result<(), String> {
+        let word = |up| if up { "up" } else { "down" };
+        let mut fields = vec![
+            ("IPCHK_EVENT", "change".to_string()),
+            ("IPCHK_TARGET", target.to_string()),
+            ("IPCHK_STATUS", word(up).to_string()),
+        ];
+        if let Some(previous) = previous {
+            fields.push(("IPCHK_PREVIOUS", word(previous).to_string()));
+        }
+        if let Some(rtt) = rtt.filter(|_| up) {
+            fields.push(("IPCHK_RTT", ms(rtt)));
+        }
+        if let Some(secs) = downtime {
+            fields.push(("IPCHK_DOWNTIME", secs.to_string()));
+        }
+        self.send(if up { NOTICE } else { WARNING }, message, &fields)
+    }
+
+    #[cfg(unix)]
+    fn send(&self, priority: u8, message: &str, fields: &[(&str, String)]) -> Result<(), String> {
+        let mut entry = Vec::new();
+        let common = [
+            ("PRIORITY", priority.to_string()),
+            ("SYSLOG_IDENTIFIER", "ipchk".to_string()),
+            ("MESSAGE", message.to_string()),
+        ];
+        for (key, value) in common.iter().chain(fields) {
+            entry.extend_from_slice(key.as_bytes());
+            // A value with a newline in it goes length-prefixed instead
+            if value.contains('\n') {
+                entry.push(b'\n');
+                entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            } else {
+                entry.push(b'=');
+            }
+            entry.extend_from_slice(value.as_bytes());
+            entry.push(b'\n');
+        }
+        self.sock
+            .send(&entry)
+            .map(|_| ())
+            .map_err(|e| format!("{SOCKET}: {e}"))
+    }
+
+    #[cfg(not(unix))]
+    fn send(&self, _: u8, _: &str, _: &[(&str, String)]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// Milliseconds, as the other outputs give RTTs
+fn ms(d: Duration) -> String {
+    format!("{:.3}", d.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,1463 @@
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+pub mod dns;
+#[cfg(unix)]
+mod icmp;
+mod privileges;
+mod registry;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod syn;
+mod tcp;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use privileges::{Privileges, descriptors_per_host, in_flight_limit};
+pub use registry::{
+    Builtin, Cancel, Deadline, Probe, ProbeOutcome, Registry, Target, sweep, sweep_until,
+};
+#[cfg(feature = "wasm")]
+pub use wasm::WasmProbe;
+
+/* -------------------- error model -------------------- */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeErrorKind {
+    Unsupported, // address family / platform has no probe path yet
+    NotFound,    // the system ping executable is missing
+    Exec,        // ping could not be run, or died without an exit code
+    Os,          // a platform API call failed (e.g. IcmpCreateFile)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Parse {
+        input: String,
+        expected: &'static str,
+    },
+    Resolve {
+        host: String,
+        reason: String,
+    },
+    Probe {
+        target: String,
+        kind: ProbeErrorKind,
+    },
+    Privilege {
+        what: String,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for ProbeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProbeErrorKind::Unsupported => "unsupported",
+            ProbeErrorKind::NotFound => "ping not found",
+            ProbeErrorKind::Exec => "ping failed to run",
+            ProbeErrorKind::Os => "system call failed",
+        })
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse { input, expected } => write!(f, "invalid {expected}: {input}"),
+            Error::Resolve { host, reason } => write!(f, "cannot resolve {host}: {reason}"),
+            Error::Probe { target, kind } => write!(f, "{target}: {kind}"),
+            Error::Privilege { what } => write!(f, "insufficient privileges: {what}"),
+            Error::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/* -------------------- target parsing -------------------- */
+
+pub fn parse_target(s: &str) -> Result<IpAddr, Error> {
+    s.parse().map_err(|_| Error::Parse {
+        input: s.to_string(),
+        expected: "IP address",
+    })
+}
+
+// The host of a URL pasted as a target, and its port: the one given, else
+// the scheme's usual one when it has one. None when `s` isn't a URL.
+// "https://admin@grafana.lan:3000/d/x?orgId=1" is ("grafana.lan", Some(3000)).
+pub fn url_target(s: &str) -> Option<(String, Option<u16>)> {
+    let (scheme, rest) = s.split_once("://")?;
+    let scheme_ok = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    if !scheme_ok {
+        return None;
+    }
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let hostport = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    let (host, port) = match hostport.strip_prefix('[') {
+        // [fd00::5]:8443
+        Some(v6) => {
+            let (host, after) = v6.split_once(']')?;
+            (host, after.strip_prefix(':'))
+        }
+        None => match hostport.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (hostport, None),
+        },
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let port = match port.filter(|p| !p.is_empty()) {
+        Some(p) => Some(p.parse().ok()?),
+        None => scheme_port(&scheme.to_ascii_lowercase()),
+    };
+    Some((host.to_string(), port))
+}
+
+fn scheme_port(scheme: &str) -> Option<u16> {
+    Some(match scheme {
+        "http" | "ws" => 80,
+        "https" | "wss" => 443,
+        "ftp" => 21,
+        "ssh" | "sftp" => 22,
+        "telnet" => 23,
+        "smtp" => 25,
+        "imap" => 143,
+        "ldap" => 389,
+        "smtps" => 465,
+        "imaps" => 993,
+        "ldaps" => 636,
+        "mysql" => 3306,
+        "rdp" => 3389,
+        "postgres" | "postgresql" => 5432,
+        "amqp" => 5672,
+        "vnc" => 5900,
+        "redis" => 6379,
+        "mongodb" => 27017,
+        _ => return None,
+    })
+}
+
+pub fn parse_ipv4(s: &str) -> Result<Ipv4Addr, Error> {
+    s.parse().map_err(|_| Error::Parse {
+        input: s.to_string(),
+        expected: "IPv4 address",
+    })
+}
+
+// Upper bound on what a single brace pattern may expand to
+pub const MAX_EXPANSION: usize = 65_536;
+
+// Shell-style brace expansion, applied to targets before resolution:
+// "web{01..24}.example.com", "{db,cache}{1,2}.lan", "10.0.{0..3}.1",
+// "{1..9..2}". Groups without a comma or a range stay literal, like bash.
+pub fn expand_braces(s: &str) -> Result<Vec<String>, Error> {
+    let mut out = Vec::new();
+    expand_from(s, 0, &mut out).ok_or_else(|| Error::Parse {
+        input: s.to_string(),
+        expected: "brace pattern (at most 65536 expansions)",
+    })?;
+    Ok(out)
+}
+
+fn expand_from(s: &str, from: usize, out: &mut Vec<String>) -> Option<()> {
+    let Some((open, close)) = brace_group(s, from) else {
+        out.push(s.to_string());
+        return (out.len() <= MAX_EXPANSION).then_some(());
+    };
+    let (head, body, tail) = (&s[..open], &s[open + 1..close], &s[close + 1..]);
+    let alts = split_alternatives(body).or_else(|| brace_range(body));
+    let Some(alts) = alts else {
+        return expand_from(s, open + 1, out); // literal braces
+    };
+    for alt in alts {
+        expand_from(&format!("{head}{alt}{tail}"), head.len(), out)?;
+    }
+    Some(())
+}
+
+// Byte offsets of the first balanced "{...}" at or after `from`
+fn brace_group(s: &str, from: usize) -> Option<(usize, usize)> {
+    let open = from + s[from..].find('{')?;
+    let mut depth = 0;
+    for (i, c) in s[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((open, open + i));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// "a,b,{c,d}" split at top-level commas; None without one
+fn split_alternatives(body: &str) -> Option<Vec<String>> {
+    let (mut alts, mut depth, mut start) = (Vec::new(), 0, 0);
+    for (i, c) in body.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alts.push(body[start..i].to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if alts.is_empty() {
+        return None;
+    }
+    alts.push(body[start..].to_string());
+    Some(alts)
+}
+
+// "01..24" (zero-padded to the wider end), "9..1", "1..9..2" or "a..f"
+fn brace_range(body: &str) -> Option<Vec<String>> {
+    let parts: Vec<&str> = body.split("..").collect();
+    let (a, b, step) = match parts[..] {
+        [a, b] => (a, b, 1),
+        [a, b, step] => (a, b, step.parse::<i64>().ok()?.unsigned_abs().max(1)),
+        _ => return None,
+    };
+    let step = usize::try_from(step).ok()?;
+    if let (Ok(x), Ok(y)) = (a.parse::<i64>(), b.parse::<i64>()) {
+        let padded = |t: &str| {
+            t.trim_start_matches('-').len() > 1 && t.trim_start_matches('-').starts_with('0')
+        };
+        let width = if padded(a) || padded(b) {
+            a.len().max(b.len())
+        } else {
+            0
+        };
+        // One past the cap is enough for expand_from to reject the pattern
+        let vals: Vec<i64> = if x <= y {
+            (x..=y).step_by(step).take(MAX_EXPANSION + 1).collect()
+        } else {
+            (y..=x)
+                .rev()
+                .step_by(step)
+                .take(MAX_EXPANSION + 1)
+                .collect()
+        };
+        return Some(vals.into_iter().map(|v| format!("{v:0width$}")).collect());
+    }
+    let (mut ca, mut cb) = (a.chars(), b.chars());
+    match (ca.next(), ca.next(), cb.next(), cb.next()) {
+        (Some(x), None, Some(y), None) if x.is_ascii_alphabetic() && y.is_ascii_alphabetic() => {
+            // Stepped from `x`, as the numbers are
+            let (x, y) = (x as u8, y as u8);
+            let chars: Vec<u8> = if x <= y {
+                (x..=y).step_by(step).collect()
+            } else {
+                (y..=x).rev().step_by(step).collect()
+            };
+            Some(chars.into_iter().map(|c| (c as char).to_string()).collect())
+        }
+        _ => None,
+    }
+}
+
+pub fn v4_key(ip: Ipv4Addr) -> u32 {
+    u32::from_be_bytes(ip.octets())
+}
+
+// "500ms", "30s", "5m", "2h", "1d"; a bare number is seconds
+pub fn parse_duration(s: &str) -> Result<Duration, Error> {
+    let invalid = || Error::Parse {
+        input: s.to_string(),
+        expected: "duration (use ms, s, m, h or d)",
+    };
+    let t = s.trim();
+    let split = t.find(|c: char| !c.is_ascii_digit()).unwrap_or(t.len());
+    let (num, unit) = t.split_at(split);
+    let n: u64 = num.parse().map_err(|_| invalid())?;
+    let ms = match unit {
+        "ms" => n,
+        "" | "s" => n.saturating_mul(1000),
+        "m" => n.saturating_mul(60_000),
+        "h" => n.saturating_mul(3_600_000),
+        "d" => n.saturating_mul(86_400_000),
+        _ => return Err(invalid()),
+    };
+    Ok(Duration::from_millis(ms))
+}
+
+// "deadbeef" or "0xDEADBEEF": whole bytes only
+pub fn parse_hex(s: &str) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::Parse {
+        input: s.to_string(),
+        expected: "hex pattern (pairs of 0-9/a-f)",
+    };
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(digits.get(i..i + 2).ok_or_else(invalid)?, 16).map_err(|_| invalid())
+        })
+        .collect()
+}
+
+// "22,80,443,8000-8100": ports and inclusive ranges, duplicates dropped,
+// in the order given
+pub fn parse_ports(s: &str) -> Result<Vec<u16>, Error> {
+    let invalid = || Error::Parse {
+        input: s.to_string(),
+        expected: "port list (e.g. 22,80,8000-8100)",
+    };
+    let port = |p: &str| {
+        p.trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|&p| p > 0)
+            .ok_or_else(invalid)
+    };
+    let mut ports = Vec::new();
+    for part in s.split(',') {
+        let (lo, hi) = match part.split_once('-') {
+            Some((lo, hi)) => (port(lo)?, port(hi)?),
+            None => (port(part)?, port(part)?),
+        };
+        if lo > hi {
+            return Err(invalid());
+        }
+        for p in lo..=hi {
+            if !ports.contains(&p) {
+                ports.push(p);
+            }
+        }
+    }
+    Ok(ports)
+}
+
+// The TCP ports most often found open, most common first (nmap-services
+// frequency order), for --top-ports
+pub const TOP_PORTS: [u16; 100] = [
+    80, 23, 443, 21, 22, 25, 3389, 110, 445, 139, 143, 53, 135, 3306, 8080, 1723, 111, 995, 993,
+    5900, 1025, 587, 8888, 199, 1720, 465, 548, 113, 81, 6001, 10000, 514, 5060, 179, 1026, 2000,
+    8443, 8000, 32768, 554, 26, 1433, 49152, 2001, 515, 8008, 49154, 1027, 5666, 646, 5000, 5631,
+    631, 49153, 8081, 2049, 88, 79, 5800, 106, 2121, 1110, 49155, 6000, 513, 990, 5357, 427, 49156,
+    543, 544, 5101, 144, 7, 389, 8009, 3128, 444, 9999, 5009, 7070, 5190, 3000, 5432, 1900, 3986,
+    13, 1029, 9, 5051, 6646, 49157, 1028, 873, 1755, 2717, 4899, 9100, 119, 37,
+];
+
+/* -------------------- range iterator -------------------- */
+
+pub struct IpRange {
+    cur: u32,
+    end: u32,
+} // inclusive
+impl IpRange {
+    pub fn new(a: Ipv4Addr, b: Ipv4Addr) -> Self {
+        let mut lo = u32::from_be_bytes(a.octets());
+        let mut hi = u32::from_be_bytes(b.octets());
+        if lo > hi {
+            std::mem::swap(&mut lo, &mut hi);
+        }
+        IpRange { cur: lo, end: hi }
+    }
+}
+impl Iterator for IpRange {
+    type Item = Ipv4Addr;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cur > self.end {
+            return None;
+        }
+        let out = Ipv4Addr::from(self.cur.to_be_bytes());
+        self.cur = self.cur.wrapping_add(1);
+        Some(out)
+    }
+}
+
+/* -------------------- IPv4 prefixes -------------------- */
+
+// An IPv4 network; `net` always has its host bits cleared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cidr {
+    pub net: Ipv4Addr,
+    pub prefix: u32,
+}
+
+impl Cidr {
+    pub fn new(addr: Ipv4Addr, prefix: u32) -> Self {
+        let prefix = prefix.min(32);
+        Cidr {
+            net: Ipv4Addr::from(u32::from(addr) & mask_bits(prefix)),
+            prefix,
+        }
+    }
+
+    pub fn mask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(mask_bits(self.prefix))
+    }
+
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.net) | !mask_bits(self.prefix))
+    }
+
+    // Every address, network and broadcast included
+    pub fn size(&self) -> u64 {
+        1 << (32 - self.prefix)
+    }
+
+    // Usable host addresses: all but the network and broadcast address,
+    // except on /31 point-to-point links and /32 host routes
+    pub fn hosts(&self) -> IpRange {
+        let (first, last) = self.host_range();
+        IpRange::new(first, last)
+    }
+
+    // First and last usable host address
+    pub fn host_range(&self) -> (Ipv4Addr, Ipv4Addr) {
+        let (net, bcast) = (u32::from(self.net), u32::from(self.broadcast()));
+        if self.prefix >= 31 {
+            (net.into(), bcast.into())
+        } else {
+            ((net + 1).into(), (bcast - 1).into())
+        }
+    }
+
+    pub fn host_count(&self) -> u64 {
+        match self.prefix {
+            32 => 1,
+            31 => 2,
+            _ => self.size() - 2,
+        }
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        u32::from(ip) & mask_bits(self.prefix) == u32::from(self.net)
+    }
+
+    // The /`prefix` subnets this splits into, in order (none if `prefix`
+    // is shorter than our own)
+    pub fn split(&self, prefix: u32) -> impl Iterator<Item = Cidr> + use<> {
+        let (net, own) = (u32::from(self.net) as u64, self.prefix);
+        let count = if prefix >= own && prefix <= 32 {
+            1u64 << (prefix - own)
+        } else {
+            0
+        };
+        let step = 1u64 << (32 - prefix.min(32));
+        (0..count).map(move |i| Cidr::new(Ipv4Addr::from((net + i * step) as u32), prefix))
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.net, self.prefix)
+    }
+}
+
+fn mask_bits(prefix: u32) -> u32 {
+    u32::MAX.checked_shl(32 - prefix.min(32)).unwrap_or(0)
+}
+
+// "10.0.0.0/24", "10.0.0.7/24" (host bits are dropped), "10.0.0.0/255.255.255.0"
+// or a bare address as its /32
+pub fn parse_cidr(s: &str) -> Result<Cidr, Error> {
+    let invalid = || Error::Parse {
+        input: s.to_string(),
+        expected: "IPv4 prefix",
+    };
+    let (addr, len) = s.split_once('/').unwrap_or((s, "32"));
+    let addr: Ipv4Addr = addr.parse().map_err(|_| invalid())?;
+    let prefix = match (len.parse::<u32>(), len.parse::<Ipv4Addr>()) {
+        (Ok(p), _) if p <= 32 => p,
+        (_, Ok(mask))
+            if (!u32::from(mask)).wrapping_add(1).is_power_of_two() || mask.is_unspecified() =>
+        {
+            u32::from(mask).count_ones()
+        }
+        _ => return Err(invalid()),
+    };
+    Ok(Cidr::new(addr, prefix))
+}
+
+// The fewest prefixes that cover exactly `addrs` (in any order, repeats
+// allowed), e.g. for a firewall allow-list
+pub fn aggregate(addrs: &[Ipv4Addr]) -> Vec<Cidr> {
+    let mut sorted: Vec<u32> = addrs.iter().map(|&a| u32::from(a)).collect();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let mut out = Vec::new();
+    let mut runs = sorted.into_iter().peekable();
+    while let Some(start) = runs.next() {
+        let mut end = start;
+        while runs.peek() == Some(&end.wrapping_add(1)) && end != u32::MAX {
+            end = runs.next().unwrap_or(end);
+        }
+        out.extend(range_cidrs(start.into(), end.into()));
+    }
+    out
+}
+
+// The fewest prefixes that exactly cover `start..=end`
+pub fn range_cidrs(start: Ipv4Addr, end: Ipv4Addr) -> Vec<Cidr> {
+    let (mut lo, hi) = (
+        u32::from(start.min(end)) as u64,
+        u32::from(start.max(end)) as u64,
+    );
+    let mut out = Vec::new();
+    while lo <= hi {
+        // Largest aligned block starting at `lo` that doesn't pass `hi`
+        let mut size = if lo == 0 {
+            1 << 32
+        } else {
+            1u64 << lo.trailing_zeros()
+        };
+        while lo + size - 1 > hi {
+            size >>= 1;
+        }
+        out.push(Cidr::new(
+            Ipv4Addr::from(lo as u32),
+            32 - size.trailing_zeros(),
+        ));
+        lo += size;
+    }
+    out
+}
+
+/* -------------------- probing -------------------- */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reply {
+    pub seq: u32,   // 0-based attempt index
+    pub bytes: u32, // ICMP size as reported (header + payload)
+    pub rtt: Duration,
+    pub sent_at: Option<SystemTime>, // when the echo went out, if the engine says
+    pub clock_offset_ms: Option<i64>, // timestamp probes: the host's clock minus ours
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rtt {
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+    pub mdev: Duration, // standard deviation, as iputils reports it
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub transmitted: u32,
+    pub received: u32,
+    pub loss: f64,        // percent
+    pub rtt: Option<Rtt>, // None when nothing answered
+}
+
+impl Stats {
+    // Summary computed from individual replies, for probe paths (or ping
+    // builds) that don't print one of their own
+    pub fn from_replies(sent: u32, replies: &[Reply]) -> Stats {
+        let n = replies.len();
+        let rtt = (n > 0).then(|| {
+            let secs: Vec<f64> = replies.iter().map(|r| r.rtt.as_secs_f64()).collect();
+            let avg = secs.iter().sum::<f64>() / n as f64;
+            let var = secs.iter().map(|x| x * x).sum::<f64>() / n as f64 - avg * avg;
+            Rtt {
+                min: replies.iter().map(|r| r.rtt).min().unwrap_or_default(),
+                avg: Duration::from_secs_f64(avg),
+                max: replies.iter().map(|r| r.rtt).max().unwrap_or_default(),
+                mdev: Duration::from_secs_f64(var.max(0.0).sqrt()),
+            }
+        });
+        Stats {
+            transmitted: sent,
+            received: n as u32,
+            loss: if sent == 0 {
+                0.0
+            } else {
+                sent.saturating_sub(n as u32) as f64 * 100.0 / sent as f64
+            },
+            rtt,
+        }
+    }
+}
+
+// Nearest-rank percentile (0-100) of ascending `sorted`
+pub fn percentile(sorted: &[Duration], p: f64) -> Option<Duration> {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.clamp(1, sorted.len().max(1)) - 1).copied()
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outcome {
+    pub up: bool,
+    pub sent: u32,
+    pub replies: Vec<Reply>, // may be empty for an up host if ping's output was unrecognised
+    pub stats: Stats,
+    pub icmp: Option<(u16, u16)>, // native engine: identifier and first sequence number
+    pub corrupted: u32, // native engine: answers whose payload came back altered (counted lost)
+    pub frag_needed: u32, // --df: probes refused for needing fragmentation
+    pub next_hop_mtu: Option<u16>, // ... and the MTU a router reported, if any
+    pub ports: Vec<(u16, PortState)>, // TCP probes: every port tried, in order
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortState {
+    Open,     // handshake completed
+    Closed,   // reset: nothing listening, but the host is there
+    Filtered, // no answer, or an ICMP unreachable
+}
+
+impl fmt::Display for PortState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PortState::Open => "open",
+            PortState::Closed => "closed",
+            PortState::Filtered => "filtered",
+        })
+    }
+}
+
+impl Outcome {
+    // Median clock offset over the timestamp replies
+    pub fn clock_offset_ms(&self) -> Option<i64> {
+        let mut offsets: Vec<i64> = self
+            .replies
+            .iter()
+            .filter_map(|r| r.clock_offset_ms)
+            .collect();
+        if offsets.is_empty() {
+            return None;
+        }
+        offsets.sort();
+        Some(offsets[(offsets.len() - 1) / 2])
+    }
+
+    // RTT percentile over this host's replies, None if none were parsed
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        let mut rtts: Vec<Duration> = self.replies.iter().map(|r| r.rtt).collect();
+        rtts.sort();
+        percentile(&rtts, p)
+    }
+}
+
+/* -------------------- probe options -------------------- */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    #[default]
+    Ping, // the system ping (Unix), IcmpSendEcho (Windows)
+    Native, // ICMP sockets of our own (Unix); IcmpSendEcho on Windows
+}
+
+impl std::str::FromStr for Engine {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ping" => Ok(Engine::Ping),
+            "native" => Ok(Engine::Native),
+            _ => Err(format!("unknown engine {s:?} (use ping or native)")),
+        }
+    }
+}
+
+// Echo payload: `pattern` repeated to `size` bytes. An empty pattern means
+// bytes counting up from 0x10, the way iputils fills its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload {
+    pub pattern: Vec<u8>,
+    pub size: usize,
+}
+
+impl Payload {
+    pub const DEFAULT_SIZE: usize = 56; // 64 bytes on the wire with the ICMP header
+    pub const MAX_SIZE: usize = 65_500;
+
+    pub fn bytes(&self) -> Vec<u8> {
+        if self.pattern.is_empty() {
+            return (0..self.size).map(|i| (0x10 + i) as u8).collect();
+        }
+        self.pattern
+            .iter()
+            .copied()
+            .cycle()
+            .take(self.size)
+            .collect()
+    }
+}
+
+impl Default for Payload {
+    fn default() -> Self {
+        Payload {
+            pattern: Vec::new(),
+            size: Payload::DEFAULT_SIZE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProbeType {
+    #[default]
+    Echo, // ICMP echo, as ping sends
+    Timestamp, // ICMP timestamp request (type 13), native engine only
+    Tcp,       // TCP connects to ProbeOptions::ports
+}
+
+impl std::str::FromStr for ProbeType {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "echo" => Ok(ProbeType::Echo),
+            "icmp-timestamp" => Ok(ProbeType::Timestamp),
+            "tcp" => Ok(ProbeType::Tcp),
+            _ => Err(format!(
+                "unknown probe {s:?} (use echo, icmp-timestamp or tcp)"
+            )),
+        }
+    }
+}
+
+// How --probe tcp tries each port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scan {
+    #[default]
+    Connect, // full handshakes through the OS
+    Syn, // half-open SYNs on a raw socket (Linux, privileged)
+}
+
+impl std::str::FromStr for Scan {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "connect" => Ok(Scan::Connect),
+            "syn" => Ok(Scan::Syn),
+            _ => Err(format!("unknown scan {s:?} (use connect or syn)")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeOptions {
+    pub timeout: Duration,          // per probe
+    pub count: u32,                 // probes per host
+    pub interval: Option<Duration>, // between probes; None keeps the engine's default
+    pub engine: Engine,
+    pub probe: ProbeType,
+    pub payload: Option<Payload>,   // None keeps the engine's default
+    pub df: bool,                   // Don't Fragment (native engine and Windows)
+    pub ports: Vec<u16>,            // TCP probes
+    pub scan: Scan,                 // ... and how they're tried
+    pub proxy: Option<SocketAddr>,  // ... made by this SOCKS5 proxy instead
+    pub ping_args: Vec<String>,     // passed on to the system ping, after ipchk's own
+    pub ping_path: Option<PathBuf>, // the system ping to run; None finds one on PATH
+    pub connect_timeout: Option<Duration>, // TCP handshakes; None uses timeout
+    pub response_timeout: Option<Duration>, // the SOCKS5 proxy's answers; None uses timeout
+}
+
+/* -------------------- system ping dialects -------------------- */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PingFlavor {
+    Iputils,   // most Linux distros: -W in seconds
+    Busybox,   // Alpine, embedded: -W in seconds, no -n
+    Inetutils, // GNU inetutils: -W in seconds
+    Bsd,       // macOS, FreeBSD, DragonFly: -W in milliseconds
+    OpenBsd,   // -w: seconds to wait after the last request
+    NetBsd,    // -w: seconds before it exits, however far it got
+}
+
+impl fmt::Display for PingFlavor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PingFlavor::Iputils => "iputils",
+            PingFlavor::Busybox => "busybox",
+            PingFlavor::Inetutils => "inetutils",
+            PingFlavor::Bsd => "bsd",
+            PingFlavor::OpenBsd => "openbsd",
+            PingFlavor::NetBsd => "netbsd",
+        })
+    }
+}
+
+// The system ping `opts` runs
+#[cfg(not(windows))]
+fn ping_program(opts: &ProbeOptions) -> &std::ffi::OsStr {
+    opts.ping_path
+        .as_deref()
+        .map_or("ping".as_ref(), std::path::Path::as_os_str)
+}
+
+// The ping for IPv6 targets and the flag it needs: `ping -6` where ping
+// does both families (iputils, busybox, or what --ping-path names), else
+// ping6 (macOS, the BSDs, inetutils) when there is one on PATH
+#[cfg(not(windows))]
+fn ping6_program(opts: &ProbeOptions) -> (std::ffi::OsString, Option<&'static str>) {
+    let ping = ping_program(opts);
+    let dual = opts.ping_path.is_some()
+        || matches!(ping_flavor(ping), PingFlavor::Iputils | PingFlavor::Busybox);
+    let ping6 = std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|d| d.join("ping6").is_file()));
+    if dual || !ping6 {
+        (ping.to_owned(), Some("-6"))
+    } else {
+        ("ping6".into(), None)
+    }
+}
+
+// Which ping `program` is, asked once per process via `ping -V`: iputils
+// and inetutils print a version banner, busybox rejects the flag but names
+// itself in the usage text, and the BSDs just reject it.
+#[cfg(not(windows))]
+pub fn ping_flavor(program: &std::ffi::OsStr) -> PingFlavor {
+    use std::collections::HashMap;
+    use std::ffi::OsString;
+    use std::process::{Command, Stdio};
+    use std::sync::{Mutex, OnceLock};
+
+    static FLAVORS: OnceLock<Mutex<HashMap<OsString, PingFlavor>>> = OnceLock::new();
+    let mut flavors = FLAVORS
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    *flavors.entry(program.to_owned()).or_insert_with(|| {
+        let banner = Command::new(program)
+            .arg("-V")
+            .stdin(Stdio::null())
+            .output()
+            .map(|o| {
+                let mut text = String::from_utf8_lossy(&o.stdout).into_owned();
+                text.push_str(&String::from_utf8_lossy(&o.stderr));
+                text
+            })
+            .unwrap_or_default();
+        if banner.contains("iputils") {
+            PingFlavor::Iputils
+        } else if banner.contains("BusyBox") {
+            PingFlavor::Busybox
+        } else if banner.contains("inetutils") {
+            PingFlavor::Inetutils
+        } else if cfg!(target_os = "openbsd") {
+            PingFlavor::OpenBsd
+        } else if cfg!(target_os = "netbsd") {
+            PingFlavor::NetBsd
+        } else if cfg!(any(
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "dragonfly"
+        )) {
+            PingFlavor::Bsd
+        } else {
+            PingFlavor::Iputils // closest match for an unrecognised Linux/BSD ping
+        }
+    })
+}
+
+#[cfg(not(windows))]
+fn ping_args(cmd: &mut std::process::Command, flavor: PingFlavor, opts: &ProbeOptions) {
+    cmd.args(ping_command(flavor, opts)).args(&opts.ping_args);
+}
+
+// The arguments a `flavor` ping needs for `opts`, up to the target; also
+// for pings run elsewhere (--via-ssh)
+pub fn ping_command(flavor: PingFlavor, opts: &ProbeOptions) -> Vec<String> {
+    let ProbeOptions {
+        timeout,
+        count,
+        interval,
+        ..
+    } = *opts;
+    let mut args: Vec<String> = Vec::new();
+    if flavor != PingFlavor::Busybox {
+        args.push("-n".into());
+    }
+    // "[1714566896.123456] 64 bytes from ...": receive times, from which
+    // the send times follow
+    if flavor == PingFlavor::Iputils {
+        args.push("-D".into());
+    }
+    args.extend(["-c".into(), count.to_string()]);
+
+    // Seconds, fractional; every flavour takes "-i 0.5". Pings without
+    // root may refuse very short intervals, which surfaces as a failed probe.
+    if let Some(i) = interval.filter(|_| count > 1) {
+        args.extend(["-i".into(), format!("{:.3}", i.as_secs_f64())]);
+    }
+
+    // Per-reply timeout. OpenBSD only waits after the last request, and
+    // NetBSD only has a deadline for the whole run, so that is the sends
+    // plus one timeout.
+    let secs = timeout.as_secs().max(1);
+    match flavor {
+        PingFlavor::Bsd => {
+            args.extend([
+                "-W".into(),
+                timeout.as_millis().clamp(1, 60_000).to_string(),
+            ]);
+        }
+        PingFlavor::OpenBsd => args.extend(["-w".into(), secs.to_string()]),
+        PingFlavor::NetBsd => {
+            let gap = interval.unwrap_or(Duration::from_secs(1));
+            let sends = gap * count.saturating_sub(1);
+            let deadline = (sends + timeout).as_secs_f64().ceil() as u64;
+            args.extend(["-w".into(), deadline.max(1).to_string()]);
+        }
+        _ => args.extend(["-W".into(), secs.to_string()]),
+    }
+
+    // ping repeats -p to fill -s bytes, after a timestamp of its own;
+    // busybox only takes one byte of pattern
+    if let Some(p) = &opts.payload {
+        args.extend(["-s".into(), p.size.to_string()]);
+        let pattern = match flavor {
+            PingFlavor::Busybox => &p.pattern[..p.pattern.len().min(1)],
+            _ => &p.pattern[..],
+        };
+        if !pattern.is_empty() {
+            let hex: String = pattern.iter().map(|b| format!("{b:02x}")).collect();
+            args.extend(["-p".into(), hex]);
+        }
+    }
+    args
+}
+
+// "64 bytes from 10.0.0.1: icmp_seq=1 ttl=64 time=0.045 ms" (iputils, BSD,
+// macOS; busybox says "seq="), with iputils -D's "[unix secs]" prefix giving
+// the send time
+fn parse_reply_line(line: &str) -> Option<(u32, u32, Duration, Option<SystemTime>)> {
+    if !line.contains("bytes from") {
+        return None;
+    }
+    let (received, line) = match line.strip_prefix('[').and_then(|l| l.split_once(']')) {
+        Some((stamp, rest)) => (stamp.parse::<f64>().ok(), rest),
+        None => (None, line),
+    };
+    let bytes = line.split_whitespace().next()?.parse().ok()?;
+    let field = |key: &str| {
+        line.split_whitespace()
+            .find_map(|w| w.strip_prefix(key))
+            .map(|v| v.trim_end_matches([',', ':']))
+    };
+    let seq = field("icmp_seq=").or_else(|| field("seq="))?.parse().ok()?;
+    let ms: f64 = field("time=")?.parse().ok()?;
+    let rtt = Duration::from_secs_f64(ms / 1000.0);
+    let sent_at = received
+        .and_then(|secs| {
+            SystemTime::UNIX_EPOCH.checked_add(Duration::try_from_secs_f64(secs).ok()?)
+        })
+        .and_then(|t| t.checked_sub(rtt));
+    Some((seq, bytes, rtt, sent_at))
+}
+
+// "4 packets transmitted, 3 received, +1 errors, 25% packet loss, time 3004ms"
+// (iputils) or "4 packets transmitted, 3 packets received, 25.0% packet loss"
+// (BSD, macOS, busybox)
+fn parse_counts_line(line: &str) -> Option<(u32, u32, f64)> {
+    let mut counts = (None, None, None);
+    for part in line.split(',') {
+        let mut words = part.split_whitespace();
+        let num = words.next()?;
+        match words.next() {
+            Some("packets") if part.contains("transmitted") => counts.0 = num.parse().ok(),
+            Some("received") | Some("packets") if part.contains("received") => {
+                counts.1 = num.parse().ok()
+            }
+            Some("packet") if part.contains("loss") => {
+                counts.2 = num.strip_suffix('%')?.parse().ok()
+            }
+            _ => {}
+        }
+    }
+    Some((counts.0?, counts.1?, counts.2?))
+}
+
+// "rtt min/avg/max/mdev = 0.035/0.045/0.055/0.008 ms" (iputils),
+// "round-trip min/avg/max/stddev = ..." (BSD, macOS) or
+// "round-trip min/avg/max = 0.1/0.2/0.3 ms" (busybox, no deviation)
+fn parse_rtt_line(line: &str) -> Option<(Duration, Duration, Duration, Option<Duration>)> {
+    let (head, tail) = line.split_once('=')?;
+    if !head.contains("min/avg/max") {
+        return None;
+    }
+    let ms = |v: &str| -> Option<Duration> {
+        let v: f64 = v.parse().ok()?;
+        Some(Duration::from_secs_f64(v / 1000.0))
+    };
+    let nums = tail.split_whitespace().next()?;
+    let mut vals = nums.split('/');
+    let (min, avg, max) = (ms(vals.next()?)?, ms(vals.next()?)?, ms(vals.next()?)?);
+    Some((min, avg, max, vals.next().and_then(ms)))
+}
+
+// iputils numbers echoes from 1, BSD/macOS/busybox from 0. Best effort:
+// treat the sequence as 1-based only if nothing was numbered 0 and the
+// highest number seen equals the number sent.
+fn normalise_seqs(raw: Vec<(u32, u32, Duration, Option<SystemTime>)>, sent: u32) -> Vec<Reply> {
+    let one_based = !raw.iter().any(|r| r.0 == 0) && raw.iter().map(|r| r.0).max() == Some(sent);
+    let mut replies: Vec<Reply> = raw
+        .into_iter()
+        .map(|(seq, bytes, rtt, sent_at)| Reply {
+            seq: if one_based { seq - 1 } else { seq },
+            bytes,
+            rtt,
+            sent_at,
+            clock_offset_ms: None,
+        })
+        .collect();
+    replies.sort_by_key(|r| r.seq);
+    replies.dedup_by_key(|r| r.seq); // DUP! replies
+    replies
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly",
+    target_os = "macos"
+))]
+fn ping_unix_cmd(ip: &str, v6: bool, opts: &ProbeOptions) -> Result<Outcome, Error> {
+    use std::process::{Command, Stdio};
+
+    let count = opts.count;
+    let (program, family) = if v6 {
+        ping6_program(opts)
+    } else {
+        (ping_program(opts).to_owned(), None)
+    };
+    let mut cmd = Command::new(&program);
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    ping_args(&mut cmd, ping_flavor(&program), opts);
+    cmd.args(family);
+
+    let out = cmd.arg(ip).output().map_err(|e| Error::Probe {
+        target: ip.to_string(),
+        kind: if e.kind() == io::ErrorKind::NotFound {
+            ProbeErrorKind::NotFound
+        } else {
+            ProbeErrorKind::Exec
+        },
+    })?;
+    if out.status.code().is_none() {
+        return Err(Error::Probe {
+            target: ip.to_string(),
+            kind: ProbeErrorKind::Exec,
+        });
+    }
+
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    Ok(parse_ping(&stdout, count, out.status.success()))
+}
+
+// The outcome of `count` probes from a ping's output and exit status
+pub fn parse_ping(stdout: &str, count: u32, success: bool) -> Outcome {
+    let raw = stdout.lines().filter_map(parse_reply_line).collect();
+    let replies = normalise_seqs(raw, count);
+
+    // Prefer ping's own summary; fill any gaps from the replies we parsed
+    let mut stats = Stats::from_replies(count, &replies);
+    if let Some((tx, rx, loss)) = stdout.lines().find_map(parse_counts_line) {
+        (stats.transmitted, stats.received, stats.loss) = (tx, rx, loss);
+    }
+    if let Some((min, avg, max, mdev)) = stdout.lines().find_map(parse_rtt_line) {
+        let fallback = stats.rtt.map(|r| r.mdev).unwrap_or_default();
+        stats.rtt = Some(Rtt {
+            min,
+            avg,
+            max,
+            mdev: mdev.unwrap_or(fallback),
+        });
+    }
+
+    Outcome {
+        up: success,
+        sent: count,
+        replies,
+        stats,
+        icmp: None,
+        corrupted: 0,
+        frag_needed: 0,
+        next_hop_mtu: None,
+        ports: Vec::new(),
+    }
+}
+
+#[cfg(windows)]
+fn ping_windows_icmp(ipv4: Ipv4Addr, opts: &ProbeOptions) -> Result<Outcome, Error> {
+    use std::ffi::c_void;
+    use windows_sys::Win32::Foundation::{GetLastError, HANDLE};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        ICMP_ECHO_REPLY, IP_FLAG_DF, IP_OPTION_INFORMATION, IP_PACKET_TOO_BIG, IcmpCloseHandle,
+        IcmpCreateFile, IcmpSendEcho,
+    };
+
+    unsafe {
+        let h: HANDLE = IcmpCreateFile();
+        if h.is_null() {
+            return Err(Error::Probe {
+                target: ipv4.to_string(),
+                kind: ProbeErrorKind::Os,
+            });
+        }
+
+        // Destination in network byte order
+        let addr_u32 = u32::from(ipv4).to_be();
+
+        // Small payload unless asked otherwise, and a reply buffer
+        // (ICMP_ECHO_REPLY + payload bytes + room for an ICMP error)
+        let req = opts
+            .payload
+            .as_ref()
+            .map_or_else(|| vec![0x61; 8], Payload::bytes);
+        let reply_len = std::mem::size_of::<ICMP_ECHO_REPLY>() + req.len() + 8;
+        let mut reply = vec![0u8; reply_len];
+
+        let (timeout, interval) = (opts.timeout, opts.interval);
+        let tries = opts.count.max(1);
+        let mut replies = Vec::new();
+        let mut frag_needed = 0;
+        let options = IP_OPTION_INFORMATION {
+            Ttl: 128,
+            Tos: 0,
+            Flags: IP_FLAG_DF as u8,
+            OptionsSize: 0,
+            OptionsData: std::ptr::null_mut(),
+        };
+        let options: *const IP_OPTION_INFORMATION =
+            if opts.df { &options } else { std::ptr::null() };
+
+        for seq in 0..tries {
+            if let Some(i) = interval.filter(|_| seq > 0) {
+                std::thread::sleep(i);
+            }
+            let sent_at = SystemTime::now();
+            let ret = IcmpSendEcho(
+                h,
+                addr_u32,
+                req.as_ptr() as *const c_void,
+                req.len() as u16,
+                options,
+                reply.as_mut_ptr() as *mut c_void,
+                reply.len() as u32,
+                timeout.as_millis().min(u128::from(u32::MAX)) as u32,
+            );
+
+            if ret > 0 {
+                // Interpret the first ICMP_ECHO_REPLY
+                let echo: &ICMP_ECHO_REPLY = &*(reply.as_ptr() as *const ICMP_ECHO_REPLY);
+                // IP_SUCCESS == 0
+                if echo.Status == 0 {
+                    replies.push(Reply {
+                        seq,
+                        bytes: u32::from(echo.DataSize) + 8,
+                        rtt: Duration::from_millis(u64::from(echo.RoundTripTime)),
+                        sent_at: Some(sent_at),
+                        clock_offset_ms: None,
+                    });
+                }
+                if echo.Status == IP_PACKET_TOO_BIG {
+                    frag_needed += 1;
+                }
+                // else: got a reply structure with a failure Status; keep trying
+            } else if GetLastError() == IP_PACKET_TOO_BIG {
+                // Bigger than the local MTU with DF set, so never sent
+                frag_needed += 1;
+            }
+            // ret == 0 → timed out; keep trying
+        }
+
+        IcmpCloseHandle(h);
+        Ok(Outcome {
+            up: !replies.is_empty(),
+            sent: tries,
+            stats: Stats::from_replies(tries, &replies),
+            replies,
+            icmp: None,
+            corrupted: 0,
+            frag_needed,
+            next_hop_mtu: None,
+            ports: Vec::new(),
+        })
+    }
+}
+
+// Make sure the probe engine works before a sweep starts, so a missing or
+// unusable ping fails once instead of reporting every host as down.
+// Probes loopback; an unanswered loopback is not treated as a failure.
+#[cfg(not(windows))]
+pub fn preflight(opts: &ProbeOptions) -> Result<(), Error> {
+    // Plain sockets, nothing to check
+    if opts.probe == ProbeType::Tcp {
+        return match opts.scan {
+            Scan::Connect => Ok(()),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Scan::Syn => syn::preflight(),
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            Scan::Syn => Err(syn_unsupported("--scan syn")),
+        };
+    }
+    if opts.engine == Engine::Native || opts.probe == ProbeType::Timestamp {
+        return icmp::preflight(opts);
+    }
+    ping_preflight(opts, false)
+}
+
+// Checks that the system ping `probe_v6` runs works, whatever the engine
+#[cfg(not(windows))]
+pub fn preflight_v6(opts: &ProbeOptions) -> Result<(), Error> {
+    if opts.probe != ProbeType::Echo {
+        return Ok(());
+    }
+    ping_preflight(opts, true)
+}
+
+// Windows has no IPv6 path yet; each such target says so itself
+#[cfg(windows)]
+pub fn preflight_v6(_: &ProbeOptions) -> Result<(), Error> {
+    Ok(())
+}
+
+// One ping of the loopback address, as the sweep would run it
+#[cfg(not(windows))]
+fn ping_preflight(opts: &ProbeOptions, v6: bool) -> Result<(), Error> {
+    use std::process::{Command, Stdio};
+
+    let broken = |kind| Error::Probe {
+        target: "ping".into(),
+        kind,
+    };
+    let opts = ProbeOptions {
+        timeout: Duration::from_secs(1),
+        count: 1,
+        interval: None,
+        payload: None,
+        ..opts.clone()
+    };
+    let (program, family) = if v6 {
+        ping6_program(&opts)
+    } else {
+        (ping_program(&opts).to_owned(), None)
+    };
+    let mut cmd = Command::new(&program);
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    ping_args(&mut cmd, ping_flavor(&program), &opts);
+    cmd.args(family);
+    let loopback = if v6 { "::1" } else { "127.0.0.1" };
+    let out = cmd.arg(loopback).output().map_err(|e| {
+        broken(if e.kind() == io::ErrorKind::NotFound {
+            ProbeErrorKind::NotFound
+        } else {
+            ProbeErrorKind::Exec
+        })
+    })?;
+    if out.status.code().is_none() {
+        return Err(broken(ProbeErrorKind::Exec));
+    }
+    // e.g. "ping: socket: Operation not permitted" without cap_net_raw/setuid
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if let Some(line) = stderr
+        .lines()
+        .find(|l| l.contains("not permitted") || l.contains("Permission denied"))
+    {
+        return Err(Error::Privilege {
+            what: line.trim().to_string(),
+        });
+    }
+    // A usage error (iputils exits 2, the BSDs 64) over --ping-args would
+    // otherwise leave every host down
+    if !opts.ping_args.is_empty() && !matches!(out.status.code(), Some(0 | 1)) {
+        let why = stderr.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+        return Err(Error::Parse {
+            input: format!("{} ({})", opts.ping_args.join(" "), why.trim()),
+            expected: "ping flags",
+        });
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn preflight(opts: &ProbeOptions) -> Result<(), Error> {
+    if opts.probe == ProbeType::Tcp {
+        return match opts.scan {
+            Scan::Connect => Ok(()),
+            Scan::Syn => Err(syn_unsupported("--scan syn")),
+        };
+    }
+    if opts.probe == ProbeType::Timestamp {
+        return Err(Error::Probe {
+            target: "icmp-timestamp".into(),
+            kind: ProbeErrorKind::Unsupported,
+        });
+    }
+    use windows_sys::Win32::NetworkManagement::IpHelper::{IcmpCloseHandle, IcmpCreateFile};
+
+    unsafe {
+        let h = IcmpCreateFile();
+        if h.is_null() {
+            return Err(Error::Probe {
+                target: "IcmpCreateFile".into(),
+                kind: ProbeErrorKind::Os,
+            });
+        }
+        IcmpCloseHandle(h);
+    }
+    Ok(())
+}
+
+impl Outcome {
+    // N-of-M success: up only with at least `n` replies, rather than the
+    // first one
+    pub fn require(mut self, n: u32) -> Self {
+        if n > 1 {
+            self.up = self.up && self.stats.received >= n;
+        }
+        self
+    }
+}
+
+// --scan syn, over raw sockets
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn syn_scan(v4: Ipv4Addr, opts: &ProbeOptions) -> Result<Outcome, Error> {
+    syn::probe(v4, opts)
+}
+
+// Other systems don't hand incoming TCP segments to raw sockets
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn syn_scan(v4: Ipv4Addr, _: &ProbeOptions) -> Result<Outcome, Error> {
+    Err(syn_unsupported(&v4.to_string()))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn syn_unsupported(target: &str) -> Error {
+    Error::Probe {
+        target: target.to_string(),
+        kind: ProbeErrorKind::Unsupported,
+    }
+}
+
+// Platform dispatch: Windows ICMP API, otherwise the system ping or the
+// native engine (always, for timestamp probes). `up == false` means no
+// reply; Err means the probe itself could not run. An unset interval keeps
+// each engine's default (back to back on Windows and natively, ping's own
+// 1s otherwise).
+pub fn probe(v4: Ipv4Addr, opts: &ProbeOptions) -> Result<Outcome, Error> {
+    if opts.probe == ProbeType::Tcp {
+        return match opts.scan {
+            Scan::Connect => tcp::probe(v4, opts),
+            Scan::Syn => syn_scan(v4, opts),
+        };
+    }
+
+    #[cfg(windows)]
+    return match opts.probe {
+        ProbeType::Timestamp => Err(Error::Probe {
+            target: v4.to_string(),
+            kind: ProbeErrorKind::Unsupported,
+        }),
+        _ => ping_windows_icmp(v4, opts),
+    };
+
+    #[cfg(not(windows))]
+    match (opts.engine, opts.probe) {
+        (Engine::Native, _) | (_, ProbeType::Timestamp) => icmp::ping(v4, opts),
+        (Engine::Ping, _) => ping_unix_cmd(&v4.to_string(), false, opts),
+    }
+}
+
+// IPv6 targets, until there is a native ICMPv6 engine: echo probes through
+// the system ping, whichever engine `opts` names
+#[cfg(not(windows))]
+pub fn probe_v6(v6: Ipv6Addr, opts: &ProbeOptions) -> Result<Outcome, Error> {
+    if opts.probe != ProbeType::Echo {
+        return Err(Error::Probe {
+            target: v6.to_string(),
+            kind: ProbeErrorKind::Unsupported,
+        });
+    }
+    ping_unix_cmd(&v6.to_string(), true, opts)
+}
+
+#[cfg(windows)]
+pub fn probe_v6(v6: Ipv6Addr, _: &ProbeOptions) -> Result<Outcome, Error> {
+    Err(Error::Probe {
+        target: v6.to_string(),
+        kind: ProbeErrorKind::Unsupported,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expanded(s: &str) -> Vec<String> {
+        expand_braces(s).unwrap()
+    }
+
+    #[test]
+    fn braces_pad_to_the_wider_end() {
+        assert_eq!(expanded("web{08..10}"), ["web08", "web09", "web10"]);
+        assert_eq!(expanded("{1..3}"), ["1", "2", "3"]);
+        assert_eq!(expanded("{8..010}"), ["008", "009", "010"]);
+    }
+
+    #[test]
+    fn braces_count_down() {
+        assert_eq!(expanded("{3..1}"), ["3", "2", "1"]);
+        assert_eq!(expanded("{c..a}"), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn braces_step_from_the_start() {
+        assert_eq!(expanded("{1..9..3}"), ["1", "4", "7"]);
+        assert_eq!(expanded("{9..1..3}"), ["9", "6", "3"]);
+        assert_eq!(expanded("{1..9..-3}"), ["1", "4", "7"]);
+        assert_eq!(expanded("{a..f..2}"), ["a", "c", "e"]);
+        assert_eq!(expanded("{f..a..2}"), ["f", "d", "b"]);
+    }
+
+    #[test]
+    fn braces_combine_lists_and_ranges() {
+        assert_eq!(
+            expanded("{db,cache}{1..2}.lan"),
+            ["db1.lan", "db2.lan", "cache1.lan", "cache2.lan"]
+        );
+        assert_eq!(expanded("10.0.{0..1}.1"), ["10.0.0.1", "10.0.1.1"]);
+    }
+
+    #[test]
+    fn braces_without_a_list_or_range_stay() {
+        assert_eq!(expanded("a{b}c"), ["a{b}c"]);
+        assert_eq!(expanded("{a..bb}"), ["{a..bb}"]);
+    }
+
+    #[test]
+    fn braces_are_capped() {
+        assert!(expand_braces("{0..65536}").is_err());
+        assert_eq!(expanded("{1..65536}").len(), MAX_EXPANSION);
+    }
+}
@@ -1,202 +1,1155 @@
+mod agent;
+mod alias;
+mod baseline;
+mod bench;
+mod cache;
+mod calc;
+mod config;
+mod daemon;
+mod enrich;
+mod eventlog;
+mod geoip;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "grpc")]
+mod h2;
+mod http;
+mod input;
+mod internet;
+mod journald;
+mod json;
+mod merge;
+mod myip;
+mod netbox;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod netlink;
+mod notify;
+mod otel;
+mod output;
+mod ports;
+mod post;
+mod progress;
+mod report;
+mod service;
+mod smtp;
+mod ssh;
+mod stats;
+mod statsd;
+
+use ipchk::dns::{self, Resolver};
+use ipchk::{
+    Builtin, Deadline, Engine, Error, IpRange, Outcome, Payload, PortState, Privileges,
+    ProbeErrorKind, ProbeOptions, ProbeType, Scan, parse_duration, parse_ipv4, v4_key,
+};
 use pico_args::Arguments;
 use std::{
+    collections::{HashMap, HashSet},
     env,
-    net::{IpAddr, Ipv4Addr},
-    sync::mpsc,
+    ffi::OsString,
+    io::IsTerminal,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, atomic::AtomicBool, mpsc},
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 const DEFAULT_TIMEOUT_MS: u64 = 2000;
 const DEFAULT_COUNT: u32 = 4;
 const DEFAULT_CONCURRENCY: usize = 128;
+const DEFAULT_EVERY_SECS: u64 = 30;
+const DEFAULT_FLAP_WINDOW_SECS: u64 = 600;
+const DEFAULT_DNS_CONCURRENCY: usize = 32;
+const DEFAULT_TCP_PORT: u16 = 80;
+const DEFAULT_SOCKS_PORT: u16 = 1080;
 
 #[derive(Debug)]
 struct PingResult {
     msg: String,
-    sort_key: u32,
-}
-
-fn parse_ip(s: &str) -> Option<IpAddr> {
-    s.parse().ok()
-}
-fn v4_key(ip: Ipv4Addr) -> u32 {
-    u32::from_be_bytes(ip.octets())
-}
-
-#[cfg(any(
-    target_os = "linux",
-    target_os = "android",
-    target_os = "freebsd",
-    target_os = "openbsd",
-    target_os = "netbsd",
-    target_os = "dragonfly",
-    target_os = "macos"
-))]
-fn ping_unix_cmd(ip: &str, timeout: Duration, count: u32) -> bool {
-    use std::process::{Command, Stdio};
-
-    let mut cmd = Command::new("ping");
-    cmd.stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .arg("-n")
-        .arg("-c")
-        .arg(count.to_string());
-
-    // Per-reply timeout: macOS uses ms, most others use seconds
-    #[cfg(target_os = "macos")]
-    {
-        let ms = timeout.as_millis().clamp(1, 60_000) as u128;
-        cmd.arg("-W").arg(ms.to_string());
-    }
-    #[cfg(not(target_os = "macos"))]
-    {
-        let secs = timeout.as_secs().max(1).to_string();
-        cmd.arg("-W").arg(secs);
+    sort_key: SortKey,
+    index: usize,                       // the target's place in the input
+    ip: String,                         // the target as given
+    name: Option<String>,               // hostname, --zone-file owner or --rdns PTR name
+    addr: Option<Ipv4Addr>,             // what was actually probed
+    grouped: bool,                      // one of several --all-addresses results under `ip`
+    up: Option<bool>,                   // None for invalid/unsupported targets
+    cached: bool,                       // answered from --cache rather than probed
+    error: Option<Error>,               // why `up` is None
+    outcome: Option<Outcome>,           // per-probe detail when actually probed
+    latency: Latency,                   // against --warn-rtt/--crit-rtt
+    at: SystemTime,                     // when the verdict was reached (or cached)
+    mac: Option<String>,                // --aliases: the up host's link-layer address
+    alias_of: Option<String>,           // --aliases: the first address with the same MAC
+    label: Option<String>,              // from "10.0.0.5=core-switch" or a label column
+    baseline: Option<baseline::Change>, // --baseline: how it stands against the file
+}
+
+// Results list IPv4 addresses in order, then IPv6 ones, then targets with
+// neither (unresolved names, invalid entries) in the order they were given
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    V4(u32),
+    V6(u128),
+    Input(usize),
+}
+
+impl SortKey {
+    // By the probed address, else the target's own, else `index` in the input
+    fn of(target: &str, addr: Option<Ipv4Addr>, index: usize) -> Self {
+        match addr.map(IpAddr::V4).or_else(|| target.parse().ok()) {
+            Some(IpAddr::V4(a)) => SortKey::V4(v4_key(a)),
+            Some(IpAddr::V6(a)) => SortKey::V6(u128::from(a)),
+            None => SortKey::Input(index),
+        }
     }
+}
 
-    let status = cmd.arg(ip).status();
-    matches!(status.as_ref().map(|s| s.success()), Ok(true))
+// An up host's average RTT against --warn-rtt/--crit-rtt
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Latency {
+    Ok,
+    Warn, // degraded, shown in yellow
+    Crit, // degraded, shown in red
 }
 
-#[cfg(windows)]
-fn ping_windows_icmp(ipv4: Ipv4Addr, timeout: Duration, count: u32) -> bool {
-    use std::ffi::c_void;
-    use windows_sys::Win32::Foundation::HANDLE;
-    use windows_sys::Win32::NetworkManagement::IpHelper::{
-        ICMP_ECHO_REPLY, IcmpCloseHandle, IcmpCreateFile, IcmpSendEcho,
-    };
+// --order: how the results are listed
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Order {
+    Input,      // as the targets were given
+    Completion, // as their verdicts came in
+    #[default]
+    Address, // by SortKey
+}
 
-    unsafe {
-        let h: HANDLE = IcmpCreateFile();
-        if h.is_null() {
-            return false;
+impl std::str::FromStr for Order {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "input" => Ok(Order::Input),
+            "completion" => Ok(Order::Completion),
+            "address" => Ok(Order::Address),
+            _ => Err(format!(
+                "unknown order {s:?} (use input, completion or address)"
+            )),
         }
+    }
+}
 
-        // Destination in network byte order
-        let addr_u32 = u32::from(ipv4).to_be();
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExitCodes {
+    Default, // 0 unless the command line was bad
+    Fping,   // 0 all up, 1 some down, 2 invalid/unresolved, 3 usage, 4 system error
+    Nagios,  // 0 OK, 1 WARNING (slow), 2 CRITICAL (down/very slow), 3 UNKNOWN
+}
 
-        // Small payload and reply buffer (ICMP_ECHO_REPLY + payload bytes)
-        let req: [u8; 8] = [0x61; 8];
-        let reply_len = std::mem::size_of::<ICMP_ECHO_REPLY>() + req.len();
-        let mut reply = vec![0u8; reply_len];
+impl std::str::FromStr for ExitCodes {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(ExitCodes::Default),
+            "fping" => Ok(ExitCodes::Fping),
+            "nagios" => Ok(ExitCodes::Nagios),
+            _ => Err(format!(
+                "unknown policy {s:?} (use default, fping or nagios)"
+            )),
+        }
+    }
+}
 
-        let tries = count.max(1);
-        let mut ok_any = false;
+impl ExitCodes {
+    // When the sweep can't run at all
+    fn system_error(self) -> i32 {
+        match self {
+            ExitCodes::Fping => 4,
+            ExitCodes::Nagios => 3,
+            ExitCodes::Default => 2,
+        }
+    }
+}
 
-        for _ in 0..tries {
-            let ret = IcmpSendEcho(
-                h,
-                addr_u32,
-                req.as_ptr() as *const c_void,
-                req.len() as u16,
-                std::ptr::null_mut(),
-                reply.as_mut_ptr() as *mut c_void,
-                reply.len() as u32,
-                timeout.as_millis().min(u128::from(u32::MAX)) as u32,
-            );
+// --check-gateway: what an unreachable default gateway does to the sweep
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GatewayCheck {
+    Warn,  // say so on stderr, then sweep anyway
+    Abort, // don't sweep
+}
 
-            if ret > 0 {
-                // Interpret the first ICMP_ECHO_REPLY
-                let echo: &ICMP_ECHO_REPLY = &*(reply.as_ptr() as *const ICMP_ECHO_REPLY);
-                // IP_SUCCESS == 0
-                if echo.Status == 0 {
-                    ok_any = true;
-                    break;
-                }
-                // else: got a reply structure with a failure Status; keep trying
-            }
-            // ret == 0 → timed out; keep trying
+impl std::str::FromStr for GatewayCheck {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(GatewayCheck::Warn),
+            "abort" => Ok(GatewayCheck::Abort),
+            _ => Err(format!("unknown action {s:?} (use warn or abort)")),
         }
+    }
+}
 
-        IcmpCloseHandle(h);
-        ok_any
+fn fping_exit_code(results: &[PingResult]) -> i32 {
+    let mut code = 0;
+    for r in results {
+        let c = match (&r.error, r.up) {
+            (None, Some(true)) => 0,
+            (None, _) => 1,
+            (Some(Error::Parse { .. } | Error::Resolve { .. }), _) => 2,
+            (
+                Some(Error::Probe {
+                    kind: ProbeErrorKind::Unsupported,
+                    ..
+                }),
+                _,
+            ) => 2,
+            (Some(_), _) => 4,
+        };
+        code = code.max(c);
     }
+    code
 }
 
-fn ping_one(
-    ip_str: String,
-    tx: mpsc::Sender<PingResult>,
-    timeout: Duration,
-    count: u32,
-    raw: bool,
-) {
-    let parsed = match parse_ip(&ip_str) {
-        Some(ip) => ip,
-        None => {
-            let _ = tx.send(PingResult {
-                sort_key: 0,
-                msg: format!("\x1b[0m{}\x1b[0m is \x1b[1m\x1b[31minvalid\x1b[0m", ip_str),
-            });
-            return;
-        }
+// Nagios plugin state, with the one-line status Nagios shows
+fn nagios_status(results: &[PingResult]) -> (i32, String) {
+    let count = |f: &dyn Fn(&PingResult) -> bool| results.iter().filter(|r| f(r)).count();
+    let unknown = count(&|r| r.error.is_some());
+    let down = count(&|r| r.error.is_none() && r.up == Some(false));
+    let crit = count(&|r| r.latency == Latency::Crit);
+    let warn = count(&|r| r.latency == Latency::Warn);
+    let up = count(&|r| r.up == Some(true));
+    let (code, state) = if unknown > 0 {
+        (3, "UNKNOWN")
+    } else if down + crit > 0 {
+        (2, "CRITICAL")
+    } else if warn > 0 {
+        (1, "WARNING")
+    } else {
+        (0, "OK")
     };
+    let mut line = format!("IPCHK {state} - {up}/{} up", results.len());
+    if warn + crit > 0 {
+        line.push_str(&format!(", {} degraded", warn + crit));
+    }
+    if unknown > 0 {
+        line.push_str(&format!(", {unknown} unknown"));
+    }
+    (code, line)
+}
 
-    let v4 = match parsed {
-        IpAddr::V4(v4) => v4,
-        IpAddr::V6(_) => {
-            let _ = tx.send(PingResult {
-                sort_key: 0,
-                msg: format!(
-                    "\x1b[0m{}\x1b[0m is \x1b[33mIPv6 currently unsupported\x1b[0m",
-                    ip_str
-                ),
-            });
-            return;
-        }
+// parse_args reports its own failures, so recover the policy from argv to
+// pick the usage-error code.
+fn usage_exit_code() -> i32 {
+    let argv: Vec<String> = env::args().collect();
+    let policy = |name: &str| {
+        argv.windows(2)
+            .any(|w| w[0] == "--exit-codes" && w[1] == name)
+            || argv.iter().any(|a| *a == format!("--exit-codes={name}"))
     };
+    if policy("fping") || policy("nagios") {
+        3
+    } else {
+        2
+    }
+}
 
-    #[cfg(windows)]
-    let up = ping_windows_icmp(v4, timeout, count);
+// -h/--help, recovered from argv like the policy above
+fn asked_for_help() -> bool {
+    env::args().skip(1).any(|a| a == "-h" || a == "--help")
+}
 
-    #[cfg(not(windows))]
-    let up = ping_unix_cmd(&ip_str, timeout, count);
+// How verdicts are written: in colour or plain, with words or symbols
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Style {
+    raw: bool,
+    symbols: output::Symbols,
+}
+
+impl Style {
+    // For log files: no colour, and words a grep can find
+    const PLAIN: Style = Style {
+        raw: true,
+        symbols: output::Symbols::Words,
+    };
+}
 
-    let msg = if raw {
+fn format_verdict(ip_str: &str, up: bool, style: Style, cached: bool) -> String {
+    let raw = style.raw;
+    let mark = style.symbols.mark(if up { "up" } else { "down" });
+    let mut msg = if let Some(mark) = mark {
+        match (raw, up) {
+            (true, _) => format!("{mark} {ip_str}"),
+            (false, true) => format!("\x1b[1m\x1b[32m{mark}\x1b[0m \x1b[1m{ip_str}\x1b[0m"),
+            (false, false) => format!("\x1b[1m\x1b[31m{mark}\x1b[0m {ip_str}"),
+        }
+    } else if raw {
         if up {
             format!("{ip_str} is up")
         } else {
             format!("{ip_str} is down")
         }
+    } else if up {
+        format!("\x1b[1m{}\x1b[0m is \x1b[1m\x1b[32mup\x1b[0m", ip_str)
     } else {
-        if up {
-            format!("\x1b[1m{}\x1b[0m is \x1b[1m\x1b[32mup\x1b[0m", ip_str)
+        format!("\x1b[0m{}\x1b[0m is \x1b[1m\x1b[31mdown\x1b[0m", ip_str)
+    };
+    if cached {
+        msg.push_str(if raw {
+            " (cached)"
+        } else {
+            " \x1b[2m(cached)\x1b[0m"
+        });
+    }
+    msg
+}
+
+// "10.0.0.1 is up (4.2 ms)", or "is degraded" past --warn-rtt/--crit-rtt
+fn format_latency(ip_str: &str, rtt: Duration, level: Latency, style: Style) -> String {
+    let raw = style.raw;
+    let ms = format!("{:.1} ms", rtt.as_secs_f64() * 1000.0);
+    let (word, color) = match level {
+        Latency::Ok => ("up", "\x1b[32m"),
+        Latency::Warn => ("degraded", "\x1b[33m"),
+        Latency::Crit => ("degraded", "\x1b[31m"),
+    };
+    if let Some(mark) = style.symbols.mark(word) {
+        if raw {
+            format!("{mark} {ip_str} ({ms})")
+        } else {
+            format!("\x1b[1m{color}{mark}\x1b[0m \x1b[1m{ip_str}\x1b[0m ({ms})")
+        }
+    } else if raw {
+        format!("{ip_str} is {word} ({ms})")
+    } else if level == Latency::Ok {
+        format!("\x1b[1m{ip_str}\x1b[0m is \x1b[1m{color}{word}\x1b[0m ({ms})")
+    } else {
+        format!("\x1b[1m{ip_str}\x1b[0m is \x1b[1m{color}{word}\x1b[0m ({color}{ms}\x1b[0m)")
+    }
+}
+
+fn port_summary(ports: &[(u16, PortState)]) -> String {
+    let open: Vec<String> = ports
+        .iter()
+        .filter(|(_, s)| *s == PortState::Open)
+        .map(|&(p, _)| ports::label(p))
+        .collect();
+    let count = |state| ports.iter().filter(|(_, s)| *s == state).count();
+    let mut parts = Vec::new();
+    if !open.is_empty() {
+        parts.push(format!("open {}", open.join(",")));
+    }
+    for state in [PortState::Closed, PortState::Filtered] {
+        if count(state) > 0 {
+            parts.push(format!("{} {state}", count(state)));
+        }
+    }
+    parts.join("; ")
+}
+
+// --df probes that only met "fragmentation needed": reachable, perhaps, but
+// not at this size
+fn format_mtu_limited(ip_str: &str, o: &Outcome, raw: bool) -> String {
+    let why = match o.next_hop_mtu {
+        Some(mtu) => format!("next-hop MTU {mtu}"),
+        None => "needs fragmentation".into(),
+    };
+    if raw {
+        format!("{ip_str} is mtu-limited ({why})")
+    } else {
+        format!("\x1b[1m{ip_str}\x1b[0m is \x1b[1m\x1b[33mmtu-limited\x1b[0m ({why})")
+    }
+}
+
+fn format_error(ip_str: &str, err: &Error, raw: bool) -> String {
+    let (what, color) = match err {
+        Error::Parse { .. } => ("invalid".to_string(), "\x1b[1m\x1b[31m"),
+        Error::Resolve { reason, .. } => (format!("unresolved ({reason})"), "\x1b[33m"),
+        Error::Probe { kind, .. } => (format!("unknown ({kind})"), "\x1b[33m"),
+        e => (format!("unknown ({e})"), "\x1b[33m"),
+    };
+    if raw {
+        format!("{ip_str} is {what}")
+    } else {
+        format!("\x1b[0m{ip_str}\x1b[0m is {color}{what}\x1b[0m")
+    }
+}
+
+// Bold red on stderr, for problems that make the whole sweep suspect
+fn warn_loudly(problem: &str, raw: bool) {
+    let line = format!("ipchk: WARNING: {problem}");
+    if raw {
+        eprintln!("{line}");
+    } else {
+        eprintln!("\x1b[1;31m{line}\x1b[0m");
+    }
+}
+
+// The ways to probe without a working system ping
+const NO_PING: &str = "use --engine native with root, CAP_NET_RAW or ping_group_range, \
+                       or check ports instead with --probe tcp -p <ports>";
+
+// Diagnostic for a failed ipchk::preflight()
+fn engine_error(e: &Error, opts: &ProbeOptions) -> String {
+    let syn = opts.probe == ProbeType::Tcp && opts.scan == Scan::Syn;
+    match e {
+        Error::Privilege { what } if syn => format!(
+            "SYN scans need a raw socket ({what}); run as root, grant ipchk \
+             cap_net_raw, or use --scan connect"
+        ),
+        Error::Probe {
+            kind: ProbeErrorKind::Unsupported,
+            ..
+        } if syn => "SYN scans are only supported on Linux; use --scan connect".into(),
+        // Datagram ICMP sockets only carry echo, so ping_group_range won't do
+        Error::Privilege { what } if opts.probe == ProbeType::Timestamp => format!(
+            "timestamp probes need a raw ICMP socket ({what}); run as root \
+             or grant ipchk cap_net_raw"
+        ),
+        Error::Privilege { what } if opts.engine == Engine::Native => format!(
+            "the native engine can't open an ICMP socket ({what}); run as root, \
+             grant ipchk cap_net_raw, allow its group in net.ipv4.ping_group_range, \
+             or use --engine ping"
+        ),
+        // Picked only because no ICMP socket opened, so name the other ways
+        Error::Probe {
+            kind: ProbeErrorKind::NotFound,
+            ..
+        } => format!(
+            "no `ping` executable found in PATH; install iputils-ping (or busybox), {NO_PING}"
+        ),
+        Error::Probe {
+            kind: ProbeErrorKind::Exec,
+            ..
+        } => format!("`ping` is installed but could not be run; check its permissions, {NO_PING}"),
+        Error::Privilege { what } => format!(
+            "the system ping lacks the privileges to send ICMP ({what}); \
+             grant it cap_net_raw or the setuid bit"
+        ),
+        e => format!("probe engine unavailable: {e}"),
+    }
+}
+
+// Why ipchk::preflight_v6 failed: IPv6 echoes go through the system ping
+// whatever the engine, as neither the native engine nor TCP probes take
+// IPv6 yet
+fn v6_engine_error(e: &Error, opts: &ProbeOptions) -> String {
+    match e {
+        Error::Probe {
+            kind: ProbeErrorKind::NotFound,
+            ..
+        } => "IPv6 targets need the system ping, and there is no `ping` in PATH; \
+              install iputils-ping (or busybox)"
+            .to_string(),
+        Error::Probe {
+            kind: ProbeErrorKind::Exec,
+            ..
+        } => "IPv6 targets need the system ping, which is installed but could \
+              not be run; check its permissions"
+            .to_string(),
+        e => engine_error(
+            e,
+            &ProbeOptions {
+                engine: Engine::Ping,
+                ..opts.clone()
+            },
+        ),
+    }
+}
+
+// --probe wasm:<module>, compiled once for the whole sweep
+#[cfg(feature = "wasm")]
+fn wasm_probe(path: &Path, opts: &ProbeOptions) -> Result<Arc<dyn ipchk::Probe>, String> {
+    match ipchk::WasmProbe::load(path, opts) {
+        Ok(probe) => Ok(Arc::new(probe)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+fn wasm_probe(_: &Path, _: &ProbeOptions) -> Result<Arc<dyn ipchk::Probe>, String> {
+    Err("this ipchk was built without WASM support (build with --features wasm)".into())
+}
+
+// ipchk grpc --listen, until killed
+#[cfg(feature = "grpc")]
+fn grpc_server(
+    listen: &str,
+    probe: ProbeOptions,
+    require: u32,
+    concurrency: usize,
+) -> Result<(), String> {
+    let cfg = grpc::Config {
+        probe,
+        require,
+        concurrency,
+    };
+    grpc::listen(listen, cfg)
+}
+
+#[cfg(not(feature = "grpc"))]
+fn grpc_server(_: &str, _: ProbeOptions, _: u32, _: usize) -> Result<(), String> {
+    Err("this ipchk was built without gRPC support (build with --features grpc)".into())
+}
+
+// No --engine: the native engine where it can open a socket, else the
+// system ping. Sweeps say which on a terminal, and what more privileges
+// would allow.
+fn pick_engine(args: &Args) -> Engine {
+    let privileges = Privileges::detect();
+    let engine = privileges.best_engine();
+    let noted = matches!(args.command, Command::Sweep | Command::Daemon)
+        && args.probe == ProbeType::Echo
+        && args.wasm.is_none()
+        && args.via.is_empty();
+    if noted && std::io::stderr().is_terminal() {
+        let note = engine_note(&privileges, engine);
+        if args.raw {
+            eprintln!("{note}");
         } else {
-            format!("\x1b[0m{}\x1b[0m is \x1b[1m\x1b[31mdown\x1b[0m", ip_str)
+            eprintln!("\x1b[2m{note}\x1b[0m");
+        }
+    }
+    engine
+}
+
+fn engine_note(p: &Privileges, engine: Engine) -> String {
+    if cfg!(windows) {
+        return if p.root {
+            "ipchk: probing with IcmpSendEcho, as administrator".into()
+        } else {
+            "ipchk: probing with IcmpSendEcho; as administrator, ipchk service install would work too"
+                .into()
+        };
+    }
+    let linux = cfg!(any(target_os = "linux", target_os = "android"));
+    match engine {
+        Engine::Native if p.raw_icmp => {
+            let why = if p.root {
+                ", as root"
+            } else if p.cap_net_raw {
+                ", with CAP_NET_RAW"
+            } else {
+                ""
+            };
+            format!("ipchk: using the native engine (raw ICMP socket{why})")
+        }
+        Engine::Native => {
+            let why = if p.ping_group {
+                ", via ping_group_range"
+            } else {
+                ""
+            };
+            let more = if linux {
+                "root or CAP_NET_RAW would add icmp-timestamp, --scan syn and routers' --df errors"
+            } else {
+                "root would add routers' --df errors"
+            };
+            format!("ipchk: using the native engine (ICMP datagram socket{why}); {more}")
+        }
+        Engine::Ping => {
+            let who = if linux {
+                "a group in net.ipv4.ping_group_range, CAP_NET_RAW or root"
+            } else {
+                "root"
+            };
+            format!(
+                "ipchk: using the system ping (no ICMP socket allowed); {who} would allow the native engine"
+            )
+        }
+    }
+}
+
+// How each host is probed and judged
+#[derive(Clone)]
+struct Probing {
+    probe: Arc<dyn ipchk::Probe>,
+    budget: Duration,           // for one target's verdict
+    require: u32,               // replies needed to count as up (--require)
+    warn_rtt: Option<Duration>, // --warn-rtt
+    crit_rtt: Option<Duration>, // --crit-rtt
+    slow_rtt: Option<Duration>, // --latency-factor times its --baseline RTT
+    v6: Option<ProbeOptions>,   // for IPv6 targets, with a built-in probe
+}
+
+impl Probing {
+    // The average RTT of an up host, when thresholds make it worth showing
+    fn latency(&self, o: &Outcome) -> Option<(Duration, Latency)> {
+        let thresholds = [self.warn_rtt, self.crit_rtt, self.slow_rtt];
+        if !o.up || thresholds.iter().all(Option::is_none) {
+            return None;
+        }
+        let rtt = o.stats.rtt.map(|r| r.avg)?;
+        let level = if self.crit_rtt.is_some_and(|c| rtt >= c) {
+            Latency::Crit
+        } else if self.warn_rtt.is_some_and(|w| rtt >= w) || self.slow_rtt.is_some_and(|s| rtt > s)
+        {
+            Latency::Warn
+        } else {
+            Latency::Ok
+        };
+        Some((rtt, level))
+    }
+}
+
+// Addresses of a hostname target, looked up as its batch is probed
+type Resolved = Result<Vec<IpAddr>, Error>;
+
+// -4/-6: the one address family of a name's addresses that counts
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    fn pick(self, host: &str, addrs: Vec<IpAddr>) -> Resolved {
+        let kept: Vec<IpAddr> = addrs
+            .into_iter()
+            .filter(|a| a.is_ipv4() == (self == Family::V4))
+            .collect();
+        if kept.is_empty() {
+            let family = if self == Family::V4 { "IPv4" } else { "IPv6" };
+            return Err(Error::Resolve {
+                host: host.to_string(),
+                reason: format!("no {family} address"),
+            });
+        }
+        Ok(kept)
+    }
+}
+
+// Whether `target` is a name to look up (address literals and non-names
+// are probed, or rejected, as they are)
+fn needs_lookup(target: &str) -> bool {
+    target.parse::<IpAddr>().is_err() && dns::is_hostname(target)
+}
+
+// Look up `names`, --dns-concurrency at a time, handing each answer over as
+// it arrives so its probe can start while the other lookups are in flight,
+// rather than every probe waiting on the slowest name
+fn resolve_stream(
+    names: Vec<(usize, String)>,
+    dns: Option<Resolver>,
+    timeout: Duration,
+    concurrency: usize,
+    family: Option<Family>,
+) -> mpsc::Receiver<(usize, String, Resolved)> {
+    let (tx, rx) = mpsc::channel();
+    let workers = concurrency.min(names.len());
+    let queue = Arc::new(Mutex::new(names.into_iter()));
+    for _ in 0..workers {
+        let (tx, queue) = (tx.clone(), Arc::clone(&queue));
+        thread::spawn(move || {
+            loop {
+                let next = queue.lock().map_or(None, |mut q| q.next());
+                let Some((index, name)) = next else {
+                    break;
+                };
+                let resolved =
+                    dns::resolve(&name, dns.as_ref(), timeout).and_then(|addrs| match family {
+                        Some(f) => f.pick(&name, addrs),
+                        None => Ok(addrs),
+                    });
+                if tx.send((index, name, resolved)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    rx
+}
+
+fn check(
+    ip_str: &str,
+    probing: &Probing,
+    resolved: Option<Resolved>,
+) -> Result<(IpAddr, Outcome), Error> {
+    let ip = match resolved {
+        Some(addrs) => addrs?[0],
+        None => ipchk::parse_target(ip_str)?,
+    };
+    let v4 = match (ip, &probing.v6) {
+        (IpAddr::V4(v4), _) => v4,
+        // Only the system ping reaches these for now
+        (IpAddr::V6(v6), Some(opts)) => {
+            let outcome = ipchk::probe_v6(v6, opts)?;
+            return Ok((ip, outcome.require(probing.require)));
+        }
+        (IpAddr::V6(_), None) => {
+            return Err(Error::Probe {
+                target: ip_str.to_string(),
+                kind: ProbeErrorKind::Unsupported,
+            });
         }
     };
+    let target = ipchk::Target {
+        addr: v4,
+        name: (ip_str.parse::<IpAddr>().is_err()).then(|| ip_str.to_string()),
+    };
+    let outcome = probing
+        .probe
+        .probe(&target, Deadline::after(probing.budget))?;
+    Ok((ip, outcome.require(probing.require)))
+}
+
+// "host (10.0.0.1)" for hostname targets, "10.0.0.1 (ptr.name)" with --rdns;
+// a user label joins them, as in "host (10.0.0.1, core-switch)"
+fn label(ip_str: &str, name: Option<&str>, addr: Option<IpAddr>, given: Option<&str>) -> String {
+    let mut notes = match (name, addr) {
+        (Some(n), Some(a)) if n == ip_str => vec![a.to_string()],
+        (Some(n), _) if n != ip_str => vec![dns::to_unicode(n)],
+        _ => Vec::new(),
+    };
+    notes.extend(given.map(str::to_string));
+    // Punycode names read as the Unicode they stand for
+    let ip_str = dns::to_unicode(ip_str);
+    if notes.is_empty() {
+        ip_str
+    } else {
+        format!("{ip_str} ({})", notes.join(", "))
+    }
+}
 
-    let _ = tx.send(PingResult {
-        sort_key: v4_key(v4),
+// `grouped` probes the single address in `resolved` on behalf of hostname
+// `ip_str`, and is shown by that address alone. `index` is the target's
+// place in the input.
+fn ping_one(
+    ip_str: String,
+    index: usize,
+    resolved: Option<Resolved>,
+    grouped: bool,
+    probing: Probing,
+    style: Style,
+    enrich: enrich::Enrich,
+) -> PingResult {
+    let raw = style.raw;
+    let member = match (&resolved, grouped) {
+        (Some(Ok(addrs)), true) => Some(addrs[0].to_string()),
+        _ => None,
+    };
+    let target = member.as_deref().unwrap_or(&ip_str);
+    let (probed, error, outcome) = match check(target, &probing, resolved) {
+        Ok((ip, o)) => (Some(ip), None, Some(o)),
+        Err(e) => (None, Some(e), None),
+    };
+    // IPv6 addresses are shown, but not carried as results' `address` yet
+    let addr = match probed {
+        Some(IpAddr::V4(v4)) => Some(v4),
+        _ => None,
+    };
+    let at = SystemTime::now();
+    let name = if ip_str.parse::<IpAddr>().is_err() && dns::is_hostname(&ip_str) {
+        Some(ip_str.clone())
+    } else {
+        addr.and_then(|a| enrich.name(a))
+    };
+    let given = enrich.label(&ip_str);
+    // Grouped results are listed under their name, which carries the label
+    let shown = match &member {
+        Some(m) => m.clone(),
+        None => label(&ip_str, name.as_deref(), probed, given.as_deref()),
+    };
+    let latency = outcome.as_ref().and_then(|o| probing.latency(o));
+    let mut msg = match (&outcome, &error, latency) {
+        (Some(_), _, Some((rtt, level))) => format_latency(&shown, rtt, level, style),
+        (Some(o), _, None) if !o.up && o.frag_needed > 0 => format_mtu_limited(&shown, o, raw),
+        (Some(o), _, None) => format_verdict(&shown, o.up, style, false),
+        (None, Some(e), _) => format_error(&shown, e, raw),
+        (None, None, _) => unreachable!(),
+    };
+    // Answered, but not often enough for --require
+    if let Some(o) = outcome.as_ref().filter(|o| !o.up && o.stats.received > 0) {
+        msg.push_str(&format!(
+            " ({}/{} replies, {} required)",
+            o.stats.received, o.stats.transmitted, probing.require
+        ));
+    }
+    // --probe tcp: "open 22/ssh,80/http; 1 closed; 99 filtered"
+    if let Some(o) = outcome.as_ref().filter(|o| o.up && !o.ports.is_empty()) {
+        msg.push_str(&format!(" ({})", port_summary(&o.ports)));
+    }
+    // How far the host's clock is from ours, from its timestamp replies
+    if let Some(offset) = outcome.as_ref().and_then(Outcome::clock_offset_ms) {
+        msg.push_str(&format!(" (clock {offset:+} ms)"));
+    }
+    if let Some(o) = outcome.as_ref().filter(|o| o.corrupted > 0) {
+        let note = format!(" ({}/{} replies corrupted)", o.corrupted, o.sent);
+        msg.push_str(&if raw {
+            note
+        } else {
+            format!("\x1b[33m{note}\x1b[0m")
+        });
+    }
+    let notes = addr.map(|a| enrich.notes(a)).unwrap_or_default();
+    if !notes.is_empty() {
+        let notes = notes.join("; ");
+        msg.push_str(&if raw {
+            format!(" [{notes}]")
+        } else {
+            format!(" \x1b[2m[{notes}]\x1b[0m")
+        });
+    }
+    PingResult {
+        sort_key: SortKey::of(&ip_str, addr, index),
+        index,
         msg,
-    });
+        ip: ip_str,
+        name,
+        addr,
+        grouped,
+        up: outcome.as_ref().map(|o| o.up),
+        cached: false,
+        error,
+        outcome,
+        latency: latency.map_or(Latency::Ok, |(_, level)| level),
+        at,
+        mac: None,
+        alias_of: None,
+        label: given,
+        baseline: None,
+    }
+}
+
+// --ptr-only: reverse lookups instead of probes, printed chunk by chunk so
+// large ranges stream. Addresses without a PTR record are left out.
+fn ptr_sweep(
+    targets: impl Iterator<Item = String>,
+    resolver: Resolver,
+    concurrency: usize,
+    raw: bool,
+) {
+    let mut targets = targets.peekable();
+    while targets.peek().is_some() {
+        let chunk: Vec<String> = targets.by_ref().take(concurrency).collect();
+        let names: Vec<Result<Option<String>, Error>> = thread::scope(|s| {
+            let handles: Vec<_> = chunk
+                .iter()
+                .map(|t| {
+                    s.spawn(move || ipchk::parse_target(t).and_then(|ip| resolver.reverse(ip)))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or(Ok(None)))
+                .collect()
+        });
+        for (target, name) in chunk.iter().zip(names) {
+            match name {
+                Ok(Some(name)) if raw => println!("{target} -> {}", dns::to_unicode(&name)),
+                Ok(Some(name)) => println!(
+                    "\x1b[1m{target}\x1b[0m -> \x1b[1m{}\x1b[0m",
+                    dns::to_unicode(&name)
+                ),
+                Ok(None) => {}
+                Err(e @ Error::Parse { .. }) => println!("{}", format_error(target, &e, raw)),
+                Err(e) => eprintln!("--ptr-only: {e}"),
+            }
+        }
+    }
 }
 
 /* -------------------- pico-args plumbing -------------------- */
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Command {
+    Sweep,                    // one-shot check (default)
+    Daemon,                   // ipchk daemon: monitor continuously, answer queries
+    Status,                   // ipchk status: ask a running daemon
+    Service(service::Action), // ipchk service install|run|uninstall (Windows)
+    Calc,                     // ipchk calc: subnet calculator, no probing
+    Internet,                 // ipchk internet: gateway, WAN and DNS check
+    MyIp,                     // ipchk myip: public addresses via STUN
+    Bench,                    // ipchk bench: the same targets through each engine
+    Agent,                    // ipchk agent: probe on behalf of --via coordinators
+    Grpc,                     // ipchk grpc: start and follow sweeps over gRPC
+    Merge,                    // ipchk merge: combine result files, no probing
+    Report,                   // ipchk report: availability from daemon logs
+}
+
 #[derive(Debug)]
 struct Args {
-    range: Option<(Ipv4Addr, Ipv4Addr)>, // -r/--range start end
-    timeout_ms: u64,                     // -t/--timeout (ms)
-    count: u32,                          // -n/--count probes per host
-    concurrency: usize,                  // -c/--concurrency
-    ips: Vec<String>,                    // positional IPs
-    raw: bool,                           // -a/--ascii/--raw
+    command: Command,
+    range: Option<(Ipv4Addr, Ipv4Addr)>,  // -r/--range start end
+    timeout_ms: u64,                      // -t/--timeout (ms)
+    count: u32,                           // -n/--count probes per host
+    require: u32,                         // --require replies to count as up
+    interval: Option<Duration>,           // -i/--interval (ms) between probes
+    engine: Option<Engine>,               // --engine ping|native; None to pick one
+    ping_args: Vec<String>,               // --ping-args, word by word
+    ping_path: Option<PathBuf>,           // --ping-path, else $IPCHK_PING
+    probe: ProbeType,                     // --probe echo|icmp-timestamp
+    wasm: Option<PathBuf>,                // --probe wasm:<module> instead
+    df: bool,                             // --df: Don't Fragment
+    ports: Vec<u16>,                      // -p/--ports for --probe tcp
+    scan: Scan,                           // --scan connect|syn
+    proxy: Option<SocketAddr>,            // --proxy socks5://host:port
+    connect_timeout: Option<Duration>,    // --connect-timeout (ms) for TCP handshakes
+    response_timeout: Option<Duration>,   // --response-timeout (ms) for the proxy's answers
+    payload: Option<Payload>,             // --pattern/--payload-ascii/--size
+    warn_rtt: Option<Duration>,           // --warn-rtt (ms): up but degraded
+    crit_rtt: Option<Duration>,           // --crit-rtt (ms): degraded, critical
+    concurrency: usize,                   // -c/--concurrency
+    ips: Vec<String>,                     // positional IPs
+    raw: bool,                            // -a/--ascii/--raw
+    symbols: output::Symbols,             // --symbols
+    cache_ttl: Option<Duration>,          // --cache <duration>
+    every: Duration,                      // daemon: --every <duration> between sweeps
+    socket: Option<PathBuf>,              // daemon/status: --socket <path>
+    log: Option<PathBuf>,                 // daemon: --log <path> state-change log
+    flapping: Option<daemon::Flapping>,   // daemon: --flap-threshold, --flap-window
+    notify: notify::Notifiers,            // daemon: --notify-* state-change alerts
+    exit_codes: ExitCodes,                // --exit-codes <policy>
+    output: output::Format,               // --output <format>
+    columns: Option<Vec<output::Column>>, // --fields: the csv columns, in order
+    dns: Option<Resolver>,                // --dns-server <ip[:port]>
+    enrich: enrich::Enrich,               // --rdns/--asn lookups (via --dns-server or resolv.conf)
+    dns_timeout: Duration,                // --dns-timeout <duration> per lookup
+    dns_concurrency: usize,               // --dns-concurrency lookups in flight
+    all_addresses: bool,                  // --all-addresses: probe every A/AAAA record
+    family: Option<Family>,               // -4/-6: only a name's addresses of that family
+    ptr_only: bool,                       // --ptr-only: reverse DNS sweep, no probing
+    fields: input::Fields,                // --input: inventory columns per target
+    overrides: HashMap<String, input::Overrides>, // --input list: a target's own settings
+    stdin: bool,                          // --input read stdin, which a daemon can't reread
+    split: Option<u32>,                   // calc: --split <len>
+    supernet: Option<u32>,                // calc: --supernet <len>
+    prefer: merge::Prefer,                // merge: --prefer latest|up
+    period: Option<Duration>,             // report: --period <duration>
+    aggregate: bool,                      // --aggregate: up hosts as CIDR prefixes
+    stats: bool,                          // -s/--stats: summary and RTT histogram
+    aliases: bool,                        // --aliases: flag addresses sharing a MAC
+    baseline: Option<baseline::Baseline>, // --baseline <file>: known hosts
+    baseline_update: bool,                // --baseline-update: stamp the ones seen
+    first: Vec<String>,                   // --first: targets probed (and shown) first
+    order: Order,                         // --order input|completion|address
+    progress: Option<progress::Format>,   // --progress json: records on stderr
+    check_gateway: Option<GatewayCheck>,  // --check-gateway warn|abort
+    captive_check: bool,                  // --captive-check before the sweep
+    verbose: bool,                        // -v/--verbose: per-probe detail
+    listen: Option<String>,               // agent/grpc: --listen <addr[:port]>
+    via: Vec<agent::Endpoint>,            // --via <agent,...> and --via-ssh <host,...>
+    netbox: Option<(String, String)>,     // --netbox-url and its token
+    post: Option<post::Collector>,        // --post-url, --post-token, --post-batch
+    otel: Option<otel::Exporter>,         // --otel <endpoint>: OTLP metrics and a span
+    statsd: Option<statsd::Client>,       // --statsd <host[:port]> and --statsd-format
+    journal: Option<journald::Journal>,   // --journald: results as journal entries
+    eventlog: Option<eventlog::EventLog>, // --eventlog: summaries as Windows events
+}
+
+// (year, month, day, hour, minute, second) in UTC
+fn civil_utc(t: SystemTime) -> (i64, i64, i64, u64, u64, u64) {
+    let secs = t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // Civil-from-days (Howard Hinnant), valid for any date after 1970
+    let z = days as i64 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day, rem / 3600, rem / 60 % 60, rem % 60)
+}
+
+// "2024-05-01T12:34:56Z"
+fn rfc3339_utc(t: SystemTime) -> String {
+    let (year, month, day, h, m, s) = civil_utc(t);
+    format!("{year:04}-{month:02}-{day:02}T{h:02}:{m:02}:{s:02}Z")
+}
+
+// "2024-05-01T12:34:56Z", as rfc3339_utc writes it
+fn parse_rfc3339(s: &str) -> Option<SystemTime> {
+    let s = s.strip_suffix('Z')?;
+    let (date, time) = s.split_once('T')?;
+    let mut d = date.splitn(3, '-').map(str::parse::<i64>);
+    let (y, m, day) = (d.next()?.ok()?, d.next()?.ok()?, d.next()?.ok()?);
+    let mut t = time.splitn(3, ':').map(str::parse::<u64>);
+    let (h, min, sec) = (t.next()?.ok()?, t.next()?.ok()?, t.next()?.ok()?);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&day) || h > 23 || min > 59 || sec > 60 {
+        return None;
+    }
+    // Days-from-civil (Howard Hinnant), the inverse of civil_utc
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = u64::try_from(era * 146_097 + doe - 719_468).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86_400 + h * 3600 + min * 60 + sec))
+}
+
+// "2024-05-01T12:34:56.789Z", for per-result and per-probe times
+fn rfc3339_utc_ms(t: SystemTime) -> String {
+    let ms = t
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_millis());
+    let stamp = rfc3339_utc(t);
+    format!("{}.{ms:03}Z", &stamp[..stamp.len() - 1])
 }
 
 fn usage(program: &str) -> String {
     format!(
         "Usage:
   {p} <IP1> <IP2> ...                       # ping positional addresses
+  {p} <IP>=<label> ...                      # ... shown with a label
+  {p} <URL> ...                             # ... the host of each URL
+  {p} <MAC> ...                             # ... the address holding each MAC
   {p} -r <start_ipv4> <end_ipv4>            # ping inclusive IPv4 range
+  {p} daemon [OPTIONS] <targets>            # monitor targets, answer queries
+  {p} status [<IP>...]                      # query a running daemon
+  {p} service install|run|uninstall ...     # daemon as a Windows service
+  {p} calc <prefix>|<start> <end>           # subnet calculator
+  {p} internet [<name>]                     # check gateway, WAN, DNS, portal
+  {p} myip [<stun server>...]               # public IPv4/IPv6 addresses
+  {p} bench [OPTIONS] <targets>             # compare the probe engines
+  {p} agent --listen <addr[:port]>          # probe for --via coordinators
+  {p} grpc --listen <addr[:port]>           # start and follow sweeps over gRPC
+  {p} merge [--prefer latest|up] <file>...  # combine --output json results
+  {p} report [--period 30d] <daemon.log>... # availability from daemon --log files
 
 Options:
   -r, --range            Upper- and lower-limit IPv4 addresses (inclusive)
   -a, --ascii, --raw     Force plain ASCII output (no colours)
+  --symbols <set>        Up/down as unicode (✓/✗) or ascii (+/-) marks, or none
+                         (words, the default)
+  --output <format>      text (default), fping (like fping -a), fping-c (like
+                         fping -c), nmap (like nmap -sn), csv, table, json,
+                         jsonl or netbox (CSV for NetBox's IP address import);
+                         report: text, csv, json or html
+  --table                Aligned columns under a header (same as --output table)
+  --fields <col,...>     csv/table: these columns, in this order: ip, address,
+                         name, label, status, rtt, loss, time, mac, --input ones
   -t, --timeout          Per-probe timeout in milliseconds (default: {dto})
-  -n, --count            Probes per host; succeed on first reply (default: {dn})
+  -n, --count            Probes per host; up on the first reply (default: {dn})
+  --require <n>          Up only if at least <n> of the --count probes reply
+  -i, --interval <ms>    Pause between probes to the same host (default:
+                         back to back, or ping's own 1s when ping probes)
+  --engine <engine>      ping (the system ping) or native (ICMP sockets; needs
+                         root, CAP_NET_RAW or ping_group_range); by default
+                         native where it can open a socket, else ping
+  --ping-args <flags>    Extra flags for the system ping, e.g. '-Q 0x10';
+                         implies --engine ping
+  --ping-path <path>     The ping to run instead of the one on PATH (default:
+                         $IPCHK_PING); implies --engine ping
+  --probe <type>         echo (default), icmp-timestamp (ICMP type 13, which
+                         some filtered hosts still answer; shows clock offset)
+                         or tcp (connects; up if any port answers); or
+                         wasm:<module> to run a WebAssembly probe
+  -p, --ports <list>     Ports for --probe tcp, e.g. 22,80,443,8000-8100
+                         (default: 80); each is shown open, closed or filtered
+  --proxy <url>          Make --probe tcp's connections through a SOCKS5 proxy,
+                         e.g. socks5://127.0.0.1:1080 from `ssh -D 1080 jump`
+  --connect-timeout <ms> Give TCP handshakes this long instead of --timeout
+  --response-timeout <ms>
+                         Give the --proxy this long to answer once connected
+                         (default: --timeout)
+  --top-ports <n>        ... or the <n> most commonly open ports (up to 100)
+  --scan <how>           connect (full handshakes, default) or syn (half-open,
+                         raw socket; Linux, needs root or CAP_NET_RAW)
+  --df                   Set Don't Fragment; hosts only hit with \"fragmentation
+                         needed\" are mtu-limited (native engine)
+  --pattern <hex>        Fill the echo payload with these bytes, repeated (up
+                         to 16 with the system ping)
+  --payload-ascii <str>  Send <str> as the echo payload, to spot probes in
+                         packet captures
+  --size <bytes>         Echo payload size (default: 56, or the --payload-ascii
+                         length)
+  --warn-rtt <ms>        Show latencies; average RTTs of <ms> or more are
+                         degraded (yellow)
+  --crit-rtt <ms>        ... and from <ms> up critical (red)
   -c, --concurrency      Max simultaneous hosts in flight (default: {dc})
+  --dns-server <ip[:port]>
+                         Resolve hostname targets (and --rdns) via this server
+  --rdns                 Show the reverse-DNS (PTR) name of each address
+  --asn                  Show the origin AS of public addresses (Team Cymru DNS)
+  --geoip <mmdb>         Show country/city of public addresses from a MaxMind DB
+  --dns-timeout <dur>    Give up on a lookup after <dur> (default: {dnt}s)
+  --dns-concurrency <n>  Max simultaneous lookups (default: {dnc})
+  --all-addresses        Probe every address of a multi-homed hostname
+  -4, -6                 Resolve and probe only hostnames' IPv4 (or IPv6) addresses
+  --aggregate            Print the up hosts as the fewest covering CIDR prefixes
+  -s, --stats            Print a summary and RTT histogram to stderr at the end
+  --first <ip,...>       Probe these targets before the rest and, with text,
+                         fping or jsonl output, print them straight away
+  --order <order>        List the results as the targets were given (input), as
+                         they were checked (completion) or by address (default)
+  --progress json        Print a JSON progress record to stderr every second
+  --check-gateway <act>  Ping the default gateway first; if it doesn't answer,
+                         warn (and sweep anyway) or abort
+  --captive-check        Warn if a captive portal intercepts clear-text HTTP
+                         (also part of ipchk internet)
+  --aliases              Flag up addresses sharing a MAC (from the input's mac
+                         column or the ARP cache) as aliases of one device
+  --baseline <file>      Flag up hosts that aren't listed in <file> (one per
+                         line, e.g. from --output fping) as new, and listed
+                         ones that are down as missing
+  --baseline-update      Record in the --baseline file when each listed host
+                         last answered, so missing ones say for how long, and
+                         its usual RTT
+  --latency-factor <x>   Flag up hosts over <x> times their usual --baseline
+                         RTT as degraded
+  -v, --verbose          Show when each verdict was reached and every probe's
+                         send time and RTT (text and JSON output)
+  --ptr-only             Don't probe; list the addresses that have PTR records
+  --zone-file <path>     Probe every A/AAAA record of a BIND zone file
+  --from-arp-cache       Probe every neighbour in the kernel's ARP cache
+  --from-routes          Sweep every directly connected subnet (up to /16)
+  -I, --interface <name> ... only those on <name>; repeat to sweep several side by side
+  --profile <name>       Take the settings of [profile.<name>] in the config file
+                         ($IPCHK_CONFIG, else ~/.config/ipchk/config.toml) that
+                         the command line doesn't set
+  --input <format>       Read targets from the positional files (or stdin) in
+                         <format>: csv (with an ip column), json, nmap-xml or
+                         list (a target per line, then its own timeout=,
+                         count=, interval=, probe= or ports= settings);
+                         a label column labels each row's target
+  --cache <duration>     Reuse results younger than <duration> (e.g. 30s, 5m)
+  --split <len>          calc: list the /<len> subnets of the prefix
+  --supernet <len>       calc: show the enclosing /<len>
+  --prefer <which>       merge: for a target in several files keep the latest
+                         result (default) or, with up, any up one
+  --period <duration>    report: how far back to look (default: 30d)
+  --every <duration>     daemon: pause between sweeps (default: {de}s); on
+                         SIGHUP it takes its options, --profile and --input
+                         files afresh, keeping what it knows of each host
+  --socket <path>        daemon/status: query socket (default: {ds})
+  --log <path>           daemon: also append state changes to <path>
+  --flap-threshold <n>   daemon: hold the notifications of a host that changes
+                         state more than <n> times within --flap-window
+  --flap-window <dur>    daemon: the window for --flap-threshold (default: {dfw}s)
+  --listen <addr[:port]> agent: accept coordinators here (default port: {dp});
+                         grpc: gRPC clients (default port: 50051)
+  --stdio                agent: serve one coordinator on stdin/stdout instead
+  --via <agent,...>      Probe from these agents (host[:port]) instead of from
+                         here, spreading the targets between them
+  --via-ssh <host,...>   The same through ssh to [user@]host, running (or
+                         copying over) ipchk there, else its ping
+  --notify-desktop       daemon: desktop notification when a host changes state
+  --audible              Ring the terminal bell when a host is (or comes) up
+  --audible-down         Ring the terminal bell when a host is (or goes) down
+  --email-to <addr>      Mail state changes (daemon) or new downs/recoveries (sweep)
+  --smtp-server <host>   SMTP relay for --email-to, host[:port] (no TLS/auth)
+  --email-from <addr>    Sender address (default: ipchk@<hostname>)
+  --exit-codes <policy>  default (always 0), fping (0 all up, 1 some down,
+                         2 invalid, 3 usage error, 4 system error) or nagios
+                         (0 OK, 1 degraded, 2 down or critical, 3 unknown)
+  --webhook <url>        POST the same alerts as JSON to <url> (via curl)
+  --notify-format <fmt>  Webhook payload: generic, slack or discord (default: generic)
+  --netbox-url <url>     Add up hosts NetBox doesn't have yet to its IPAM
+  --netbox-token <tok>   API token for --netbox-url (default: $NETBOX_TOKEN)
+  --post-url <url>       POST the --output json document to <url> after the sweep
+  --post-token <tok>     Bearer token for --post-url (default: $IPCHK_POST_TOKEN)
+  --post-batch <n>       POST JSON Lines instead, <n> results at a time
+  --otel <endpoint>      Send RTT and up/down metrics and a span for the sweep
+                         over OTLP/HTTP, e.g. http://collector:4318
+  --statsd <host[:port]> Send per-host up and RTT gauges and sweep counters to
+                         StatsD (default port: 8125); daemon: after each sweep
+  --statsd-format <fmt>  dogstatsd (host and status as tags, the default) or
+                         statsd (in the metric names)
+  --journald             Log each result (daemon: state change) to the systemd
+                         journal, with IPCHK_TARGET, IPCHK_STATUS, IPCHK_RTT
+  --eventlog             Windows: write a sweep summary (daemon: state changes)
+                         to the Application event log, source ipchk
   -h, --help             Show this help
   --version              Show version information
 
@@ -204,17 +1157,72 @@ Examples:
   {p} 192.168.1.1 192.168.1.2 1.1.1.1
   {p} -r 172.16.0.1 172.16.1.254 -t 750 -n 3 -c 256
   {p} -r 10.0.0.1 10.0.0.254 --ascii
+  {p} --cache 1m 10.0.0.1 10.0.0.2
+  {p} --input csv --output csv inventory.csv
+  {p} daemon --every 10s -r 10.0.0.1 10.0.0.254
+  {p} status 10.0.0.1
+  {p} calc 10.0.0.0/22 --split 24
+  {p} merge --prefer up monday.json tuesday.json
+  {p} internet
+  {p} myip --output json
+  {p} service install --every 1m -r 10.0.0.1 10.0.0.254
+  {p} --via 10.1.0.5,10.2.0.5 -r 10.1.0.1 10.2.255.254
+  {p} --via-ssh admin@jump.dmz -r 172.20.0.1 172.20.0.254
 ",
         p = program,
         dto = DEFAULT_TIMEOUT_MS,
         dn = DEFAULT_COUNT,
-        dc = DEFAULT_CONCURRENCY
+        dc = DEFAULT_CONCURRENCY,
+        de = DEFAULT_EVERY_SECS,
+        dfw = DEFAULT_FLAP_WINDOW_SECS,
+        dnt = dns::DEFAULT_TIMEOUT.as_secs(),
+        dnc = DEFAULT_DNS_CONCURRENCY,
+        ds = daemon::default_socket().display(),
+        dp = agent::DEFAULT_PORT
     )
 }
 
 fn parse_args() -> Result<Args, String> {
-    let mut pargs = Arguments::from_env();
-    let program = env::args().next().unwrap_or_else(|| "ipchk".to_string());
+    parse_argv(env::args_os().collect())
+}
+
+// `argv` as env::args_os() gives it, the program first; a daemon parses its
+// own again on SIGHUP
+fn parse_argv(argv: Vec<OsString>) -> Result<Args, String> {
+    let word = |i: usize| argv.get(i).map(|a| a.to_string_lossy().into_owned());
+    let program = word(0).unwrap_or_else(|| "ipchk".to_string());
+    let mut pargs = Arguments::from_vec(config::with_profile(
+        argv.iter().skip(1).cloned().collect(),
+    )?);
+
+    // Subcommands are only recognised as the first argument so that a bare
+    // positional target list keeps working.
+    let command = match word(1).as_deref() {
+        Some("daemon") => Command::Daemon,
+        Some("status") => Command::Status,
+        Some("calc") => Command::Calc,
+        Some("internet") => Command::Internet,
+        Some("myip") => Command::MyIp,
+        Some("bench") => Command::Bench,
+        Some("agent") => Command::Agent,
+        Some("grpc") => Command::Grpc,
+        Some("merge") => Command::Merge,
+        Some("report") => Command::Report,
+        Some("service") => {
+            let _ = pargs.subcommand();
+            let action = match word(2).as_deref() {
+                Some("install") => service::Action::Install,
+                Some("run") => service::Action::Run,
+                Some("uninstall") => service::Action::Uninstall,
+                _ => return Err("Usage: ipchk service install|run|uninstall [OPTIONS]".into()),
+            };
+            Command::Service(action)
+        }
+        _ => Command::Sweep,
+    };
+    if command != Command::Sweep {
+        let _ = pargs.subcommand();
+    }
 
     if pargs.contains(["-h", "--help"]) {
         return Err(usage(&program));
@@ -256,18 +1264,707 @@ fn parse_args() -> Result<Args, String> {
         .unwrap_or(DEFAULT_COUNT)
         .max(1);
 
+    let require = pargs
+        .opt_value_from_str::<_, u32>("--require")
+        .map_err(|e| format!("--require: {e}"))?
+        .unwrap_or(1)
+        .max(1);
+    let interval = pargs
+        .opt_value_from_str::<_, u64>(["-i", "--interval"])
+        .map_err(|e| format!("--interval: {e}"))?
+        .map(Duration::from_millis);
+    let engine: Option<Engine> = pargs
+        .opt_value_from_str("--engine")
+        .map_err(|e| format!("--engine: {e}"))?;
+    // "wasm:<file>" is a module to run instead of a built-in type
+    let probe: Option<String> = pargs
+        .opt_value_from_str("--probe")
+        .map_err(|e| format!("--probe: {e}"))?;
+    let wasm = probe
+        .as_deref()
+        .and_then(|p| p.strip_prefix("wasm:"))
+        .map(PathBuf::from);
+    let probe: ProbeType = match probe {
+        Some(p) if wasm.is_none() => p.parse().map_err(|e| format!("--probe: {e}"))?,
+        _ => ProbeType::Echo,
+    };
+    if wasm.as_ref().is_some_and(|p| p.as_os_str().is_empty()) {
+        return Err("--probe: wasm: needs the module's path".into());
+    }
+    if wasm.is_some() && command != Command::Sweep {
+        return Err("--probe wasm: only applies to a sweep".into());
+    }
+
+    let ports = pargs
+        .opt_value_from_fn(["-p", "--ports"], ipchk::parse_ports)
+        .map_err(|e| format!("--ports: {e}"))?;
+    let top: Option<usize> = pargs
+        .opt_value_from_str("--top-ports")
+        .map_err(|e| format!("--top-ports: {e}"))?;
+    if top.is_some_and(|n| n == 0 || n > ipchk::TOP_PORTS.len()) {
+        return Err(format!("--top-ports: 1 to {}", ipchk::TOP_PORTS.len()));
+    }
+    let ports = match (ports, top) {
+        (Some(_), Some(_)) => return Err("--top-ports cannot be combined with --ports".into()),
+        (None, Some(n)) => Some(ipchk::TOP_PORTS[..n].to_vec()),
+        (ports, None) => ports,
+    };
+    if ports.is_some() && probe != ProbeType::Tcp {
+        let flag = if top.is_some() {
+            "--top-ports"
+        } else {
+            "--ports"
+        };
+        return Err(format!("{flag} needs --probe tcp"));
+    }
+    let scan: Option<Scan> = pargs
+        .opt_value_from_str("--scan")
+        .map_err(|e| format!("--scan: {e}"))?;
+    if scan.is_some() && probe != ProbeType::Tcp {
+        return Err("--scan needs --probe tcp".into());
+    }
+    let scan = scan.unwrap_or_default();
+    // "socks5://127.0.0.1:1080", as `ssh -D 1080 jump` offers
+    let proxy: Option<String> = pargs
+        .opt_value_from_str("--proxy")
+        .map_err(|e| format!("--proxy: {e}"))?;
+    let proxy = match proxy {
+        Some(spec) => {
+            let Some(addr) = spec.strip_prefix("socks5://") else {
+                return Err(format!("--proxy: {spec}: use socks5://host:port"));
+            };
+            let addr = addr.trim_end_matches('/');
+            Some(agent::address_on(addr, DEFAULT_SOCKS_PORT).map_err(|e| format!("--proxy: {e}"))?)
+        }
+        None => None,
+    };
+    if proxy.is_some() && probe != ProbeType::Tcp {
+        return Err("--proxy needs --probe tcp".into());
+    }
+    if proxy.is_some() && scan == Scan::Syn {
+        return Err("--proxy cannot be combined with --scan syn".into());
+    }
+    // A handshake that never completes is another failure than a proxy
+    // that takes the connection and then says nothing
+    let connect_timeout = pargs
+        .opt_value_from_str::<_, u64>("--connect-timeout")
+        .map_err(|e| format!("--connect-timeout: {e}"))?
+        .map(Duration::from_millis);
+    if connect_timeout.is_some() && probe != ProbeType::Tcp {
+        return Err("--connect-timeout needs --probe tcp".into());
+    }
+    let response_timeout = pargs
+        .opt_value_from_str::<_, u64>("--response-timeout")
+        .map_err(|e| format!("--response-timeout: {e}"))?
+        .map(Duration::from_millis);
+    // Plain connects read nothing once the handshake is done
+    if response_timeout.is_some() && proxy.is_none() {
+        return Err("--response-timeout needs --proxy".into());
+    }
+
+    // The system ping's DF flags differ everywhere, and so do its errors
+    let df = pargs.contains("--df");
+    if df && wasm.is_some() {
+        return Err("--df doesn't apply to WASM probes".into());
+    }
+    if df && probe == ProbeType::Tcp {
+        return Err("--df only applies to ICMP probes".into());
+    }
+    if df && cfg!(not(windows)) && engine == Some(Engine::Ping) && probe == ProbeType::Echo {
+        return Err("--df needs --engine native".into());
+    }
+
+    // Flags of the system ping that ipchk has no option for; they need it
+    // to be the one probing
+    let ping_args: Vec<String> = pargs
+        .opt_value_from_str::<_, String>("--ping-args")
+        .map_err(|e| format!("--ping-args: {e}"))?
+        .map(|s| s.split_whitespace().map(String::from).collect())
+        .unwrap_or_default();
+    if !ping_args.is_empty() {
+        if cfg!(windows) {
+            return Err("--ping-args: Windows probes with IcmpSendEcho, not a ping command".into());
+        }
+        if wasm.is_some() || probe != ProbeType::Echo {
+            return Err("--ping-args only applies to echo probes".into());
+        }
+        if engine == Some(Engine::Native) || df {
+            return Err("--ping-args needs --engine ping".into());
+        }
+    }
+
+    // The ping to run instead of the first on PATH. Naming one on the
+    // command line means using it; $IPCHK_PING only says which one it is.
+    let ping_flag: Option<PathBuf> = pargs
+        .opt_value_from_os_str("--ping-path", |s| Ok::<_, String>(PathBuf::from(s)))
+        .map_err(|e| format!("--ping-path: {e}"))?;
+    if ping_flag.is_some() {
+        if cfg!(windows) {
+            return Err("--ping-path: Windows probes with IcmpSendEcho, not a ping command".into());
+        }
+        if wasm.is_some() || probe != ProbeType::Echo {
+            return Err("--ping-path only applies to echo probes".into());
+        }
+        if engine == Some(Engine::Native) || df {
+            return Err("--ping-path needs --engine ping".into());
+        }
+    }
+    let named = ping_flag.is_some();
+    let (flag, ping_path) = match ping_flag {
+        Some(p) => ("--ping-path", Some(p)),
+        None if cfg!(windows) => ("IPCHK_PING", None),
+        None => ("IPCHK_PING", env::var_os("IPCHK_PING").map(PathBuf::from)),
+    };
+    if let Some(p) = ping_path.as_ref().filter(|p| !p.is_file()) {
+        return Err(format!("{flag}: {}: no such file", p.display()));
+    }
+    let engine = if ping_args.is_empty() && !named {
+        engine
+    } else {
+        Some(Engine::Ping)
+    };
+
+    let pattern = pargs
+        .opt_value_from_fn("--pattern", ipchk::parse_hex)
+        .map_err(|e| format!("--pattern: {e}"))?;
+    let ascii: Option<String> = pargs
+        .opt_value_from_str("--payload-ascii")
+        .map_err(|e| format!("--payload-ascii: {e}"))?;
+    let size: Option<usize> = pargs
+        .opt_value_from_str("--size")
+        .map_err(|e| format!("--size: {e}"))?;
+    // Timestamp requests have a fixed layout, TCP connects no payload
+    if probe != ProbeType::Echo || wasm.is_some() {
+        let set = [
+            ("--pattern", pattern.is_some()),
+            ("--payload-ascii", ascii.is_some()),
+            ("--size", size.is_some()),
+        ];
+        if let Some((flag, _)) = set.iter().find(|(_, on)| *on) {
+            return Err(format!("{flag} only applies to echo probes"));
+        }
+    }
+    if size.is_some_and(|s| s > Payload::MAX_SIZE) {
+        return Err(format!("--size: at most {} bytes", Payload::MAX_SIZE));
+    }
+    // (flag, pattern, default size): a text tag is sent as is unless --size
+    // asks for more
+    let payload = match (pattern, ascii) {
+        (Some(_), Some(_)) => {
+            return Err("--pattern cannot be combined with --payload-ascii".into());
+        }
+        (Some(p), None) => Some(("--pattern", p, Payload::DEFAULT_SIZE)),
+        (None, Some(s)) if s.is_empty() => return Err("--payload-ascii: empty payload".into()),
+        (None, Some(s)) => Some(("--payload-ascii", s.clone().into_bytes(), s.len())),
+        (None, None) => None,
+    };
+    // The system ping takes -p as at most 16 bytes, repeated
+    if let Some((flag, p, _)) = &payload
+        && cfg!(not(windows))
+        && engine == Some(Engine::Ping)
+        && p.len() > 16
+    {
+        return Err(format!(
+            "{flag}: the system ping takes at most 16 bytes (use --engine native)"
+        ));
+    }
+    // ... so without --engine, those need the native one
+    let long_pattern = payload.as_ref().is_some_and(|(_, p, _)| p.len() > 16);
+    let engine = engine.or((df || long_pattern).then_some(Engine::Native));
+    let payload = match (payload, size) {
+        (Some((_, pattern, default)), size) => Some(Payload {
+            pattern,
+            size: size.unwrap_or(default),
+        }),
+        (None, Some(size)) => Some(Payload {
+            size,
+            ..Payload::default()
+        }),
+        (None, None) => None,
+    };
+
+    let warn_rtt = pargs
+        .opt_value_from_str::<_, u64>("--warn-rtt")
+        .map_err(|e| format!("--warn-rtt: {e}"))?
+        .map(Duration::from_millis);
+    let crit_rtt = pargs
+        .opt_value_from_str::<_, u64>("--crit-rtt")
+        .map_err(|e| format!("--crit-rtt: {e}"))?
+        .map(Duration::from_millis);
+    if let (Some(w), Some(c)) = (warn_rtt, crit_rtt)
+        && w > c
+    {
+        return Err("--warn-rtt must not exceed --crit-rtt".into());
+    }
+
+    if require > count {
+        return Err(format!(
+            "--require: {require} replies can't come from {count} probes (see --count)"
+        ));
+    }
+
     let concurrency = pargs
         .opt_value_from_str::<_, usize>(["-c", "--concurrency"])
         .map_err(|e| format!("--concurrency: {e}"))?
         .unwrap_or(DEFAULT_CONCURRENCY)
         .max(1);
 
+    let cache_ttl = pargs
+        .opt_value_from_fn("--cache", parse_duration)
+        .map_err(|e| format!("--cache: {e}"))?;
+    // Verdicts are stamped in whole seconds
+    if cache_ttl.is_some_and(|ttl| ttl < Duration::from_secs(1)) {
+        return Err("--cache: use at least 1s".into());
+    }
+
+    let every = pargs
+        .opt_value_from_fn("--every", parse_duration)
+        .map_err(|e| format!("--every: {e}"))?
+        .unwrap_or(Duration::from_secs(DEFAULT_EVERY_SECS));
+
+    let socket = pargs
+        .opt_value_from_os_str("--socket", |s| {
+            Ok::<_, std::convert::Infallible>(PathBuf::from(s))
+        })
+        .map_err(|e| format!("--socket: {e}"))?;
+
+    let log = pargs
+        .opt_value_from_os_str("--log", |s| {
+            Ok::<_, std::convert::Infallible>(PathBuf::from(s))
+        })
+        .map_err(|e| format!("--log: {e}"))?;
+
+    let flap_threshold = pargs
+        .opt_value_from_str::<_, usize>("--flap-threshold")
+        .map_err(|e| format!("--flap-threshold: {e}"))?;
+    let flap_window = pargs
+        .opt_value_from_fn("--flap-window", parse_duration)
+        .map_err(|e| format!("--flap-window: {e}"))?;
+    let flapping = match flap_threshold {
+        Some(_) if !matches!(command, Command::Daemon | Command::Service(_)) => {
+            return Err("--flap-threshold only applies to ipchk daemon".into());
+        }
+        Some(0) => return Err("--flap-threshold: must be at least 1".into()),
+        Some(threshold) => Some(daemon::Flapping {
+            threshold,
+            window: flap_window.unwrap_or(Duration::from_secs(DEFAULT_FLAP_WINDOW_SECS)),
+        }),
+        None if flap_window.is_some() => {
+            return Err("--flap-window needs --flap-threshold".into());
+        }
+        None => None,
+    };
+
+    let email_to: Vec<String> = pargs
+        .values_from_str::<_, String>("--email-to")
+        .map_err(|e| format!("--email-to: {e}"))?
+        .iter()
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect();
+    let smtp_server: Option<String> = pargs
+        .opt_value_from_str("--smtp-server")
+        .map_err(|e| format!("--smtp-server: {e}"))?;
+    let email_from: Option<String> = pargs
+        .opt_value_from_str("--email-from")
+        .map_err(|e| format!("--email-from: {e}"))?;
+    let email = match (email_to.is_empty(), smtp_server) {
+        (true, _) => None,
+        (false, None) => return Err("--email-to requires --smtp-server".into()),
+        (false, Some(server)) => Some(notify::Email {
+            to: email_to,
+            server,
+            from: email_from.unwrap_or_else(|| format!("ipchk@{}", smtp::hostname())),
+        }),
+    };
+
+    let webhook_url: Option<String> = pargs
+        .opt_value_from_str("--webhook")
+        .map_err(|e| format!("--webhook: {e}"))?;
+    let notify_format = pargs
+        .opt_value_from_str::<_, notify::Format>("--notify-format")
+        .map_err(|e| format!("--notify-format: {e}"))?
+        .unwrap_or(notify::Format::Generic);
+    let webhook = webhook_url.map(|url| notify::Webhook {
+        url,
+        format: notify_format,
+    });
+
+    let netbox_url: Option<String> = pargs
+        .opt_value_from_str("--netbox-url")
+        .map_err(|e| format!("--netbox-url: {e}"))?;
+    let netbox_token: Option<String> = pargs
+        .opt_value_from_str("--netbox-token")
+        .map_err(|e| format!("--netbox-token: {e}"))?;
+    let netbox = match (
+        netbox_url,
+        netbox_token.or_else(|| env::var("NETBOX_TOKEN").ok()),
+    ) {
+        (Some(_), _) if command != Command::Sweep => {
+            return Err("--netbox-url only applies to a sweep".into());
+        }
+        (Some(url), Some(token)) => Some((url, token)),
+        (Some(_), None) => return Err("--netbox-url needs --netbox-token or $NETBOX_TOKEN".into()),
+        (None, _) => None,
+    };
+
+    let post_url: Option<String> = pargs
+        .opt_value_from_str("--post-url")
+        .map_err(|e| format!("--post-url: {e}"))?;
+    let post_token: Option<String> = pargs
+        .opt_value_from_str("--post-token")
+        .map_err(|e| format!("--post-token: {e}"))?;
+    let post_batch = pargs
+        .opt_value_from_str::<_, usize>("--post-batch")
+        .map_err(|e| format!("--post-batch: {e}"))?;
+    if post_batch == Some(0) {
+        return Err("--post-batch: needs at least one result per POST".into());
+    }
+    let post = match post_url {
+        Some(_) if command != Command::Sweep => {
+            return Err("--post-url only applies to a sweep".into());
+        }
+        Some(url) => Some(post::Collector {
+            url,
+            token: post_token.or_else(|| env::var("IPCHK_POST_TOKEN").ok()),
+            batch: post_batch,
+        }),
+        None if post_token.is_some() => return Err("--post-token needs --post-url".into()),
+        None if post_batch.is_some() => return Err("--post-batch needs --post-url".into()),
+        None => None,
+    };
+    let otel: Option<String> = pargs
+        .opt_value_from_str("--otel")
+        .map_err(|e| format!("--otel: {e}"))?;
+    if otel.is_some() && command != Command::Sweep {
+        return Err("--otel only applies to a sweep".into());
+    }
+    let otel = otel.map(otel::Exporter::new);
+    let statsd: Option<String> = pargs
+        .opt_value_from_str("--statsd")
+        .map_err(|e| format!("--statsd: {e}"))?;
+    let statsd_format = pargs
+        .opt_value_from_str::<_, statsd::Format>("--statsd-format")
+        .map_err(|e| format!("--statsd-format: {e}"))?;
+    let statsd = match statsd {
+        Some(_)
+            if !matches!(
+                command,
+                Command::Sweep | Command::Daemon | Command::Service(_)
+            ) =>
+        {
+            return Err("--statsd only applies to a sweep and ipchk daemon".into());
+        }
+        Some(spec) => Some(
+            statsd::Client::new(&spec, statsd_format.unwrap_or(statsd::Format::DogStatsd))
+                .map_err(|e| format!("--statsd: {e}"))?,
+        ),
+        None if statsd_format.is_some() => {
+            return Err("--statsd-format needs --statsd".into());
+        }
+        None => None,
+    };
+    let journal = if pargs.contains("--journald") {
+        if !matches!(command, Command::Sweep | Command::Daemon) {
+            return Err("--journald only applies to a sweep and ipchk daemon".into());
+        }
+        Some(journald::Journal::open().map_err(|e| format!("--journald: {e}"))?)
+    } else {
+        None
+    };
+    let eventlog = if pargs.contains("--eventlog") {
+        if !matches!(
+            command,
+            Command::Sweep | Command::Daemon | Command::Service(_)
+        ) {
+            return Err("--eventlog only applies to a sweep and ipchk daemon".into());
+        }
+        Some(eventlog::EventLog::open().map_err(|e| format!("--eventlog: {e}"))?)
+    } else {
+        None
+    };
+
+    let desktop = pargs.contains("--notify-desktop");
+    if desktop && !matches!(command, Command::Daemon | Command::Service(_)) {
+        return Err("--notify-desktop only applies to ipchk daemon".into());
+    }
+    let notify = notify::Notifiers {
+        desktop,
+        bell_up: pargs.contains("--audible"),
+        bell_down: pargs.contains("--audible-down"),
+        email,
+        webhook,
+    };
+
+    let output = pargs
+        .opt_value_from_str::<_, output::Format>("--output")
+        .map_err(|e| format!("--output: {e}"))?;
+    // --table is short for --output table
+    let output = match (output, pargs.contains("--table")) {
+        (Some(f), true) if f != output::Format::Table => {
+            return Err("--table cannot be combined with --output".into());
+        }
+        (_, true) => output::Format::Table,
+        (f, false) => f.unwrap_or(output::Format::Text),
+    };
+    if output == output::Format::Html && command != Command::Report {
+        return Err("--output html only applies to ipchk report".into());
+    }
+    let columns = pargs
+        .opt_value_from_fn("--fields", output::parse_columns)
+        .map_err(|e| format!("--fields: {e}"))?;
+    if columns.is_some() && !matches!(output, output::Format::Csv | output::Format::Table) {
+        return Err("--fields only applies to --output csv and --table".into());
+    }
+
+    let exit_codes = pargs
+        .opt_value_from_str::<_, ExitCodes>("--exit-codes")
+        .map_err(|e| format!("--exit-codes: {e}"))?
+        .unwrap_or(ExitCodes::Default);
+
+    let dns_timeout = pargs
+        .opt_value_from_fn("--dns-timeout", parse_duration)
+        .map_err(|e| format!("--dns-timeout: {e}"))?
+        .unwrap_or(dns::DEFAULT_TIMEOUT);
+    let dns_concurrency = pargs
+        .opt_value_from_str::<_, usize>("--dns-concurrency")
+        .map_err(|e| format!("--dns-concurrency: {e}"))?
+        .unwrap_or(DEFAULT_DNS_CONCURRENCY)
+        .max(1);
+    let with_timeout = |server| Resolver {
+        server,
+        timeout: dns_timeout,
+    };
+    let dns = pargs
+        .opt_value_from_fn("--dns-server", dns::parse_server)
+        .map_err(|e| format!("--dns-server: {e}"))?
+        .map(with_timeout);
+    let ptr_only = pargs.contains("--ptr-only");
+    // --rdns and --asn ask --dns-server, else the system's first nameserver
+    let lookups =
+        |flag: &str| match dns.or_else(|| Resolver::system().map(|r| with_timeout(r.server))) {
+            Some(r) => Ok(r),
+            None => Err(format!(
+                "{flag}: no system DNS server found; use --dns-server"
+            )),
+        };
+    let mut enrich = enrich::Enrich {
+        rdns: if pargs.contains("--rdns") || ptr_only {
+            Some(lookups(if ptr_only { "--ptr-only" } else { "--rdns" })?)
+        } else {
+            None
+        },
+        asn: if pargs.contains("--asn") {
+            Some(lookups("--asn")?)
+        } else {
+            None
+        },
+        geoip: pargs
+            .opt_value_from_os_str("--geoip", |s| {
+                Ok::<_, std::convert::Infallible>(PathBuf::from(s))
+            })
+            .map_err(|e| format!("--geoip: {e}"))?
+            .map(|path| geoip::Db::open(&path).map(Arc::new))
+            .transpose()
+            .map_err(|e| format!("--geoip: {e}"))?,
+        names: None,
+        labels: None,
+    };
+
+    let all_addresses = pargs.contains("--all-addresses");
+    let family = match (pargs.contains("-4"), pargs.contains("-6")) {
+        (true, true) => return Err("-4 cannot be combined with -6".into()),
+        (true, false) => Some(Family::V4),
+        (false, true) => Some(Family::V6),
+        (false, false) => None,
+    };
+    if let Some(f) = family
+        && command != Command::Sweep
+    {
+        let flag = if f == Family::V4 { "-4" } else { "-6" };
+        return Err(format!("{flag} only applies to a sweep"));
+    }
+    let aggregate = pargs.contains("--aggregate");
+    let stats = pargs.contains(["-s", "--stats"]);
+    let aliases = pargs.contains("--aliases");
+    let latency_factor = pargs
+        .opt_value_from_str::<_, f64>("--latency-factor")
+        .map_err(|e| format!("--latency-factor: {e}"))?;
+    if latency_factor.is_some_and(|f| f.is_nan() || f <= 1.0) {
+        return Err("--latency-factor: must be more than 1".into());
+    }
+    let baseline = pargs
+        .opt_value_from_os_str("--baseline", |s| {
+            Ok::<_, std::convert::Infallible>(PathBuf::from(s))
+        })
+        .map_err(|e| format!("--baseline: {e}"))?
+        .map(|path| baseline::Baseline::load(path, latency_factor))
+        .transpose()
+        .map_err(|e| format!("--baseline: {e}"))?;
+    if baseline.is_some() && command != Command::Sweep {
+        return Err("--baseline only applies to a sweep".into());
+    }
+    let baseline_update = pargs.contains("--baseline-update");
+    if baseline_update && baseline.is_none() {
+        return Err("--baseline-update needs --baseline".into());
+    }
+    if latency_factor.is_some() && baseline.is_none() {
+        return Err("--latency-factor needs --baseline".into());
+    }
+    let first: Vec<String> = pargs
+        .opt_value_from_str::<_, String>("--first")
+        .map_err(|e| format!("--first: {e}"))?
+        .map(|list| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    let order = pargs
+        .opt_value_from_str::<_, Order>("--order")
+        .map_err(|e| format!("--order: {e}"))?;
+    if order.is_some() && command != Command::Sweep {
+        return Err("--order only applies to a sweep".into());
+    }
+    let order = order.unwrap_or_default();
+    let progress = pargs
+        .opt_value_from_str::<_, progress::Format>("--progress")
+        .map_err(|e| format!("--progress: {e}"))?;
+    if progress.is_some() {
+        if command != Command::Sweep {
+            return Err("--progress only applies to a sweep".into());
+        }
+        if ptr_only {
+            return Err("--progress cannot be combined with --ptr-only".into());
+        }
+    }
+    let check_gateway = pargs
+        .opt_value_from_str::<_, GatewayCheck>("--check-gateway")
+        .map_err(|e| format!("--check-gateway: {e}"))?;
+    let captive_check = pargs.contains("--captive-check");
+    let verbose = pargs.contains(["-v", "--verbose"]);
+
+    let listen: Option<String> = pargs
+        .opt_value_from_str("--listen")
+        .map_err(|e| format!("--listen: {e}"))?;
+    // --stdio is how --via-ssh starts an agent
+    let stdio = pargs.contains("--stdio");
+    if command == Command::Agent && listen.is_some() == stdio {
+        return Err("Usage: ipchk agent --listen <addr[:port]> | --stdio".into());
+    }
+    if command == Command::Grpc && listen.is_none() {
+        return Err("Usage: ipchk grpc --listen <addr[:port]> [OPTIONS]".into());
+    }
+    if listen.is_some() && !matches!(command, Command::Agent | Command::Grpc) {
+        return Err("--listen only applies to ipchk agent and ipchk grpc".into());
+    }
+    if stdio && command != Command::Agent {
+        return Err("--stdio only applies to ipchk agent".into());
+    }
+    let mut via = Vec::new();
+    for flag in ["--via", "--via-ssh"] {
+        let list: Option<String> = pargs
+            .opt_value_from_str(flag)
+            .map_err(|e| format!("{flag}: {e}"))?;
+        let Some(list) = list else {
+            continue;
+        };
+        if command != Command::Sweep {
+            return Err(format!("{flag} only applies to a sweep"));
+        }
+        // The agents connect from where they are
+        if proxy.is_some() {
+            return Err(format!("--proxy cannot be combined with {flag}"));
+        }
+        // The module would have to travel with every target
+        if wasm.is_some() {
+            return Err(format!("{flag} can't run WASM probes"));
+        }
+        for host in list.split(',').map(str::trim).filter(|a| !a.is_empty()) {
+            via.push(if flag == "--via" {
+                agent::address(host).map_err(|e| format!("--via: {e}"))?;
+                agent::Endpoint::Agent(host.to_string())
+            } else if host.starts_with('-') {
+                return Err(format!("--via-ssh: {host}: not a host"));
+            } else {
+                agent::Endpoint::Ssh(host.to_string())
+            });
+        }
+    }
+
+    let zone_file = pargs
+        .opt_value_from_os_str("--zone-file", |s| {
+            Ok::<_, std::convert::Infallible>(PathBuf::from(s))
+        })
+        .map_err(|e| format!("--zone-file: {e}"))?;
+
+    let split = pargs
+        .opt_value_from_fn("--split", calc::parse_len)
+        .map_err(|e| format!("--split: {e}"))?;
+    let supernet = pargs
+        .opt_value_from_fn("--supernet", calc::parse_len)
+        .map_err(|e| format!("--supernet: {e}"))?;
+    let prefer = pargs
+        .opt_value_from_str::<_, merge::Prefer>("--prefer")
+        .map_err(|e| format!("--prefer: {e}"))?;
+    if prefer.is_some() && command != Command::Merge {
+        return Err("--prefer only applies to ipchk merge".into());
+    }
+    let period = pargs
+        .opt_value_from_fn("--period", parse_duration)
+        .map_err(|e| format!("--period: {e}"))?;
+    if period.is_some() && command != Command::Report {
+        return Err("--period only applies to ipchk report".into());
+    }
+
+    let input_format = pargs
+        .opt_value_from_str::<_, input::Format>("--input")
+        .map_err(|e| format!("--input: {e}"))?;
+
     let range_mode = pargs.contains(["-r", "--range"]);
+    if range_mode && zone_file.is_some() {
+        return Err("--zone-file cannot be combined with --range".into());
+    }
+    if range_mode && input_format.is_some() {
+        return Err("--input cannot be combined with --range".into());
+    }
+    let from_arp = pargs.contains("--from-arp-cache");
+    if range_mode && from_arp {
+        return Err("--from-arp-cache cannot be combined with --range".into());
+    }
+    let from_routes = pargs.contains("--from-routes");
+    if range_mode && from_routes {
+        return Err("--from-routes cannot be combined with --range".into());
+    }
+    let interfaces: Vec<String> = pargs
+        .values_from_str(["-I", "--interface"])
+        .map_err(|e| format!("--interface: {e}"))?;
+    if range_mode && !interfaces.is_empty() {
+        return Err("--interface cannot be combined with --range".into());
+    }
+    if from_routes && !interfaces.is_empty() {
+        return Err("--interface cannot be combined with --from-routes".into());
+    }
 
     let raw = pargs.contains(["-a", "--ascii"]) || pargs.contains("--raw");
+    let symbols = pargs
+        .opt_value_from_str("--symbols")
+        .map_err(|e| format!("--symbols: {e}"))?
+        .unwrap_or_default();
 
-    let free: Vec<std::ffi::OsString> = pargs.finish();
+    let free: Vec<OsString> = pargs.finish();
 
+    let mut range = None;
+    let mut ips = Vec::new();
+    let mut fields = input::Fields::default();
+    let mut stdin = false;
+    let mut labels: HashMap<String, String> = HashMap::new();
+    let mut url_ports: Vec<u16> = Vec::new();
+    let mut overrides: HashMap<String, input::Overrides> = HashMap::new();
+    let mut macs: Vec<(usize, String, Option<String>)> = Vec::new();
     if range_mode {
         if free.len() != 2 {
             return Err("Usage: ipchk -r <start_ipv4> <end_ipv4>".into());
@@ -275,141 +1972,1021 @@ fn parse_args() -> Result<Args, String> {
         let start_str = free[0].to_string_lossy();
         let end_str = free[1].to_string_lossy();
 
-        let start: Ipv4Addr = start_str
-            .parse::<Ipv4Addr>()
-            .map_err(|_| format!("range: start must be IPv4: {start_str}"))?;
-        let end: Ipv4Addr = end_str
-            .parse::<Ipv4Addr>()
-            .map_err(|_| format!("range: end must be IPv4: {end_str}"))?;
-
-        Ok(Args {
-            range: Some((start, end)),
-            timeout_ms,
-            count,
-            concurrency,
-            raw,
-            ips: Vec::new(),
-        })
-    } else {
-        let ips: Vec<String> = free
-            .into_iter()
-            .map(|s| s.to_string_lossy().into_owned())
+        let start = parse_ipv4(&start_str).map_err(|e| format!("range: start: {e}"))?;
+        let end = parse_ipv4(&end_str).map_err(|e| format!("range: end: {e}"))?;
+        range = Some((start, end));
+    } else if matches!(
+        command,
+        Command::Calc | Command::Internet | Command::MyIp | Command::Merge | Command::Report
+    ) {
+        if command == Command::Calc && free.is_empty() {
+            return Err(calc::USAGE.into());
+        }
+        if command == Command::Merge && free.is_empty() {
+            return Err(merge::USAGE.into());
+        }
+        if command == Command::Report && free.is_empty() {
+            return Err(report::USAGE.into());
+        }
+        if command == Command::Internet && free.len() > 1 {
+            return Err(internet::USAGE.into());
+        }
+        ips = free
+            .iter()
+            .map(|a| a.to_string_lossy().into_owned())
             .collect();
+    } else if let Some(format) = input_format {
+        // Positionals name the files to read, stdin when there are none
+        let files: Vec<PathBuf> = if free.is_empty() {
+            vec![PathBuf::from("-")]
+        } else {
+            free.iter().map(PathBuf::from).collect()
+        };
+        stdin = files.iter().any(|f| f == Path::new("-"));
+        for path in files {
+            for mut target in input::read(format, &path).map_err(|e| format!("--input: {e}"))? {
+                if let Some((host, port)) = ipchk::url_target(&target.ip) {
+                    target.ip = host;
+                    url_ports.extend(port);
+                }
+                let given = target.take_label();
+                if fields.add(&target) {
+                    labels.extend(given.map(|l| (target.ip.clone(), l)));
+                    // As with -p, ports only mean something to a TCP probe
+                    let own = &target.options;
+                    if own.ports.is_some() && own.probe.unwrap_or(probe) != ProbeType::Tcp {
+                        return Err(format!("--input: {}: ports= needs probe=tcp", target.ip));
+                    }
+                    if !target.options.is_empty() {
+                        overrides.insert(target.ip.clone(), target.options);
+                    }
+                    ips.push(target.ip);
+                } else {
+                    eprintln!(
+                        "--input: {} listed more than once, first row kept",
+                        target.ip
+                    );
+                }
+            }
+        }
+        // They are the built-in probes' options, set per sweep
+        if !overrides.is_empty() {
+            if command != Command::Sweep {
+                return Err("--input list: per-target settings only apply to a sweep".into());
+            }
+            if wasm.is_some() || !via.is_empty() {
+                return Err(
+                    "--input list: per-target settings don't apply to --probe wasm or --via".into(),
+                );
+            }
+        }
+    } else {
+        // Expanded here so quoting "web{01..24}.lan" doesn't depend on the shell;
+        // "10.0.0.0/24" stands for its host addresses. "<target>=<label>"
+        // labels every address the target expands to.
+        for arg in free {
+            let arg = arg.to_string_lossy();
+            // A URL pasted from a ticket stands for its host; its '=' are
+            // the query's, not a label
+            if let Some((host, port)) = ipchk::url_target(&arg) {
+                ips.push(host);
+                url_ports.extend(port);
+                continue;
+            }
+            let (arg, given) = match arg.split_once('=') {
+                Some((_, "")) => return Err(format!("{arg}: empty label")),
+                Some((target, l)) => (target, Some(l)),
+                None => (&*arg, None),
+            };
+            // A MAC stands for the address that holds it now, found below
+            if let Some(mac) = input::normalise_mac(arg).filter(|_| arg.parse::<IpAddr>().is_err())
+            {
+                macs.push((ips.len(), mac, given.map(str::to_string)));
+                continue;
+            }
+            let first = ips.len();
+            for target in ipchk::expand_braces(arg).map_err(|e| e.to_string())? {
+                match ipchk::parse_cidr(&target) {
+                    Ok(block) if target.contains('/') => {
+                        if block.host_count() > ipchk::MAX_EXPANSION as u64 {
+                            return Err(format!(
+                                "{target}: more than {} addresses; use a longer prefix",
+                                ipchk::MAX_EXPANSION
+                            ));
+                        }
+                        ips.extend(block.hosts().map(|ip| ip.to_string()));
+                    }
+                    _ => ips.push(target),
+                }
+            }
+            if let Some(l) = given {
+                labels.extend(ips[first..].iter().map(|ip| (ip.clone(), l.to_string())));
+            }
+        }
+        if !macs.is_empty() {
+            let wanted: Vec<String> = macs.iter().map(|(_, mac, _)| mac.clone()).collect();
+            let found = input::locate_macs(&wanted).map_err(|e| format!("{}: {e}", wanted[0]))?;
+            // Last first, so the places of the earlier ones stay put; each is
+            // labelled with its MAC unless it was given a label
+            for (at, mac, given) in macs.into_iter().rev() {
+                let Some((ip, dev)) = found.get(&mac) else {
+                    eprintln!("ipchk: {mac}: no host on the local subnets has that MAC");
+                    continue;
+                };
+                let target = input::Target {
+                    ip: ip.to_string(),
+                    fields: vec![
+                        ("mac".into(), mac.clone()),
+                        ("interface".into(), dev.clone()),
+                    ],
+                    options: input::Overrides::default(),
+                };
+                fields.add(&target);
+                labels.insert(target.ip.clone(), given.unwrap_or(mac));
+                ips.insert(at, target.ip);
+            }
+        }
+    }
+
+    if from_arp {
+        let neighbours = input::arp_cache().map_err(|e| format!("--from-arp-cache: {e}"))?;
+        if neighbours.is_empty() && ips.is_empty() {
+            return Err("--from-arp-cache: the ARP cache is empty".into());
+        }
+        for target in neighbours {
+            // The same address can be cached on several interfaces
+            if !ips.contains(&target.ip) && fields.add(&target) {
+                ips.push(target.ip);
+            }
+        }
+    }
+
+    if from_routes {
+        let hosts = input::routes().map_err(|e| format!("--from-routes: {e}"))?;
+        if hosts.is_empty() && ips.is_empty() {
+            return Err("--from-routes: no directly connected subnets to sweep".into());
+        }
+        for target in hosts {
+            if !ips.contains(&target.ip) && fields.add(&target) {
+                ips.push(target.ip);
+            }
+        }
+    }
+
+    // Labelled with the interface in text output, unless they have a label
+    if !interfaces.is_empty() {
+        let hosts = input::interfaces(&interfaces).map_err(|e| format!("--interface: {e}"))?;
+        for target in hosts {
+            if !ips.contains(&target.ip) && fields.add(&target) {
+                if let Some((_, dev)) = target.fields.iter().find(|(k, _)| k == "interface") {
+                    labels
+                        .entry(target.ip.clone())
+                        .or_insert_with(|| dev.clone());
+                }
+                ips.push(target.ip);
+            }
+        }
+    }
+
+    if !range_mode {
+        // Each address once, shown with every name that points at it
+        if let Some(path) = &zone_file {
+            let records = input::zone_file(path).map_err(|e| format!("--zone-file: {e}"))?;
+            let mut names: HashMap<Ipv4Addr, String> = HashMap::new();
+            let mut seen: HashSet<IpAddr> = HashSet::new();
+            for (name, addr) in records {
+                if seen.insert(addr) {
+                    ips.push(addr.to_string());
+                }
+                if let IpAddr::V4(v4) = addr {
+                    let entry = names.entry(v4).or_default();
+                    if !entry.split(", ").any(|n| n == name) {
+                        if !entry.is_empty() {
+                            entry.push_str(", ");
+                        }
+                        entry.push_str(&name);
+                    }
+                }
+            }
+            enrich.names = Some(Arc::new(names));
+        }
+        if !labels.is_empty() {
+            enrich.labels = Some(Arc::new(labels));
+        }
 
-        if ips.is_empty() {
+        // `ipchk status` with no targets asks for everything the daemon tracks
+        let needs_targets = !matches!(
+            command,
+            Command::Status
+                | Command::Internet
+                | Command::MyIp
+                | Command::Agent
+                | Command::Grpc
+                | Command::Service(service::Action::Uninstall)
+        );
+        if ips.is_empty() && needs_targets {
             return Err(usage(&program));
         }
+    }
 
-        Ok(Args {
-            range: None,
-            timeout_ms,
-            count,
-            concurrency,
-            ips,
-            raw,
-        })
+    // Anything --fields doesn't know itself has to be an --input column
+    let unknown = columns.iter().flatten().find_map(|c| match c {
+        output::Column::Input(name) if !fields.columns.contains(name) => Some(name),
+        _ => None,
+    });
+    if let Some(name) = unknown {
+        return Err(format!(
+            "--fields: unknown column {name:?} (use ip, address, name, label, status, rtt, \
+             loss, time, mac or a column of the --input file)"
+        ));
     }
+
+    // --probe tcp without a port list tries the ports the URL targets name
+    url_ports.sort_unstable();
+    url_ports.dedup();
+    let ports = match ports {
+        Some(ports) => ports,
+        None if !url_ports.is_empty() => url_ports,
+        None => vec![DEFAULT_TCP_PORT],
+    };
+
+    Ok(Args {
+        command,
+        range,
+        timeout_ms,
+        count,
+        require,
+        interval,
+        engine,
+        ping_args,
+        ping_path,
+        probe,
+        wasm,
+        df,
+        ports,
+        scan,
+        proxy,
+        connect_timeout,
+        response_timeout,
+        payload,
+        warn_rtt,
+        crit_rtt,
+        concurrency,
+        ips,
+        raw,
+        symbols,
+        cache_ttl,
+        every,
+        socket,
+        log,
+        flapping,
+        notify,
+        exit_codes,
+        output,
+        columns,
+        dns,
+        enrich,
+        dns_timeout,
+        dns_concurrency,
+        all_addresses,
+        family,
+        ptr_only,
+        fields,
+        overrides,
+        stdin,
+        split,
+        supernet,
+        prefer: prefer.unwrap_or_default(),
+        period,
+        aggregate,
+        stats,
+        aliases,
+        baseline,
+        baseline_update,
+        first,
+        order,
+        progress,
+        check_gateway,
+        captive_check,
+        verbose,
+        listen,
+        via,
+        netbox,
+        post,
+        otel,
+        statsd,
+        journal,
+        eventlog,
+    })
 }
 
 /* -------------------- range iterator + main -------------------- */
 
-struct IpRange {
-    cur: u32,
-    end: u32,
-} // inclusive
-impl IpRange {
-    fn new(a: Ipv4Addr, b: Ipv4Addr) -> Self {
-        let mut lo = u32::from_be_bytes(a.octets());
-        let mut hi = u32::from_be_bytes(b.octets());
-        if lo > hi {
-            std::mem::swap(&mut lo, &mut hi);
-        }
-        IpRange { cur: lo, end: hi }
+// The probe options the parsed arguments ask for
+fn probe_options(args: &Args) -> ProbeOptions {
+    ProbeOptions {
+        timeout: Duration::from_millis(args.timeout_ms),
+        count: args.count,
+        interval: args.interval,
+        engine: args.engine.unwrap_or_else(|| pick_engine(args)),
+        probe: args.probe,
+        payload: args.payload.clone(),
+        df: args.df,
+        ports: args.ports.clone(),
+        scan: args.scan,
+        proxy: args.proxy,
+        connect_timeout: args.connect_timeout,
+        response_timeout: args.response_timeout,
+        ping_args: args.ping_args.clone(),
+        ping_path: args.ping_path.clone(),
     }
 }
-impl Iterator for IpRange {
-    type Item = Ipv4Addr;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.cur > self.end {
-            return None;
+
+// Each host in flight holds a socket, or with the system ping a child's
+// pipes; past the open-file limit they would fail mid-sweep as down
+fn fit_concurrency(args: &mut Args, opts: &ProbeOptions) {
+    let probes = matches!(
+        args.command,
+        Command::Sweep | Command::Daemon | Command::Service(_) | Command::Bench | Command::Grpc
+    );
+    if probes && args.via.is_empty() {
+        // IPv6 literals, and names that may resolve to IPv6, take the ping
+        let v6 = args.ips.iter().any(|t| {
+            t.parse::<Ipv6Addr>().is_ok() || (needs_lookup(t) && args.family != Some(Family::V4))
+        });
+        let held = |o: &ProbeOptions| ipchk::descriptors_per_host(o, v6);
+        // A target's own --input list settings may need more than the flags
+        let per_host = match args.wasm {
+            Some(_) => 1,
+            None => args
+                .overrides
+                .values()
+                .map(|o| held(&o.apply(opts)))
+                .fold(held(opts), usize::max),
+        };
+        let (fits, limit) = ipchk::in_flight_limit(args.concurrency, per_host);
+        if let Some(limit) = limit {
+            eprintln!(
+                "ipchk: --concurrency {} needs more open files than the limit of {limit} allows; using {fits}",
+                args.concurrency
+            );
+            args.concurrency = fits;
         }
-        let out = Ipv4Addr::from(self.cur.to_be_bytes());
-        self.cur = self.cur.wrapping_add(1);
-        Some(out)
     }
 }
 
+// What `ipchk daemon` (and status, service) run with. `argv` is kept for
+// SIGHUP, unless the targets came from stdin.
+fn daemon_config(args: Args, probe: ProbeOptions, argv: Vec<OsString>) -> daemon::Config {
+    let targets: Vec<String> = match args.range {
+        Some((start, end)) => IpRange::new(start, end).map(|ip| ip.to_string()).collect(),
+        None => args.ips,
+    };
+    daemon::Config {
+        targets,
+        every: args.every,
+        probe,
+        require: args.require,
+        concurrency: args.concurrency,
+        style: Style {
+            raw: args.raw,
+            symbols: args.symbols,
+        },
+        socket: args.socket.unwrap_or_else(daemon::default_socket),
+        log: args.log,
+        notify: args.notify,
+        argv: (!args.stdin).then_some(argv),
+        statsd: args.statsd,
+        journal: args.journal,
+        eventlog: args.eventlog,
+        flapping: args.flapping,
+    }
+}
+
+// A running daemon's command line parsed again, config file profile and
+// --input files included
+fn reconfigure(argv: &[OsString]) -> Result<daemon::Config, String> {
+    let mut args = parse_argv(argv.to_vec())?;
+    let opts = probe_options(&args);
+    fit_concurrency(&mut args, &opts);
+    Ok(daemon_config(args, opts, argv.to_vec()))
+}
+
 fn main() {
-    let args = match parse_args() {
+    let mut args = match parse_args() {
         Ok(a) => a,
         Err(msg) => {
             eprintln!("{msg}");
-            std::process::exit(if msg.starts_with("Usage:") { 0 } else { 2 });
+            // Only asking for the usage text is not a failure; getting it
+            // for want of targets is
+            let program = env::args().next().unwrap_or_else(|| "ipchk".to_string());
+            std::process::exit(if msg == usage(&program) && asked_for_help() {
+                0
+            } else {
+                usage_exit_code()
+            });
         }
     };
 
-    let timeout = Duration::from_millis(args.timeout_ms);
-    let count = args.count;
+    let opts = probe_options(&args);
     let raw = args.raw;
+    let style = Style {
+        raw,
+        symbols: args.symbols,
+    };
+
+    fit_concurrency(&mut args, &opts);
+
+    if args.command == Command::Calc {
+        let req = calc::Request {
+            args: args.ips,
+            range: args.range,
+            split: args.split,
+            supernet: args.supernet,
+        };
+        if let Err(e) = calc::run(&req) {
+            eprintln!("{e}");
+            std::process::exit(usage_exit_code());
+        }
+        return;
+    }
+
+    if args.command == Command::Merge {
+        let req = merge::Request {
+            files: args.ips,
+            prefer: args.prefer,
+            output: args.output,
+        };
+        if let Err(e) = merge::run(&req) {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+        return;
+    }
+
+    if args.command == Command::Report {
+        let req = report::Request {
+            files: args.ips,
+            period: args.period.unwrap_or(report::DEFAULT_PERIOD),
+            output: args.output,
+        };
+        if let Err(e) = report::run(&req) {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+        return;
+    }
+
+    if args.command == Command::Agent {
+        if !args.ips.is_empty() {
+            eprintln!("Usage: ipchk agent --listen <addr[:port]> | --stdio");
+            std::process::exit(usage_exit_code());
+        }
+        let served = match &args.listen {
+            Some(addr) => agent::listen(addr),
+            None => agent::stdio(),
+        };
+        if let Err(e) = served {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+        return;
+    }
+
+    if args.command == Command::Grpc {
+        if !args.ips.is_empty() {
+            eprintln!("Usage: ipchk grpc --listen <addr[:port]> [OPTIONS]");
+            std::process::exit(usage_exit_code());
+        }
+        let listen = args.listen.as_deref().unwrap_or_default();
+        if let Err(e) = grpc_server(listen, opts, args.require, args.concurrency) {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+        return;
+    }
+
+    if args.command == Command::Bench {
+        let targets: Result<Vec<Ipv4Addr>, String> = match args.range {
+            Some((start, end)) => Ok(IpRange::new(start, end).collect()),
+            None => args
+                .ips
+                .iter()
+                .map(|t| parse_ipv4(t).map_err(|e| format!("bench: {e}")))
+                .collect(),
+        };
+        let req = match targets {
+            Ok(targets) => bench::Request {
+                targets,
+                probe: opts,
+                concurrency: args.concurrency,
+                output: args.output,
+                raw,
+            },
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(usage_exit_code());
+            }
+        };
+        if let Err(e) = bench::run(&req) {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+        return;
+    }
+
+    if args.command == Command::MyIp {
+        let servers = if args.ips.is_empty() {
+            myip::DEFAULT_STUN.map(String::from).to_vec()
+        } else {
+            args.ips
+        };
+        let req = myip::Request {
+            servers,
+            timeout: opts.timeout,
+            dns: args.dns,
+            dns_timeout: args.dns_timeout,
+            output: args.output,
+            raw,
+        };
+        match myip::run(&req) {
+            Ok(found) => std::process::exit(if found { 0 } else { 1 }),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    if args.command == Command::Internet {
+        let echo = internet::echo(&opts);
+        if let Err(e) = ipchk::preflight(&echo) {
+            eprintln!("ipchk: {}", engine_error(&e, &echo));
+            std::process::exit(2);
+        }
+        let req = internet::Request {
+            probe: opts,
+            name: args
+                .ips
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| internet::DEFAULT_NAME.into()),
+            dns: args.dns,
+            dns_timeout: args.dns_timeout,
+            output: args.output,
+            raw,
+        };
+        match internet::run(&req) {
+            Ok(all_ok) => std::process::exit(if all_ok { 0 } else { 1 }),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    if args.command != Command::Sweep {
+        let command = args.command;
+        let cfg = daemon_config(args, opts.clone(), env::args_os().collect());
+        let result = match command {
+            Command::Daemon => daemon::run(cfg, &AtomicBool::new(false)).map(|()| true),
+            Command::Service(action) => service::main(action, cfg).map(|()| true),
+            _ => daemon::query(&cfg.socket, &cfg.targets, style),
+        };
+        match result {
+            Ok(all_up) => std::process::exit(if all_up { 0 } else { 1 }),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // parse_args always sets a resolver for --ptr-only
+    if let (true, Some(resolver)) = (args.ptr_only, args.enrich.rdns) {
+        let targets: Box<dyn Iterator<Item = String>> = match args.range {
+            Some((start, end)) => Box::new(IpRange::new(start, end).map(|ip| ip.to_string())),
+            None => Box::new(args.ips.into_iter()),
+        };
+        ptr_sweep(targets, resolver, args.dns_concurrency, raw);
+        return;
+    }
 
     let (tx, rx) = mpsc::channel::<PingResult>();
+    let checker: Arc<dyn ipchk::Probe> = match &args.wasm {
+        Some(path) => match wasm_probe(path, &opts) {
+            Ok(probe) => probe,
+            Err(e) => {
+                eprintln!("--probe: {e}");
+                std::process::exit(usage_exit_code());
+            }
+        },
+        None if !args.via.is_empty() => Arc::new(agent::Remote::new(&args.via, &opts, args.engine)),
+        None => Arc::new(Builtin::new(opts.clone())),
+    };
 
+    // The sweep's size, for --progress: --first targets count once
+    let reporter = args.progress.map(|_| {
+        let given = match args.range {
+            Some((start, end)) => u32::from(start).abs_diff(u32::from(end)) as usize + 1,
+            None => args.ips.len(),
+        };
+        let covered = |t: &String| match args.range {
+            Some((start, end)) => t.parse::<Ipv4Addr>().is_ok_and(|ip| {
+                let (lo, hi) = (start.min(end), start.max(end));
+                lo <= ip && ip <= hi
+            }),
+            None => args.ips.contains(t),
+        };
+        let extra = args.first.iter().filter(|t| !covered(t)).count();
+        progress::start(given + extra)
+    });
+    let progress = reporter.as_ref().map(|r| Arc::clone(&r.progress));
+    // WASM modules and agents only take IPv4 targets
+    let builtin = args.wasm.is_none() && args.via.is_empty();
+    // IPv6 echoes go through the system ping whatever the engine, so the
+    // first IPv6 target (a literal, or a name resolved to one) checks it
+    let ping_checked = std::sync::Once::new();
+    let system_error = args.exit_codes.system_error();
+    let preflight_v6 = || {
+        ping_checked.call_once(|| {
+            if let Err(e) = ipchk::preflight_v6(&opts) {
+                eprintln!("ipchk: {}", v6_engine_error(&e, &opts));
+                std::process::exit(system_error);
+            }
+        })
+    };
     // Helper to spawn a bounded batch to avoid thousands of threads
-    let spawn_batch = |batch: Vec<String>, tx: &mpsc::Sender<PingResult>| {
+    let spawn_batch = |batch: Vec<(usize, String)>, tx: &mpsc::Sender<PingResult>| {
         let mut handles = Vec::with_capacity(batch.len());
-        for ip in batch {
-            let txc = tx.clone();
-            let tmo = timeout;
-            let cnt = count;
-            let asc = raw;
-            handles.push(thread::spawn(move || ping_one(ip, txc, tmo, cnt, asc)));
+        let mut launch = |index: usize, ip: String, res: Option<Resolved>| {
+            if builtin && matches!(&res, Some(Ok(addrs)) if addrs.iter().any(IpAddr::is_ipv6)) {
+                preflight_v6();
+            }
+            // --all-addresses: one probe per address of a multi-homed name
+            let jobs: Vec<(Option<Resolved>, bool)> = match res {
+                Some(Ok(addrs)) if args.all_addresses && addrs.len() > 1 => addrs
+                    .into_iter()
+                    .map(|a| (Some(Ok(vec![a])), true))
+                    .collect(),
+                res => vec![(res, false)],
+            };
+            if let Some(p) = &progress {
+                p.expect(jobs.len() - 1);
+            }
+            // A target with settings of its own gets a probe of its own
+            let own = args.overrides.get(&ip).map(|o| o.apply(&opts));
+            let probe: Arc<dyn ipchk::Probe> = match &own {
+                Some(opts) => Arc::new(Builtin::new(opts.clone())),
+                None => Arc::clone(&checker),
+            };
+            let opts = own.as_ref().unwrap_or(&opts);
+            for (res, grouped) in jobs {
+                let txc = tx.clone();
+                let ip = ip.clone();
+                let probing = Probing {
+                    probe: Arc::clone(&probe),
+                    budget: Builtin::budget(opts),
+                    require: args.require,
+                    warn_rtt: args.warn_rtt,
+                    crit_rtt: args.crit_rtt,
+                    slow_rtt: args.baseline.as_ref().and_then(|b| b.slow_rtt(&ip)),
+                    v6: builtin.then(|| opts.clone()),
+                };
+                let enrich = args.enrich.clone();
+                let progress = progress.clone();
+                handles.push(thread::spawn(move || {
+                    let r = ping_one(ip, index, res, grouped, probing, style, enrich);
+                    if let Some(p) = &progress {
+                        p.record(&r);
+                    }
+                    let _ = txc.send(r);
+                }));
+            }
+        };
+        // Addresses go out at once; names follow one by one as they resolve
+        let (names, literals): (Vec<_>, Vec<_>) =
+            batch.into_iter().partition(|(_, ip)| needs_lookup(ip));
+        for (index, ip) in literals {
+            launch(index, ip, None);
+        }
+        let answers = resolve_stream(
+            names,
+            args.dns,
+            args.dns_timeout,
+            args.dns_concurrency,
+            args.family,
+        );
+        for (index, ip, res) in answers {
+            launch(index, ip, Some(res));
         }
         for h in handles {
             let _ = h.join();
         }
     };
 
-    if let Some((start, end)) = args.range {
-        let mut iter = IpRange::new(start, end);
-        loop {
-            let mut batch = Vec::with_capacity(args.concurrency);
-            for _ in 0..args.concurrency {
-                if let Some(ip) = iter.next() {
-                    batch.push(ip.to_string());
-                } else {
-                    break;
-                }
-            }
-            if batch.is_empty() {
+    // A sweep's alerts name what changed since the verdicts kept here, so
+    // they are kept for them without --cache too (reusing none)
+    let cache = args
+        .cache_ttl
+        .or_else(|| args.notify.alerts().then_some(Duration::ZERO))
+        .map(cache::Cache::load);
+    // A cached verdict stands for the probe setup that reached it, a
+    // target's own --input list settings included
+    let cache_key = |ip: &str| {
+        let own = args.overrides.get(ip).map(|o| o.apply(&opts));
+        let setup = format!(
+            "{:?} {} {:?} {:?}",
+            own.as_ref().unwrap_or(&opts),
+            args.require,
+            args.wasm,
+            args.via
+        );
+        cache::key(ip, &setup)
+    };
+    let (started, started_at) = (SystemTime::now(), Instant::now());
+
+    let mut engine_ok = false;
+    let mut targets: Box<dyn Iterator<Item = String>> = match args.range {
+        Some((start, end)) => Box::new(IpRange::new(start, end).map(|ip| ip.to_string())),
+        None => Box::new(args.ips.into_iter()),
+    };
+    // --first targets lead the queue, in batches of their own, and are not
+    // probed again if the sweep covers them too
+    let first = args.first.clone();
+    if !first.is_empty() {
+        let listed = first.clone();
+        targets = Box::new(
+            first
+                .clone()
+                .into_iter()
+                .chain(targets.filter(move |t| !listed.contains(t))),
+        );
+    }
+    let rank = |r: &PingResult| first.iter().position(|f| *f == r.ip).unwrap_or(usize::MAX);
+    // --first targets lead whatever the order; the early ones stay ahead of
+    // the rest with --order completion too
+    let arrange = |results: &mut Vec<PingResult>| match args.order {
+        Order::Address => results.sort_by_key(|r| (rank(r), r.sort_key)),
+        Order::Input => results.sort_by_key(|r| (rank(r), r.index)),
+        Order::Completion => {}
+    };
+    let view = output::View {
+        fields: &args.fields,
+        columns: args.columns.as_deref(),
+        verbose: args.verbose,
+        raw,
+        symbols: args.symbols,
+    };
+    // Printing them early needs a format that can be written piecemeal, and
+    // nothing that has to come before them or wait for the whole sweep
+    let stream_first = !first.is_empty()
+        && matches!(
+            args.output,
+            output::Format::Text | output::Format::Fping | output::Format::Jsonl
+        )
+        && !args.aggregate
+        && !args.aliases
+        && args.exit_codes != ExitCodes::Nagios;
+    let (mut queued, mut flushed) = (0, first.is_empty());
+    let mut early = Vec::new();
+    loop {
+        let mut batch = Vec::with_capacity(args.concurrency);
+        let mut exhausted = false;
+        while batch.len() < args.concurrency && (flushed || queued < first.len()) {
+            let Some(ip) = targets.next() else {
+                exhausted = true;
                 break;
+            };
+            let index = queued;
+            queued += 1;
+            // Answer straight from the cache when the verdict is still fresh;
+            // it holds one verdict per target, so not for --all-addresses names
+            let per_address = args.all_addresses && ip.parse::<IpAddr>().is_err();
+            if let Some((up, at)) = cache
+                .as_ref()
+                .filter(|_| !per_address)
+                .and_then(|c| c.lookup(&cache_key(&ip)))
+            {
+                let given = args.enrich.label(&ip);
+                let shown = label(&ip, None, None, given.as_deref());
+                let r = PingResult {
+                    sort_key: SortKey::of(&ip, None, index),
+                    index,
+                    msg: format_verdict(&shown, up, style, true),
+                    ip,
+                    name: None,
+                    addr: None,
+                    grouped: false,
+                    up: Some(up),
+                    cached: true,
+                    error: None,
+                    outcome: None,
+                    latency: Latency::Ok,
+                    at,
+                    mac: None,
+                    alias_of: None,
+                    label: given,
+                    baseline: None,
+                };
+                if let Some(p) = &progress {
+                    p.record(&r);
+                }
+                let _ = tx.send(r);
+                continue;
             }
-            spawn_batch(batch, &tx);
+            batch.push((index, ip));
         }
-    } else {
-        let mut it = args.ips.into_iter();
-        loop {
-            let mut batch = Vec::with_capacity(args.concurrency);
-            for _ in 0..args.concurrency {
-                if let Some(ip) = it.next() {
-                    batch.push(ip);
+        if builtin && batch.iter().any(|(_, ip)| ip.parse::<Ipv6Addr>().is_ok()) {
+            preflight_v6();
+        }
+        // Checked lazily so fully cached sweeps never touch the engine
+        if !batch.is_empty() && !engine_ok {
+            if let Err(e) = checker.preflight() {
+                // Agents explain their own engine problems
+                if args.via.is_empty() {
+                    eprintln!("ipchk: {}", engine_error(&e, &opts));
                 } else {
-                    break;
+                    eprintln!("ipchk: {e}");
                 }
+                std::process::exit(args.exit_codes.system_error());
             }
-            if batch.is_empty() {
-                break;
+            engine_ok = true;
+            // Before any verdict, so a dead uplink isn't read as a dead network
+            if let Some(problem) = args
+                .check_gateway
+                .and_then(|_| internet::gateway(&opts).err())
+            {
+                let abort = args.check_gateway == Some(GatewayCheck::Abort);
+                let advice = if abort {
+                    "not sweeping"
+                } else {
+                    "routed hosts will look down"
+                };
+                warn_loudly(&format!("{problem}; {advice}"), raw);
+                if abort {
+                    std::process::exit(args.exit_codes.system_error());
+                }
+            }
+            // A portal that answers every connection would pass for open ports
+            if args.captive_check {
+                match internet::captive(args.dns.as_ref(), args.dns_timeout) {
+                    internet::Captive::Online => {}
+                    internet::Captive::Portal(what) => warn_loudly(
+                        &format!("behind a captive portal ({what}); results may be faked"),
+                        raw,
+                    ),
+                    internet::Captive::Failed(e) => eprintln!("ipchk: --captive-check: {e}"),
+                }
             }
+        }
+        if !batch.is_empty() {
             spawn_batch(batch, &tx);
         }
+        if !flushed && queued == first.len() {
+            flushed = true;
+            early = rx.try_iter().collect();
+            arrange(&mut early);
+            if stream_first {
+                output::print(&early, args.output, &view, started, started_at.elapsed());
+            }
+        }
+        if exhausted {
+            break;
+        }
     }
     drop(tx);
 
-    let mut results = Vec::new();
+    let printed = if stream_first { early.len() } else { 0 };
+    let mut results = early;
     for r in rx {
         results.push(r);
     }
-    results.sort_by(|a, b| a.sort_key.cmp(&b.sort_key));
-    for r in results {
-        println!("{}", r.msg);
+    if let Some(reporter) = reporter {
+        reporter.finish();
+    }
+
+    if let Some(cache) = &cache {
+        let fresh: Vec<(String, bool)> = results
+            .iter()
+            .filter(|r| !r.cached && !r.grouped)
+            .filter_map(|r| r.up.map(|up| (cache_key(&r.ip), up)))
+            .collect();
+        if let Err(e) = cache.store(&fresh) {
+            eprintln!("ipchk: failed to write the result cache: {e}");
+        }
+    }
+
+    arrange(&mut results);
+    if args.aliases {
+        alias::mark(&mut results, &args.fields, raw);
+    }
+    if let Some(baseline) = &args.baseline {
+        baseline.mark(&mut results, raw);
+    }
+    // Nagios takes the first line of output as the check's status
+    let nagios = (args.exit_codes == ExitCodes::Nagios).then(|| nagios_status(&results));
+    if let Some((_, line)) = &nagios {
+        println!("{line}");
+    }
+    if args.aggregate {
+        // Cached verdicts carry no probed address, but their target is one
+        let up: Vec<Ipv4Addr> = results
+            .iter()
+            .filter(|r| r.up == Some(true))
+            .filter_map(|r| r.addr.or_else(|| r.ip.parse().ok()))
+            .collect();
+        for block in ipchk::aggregate(&up) {
+            println!("{block}");
+        }
+    } else {
+        output::print(
+            &results[printed..],
+            args.output,
+            &view,
+            started,
+            started_at.elapsed(),
+        );
+    }
+    if args.stats {
+        stats::print(&results, started_at.elapsed(), args.raw);
+    }
+    if let Some(baseline) = &args.baseline {
+        baseline.report(&results);
+        if args.baseline_update
+            && let Err(e) = baseline.update(&results)
+        {
+            eprintln!("--baseline-update: {e}");
+        }
+    }
+    let by_state = |up| {
+        results
+            .iter()
+            .filter(|r| r.up == Some(up))
+            .map(|r| r.ip.as_str())
+            .collect::<Vec<_>>()
+    };
+    let before = |ip: &str| cache.as_ref().and_then(|c| c.previous(&cache_key(ip)));
+    args.notify
+        .sweep_done(&by_state(true), &by_state(false), before);
+    if let Some((url, token)) = &args.netbox {
+        match netbox::push(url, token, &netbox::entries(&results, &args.fields)) {
+            Ok((created, existing)) => eprintln!(
+                "ipchk: NetBox: {created} address{} added, {existing} already there",
+                if created == 1 { "" } else { "es" }
+            ),
+            Err(e) => eprintln!("--netbox-url: {e}"),
+        }
+    }
+    if let Some(collector) = &args.post
+        && let Err(e) = collector.send(&results, &view, started, started_at.elapsed())
+    {
+        eprintln!("--post-url: {e}");
+    }
+    if let Some(exporter) = &args.otel
+        && let Err(e) = exporter.export(&results, checker.name(), started, started_at.elapsed())
+    {
+        eprintln!("--otel: {e}");
+    }
+    if let Some(journal) = &args.journal
+        && let Some(e) = results.iter().find_map(|r| journal.result(r).err())
+    {
+        eprintln!("--journald: {e}");
+    }
+    if let Some(Err(e)) = args.eventlog.as_ref().map(|l| l.sweep(&results)) {
+        eprintln!("--eventlog: {e}");
+    }
+    if let Some(client) = &args.statsd {
+        // --all-addresses: an address of its own for each of a name's results
+        let names: Vec<String> = results
+            .iter()
+            .map(|r| match (r.grouped, r.addr) {
+                (true, Some(a)) => a.to_string(),
+                _ => r.ip.clone(),
+            })
+            .collect();
+        let hosts: Vec<statsd::Host> = results
+            .iter()
+            .zip(&names)
+            .map(|(r, name)| statsd::Host {
+                name,
+                label: r.label.as_deref(),
+                up: r.up,
+                rtt: r
+                    .outcome
+                    .as_ref()
+                    .and_then(|o| o.stats.rtt)
+                    .map(|rtt| rtt.avg),
+            })
+            .collect();
+        if let Err(e) = client.report(&hosts, started_at.elapsed()) {
+            eprintln!("--statsd: {e}");
+        }
+    }
+
+    match (args.exit_codes, nagios) {
+        (ExitCodes::Fping, _) => std::process::exit(fping_exit_code(&results)),
+        (ExitCodes::Nagios, Some((code, _))) => std::process::exit(code),
+        _ => {}
     }
 }
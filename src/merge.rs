@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    net::IpAddr,
+};
+
+use crate::json::{self, Value};
+use crate::output::{self, Format, SCHEMA_VERSION};
+
+/* -------------------- ipchk merge: combining result files -------------------- */
+
+// Results of --output json or jsonl, from shards of one sweep or repeats
+// of it, as one set. A target in more than one file keeps one result.
+
+pub const USAGE: &str = "Usage: ipchk merge [--prefer latest|up] <results.json>...";
+
+// Which result a target keeps when files disagree
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Prefer {
+    #[default]
+    Latest, // the newest "time"; on a tie, the later file
+    Up, // an up (or degraded) result over any other, then the newest
+}
+
+impl std::str::FromStr for Prefer {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latest" => Ok(Prefer::Latest),
+            "up" => Ok(Prefer::Up),
+            _ => Err(format!("unknown preference {s:?} (use latest or up)")),
+        }
+    }
+}
+
+pub struct Request {
+    pub files: Vec<String>, // "-" for stdin
+    pub prefer: Prefer,
+    pub output: Format, // json (the default) or jsonl
+}
+
+pub fn run(req: &Request) -> Result<(), String> {
+    if req.files.is_empty() {
+        return Err(USAGE.into());
+    }
+    if !matches!(req.output, Format::Text | Format::Json | Format::Jsonl) {
+        return Err("merge: --output must be json or jsonl".into());
+    }
+    let mut merged: Vec<Value> = Vec::new();
+    let mut index: HashMap<(String, String), usize> = HashMap::new();
+    for file in &req.files {
+        for result in read(file)? {
+            let key = (text(&result, "ip"), text(&result, "address"));
+            if key.0.is_empty() {
+                return Err(format!("{file}: a result without \"ip\""));
+            }
+            match index.get(&key) {
+                Some(&i) if !wins(&result, &merged[i], req.prefer) => {}
+                Some(&i) => merged[i] = result,
+                None => {
+                    index.insert(key, merged.len());
+                    merged.push(result);
+                }
+            }
+        }
+    }
+
+    // As a sweep lists them: IPv4, then IPv6, then the rest as first seen
+    let mut keyed: Vec<((u8, u128, usize), Value)> = merged
+        .into_iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let addr = [text(&r, "address"), text(&r, "ip")]
+                .iter()
+                .find_map(|a| a.parse::<IpAddr>().ok());
+            let key = match addr {
+                Some(IpAddr::V4(a)) => (0, u128::from(u32::from(a)), 0),
+                Some(IpAddr::V6(a)) => (1, u128::from(a), 0),
+                None => (2, 0, i),
+            };
+            (key, r)
+        })
+        .collect();
+    keyed.sort_by_key(|(key, _)| *key);
+    let merged: Vec<Value> = keyed.into_iter().map(|(_, r)| r).collect();
+    match req.output {
+        Format::Jsonl => {
+            for r in &merged {
+                println!("{}", output::line(&r.to_json()));
+            }
+        }
+        _ => {
+            let files = req.files.iter().map(|f| Value::Str(f.clone())).collect();
+            let scan = Value::Object(vec![
+                ("tool".into(), Value::Str("ipchk".into())),
+                (
+                    "version".into(),
+                    Value::Str(env!("CARGO_PKG_VERSION").into()),
+                ),
+                ("merged_from".into(), Value::Array(files)),
+            ]);
+            let doc = Value::Object(vec![
+                (
+                    "schema_version".into(),
+                    Value::Num(SCHEMA_VERSION.to_string()),
+                ),
+                ("scan".into(), scan),
+                ("results".into(), Value::Array(merged)),
+            ]);
+            println!("{}", doc.to_json());
+        }
+    }
+    Ok(())
+}
+
+// One file's results, without the schema_version JSON Lines put on each
+fn read(file: &str) -> Result<Vec<Value>, String> {
+    let (name, text) = if file == "-" {
+        let mut s = String::new();
+        io::stdin()
+            .read_to_string(&mut s)
+            .map_err(|e| format!("stdin: {e}"))?;
+        ("stdin", s)
+    } else {
+        let s = fs::read_to_string(file).map_err(|e| format!("{file}: {e}"))?;
+        (file, s)
+    };
+    let mut results = json::parse_all(&text)
+        .and_then(output::results_of)
+        .map_err(|e| format!("{name}: {e}"))?;
+    for r in &mut results {
+        match r {
+            Value::Object(members) => members.retain(|(k, _)| k != "schema_version"),
+            _ => return Err(format!("{name}: not ipchk results (expected objects)")),
+        }
+    }
+    Ok(results)
+}
+
+// Whether `new` replaces `old`; later files win ties
+fn wins(new: &Value, old: &Value, prefer: Prefer) -> bool {
+    let up = |r: &Value| matches!(text(r, "status").as_str(), "up" | "degraded");
+    if prefer == Prefer::Up && up(new) != up(old) {
+        return up(new);
+    }
+    // RFC 3339 in UTC, so later times sort later
+    text(new, "time") >= text(old, "time")
+}
+
+fn text(r: &Value, key: &str) -> String {
+    r.get(key).map(Value::text).unwrap_or_default()
+}
@@ -0,0 +1,225 @@
+use crate::json::Value;
+use crate::{http, output};
+use ipchk::dns::{self, Resolver};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/* -------------------- ipchk myip: public addresses -------------------- */
+
+// STUN (RFC 5389) binding requests first: one datagram each way, no TLS.
+// The HTTPS echo services are the fallback, through curl.
+pub const DEFAULT_STUN: [&str; 2] = ["stun.cloudflare.com:3478", "stun.l.google.com:19302"];
+const STUN_PORT: u16 = 3478;
+const ECHO_V4: &str = "https://api.ipify.org";
+const ECHO_V6: &str = "https://api6.ipify.org";
+
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const MAGIC_COOKIE: u32 = 0x2112_a442;
+const MAPPED_ADDRESS: u16 = 0x0001;
+const XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+pub struct Request {
+    pub servers: Vec<String>,   // STUN servers, host[:port]
+    pub timeout: Duration,      // -t: wait for each STUN answer
+    pub dns: Option<Resolver>,  // --dns-server for the server names
+    pub dns_timeout: Duration,  // --dns-timeout
+    pub output: output::Format, // text or json
+    pub raw: bool,              // -a/--raw
+}
+
+// The address and where it came from, or everything that was tried
+type Found = Result<(IpAddr, String), String>;
+
+// Both families at once; true if either was found
+pub fn run(req: &Request) -> Result<bool, String> {
+    if !matches!(req.output, output::Format::Text | output::Format::Json) {
+        return Err("myip: --output must be text or json".into());
+    }
+    let (v4, v6) = thread::scope(|s| {
+        let v4 = s.spawn(|| discover(req, false));
+        let v6 = s.spawn(|| discover(req, true));
+        (v4.join(), v6.join())
+    });
+    let (v4, v6) = match (v4, v6) {
+        (Ok(v4), Ok(v6)) => (v4, v6),
+        _ => return Err("myip: a lookup panicked".into()),
+    };
+    match req.output {
+        output::Format::Json => {
+            let member = |found: &Found| match found {
+                Ok((ip, via)) => Value::Object(vec![
+                    ("address".into(), Value::Str(ip.to_string())),
+                    ("via".into(), Value::Str(via.clone())),
+                ]),
+                Err(e) => Value::Object(vec![("error".into(), Value::Str(e.clone()))]),
+            };
+            let doc = Value::Object(vec![
+                ("ipv4".into(), member(&v4)),
+                ("ipv6".into(), member(&v6)),
+            ]);
+            println!("{}", doc.to_json());
+        }
+        _ => {
+            for (family, found) in [("IPv4", &v4), ("IPv6", &v6)] {
+                match found {
+                    Ok((ip, via)) if req.raw => println!("{family} {ip} (via {via})"),
+                    Ok((ip, via)) => {
+                        println!("{family} \x1b[1m{ip}\x1b[0m \x1b[2m(via {via})\x1b[0m")
+                    }
+                    Err(e) => println!("{family} unknown: {e}"),
+                }
+            }
+        }
+    }
+    Ok(v4.is_ok() || v6.is_ok())
+}
+
+fn discover(req: &Request, v6: bool) -> Found {
+    let mut tried = Vec::new();
+    for server in &req.servers {
+        match stun(req, server, v6) {
+            Ok(ip) => return Ok((ip, format!("stun:{server}"))),
+            Err(e) => tried.push(format!("{server}: {e}")),
+        }
+    }
+    let (url, flag) = if v6 { (ECHO_V6, "-6") } else { (ECHO_V4, "-4") };
+    match http::get_text(url, &[flag]) {
+        Ok(body) => match body.parse::<IpAddr>() {
+            Ok(ip) if ip.is_ipv6() == v6 => return Ok((ip, url.to_string())),
+            _ => tried.push(format!("{url}: unexpected answer {body:?}")),
+        },
+        Err(e) => tried.push(e),
+    }
+    Err(tried.join("; "))
+}
+
+// "stun.example.net", "stun.example.net:19302", "192.0.2.1:3478", "[2001:db8::1]:3478"
+fn split_server(server: &str) -> (&str, u16) {
+    if let Ok(sa) = server.parse::<SocketAddr>() {
+        let host = server.rsplit_once(':').map_or(server, |(h, _)| h);
+        return (
+            host.trim_start_matches('[').trim_end_matches(']'),
+            sa.port(),
+        );
+    }
+    match server.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => match port.parse() {
+            Ok(port) => (host, port),
+            Err(_) => (server, STUN_PORT),
+        },
+        _ => (server, STUN_PORT),
+    }
+}
+
+// One binding request to `server` over IPv4 or IPv6; the mapped address
+// it reports is this host as the server saw it
+fn stun(req: &Request, server: &str, v6: bool) -> Result<IpAddr, String> {
+    let (host, port) = split_server(server);
+    let addrs = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![ip],
+        Err(_) => {
+            dns::resolve(host, req.dns.as_ref(), req.dns_timeout).map_err(|e| e.to_string())?
+        }
+    };
+    let ip = addrs
+        .into_iter()
+        .find(|ip| ip.is_ipv6() == v6)
+        .ok_or(if v6 {
+            "no IPv6 address"
+        } else {
+            "no IPv4 address"
+        })?;
+    let bind: SocketAddr = if v6 {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    };
+    let sock = UdpSocket::bind(bind).map_err(|e| e.to_string())?;
+    sock.connect((ip, port)).map_err(|e| e.to_string())?;
+
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos())
+        ^ (u128::from(std::process::id()) << 64)
+        ^ u128::from(v6);
+    let id: [u8; 12] = seed.to_be_bytes()[4..].try_into().unwrap_or_default();
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&BINDING_REQUEST.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes()); // no attributes
+    request.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(&id);
+    sock.send(&request).map_err(|e| e.to_string())?;
+
+    let deadline = Instant::now() + req.timeout;
+    let mut buf = [0u8; 1024];
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err("no answer".into());
+        }
+        let _ = sock.set_read_timeout(Some(left));
+        let n = match sock.recv(&mut buf) {
+            Ok(n) => n,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Err("no answer".into());
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        // Anything that doesn't answer this request is ignored
+        if let Some(ip) = mapped_address(&buf[..n], &id) {
+            return Ok(ip);
+        }
+    }
+}
+
+// XOR-MAPPED-ADDRESS, or the older MAPPED-ADDRESS some servers still send
+fn mapped_address(msg: &[u8], id: &[u8; 12]) -> Option<IpAddr> {
+    let u16_at = |b: &[u8], i: usize| Some(u16::from_be_bytes([*b.get(i)?, *b.get(i + 1)?]));
+    if msg.len() < 20
+        || u16_at(msg, 0)? != BINDING_SUCCESS
+        || msg[4..8] != MAGIC_COOKIE.to_be_bytes()
+        || msg[8..20] != id[..]
+    {
+        return None;
+    }
+    let attrs = msg.get(20..20 + u16_at(msg, 2)? as usize)?;
+    let mut plain = None;
+    let mut i = 0;
+    while i + 4 <= attrs.len() {
+        let (kind, len) = (u16_at(attrs, i)?, u16_at(attrs, i + 2)? as usize);
+        let value = attrs.get(i + 4..i + 4 + len)?;
+        match kind {
+            // XORed with the cookie and transaction ID, so NATs that rewrite
+            // addresses in payloads leave it alone
+            XOR_MAPPED_ADDRESS => return address(value, &msg[4..20]),
+            MAPPED_ADDRESS => plain = address(value, &[0; 16]),
+            _ => {}
+        }
+        // Attributes are padded to four bytes
+        i += 4 + len.div_ceil(4) * 4;
+    }
+    plain
+}
+
+// Reserved byte, family (1 IPv4, 2 IPv6), port, address
+fn address(value: &[u8], key: &[u8]) -> Option<IpAddr> {
+    let xor = |bytes: &[u8]| -> Vec<u8> { bytes.iter().zip(key).map(|(b, k)| b ^ k).collect() };
+    match *value.get(1)? {
+        1 => {
+            let octets: [u8; 4] = xor(value.get(4..8)?).try_into().ok()?;
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        2 => {
+            let octets: [u8; 16] = xor(value.get(4..20)?).try_into().ok()?;
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
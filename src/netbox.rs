@@ -0,0 +1,108 @@
+use crate::{
+    PingResult, http,
+    input::Fields,
+    json::{self, Value},
+    rfc3339_utc,
+};
+
+/* -------------------- NetBox IPAM (--output netbox, --netbox-url) -------------------- */
+
+// Up hosts as NetBox IP addresses. NetBox wants a prefix length with each
+// one: that of the `subnet` column (--from-routes) where there is one,
+// else /32.
+
+// How many addresses go into one API call
+const BATCH: usize = 50;
+
+pub struct Entry {
+    pub address: String, // "10.0.0.5/24"
+    pub dns_name: String,
+    pub description: String,
+}
+
+pub const CSV_HEADER: &str = "address,status,dns_name,description";
+
+pub fn entries(results: &[PingResult], fields: &Fields) -> Vec<Entry> {
+    results
+        .iter()
+        .filter(|r| r.up == Some(true))
+        .filter_map(|r| {
+            let addr = r.addr.or_else(|| r.ip.parse().ok())?;
+            let len = fields
+                .row(&r.ip)
+                .and_then(|row| row.into_iter().find(|(k, _)| *k == "subnet"))
+                .and_then(|(_, subnet)| subnet.split_once('/')?.1.parse::<u8>().ok())
+                .unwrap_or(32);
+            let seen = format!("seen up by ipchk {}", rfc3339_utc(r.at));
+            Some(Entry {
+                address: format!("{addr}/{len}"),
+                dns_name: r.name.clone().unwrap_or_default(),
+                description: match &r.label {
+                    Some(label) => format!("{label}; {seen}"),
+                    None => seen,
+                },
+            })
+        })
+        .collect()
+}
+
+// Creates the entries NetBox doesn't have yet, as active. Addresses it
+// already has are left as they are, whatever their status and mask.
+// Returns how many were created and how many were already there.
+pub fn push(url: &str, token: &str, entries: &[Entry]) -> Result<(usize, usize), String> {
+    let api = format!("{}/api/ipam/ip-addresses/", url.trim_end_matches('/'));
+    let headers = [
+        format!("Authorization: Token {token}"),
+        "Accept: application/json".to_string(),
+    ];
+    let (mut created, mut existing) = (0, 0);
+    for batch in entries.chunks(BATCH) {
+        // ?address= matches the host address whatever its mask
+        let query: Vec<String> = batch
+            .iter()
+            .map(|e| format!("address={}", host(&e.address)))
+            .collect();
+        let found = http::request(
+            "GET",
+            &format!("{api}?limit={}&{}", BATCH * 4, query.join("&")),
+            &headers,
+            None,
+        )?;
+        let found = json::parse_all(&found)?;
+        let known: Vec<String> = match found.first().and_then(|v| v.get("results")) {
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(|i| i.get("address"))
+                .map(|a| host(&a.text()).to_string())
+                .collect(),
+            _ => return Err(format!("{api}: not a NetBox API answer")),
+        };
+        let new: Vec<&Entry> = batch
+            .iter()
+            .filter(|e| !known.iter().any(|k| k == host(&e.address)))
+            .collect();
+        existing += batch.len() - new.len();
+        if new.is_empty() {
+            continue;
+        }
+        let body = Value::Array(
+            new.iter()
+                .map(|e| {
+                    Value::Object(vec![
+                        ("address".into(), Value::Str(e.address.clone())),
+                        ("status".into(), Value::Str("active".into())),
+                        ("dns_name".into(), Value::Str(e.dns_name.clone())),
+                        ("description".into(), Value::Str(e.description.clone())),
+                    ])
+                })
+                .collect(),
+        );
+        http::request("POST", &api, &headers, Some(&body.to_json()))?;
+        created += new.len();
+    }
+    Ok((created, existing))
+}
+
+fn host(address: &str) -> &str {
+    address.split('/').next().unwrap_or(address)
+}
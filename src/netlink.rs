@@ -0,0 +1,185 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::{Ipv4Addr, UdpSocket},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    thread,
+    time::{Duration, Instant},
+};
+
+/* -------------------- neighbour table over rtnetlink -------------------- */
+
+// The kernel's own answer to RTM_GETNEIGH, the same table `ip neigh` shows,
+// without parsing text whose columns differ between releases.
+
+const RTM_NEWNEIGH: u16 = 28;
+const RTM_GETNEIGH: u16 = 30;
+const NLM_F_REQUEST: u16 = 0x001;
+const NLM_F_DUMP: u16 = 0x300; // NLM_F_ROOT | NLM_F_MATCH
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLMSG_HDRLEN: usize = 16;
+const NDMSG_LEN: usize = 12;
+const NDA_DST: u16 = 1;
+const NDA_LLADDR: u16 = 2;
+
+// Reachable, stale, delay, probe or permanent: the entry has a MAC that
+// was (or still is) answering. Incomplete and failed entries don't, and
+// noarp ones (loopback, multicast) never had one.
+const NUD_VALID: u16 = 0x02 | 0x04 | 0x08 | 0x10 | 0x80;
+
+// How long solicit() waits for the kernel's ARP requests to be answered
+const SOLICIT_WAIT: Duration = Duration::from_millis(1500);
+
+// IPv4 neighbours with a usable MAC, and the interface they're on
+pub fn neighbours() -> Result<Vec<(Ipv4Addr, String, String)>, String> {
+    dump().map_err(|e| format!("netlink RTM_GETNEIGH: {e}"))
+}
+
+fn dump() -> io::Result<Vec<(Ipv4Addr, String, String)>> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let sock = unsafe { OwnedFd::from_raw_fd(fd) };
+    // A dump is quick; this is only so a wedged kernel can't hang the sweep
+    let tv = libc::timeval {
+        tv_sec: 2,
+        tv_usec: 0,
+    };
+    unsafe {
+        libc::setsockopt(
+            sock.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            (&tv as *const libc::timeval).cast(),
+            size_of::<libc::timeval>() as libc::socklen_t,
+        )
+    };
+
+    // nlmsghdr, then an ndmsg asking for AF_INET only
+    let seq: u32 = 1;
+    let mut req = Vec::with_capacity(NLMSG_HDRLEN + NDMSG_LEN);
+    req.extend(((NLMSG_HDRLEN + NDMSG_LEN) as u32).to_ne_bytes());
+    req.extend(RTM_GETNEIGH.to_ne_bytes());
+    req.extend((NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+    req.extend(seq.to_ne_bytes());
+    req.extend(0u32.to_ne_bytes()); // port ID: the kernel fills it in
+    req.push(libc::AF_INET as u8);
+    req.resize(NLMSG_HDRLEN + NDMSG_LEN, 0);
+    if unsafe { libc::send(sock.as_raw_fd(), req.as_ptr().cast(), req.len(), 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut names: HashMap<i32, String> = HashMap::new();
+    let mut out = Vec::new();
+    let mut buf = vec![0u8; 32 * 1024];
+    loop {
+        let n = unsafe { libc::recv(sock.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut msgs = &buf[..n as usize];
+        while msgs.len() >= NLMSG_HDRLEN {
+            let len = u32_at(msgs, 0) as usize;
+            if len < NLMSG_HDRLEN || len > msgs.len() {
+                return Err(io::Error::other("truncated message"));
+            }
+            let (kind, msg) = (u16_at(msgs, 4), &msgs[NLMSG_HDRLEN..len]);
+            if u32_at(msgs, 8) == seq {
+                match kind {
+                    NLMSG_DONE => return Ok(out),
+                    NLMSG_ERROR => {
+                        let errno = msg.get(..4).map_or(0, |b| u32_at(b, 0) as i32);
+                        return Err(io::Error::from_raw_os_error(-errno));
+                    }
+                    RTM_NEWNEIGH => out.extend(entry(msg, &mut names)),
+                    _ => {}
+                }
+            }
+            msgs = msgs.get(len.next_multiple_of(4)..).unwrap_or_default();
+        }
+    }
+}
+
+// ndmsg: family, padding, ifindex, state, flags, type; then attributes
+fn entry(msg: &[u8], names: &mut HashMap<i32, String>) -> Option<(Ipv4Addr, String, String)> {
+    if msg.len() < NDMSG_LEN || msg[0] != libc::AF_INET as u8 {
+        return None;
+    }
+    let ifindex = u32_at(msg, 4) as i32;
+    if u16_at(msg, 8) & NUD_VALID == 0 {
+        return None;
+    }
+    let (mut ip, mut mac) = (None, None);
+    let mut attrs = &msg[NDMSG_LEN..];
+    while attrs.len() >= 4 {
+        let len = u16_at(attrs, 0) as usize;
+        let value = attrs.get(4..len)?;
+        match u16_at(attrs, 2) {
+            NDA_DST => ip = <[u8; 4]>::try_from(value).ok().map(Ipv4Addr::from),
+            NDA_LLADDR if !value.is_empty() && value.iter().any(|&b| b != 0) => {
+                let hex: Vec<String> = value.iter().map(|b| format!("{b:02x}")).collect();
+                mac = Some(hex.join(":"));
+            }
+            _ => {}
+        }
+        attrs = attrs.get(len.next_multiple_of(4)..).unwrap_or_default();
+    }
+    let dev = names
+        .entry(ifindex)
+        .or_insert_with(|| interface_name(ifindex));
+    Some((ip?, mac?, dev.clone()))
+}
+
+fn interface_name(ifindex: i32) -> String {
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+    if unsafe { libc::if_indextoname(ifindex as u32, name.as_mut_ptr()) }.is_null() {
+        return ifindex.to_string();
+    }
+    unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn u16_at(b: &[u8], i: usize) -> u16 {
+    u16::from_ne_bytes([b[i], b[i + 1]])
+}
+
+fn u32_at(b: &[u8], i: usize) -> u32 {
+    u32::from_ne_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]])
+}
+
+// Seeds the table for `addrs`: an empty datagram to each one's discard
+// port makes the kernel ARP for it, with no privileges needed. Returns the
+// MACs of those that answered within SOLICIT_WAIT.
+pub fn solicit(addrs: &[Ipv4Addr]) -> HashMap<Ipv4Addr, String> {
+    let mut found = HashMap::new();
+    let Ok(sock) = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)) else {
+        return found;
+    };
+    for &ip in addrs {
+        let _ = sock.send_to(&[], (ip, 9));
+    }
+    let wanted: HashSet<Ipv4Addr> = addrs.iter().copied().collect();
+    let deadline = Instant::now() + SOLICIT_WAIT;
+    while found.len() < wanted.len() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(100));
+        let Ok(entries) = neighbours() else {
+            break;
+        };
+        found.extend(
+            entries
+                .into_iter()
+                .filter(|(ip, _, _)| wanted.contains(ip))
+                .map(|(ip, mac, _)| (ip, mac)),
+        );
+    }
+    found
+}
@@ -0,0 +1,310 @@
+use crate::{daemon, http, json, smtp};
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+/* -------------------- notifications and alerts -------------------- */
+
+#[derive(Debug, Default)]
+pub struct Notifiers {
+    pub desktop: bool,   // --notify-desktop
+    pub bell_up: bool,   // --audible
+    pub bell_down: bool, // --audible-down
+    pub email: Option<Email>,
+    pub webhook: Option<Webhook>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Generic,
+    Slack,
+    Discord,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "generic" => Ok(Format::Generic),
+            "slack" => Ok(Format::Slack),
+            "discord" => Ok(Format::Discord),
+            _ => Err(format!(
+                "unknown format {s:?} (use slack, discord or generic)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Webhook {
+    pub url: String,    // --webhook
+    pub format: Format, // --notify-format
+}
+
+// A daemon transition; `downtime` is how long (in seconds) a host that
+// came back up had been down
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub target: String,
+    pub up: bool,
+    pub downtime: Option<u64>,
+}
+
+impl Change {
+    // " (down for 2h13m)", or nothing
+    pub fn downtime_note(&self) -> String {
+        self.downtime
+            .map(|secs| format!(" (down for {})", daemon::fmt_secs(secs)))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug)]
+pub struct Email {
+    pub to: Vec<String>, // --email-to
+    pub server: String,  // --smtp-server
+    pub from: String,    // --email-from
+}
+
+impl Notifiers {
+    // Called when a host that had a known state flips to the other one
+    pub fn state_changed(&self, change: &Change) {
+        let body = format!(
+            "{} is {}{}",
+            change.target,
+            if change.up { "up" } else { "down" },
+            change.downtime_note()
+        );
+        if self.desktop {
+            desktop("ipchk", &body);
+        }
+        self.bell(change.up, !change.up);
+    }
+
+    // End of a daemon sweep: one summary for all of its transitions
+    pub fn changes_done(&self, changes: &[Change]) {
+        if changes.is_empty() {
+            return;
+        }
+        let pick =
+            |want: bool| -> Vec<&Change> { changes.iter().filter(|c| c.up == want).collect() };
+        let n = changes.len();
+        let summary = format!("{n} host{} changed state", if n == 1 { "" } else { "s" });
+        self.alert(&summary, &pick(true), &pick(false));
+    }
+
+    // Whether there is mail or a webhook to send, which sweeps only do for
+    // hosts whose verdict changed
+    pub fn alerts(&self) -> bool {
+        self.email.is_some() || self.webhook.is_some()
+    }
+
+    // End of a one-shot sweep: ring once for the outcome, and alert on the
+    // hosts that went down, or came back up, since `before` (the last
+    // recorded verdicts). A host seen for the first time counts if it's down.
+    pub fn sweep_done(&self, up: &[&str], down: &[&str], before: impl Fn(&str) -> Option<bool>) {
+        self.bell(!up.is_empty(), !down.is_empty());
+        let changes = |hosts: &[&str], now: bool| -> Vec<Change> {
+            hosts
+                .iter()
+                .filter(|t| before(t).map_or(!now, |was| was != now))
+                .map(|t| Change {
+                    target: t.to_string(),
+                    up: now,
+                    downtime: None,
+                })
+                .collect()
+        };
+        let (came_up, went_down) = (changes(up, true), changes(down, false));
+        if went_down.is_empty() && came_up.is_empty() {
+            return;
+        }
+        let summary = format!(
+            "{} of {} hosts down, {} newly",
+            down.len(),
+            up.len() + down.len(),
+            went_down.len()
+        );
+        self.alert(
+            &summary,
+            &came_up.iter().collect::<Vec<_>>(),
+            &went_down.iter().collect::<Vec<_>>(),
+        );
+    }
+
+    fn alert(&self, summary: &str, up: &[&Change], down: &[&Change]) {
+        if let Some(email) = &self.email {
+            let mut body = String::new();
+            for c in down {
+                body.push_str(&format!("{} is down\n", c.target));
+            }
+            for c in up {
+                body.push_str(&format!("{} is up{}\n", c.target, c.downtime_note()));
+            }
+            let subject = format!("ipchk: {summary}");
+            if let Err(e) = smtp::send(&email.server, &email.from, &email.to, &subject, &body) {
+                eprintln!("--email-to: {e}");
+            }
+        }
+        if let Some(hook) = &self.webhook {
+            let payload = match hook.format {
+                Format::Generic => generic_payload(summary, up, down),
+                Format::Slack => slack_payload(summary, up, down),
+                Format::Discord => discord_payload(summary, up, down),
+            };
+            if let Err(e) = http::post_json(&hook.url, &payload) {
+                eprintln!("--webhook: {e}");
+            }
+        }
+    }
+
+    // BEL on stderr so piped stdout stays clean
+    fn bell(&self, up: bool, down: bool) {
+        if (up && self.bell_up) || (down && self.bell_down) {
+            let mut err = io::stderr();
+            let _ = err.write_all(b"\x07");
+            let _ = err.flush();
+        }
+    }
+}
+
+/* -------------------- webhook payloads -------------------- */
+
+const COLOR_UP: u32 = 0x2eb886;
+const COLOR_DOWN: u32 = 0xd50200;
+
+// Chat services cap message sizes; long sweeps get summarised
+const MAX_LISTED: usize = 50;
+
+fn listing(targets: &[&Change], sep: &str) -> String {
+    let mut out = targets
+        .iter()
+        .take(MAX_LISTED)
+        .map(|c| format!("{}{}", c.target, c.downtime_note()))
+        .collect::<Vec<_>>()
+        .join(sep);
+    if targets.len() > MAX_LISTED {
+        out.push_str(&format!("{sep}... and {} more", targets.len() - MAX_LISTED));
+    }
+    out
+}
+
+// Targets as they are, and for those back up how long (in seconds) they
+// were down
+fn generic_payload(summary: &str, up: &[&Change], down: &[&Change]) -> String {
+    fn names<'a>(changes: &[&'a Change]) -> Vec<&'a str> {
+        changes.iter().map(|c| c.target.as_str()).collect()
+    }
+    let downtimes: Vec<String> = up
+        .iter()
+        .filter_map(|c| Some(format!("{}:{}", json::quote(&c.target), c.downtime?)))
+        .collect();
+    let mut payload = format!(
+        "{{\"source\":\"ipchk\",\"summary\":{},\"up\":{},\"down\":{}",
+        json::quote(summary),
+        json::str_array(&names(up)),
+        json::str_array(&names(down))
+    );
+    if !downtimes.is_empty() {
+        payload.push_str(&format!(",\"downtime\":{{{}}}", downtimes.join(",")));
+    }
+    payload.push('}');
+    payload
+}
+
+// One colour-coded attachment per state, each holding a mrkdwn section block
+fn slack_payload(summary: &str, up: &[&Change], down: &[&Change]) -> String {
+    let mut attachments = Vec::new();
+    for (label, color, targets) in [("Down", COLOR_DOWN, down), ("Up", COLOR_UP, up)] {
+        if targets.is_empty() {
+            continue;
+        }
+        let text = format!("*{label}*\n{}", listing(targets, "\n"));
+        attachments.push(format!(
+            "{{\"color\":\"#{color:06x}\",\"blocks\":[{{\"type\":\"section\",\"text\":{{\"type\":\"mrkdwn\",\"text\":{}}}}}]}}",
+            json::quote(&text)
+        ));
+    }
+    format!(
+        "{{\"text\":{},\"attachments\":[{}]}}",
+        json::quote(&format!("ipchk: {summary}")),
+        attachments.join(",")
+    )
+}
+
+// One colour-coded embed per state
+fn discord_payload(summary: &str, up: &[&Change], down: &[&Change]) -> String {
+    let mut embeds = Vec::new();
+    for (label, color, targets) in [("Down", COLOR_DOWN, down), ("Up", COLOR_UP, up)] {
+        if targets.is_empty() {
+            continue;
+        }
+        embeds.push(format!(
+            "{{\"title\":{},\"color\":{color},\"description\":{}}}",
+            json::quote(label),
+            json::quote(&listing(targets, "\n"))
+        ));
+    }
+    format!(
+        "{{\"username\":\"ipchk\",\"content\":{},\"embeds\":[{}]}}",
+        json::quote(&format!("ipchk: {summary}")),
+        embeds.join(",")
+    )
+}
+
+/* -------------------- desktop notifications -------------------- */
+
+// Hand the notification to the platform's own notifier, like the probe path
+// defers to the system ping. Fire-and-forget: a missing notifier must never
+// stall the monitor loop.
+fn desktop(summary: &str, body: &str) {
+    let mut cmd = desktop_command(summary, body);
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    thread::spawn(move || {
+        if let Err(e) = cmd.status() {
+            eprintln!("--notify-desktop: {:?}: {e}", cmd.get_program());
+        }
+    });
+}
+
+#[cfg(target_os = "macos")]
+fn desktop_command(summary: &str, body: &str) -> Command {
+    let esc = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut cmd = Command::new("osascript");
+    cmd.arg("-e").arg(format!(
+        "display notification \"{}\" with title \"{}\"",
+        esc(body),
+        esc(summary)
+    ));
+    cmd
+}
+
+#[cfg(windows)]
+fn desktop_command(summary: &str, body: &str) -> Command {
+    let esc = |s: &str| s.replace('\'', "''");
+    let script = format!(
+        "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null; \
+         $x = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+         $t = $x.GetElementsByTagName('text'); \
+         $t.Item(0).AppendChild($x.CreateTextNode('{}')) > $null; \
+         $t.Item(1).AppendChild($x.CreateTextNode('{}')) > $null; \
+         [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('ipchk').Show([Windows.UI.Notifications.ToastNotification]::new($x))",
+        esc(summary),
+        esc(body)
+    );
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", &script]);
+    cmd
+}
+
+// Linux and the BSDs: freedesktop notifications via libnotify's CLI
+#[cfg(not(any(target_os = "macos", windows)))]
+fn desktop_command(summary: &str, body: &str) -> Command {
+    let mut cmd = Command::new("notify-send");
+    cmd.args(["--app-name=ipchk", summary, body]);
+    cmd
+}
@@ -0,0 +1,275 @@
+use std::{
+    env,
+    hash::{BuildHasher, RandomState},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{PingResult, http, json::Value};
+
+/* -------------------- OpenTelemetry over OTLP/HTTP (--otel) -------------------- */
+
+// After the sweep, its metrics and a span covering it, in OTLP's JSON
+// encoding: POSTed to <endpoint>/v1/metrics and <endpoint>/v1/traces, as
+// an OpenTelemetry Collector takes them on port 4318. Sums and histograms
+// are cumulative from the sweep's start, so each run is a series of its own.
+
+// RTT histogram bucket bounds, in milliseconds
+const BOUNDS: [f64; 13] = [
+    0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0,
+];
+
+const CUMULATIVE: u32 = 2;
+const SPAN_KIND_INTERNAL: u32 = 1;
+
+#[derive(Debug)]
+pub struct Exporter {
+    pub endpoint: String,     // "http://collector:4318"
+    pub headers: Vec<String>, // from $OTEL_EXPORTER_OTLP_HEADERS
+    pub service: String,      // $OTEL_SERVICE_NAME, else "ipchk"
+}
+
+impl Exporter {
+    // The standard OTEL_* variables fill in what the flag doesn't say
+    pub fn new(endpoint: String) -> Self {
+        let headers = env::var("OTEL_EXPORTER_OTLP_HEADERS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|kv| kv.split_once('='))
+            .map(|(k, v)| format!("{}: {}", k.trim(), v.trim()))
+            .collect();
+        let service = env::var("OTEL_SERVICE_NAME")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "ipchk".into());
+        Exporter {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            headers,
+            service,
+        }
+    }
+
+    pub fn export(
+        &self,
+        results: &[PingResult],
+        probe: &str,
+        started: SystemTime,
+        elapsed: Duration,
+    ) -> Result<(), String> {
+        let end = started + elapsed;
+        let counts = [
+            ("up", results.iter().filter(|r| r.up == Some(true)).count()),
+            (
+                "down",
+                results.iter().filter(|r| r.up == Some(false)).count(),
+            ),
+            ("error", results.iter().filter(|r| r.up.is_none()).count()),
+        ];
+        let probe_attr = vec![attribute("ipchk.probe", probe)];
+
+        let hosts = counts
+            .iter()
+            .map(|&(status, n)| {
+                object(vec![
+                    (
+                        "attributes",
+                        Value::Array(vec![attribute("status", status)]),
+                    ),
+                    ("startTimeUnixNano", nanos(started)),
+                    ("timeUnixNano", nanos(end)),
+                    ("asInt", int(n as u64)),
+                ])
+            })
+            .collect();
+        let metrics = vec![
+            object(vec![
+                ("name", text("ipchk.probe.rtt")),
+                ("description", text("Round-trip time of each probe reply")),
+                ("unit", text("ms")),
+                (
+                    "histogram",
+                    object(vec![
+                        ("aggregationTemporality", Value::Num(CUMULATIVE.to_string())),
+                        (
+                            "dataPoints",
+                            Value::Array(vec![histogram(results, &probe_attr, started, end)]),
+                        ),
+                    ]),
+                ),
+            ]),
+            object(vec![
+                ("name", text("ipchk.hosts")),
+                (
+                    "description",
+                    text("Hosts found up or down, or that couldn't be probed"),
+                ),
+                ("unit", text("{host}")),
+                (
+                    "sum",
+                    object(vec![
+                        ("aggregationTemporality", Value::Num(CUMULATIVE.to_string())),
+                        ("isMonotonic", Value::Bool(true)),
+                        ("dataPoints", Value::Array(hosts)),
+                    ]),
+                ),
+            ]),
+        ];
+        let body = object(vec![(
+            "resourceMetrics",
+            Value::Array(vec![object(vec![
+                ("resource", self.resource()),
+                (
+                    "scopeMetrics",
+                    Value::Array(vec![object(vec![
+                        ("scope", scope()),
+                        ("metrics", Value::Array(metrics)),
+                    ])]),
+                ),
+            ])]),
+        )]);
+        self.post("/v1/metrics", &body)?;
+
+        let mut attributes = probe_attr;
+        attributes.push(int_attribute("ipchk.targets", results.len()));
+        for (status, n) in counts {
+            attributes.push(int_attribute(&format!("ipchk.hosts.{status}"), n));
+        }
+        let span = object(vec![
+            ("traceId", text(&random_hex(2))),
+            ("spanId", text(&random_hex(1))),
+            ("name", text("ipchk sweep")),
+            ("kind", Value::Num(SPAN_KIND_INTERNAL.to_string())),
+            ("startTimeUnixNano", nanos(started)),
+            ("endTimeUnixNano", nanos(end)),
+            ("attributes", Value::Array(attributes)),
+        ]);
+        let body = object(vec![(
+            "resourceSpans",
+            Value::Array(vec![object(vec![
+                ("resource", self.resource()),
+                (
+                    "scopeSpans",
+                    Value::Array(vec![object(vec![
+                        ("scope", scope()),
+                        ("spans", Value::Array(vec![span])),
+                    ])]),
+                ),
+            ])]),
+        )]);
+        self.post("/v1/traces", &body)
+    }
+
+    fn post(&self, path: &str, body: &Value) -> Result<(), String> {
+        let url = format!("{}{path}", self.endpoint);
+        http::request("POST", &url, &self.headers, Some(&body.to_json())).map(|_| ())
+    }
+
+    fn resource(&self) -> Value {
+        object(vec![(
+            "attributes",
+            Value::Array(vec![
+                attribute("service.name", &self.service),
+                attribute("service.version", env!("CARGO_PKG_VERSION")),
+            ]),
+        )])
+    }
+}
+
+// Every reply's RTT, as one explicit-bucket data point
+fn histogram(
+    results: &[PingResult],
+    attributes: &[Value],
+    start: SystemTime,
+    end: SystemTime,
+) -> Value {
+    let rtts: Vec<f64> = results
+        .iter()
+        .filter_map(|r| r.outcome.as_ref())
+        .flat_map(|o| &o.replies)
+        .map(|r| r.rtt.as_secs_f64() * 1000.0)
+        .collect();
+    let mut buckets = [0u64; BOUNDS.len() + 1];
+    for &ms in &rtts {
+        buckets[BOUNDS.iter().take_while(|&&b| ms > b).count()] += 1;
+    }
+    let mut point = vec![
+        ("attributes", Value::Array(attributes.to_vec())),
+        ("startTimeUnixNano", nanos(start)),
+        ("timeUnixNano", nanos(end)),
+        ("count", int(rtts.len() as u64)),
+        ("sum", double(rtts.iter().sum())),
+        (
+            "bucketCounts",
+            Value::Array(buckets.iter().map(|&n| int(n)).collect()),
+        ),
+        (
+            "explicitBounds",
+            Value::Array(BOUNDS.iter().map(|&b| double(b)).collect()),
+        ),
+    ];
+    // Without replies there is no min or max to give
+    if !rtts.is_empty() {
+        point.push((
+            "min",
+            double(rtts.iter().copied().fold(f64::INFINITY, f64::min)),
+        ));
+        point.push(("max", double(rtts.iter().copied().fold(0.0, f64::max))));
+    }
+    object(point)
+}
+
+fn scope() -> Value {
+    object(vec![
+        ("name", text("ipchk")),
+        ("version", text(env!("CARGO_PKG_VERSION"))),
+    ])
+}
+
+fn object(members: Vec<(&str, Value)>) -> Value {
+    Value::Object(
+        members
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+    )
+}
+
+fn attribute(key: &str, value: &str) -> Value {
+    typed_attribute(key, "stringValue", text(value))
+}
+
+fn int_attribute(key: &str, n: usize) -> Value {
+    typed_attribute(key, "intValue", int(n as u64))
+}
+
+fn typed_attribute(key: &str, kind: &str, value: Value) -> Value {
+    object(vec![
+        ("key", text(key)),
+        ("value", object(vec![(kind, value)])),
+    ])
+}
+
+fn text(s: &str) -> Value {
+    Value::Str(s.to_string())
+}
+
+// OTLP's JSON carries 64-bit integers as strings
+fn int(n: u64) -> Value {
+    Value::Str(n.to_string())
+}
+
+fn double(x: f64) -> Value {
+    Value::Num(format!("{x}"))
+}
+
+fn nanos(t: SystemTime) -> Value {
+    int(t
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64))
+}
+
+// Trace and span ids: `words` random 64-bit words, in hex
+fn random_hex(words: usize) -> String {
+    (0..words)
+        .map(|_| format!("{:016x}", RandomState::new().hash_one(SystemTime::now())))
+        .collect()
+}
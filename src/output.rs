@@ -0,0 +1,1076 @@
+use crate::{
+    Latency, PingResult,
+    baseline::Change,
+    input::Fields,
+    json::{self, Value},
+    netbox, ports, rfc3339_utc, rfc3339_utc_ms,
+};
+use ipchk::{Error, ProbeErrorKind};
+use std::time::{Duration, SystemTime};
+
+/* -------------------- result rendering (--output) -------------------- */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,   // colourised sentences (default)
+    Fping,  // `fping -a`: alive targets only, one per line
+    FpingC, // `fping -c`: per-probe lines, then per-host summaries on stderr
+    Nmap,   // `nmap -sn`: scan report per up host
+    Csv,    // one row per result, --input columns carried through
+    Table,  // aligned columns under a header line
+    Json,   // {"schema_version","scan","results"}
+    Jsonl,  // one result object per line, each with its schema_version
+    Netbox, // CSV for NetBox's IP address import, up hosts only
+    Html,   // ipchk report only: a page with the availability table
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "fping" => Ok(Format::Fping),
+            "fping-c" => Ok(Format::FpingC),
+            "nmap" => Ok(Format::Nmap),
+            "csv" => Ok(Format::Csv),
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::Jsonl),
+            "netbox" => Ok(Format::Netbox),
+            "html" => Ok(Format::Html),
+            _ => Err(format!(
+                "unknown format {s:?} (use text, fping, fping-c, nmap, csv, table, json, jsonl, netbox or html)"
+            )),
+        }
+    }
+}
+
+// --symbols: up and down as marks in place of the words
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Symbols {
+    #[default]
+    Words, // --symbols none
+    Unicode,
+    Ascii,
+}
+
+impl std::str::FromStr for Symbols {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unicode" => Ok(Symbols::Unicode),
+            "ascii" => Ok(Symbols::Ascii),
+            "none" => Ok(Symbols::Words),
+            _ => Err(format!(
+                "unknown symbols {s:?} (use unicode, ascii or none)"
+            )),
+        }
+    }
+}
+
+impl Symbols {
+    // The mark for an up, degraded or down verdict; None keeps the word
+    pub fn mark(self, status: &str) -> Option<&'static str> {
+        match (self, status) {
+            (Symbols::Words, _) => None,
+            (Symbols::Unicode, "up") => Some("\u{2713}"),
+            (Symbols::Unicode, "down") => Some("\u{2717}"),
+            (Symbols::Ascii, "up") => Some("+"),
+            (Symbols::Ascii, "down") => Some("-"),
+            (_, "degraded") => Some("!"),
+            _ => None,
+        }
+    }
+}
+
+// A column of --output csv or table, as --fields names it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Ip,
+    Address,
+    Name,
+    Label,
+    Status,
+    Rtt,  // average, in ms
+    Loss, // percent
+    Time,
+    Mac,
+    Input(String), // an --input column, by its header
+}
+
+impl std::str::FromStr for Column {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "ip" => Column::Ip,
+            "address" => Column::Address,
+            "name" => Column::Name,
+            "label" => Column::Label,
+            "status" => Column::Status,
+            "rtt" | "rtt_ms" => Column::Rtt,
+            "loss" | "loss_pct" => Column::Loss,
+            "time" => Column::Time,
+            "mac" => Column::Mac,
+            "" => return Err("empty column name".into()),
+            _ => Column::Input(s.to_string()),
+        })
+    }
+}
+
+impl Column {
+    fn header(&self) -> &str {
+        match self {
+            Column::Ip => "ip",
+            Column::Address => "address",
+            Column::Name => "name",
+            Column::Label => "label",
+            Column::Status => "status",
+            Column::Rtt => "rtt_ms",
+            Column::Loss => "loss_pct",
+            Column::Time => "time",
+            Column::Mac => "mac",
+            Column::Input(c) => c,
+        }
+    }
+
+    fn title(&self) -> &str {
+        match self {
+            Column::Ip => "IP",
+            Column::Address => "Address",
+            Column::Name => "Name",
+            Column::Label => "Label",
+            Column::Status => "Status",
+            Column::Rtt => "RTT",
+            Column::Loss => "Loss",
+            Column::Time => "Time",
+            Column::Mac => "MAC",
+            Column::Input(c) => c,
+        }
+    }
+
+    // Name and mac fall back to the --input column of that name
+    fn cell(&self, r: &PingResult, fields: &Fields) -> String {
+        let input = |col: &str| {
+            fields
+                .row(&r.ip)
+                .and_then(|row| row.into_iter().find(|(k, _)| *k == col))
+                .map(|(_, v)| v.to_string())
+                .unwrap_or_default()
+        };
+        let stats = r.outcome.as_ref().map(|o| &o.stats);
+        match self {
+            Column::Ip => r.ip.clone(),
+            Column::Address => r.addr.map(|a| a.to_string()).unwrap_or_default(),
+            Column::Name => r.name.clone().unwrap_or_else(|| input("name")),
+            Column::Label => r.label.clone().unwrap_or_default(),
+            Column::Status => status(r).to_string(),
+            Column::Rtt => stats
+                .and_then(|s| s.rtt)
+                .map(|rtt| ms(rtt.avg))
+                .unwrap_or_default(),
+            Column::Loss => stats.map(|s| s.loss.to_string()).unwrap_or_default(),
+            Column::Time => rfc3339_utc_ms(r.at),
+            Column::Mac => r.mac.clone().unwrap_or_else(|| input("mac")),
+            Column::Input(c) => input(c),
+        }
+    }
+}
+
+// --fields ip,status,rtt
+pub fn parse_columns(s: &str) -> Result<Vec<Column>, String> {
+    s.split(',').map(|c| c.trim().parse()).collect()
+}
+
+// Raised when a member of the JSON output is renamed, retyped or dropped.
+// New members can appear without it; parsers should skip those.
+pub const SCHEMA_VERSION: u32 = 1;
+
+// What results are shown with, whatever the format
+pub struct View<'a> {
+    pub fields: &'a Fields,            // --input columns
+    pub columns: Option<&'a [Column]>, // --fields
+    pub verbose: bool,
+    pub raw: bool,
+    pub symbols: Symbols,
+}
+
+// `started`/`elapsed` describe the whole sweep, for formats with a header/footer
+pub fn print(
+    results: &[PingResult],
+    format: Format,
+    view: &View,
+    started: SystemTime,
+    elapsed: Duration,
+) {
+    let &View {
+        fields,
+        columns,
+        verbose,
+        ..
+    } = view;
+    match format {
+        Format::Text => {
+            // --all-addresses results are listed together under their name
+            let mut listed: Vec<&str> = Vec::new();
+            for r in results {
+                if !r.grouped {
+                    println!("{}", r.msg);
+                    if verbose {
+                        probe_lines(r, "  ");
+                    }
+                    continue;
+                }
+                if listed.contains(&r.ip.as_str()) {
+                    continue;
+                }
+                listed.push(&r.ip);
+                println!("{}:", labelled(&r.ip, r));
+                for g in results.iter().filter(|g| g.grouped && g.ip == r.ip) {
+                    println!("  {}", g.msg);
+                    if verbose {
+                        probe_lines(g, "    ");
+                    }
+                }
+            }
+        }
+        Format::Fping => {
+            for r in results {
+                match &r.error {
+                    Some(e) => fping_error(&r.ip, e),
+                    None if r.up == Some(true) => println!("{}", labelled(&shown(r), r)),
+                    None => {}
+                }
+            }
+        }
+        Format::FpingC => fping_c(results),
+        Format::Nmap => nmap(results, started, elapsed),
+        Format::Csv => csv(results, fields, columns),
+        Format::Table => table(results, view),
+        Format::Json => {
+            println!("{}", document(results, fields, verbose, started, elapsed));
+        }
+        Format::Jsonl => {
+            for l in lines(results, fields, verbose) {
+                println!("{l}");
+            }
+        }
+        Format::Netbox => {
+            println!("{}", netbox::CSV_HEADER);
+            for e in netbox::entries(results, fields) {
+                let row = [e.address.as_str(), "active", &e.dns_name, &e.description];
+                let row: Vec<String> = row.iter().map(|f| csv_field(f)).collect();
+                println!("{}", row.join(","));
+            }
+        }
+        // Refused for sweeps when the options are parsed
+        Format::Html => unreachable!(),
+    }
+}
+
+// One word per verdict, for the structured formats
+pub fn status(r: &PingResult) -> &'static str {
+    match (&r.error, r.up) {
+        (None, Some(true)) if r.latency != Latency::Ok => "degraded",
+        (None, Some(true)) => "up",
+        (None, Some(false)) if r.outcome.as_ref().is_some_and(|o| o.frag_needed > 0) => {
+            "mtu-limited"
+        }
+        (None, _) => "down",
+        (Some(Error::Parse { .. }), _) => "invalid",
+        (Some(Error::Resolve { .. }), _) => "unresolved",
+        (
+            Some(Error::Probe {
+                kind: ProbeErrorKind::Unsupported,
+                ..
+            }),
+            _,
+        ) => "unsupported",
+        (Some(_), _) => "error",
+    }
+}
+
+pub fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+// Without --fields: ip, the --input columns in file order, then the verdict
+// and label. The probe's own name column is left out when the inventory
+// already has one.
+fn default_columns(fields: &Fields) -> Vec<Column> {
+    let mut columns = vec![Column::Ip];
+    columns.extend(fields.columns.iter().cloned().map(Column::Input));
+    columns.extend([
+        Column::Address,
+        Column::Status,
+        Column::Rtt,
+        Column::Loss,
+        Column::Time,
+    ]);
+    if !fields.columns.iter().any(|c| c == "name") {
+        columns.push(Column::Name);
+    }
+    columns.push(Column::Label);
+    columns
+}
+
+fn csv(results: &[PingResult], fields: &Fields, columns: Option<&[Column]>) {
+    let default;
+    let columns = match columns {
+        Some(c) => c,
+        None => {
+            default = default_columns(fields);
+            &default
+        }
+    };
+    let header: Vec<String> = columns.iter().map(|c| csv_field(c.header())).collect();
+    println!("{}", header.join(","));
+    for r in results {
+        let row: Vec<String> = columns
+            .iter()
+            .map(|c| csv_field(&c.cell(r, fields)))
+            .collect();
+        println!("{}", row.join(","));
+    }
+}
+
+// Without --fields: IP, Status, RTT and Name, with the address when
+// --all-addresses lists several under one target and the label when there
+// are any
+fn table(results: &[PingResult], view: &View) {
+    let &View {
+        fields,
+        columns,
+        raw,
+        symbols,
+        ..
+    } = view;
+    let default;
+    let columns = match columns {
+        Some(c) => c,
+        None => {
+            default = [
+                Some(Column::Ip),
+                results.iter().any(|r| r.grouped).then_some(Column::Address),
+                Some(Column::Status),
+                Some(Column::Rtt),
+                Some(Column::Name),
+                results
+                    .iter()
+                    .any(|r| r.label.is_some())
+                    .then_some(Column::Label),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+            &default
+        }
+    };
+    let mut rows: Vec<Vec<String>> = vec![columns.iter().map(|c| c.title().to_string()).collect()];
+    rows.extend(results.iter().map(|r| {
+        columns
+            .iter()
+            .map(|c| {
+                let word = symbols.mark(status(r)).unwrap_or(status(r));
+                let cell = match c {
+                    Column::Status if r.cached => format!("{word} (cached)"),
+                    Column::Status => word.to_string(),
+                    Column::Rtt => c.cell(r, fields) + " ms",
+                    Column::Loss => c.cell(r, fields) + "%",
+                    _ => c.cell(r, fields),
+                };
+                match cell.as_str() {
+                    "" | " ms" | "%" => "-".to_string(),
+                    _ => cell,
+                }
+            })
+            .collect()
+    }));
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    for (i, row) in rows.iter().enumerate() {
+        let line: Vec<String> = row
+            .iter()
+            .zip(columns)
+            .zip(&widths)
+            .map(|((cell, c), &width)| {
+                let pad = " ".repeat(width - cell.chars().count());
+                let shown = match (raw, i) {
+                    (true, _) => cell.clone(),
+                    (false, 0) => format!("\x1b[1m{cell}\x1b[0m"),
+                    (false, _) if *c == Column::Status => {
+                        let color = match status(&results[i - 1]) {
+                            "up" => "\x1b[32m",
+                            "down" | "invalid" => "\x1b[31m",
+                            _ if results[i - 1].latency == Latency::Crit => "\x1b[31m",
+                            _ => "\x1b[33m",
+                        };
+                        format!("\x1b[1m{color}{cell}\x1b[0m")
+                    }
+                    (false, _) => cell.clone(),
+                };
+                // Numbers line up on the right
+                match c {
+                    Column::Rtt | Column::Loss => format!("{pad}{shown}"),
+                    _ => format!("{shown}{pad}"),
+                }
+            })
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+fn opt_quote(s: Option<&str>) -> String {
+    s.map_or_else(|| "null".to_string(), json::quote)
+}
+
+fn ms(d: Duration) -> String {
+    format!("{:.3}", d.as_secs_f64() * 1000.0)
+}
+
+// What --output json prints
+pub fn document(
+    results: &[PingResult],
+    fields: &Fields,
+    verbose: bool,
+    started: SystemTime,
+    elapsed: Duration,
+) -> String {
+    let objects: Vec<String> = results.iter().map(|r| object(r, fields, verbose)).collect();
+    format!(
+        "{{\"schema_version\":{SCHEMA_VERSION},\"scan\":{},\"results\":[{}]}}",
+        scan(results, started, elapsed),
+        objects.join(",")
+    )
+}
+
+// The "scan" member of --output json: what ran, when, and how many of the
+// targets answered
+fn scan(results: &[PingResult], started: SystemTime, elapsed: Duration) -> String {
+    let mut targets: Vec<&str> = results.iter().map(|r| r.ip.as_str()).collect();
+    let mut up: Vec<&str> = results
+        .iter()
+        .filter(|r| r.up == Some(true))
+        .map(|r| r.ip.as_str())
+        .collect();
+    for list in [&mut targets, &mut up] {
+        list.sort_unstable();
+        list.dedup();
+    }
+    format!(
+        "{{\"tool\":\"ipchk\",\"version\":{},\"started\":{},\"elapsed_ms\":{},\
+         \"targets\":{},\"up\":{}}}",
+        json::quote(env!("CARGO_PKG_VERSION")),
+        json::quote(&rfc3339_utc_ms(started)),
+        elapsed.as_millis(),
+        targets.len(),
+        up.len()
+    )
+}
+
+// What --output jsonl prints, a record per result
+pub fn lines(results: &[PingResult], fields: &Fields, verbose: bool) -> Vec<String> {
+    results
+        .iter()
+        .map(|r| line(&object(r, fields, verbose)))
+        .collect()
+}
+
+// A JSON Lines record: the object with schema_version as its first member
+pub fn line(object: &str) -> String {
+    format!(
+        "{{\"schema_version\":{SCHEMA_VERSION},{}",
+        object.strip_prefix('{').unwrap_or(object)
+    )
+}
+
+// The result objects in what --output json or jsonl wrote: the "results" of
+// a document, an array as ipchk wrote before schema_version 1, or one object
+// per line. A newer schema than this build knows is refused rather than
+// half-read.
+pub fn results_of(values: Vec<Value>) -> Result<Vec<Value>, String> {
+    let results = match values.as_slice() {
+        [doc @ Value::Object(_)] => match doc.get("results") {
+            Some(Value::Array(items)) => {
+                check_version(doc)?;
+                return Ok(items.clone());
+            }
+            _ => values,
+        },
+        [Value::Array(items)] => items.clone(),
+        _ => values,
+    };
+    for r in &results {
+        check_version(r)?;
+    }
+    Ok(results)
+}
+
+fn check_version(v: &Value) -> Result<(), String> {
+    let Some(version) = v.get("schema_version").map(Value::text) else {
+        return Ok(());
+    };
+    match version.parse::<u32>() {
+        Ok(1..=SCHEMA_VERSION) => Ok(()),
+        _ => Err(format!(
+            "schema_version {version} is newer than this ipchk reads ({SCHEMA_VERSION})"
+        )),
+    }
+}
+
+// {"ip":..,"address":..,"name":..,"label":..,"status":..,"cached":..,"sent":..,
+//  "received":..,"loss_pct":..,"rtt_ms":{..},"error":..,"time":..}, then the
+//  members only some results have. The tests below pin them.
+fn object(r: &PingResult, fields: &Fields, verbose: bool) -> String {
+    let stats = r.outcome.as_ref().map(|o| &o.stats);
+    let num = |v: Option<String>| v.unwrap_or_else(|| "null".to_string());
+    let pct = |p| num(r.outcome.as_ref().and_then(|o| o.percentile(p)).map(ms));
+    let rtt = stats.and_then(|s| s.rtt).map(|rtt| {
+        format!(
+            "{{\"min\":{},\"avg\":{},\"max\":{},\"mdev\":{},\
+             \"p50\":{},\"p90\":{},\"p99\":{}}}",
+            ms(rtt.min),
+            ms(rtt.avg),
+            ms(rtt.max),
+            ms(rtt.mdev),
+            pct(50.0),
+            pct(90.0),
+            pct(99.0)
+        )
+    });
+    let mut out = format!(
+        "{{\"ip\":{},\"address\":{},\"name\":{},\"label\":{},\"status\":{},\"cached\":{},\
+         \"sent\":{},\"received\":{},\"loss_pct\":{},\"rtt_ms\":{},\"error\":{},\"time\":{}",
+        json::quote(&r.ip),
+        opt_quote(r.addr.map(|a| a.to_string()).as_deref()),
+        opt_quote(r.name.as_deref()),
+        opt_quote(r.label.as_deref()),
+        json::quote(status(r)),
+        r.cached,
+        num(stats.map(|s| s.transmitted.to_string())),
+        num(stats.map(|s| s.received.to_string())),
+        num(stats.map(|s| s.loss.to_string())),
+        num(rtt),
+        opt_quote(r.error.as_ref().map(|e| e.to_string()).as_deref()),
+        json::quote(&rfc3339_utc_ms(r.at)),
+    );
+    if let Some(mac) = &r.mac {
+        out.push_str(&format!(",\"mac\":{}", json::quote(mac)));
+    }
+    if let Some(device) = &r.alias_of {
+        out.push_str(&format!(",\"alias_of\":{}", json::quote(device)));
+    }
+    match r.baseline {
+        Some(Change::New) => out.push_str(",\"baseline\":\"new\""),
+        Some(Change::Missing(seen)) => {
+            out.push_str(",\"baseline\":\"missing\"");
+            if let Some(t) = seen {
+                out.push_str(&format!(",\"last_seen\":{}", json::quote(&rfc3339_utc(t))));
+            }
+        }
+        Some(Change::Slower(usual)) => {
+            out.push_str(&format!(
+                ",\"baseline\":\"slower\",\"baseline_rtt_ms\":{usual:.3}"
+            ));
+        }
+        None => {}
+    }
+    if let Some(mtu) = r.outcome.as_ref().and_then(|o| o.next_hop_mtu) {
+        out.push_str(&format!(",\"next_hop_mtu\":{mtu}"));
+    }
+    if let Some(offset) = r.outcome.as_ref().and_then(|o| o.clock_offset_ms()) {
+        out.push_str(&format!(",\"clock_offset_ms\":{offset}"));
+    }
+    // --probe tcp: every port's state
+    if let Some(o) = r.outcome.as_ref().filter(|o| !o.ports.is_empty()) {
+        let ports: Vec<String> = o
+            .ports
+            .iter()
+            .enumerate()
+            .map(|(i, (port, state))| {
+                let reply = o.replies.iter().find(|p| p.seq == i as u32);
+                format!(
+                    "{{\"port\":{port},\"service\":{},\"state\":\"{state}\",\"rtt_ms\":{}}}",
+                    opt_quote(ports::name(*port)),
+                    num(reply.map(|p| ms(p.rtt)))
+                )
+            })
+            .collect();
+        out.push_str(&format!(",\"ports\":[{}]", ports.join(",")));
+    }
+    // -v: every probe, answered or not; TCP's are its ports
+    if let Some(o) = r.outcome.as_ref().filter(|o| verbose && o.ports.is_empty()) {
+        let probes: Vec<String> = (0..o.sent)
+            .map(|seq| {
+                let reply = o.replies.iter().find(|p| p.seq == seq);
+                let (id, wire) = match o.icmp {
+                    Some((id, first)) => (
+                        id.to_string(),
+                        first.wrapping_add(seq as u16).to_string(),
+                    ),
+                    None => ("null".into(), "null".into()),
+                };
+                format!(
+                    "{{\"seq\":{seq},\"sent_at\":{},\"rtt_ms\":{},\"clock_offset_ms\":{},\"icmp_id\":{id},\"icmp_seq\":{wire}}}",
+                    opt_quote(reply.and_then(|p| p.sent_at).map(rfc3339_utc_ms).as_deref()),
+                    num(reply.map(|p| ms(p.rtt))),
+                    num(reply.and_then(|p| p.clock_offset_ms).map(|c| c.to_string()))
+                )
+            })
+            .collect();
+        out.push_str(&format!(",\"probes\":[{}]", probes.join(",")));
+    }
+    if let Some(cols) = fields.row(&r.ip) {
+        let members: Vec<String> = cols
+            .iter()
+            .map(|(k, v)| format!("{}:{}", json::quote(k), json::quote(v)))
+            .collect();
+        out.push_str(&format!(",\"fields\":{{{}}}", members.join(",")));
+    }
+    out.push('}');
+    out
+}
+
+// -v detail under a text verdict: when it was reached, then each probe
+fn probe_lines(r: &PingResult, indent: &str) {
+    let when = if r.cached { "cached" } else { "checked" };
+    println!("{indent}{when} {}", rfc3339_utc_ms(r.at));
+    let Some(o) = &r.outcome else {
+        return;
+    };
+    for (i, &(port, state)) in o.ports.iter().enumerate() {
+        let port = ports::label(port);
+        match o.replies.iter().find(|p| p.seq == i as u32) {
+            Some(p) => println!("{indent}port {port}: {state}, {} ms", ms(p.rtt)),
+            None => println!("{indent}port {port}: {state}"),
+        }
+    }
+    if !o.ports.is_empty() {
+        return;
+    }
+    for seq in 0..o.sent {
+        // The identifier/sequence pair on the wire, to find it in a capture
+        let wire = o
+            .icmp
+            .map(|(id, first)| format!(" [id {id:#06x} seq {}]", first.wrapping_add(seq as u16)))
+            .unwrap_or_default();
+        match o.replies.iter().find(|p| p.seq == seq) {
+            Some(p) => {
+                let sent = p
+                    .sent_at
+                    .map(|t| format!("sent {}, ", rfc3339_utc_ms(t)))
+                    .unwrap_or_default();
+                let clock = p
+                    .clock_offset_ms
+                    .map(|c| format!(", clock {c:+} ms"))
+                    .unwrap_or_default();
+                println!("{indent}seq {seq}: {sent}{} ms{clock}{wire}", ms(p.rtt));
+            }
+            None => println!("{indent}seq {seq}: no reply{wire}"),
+        }
+    }
+}
+
+// Per-address results are identified by the address, not the shared name
+fn shown(r: &PingResult) -> String {
+    match r.addr {
+        Some(a) if r.grouped => a.to_string(),
+        _ => r.ip.clone(),
+    }
+}
+
+// "10.0.0.5 (core-switch)" for a labelled target, as fping -n shows names
+fn labelled(host: &str, r: &PingResult) -> String {
+    match &r.label {
+        Some(l) => format!("{host} ({l})"),
+        None => host.to_string(),
+    }
+}
+
+// fping reports unparseable targets with getaddrinfo's wording
+fn fping_error(target: &str, e: &Error) {
+    match e {
+        Error::Parse { .. } | Error::Resolve { .. } => {
+            eprintln!("{target}: Name or service not known")
+        }
+        e => eprintln!("{e}"),
+    }
+}
+
+// fping's sprint_tm(): precision shrinks as the value grows
+fn fping_ms(d: Duration) -> String {
+    let t = d.as_secs_f64() * 1000.0;
+    if t < 1.0 {
+        format!("{t:.3}")
+    } else if t < 10.0 {
+        format!("{t:.2}")
+    } else if t < 100.0 {
+        format!("{t:.1}")
+    } else if t < 1_000_000.0 {
+        format!("{t:.0}")
+    } else {
+        format!("{t:.3e}")
+    }
+}
+
+fn fping_c(results: &[PingResult]) {
+    // fping pads every host column to the longest name
+    let w = results
+        .iter()
+        .map(|r| labelled(&shown(r), r).len())
+        .max()
+        .unwrap_or(0);
+
+    for r in results {
+        if let Some(e) = &r.error {
+            fping_error(&r.ip, e);
+            continue;
+        }
+        let Some(o) = &r.outcome else {
+            continue;
+        };
+        let (mut rcv, mut sum) = (0u32, Duration::ZERO);
+        for seq in 0..o.sent {
+            let xmt = seq + 1;
+            match o.replies.iter().find(|p| p.seq == seq) {
+                Some(p) => {
+                    rcv += 1;
+                    sum += p.rtt;
+                    println!(
+                        "{:<w$} : [{seq}], {} bytes, {} ms ({} avg, {}% loss)",
+                        labelled(&shown(r), r),
+                        p.bytes,
+                        fping_ms(p.rtt),
+                        fping_ms(sum / rcv),
+                        (xmt - rcv) * 100 / xmt
+                    );
+                }
+                None => {
+                    let avg = if rcv == 0 {
+                        "NaN".to_string()
+                    } else {
+                        fping_ms(sum / rcv)
+                    };
+                    println!(
+                        "{:<w$} : [{seq}], timed out ({avg} avg, {}% loss)",
+                        labelled(&shown(r), r),
+                        (xmt - rcv) * 100 / xmt
+                    );
+                }
+            }
+        }
+    }
+
+    eprintln!();
+    for r in results {
+        let Some(o) = &r.outcome else {
+            continue;
+        };
+        let (xmt, rcv) = (o.stats.transmitted, o.stats.received);
+        let loss = (xmt.saturating_sub(rcv) * 100)
+            .checked_div(xmt)
+            .unwrap_or(0);
+        let mut line = format!(
+            "{:<w$} : xmt/rcv/%loss = {xmt}/{rcv}/{loss}%",
+            labelled(&shown(r), r)
+        );
+        if let Some(rtt) = o.stats.rtt {
+            line.push_str(&format!(
+                ", min/avg/max = {}/{}/{}",
+                fping_ms(rtt.min),
+                fping_ms(rtt.avg),
+                fping_ms(rtt.max)
+            ));
+        }
+        eprintln!("{line}");
+    }
+}
+
+// C's "%.2g": two significant digits, trailing zeros dropped
+fn g2(v: f64) -> String {
+    if v == 0.0 || !v.is_finite() {
+        return format!("{v}");
+    }
+    let exp = v.abs().log10().floor() as i32;
+    let trim = |s: String| {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            s
+        }
+    };
+    if !(-4..2).contains(&exp) {
+        let m = trim(format!("{:.1}", v / 10f64.powi(exp)));
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{m}e{sign}{:02}", exp.abs())
+    } else {
+        trim(format!("{v:.*}", (1 - exp).max(0) as usize))
+    }
+}
+
+fn nmap(results: &[PingResult], started: SystemTime, elapsed: Duration) {
+    let stamp = rfc3339_utc(started);
+    println!(
+        "Starting ipchk {} ( {} ) at {} {} UTC",
+        env!("CARGO_PKG_VERSION"),
+        env!("CARGO_PKG_REPOSITORY"),
+        &stamp[..10],
+        &stamp[11..16]
+    );
+
+    let (mut scanned, mut up) = (0, 0);
+    for r in results {
+        match &r.error {
+            Some(Error::Parse { .. } | Error::Resolve { .. }) => {
+                eprintln!("Failed to resolve \"{}\".", r.ip);
+                continue;
+            }
+            Some(e) => eprintln!("Warning: {e}"),
+            None => {}
+        }
+        scanned += 1;
+        if r.up != Some(true) {
+            continue;
+        }
+        up += 1;
+        // A label stands in for the hostname, which nmap puts first
+        match (r.label.as_ref().or(r.name.as_ref()), r.addr) {
+            (Some(name), Some(addr)) => println!("Nmap scan report for {name} ({addr})"),
+            _ => println!("Nmap scan report for {}", r.ip),
+        }
+        match r.outcome.as_ref().and_then(|o| o.replies.first()) {
+            Some(p) => println!("Host is up ({}s latency).", g2(p.rtt.as_secs_f64())),
+            None => println!("Host is up."),
+        }
+    }
+
+    println!(
+        "Nmap done: {scanned} IP address{} ({up} host{} up) scanned in {:.2} seconds",
+        if scanned == 1 { "" } else { "es" },
+        if up == 1 { "" } else { "s" },
+        elapsed.as_secs_f64()
+    );
+}
+
+// The JSON output is a contract with whatever parses it. These fail when a
+// member is renamed, retyped, reordered or dropped: if that was meant,
+// raise SCHEMA_VERSION and say so in the README along with the fix here.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SortKey;
+    use ipchk::{Outcome, PortState, Reply, Stats};
+    use std::{collections::HashMap, time::UNIX_EPOCH};
+
+    // A value's members and their types, e.g. {ip:str,rtt_ms:{min:num}}
+    fn shape(v: &Value) -> String {
+        match v {
+            Value::Null => "null".into(),
+            Value::Bool(_) => "bool".into(),
+            Value::Num(_) => "num".into(),
+            Value::Str(_) => "str".into(),
+            Value::Array(items) => match items.first() {
+                Some(first) => format!("[{}]", shape(first)),
+                None => "[]".into(),
+            },
+            Value::Object(members) => {
+                let members: Vec<String> = members
+                    .iter()
+                    .map(|(k, v)| format!("{k}:{}", shape(v)))
+                    .collect();
+                format!("{{{}}}", members.join(","))
+            }
+        }
+    }
+
+    fn parse(text: &str) -> Value {
+        json::parse_all(text).unwrap().remove(0)
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn reply(seq: u32, ms: u64) -> Reply {
+        Reply {
+            seq,
+            bytes: 64,
+            rtt: Duration::from_millis(ms),
+            sent_at: Some(at(1_714_566_896)),
+            clock_offset_ms: None,
+        }
+    }
+
+    // 10.0.0.1 answering two of two echo probes
+    fn up() -> PingResult {
+        let replies = vec![reply(0, 1), reply(1, 3)];
+        PingResult {
+            msg: String::new(),
+            sort_key: SortKey::V4(0x0a00_0001),
+            index: 0,
+            ip: "10.0.0.1".into(),
+            name: Some("gw.example".into()),
+            addr: Some([10, 0, 0, 1].into()),
+            grouped: false,
+            up: Some(true),
+            cached: false,
+            error: None,
+            outcome: Some(Outcome {
+                up: true,
+                sent: 2,
+                stats: Stats::from_replies(2, &replies),
+                replies,
+                icmp: Some((0x1234, 1)),
+                ..Outcome::default()
+            }),
+            latency: Latency::Ok,
+            at: at(1_714_566_896),
+            mac: None,
+            alias_of: None,
+            label: None,
+            baseline: None,
+        }
+    }
+
+    // A cached verdict, never probed this time
+    fn cached() -> PingResult {
+        PingResult {
+            ip: "10.0.0.9".into(),
+            sort_key: SortKey::V4(0x0a00_0009),
+            name: None,
+            addr: None,
+            up: Some(false),
+            cached: true,
+            outcome: None,
+            ..up()
+        }
+    }
+
+    const RESULT: &str = "{ip:str,address:str,name:str,label:null,status:str,cached:bool,\
+        sent:num,received:num,loss_pct:num,\
+        rtt_ms:{min:num,avg:num,max:num,mdev:num,p50:num,p90:num,p99:num},\
+        error:null,time:str";
+
+    #[test]
+    fn result_members() {
+        let v = parse(&object(&up(), &Fields::default(), false));
+        assert_eq!(shape(&v), format!("{RESULT}}}"));
+        assert_eq!(v.get("status"), Some(&Value::Str("up".into())));
+        assert_eq!(
+            v.get("time"),
+            Some(&Value::Str("2024-05-01T12:34:56.000Z".into()))
+        );
+    }
+
+    #[test]
+    fn unprobed_result_keeps_its_members() {
+        let v = parse(&object(&cached(), &Fields::default(), false));
+        assert_eq!(
+            shape(&v),
+            "{ip:str,address:null,name:null,label:null,status:str,cached:bool,\
+             sent:null,received:null,loss_pct:null,rtt_ms:null,error:null,time:str}"
+        );
+    }
+
+    #[test]
+    fn optional_members() {
+        let mut r = up();
+        r.mac = Some("52:54:00:12:34:56".into());
+        r.alias_of = Some("10.0.0.1".into());
+        r.baseline = Some(Change::Missing(Some(at(1_714_560_000))));
+        let fields = Fields {
+            columns: vec!["site".into()],
+            rows: HashMap::from([("10.0.0.1".into(), vec!["ams".into()])]),
+        };
+        let v = parse(&object(&r, &fields, true));
+        assert_eq!(
+            shape(&v),
+            format!(
+                "{RESULT},mac:str,alias_of:str,baseline:str,last_seen:str,\
+                 probes:[{{seq:num,sent_at:str,rtt_ms:num,clock_offset_ms:null,\
+                 icmp_id:num,icmp_seq:num}}],fields:{{site:str}}}}"
+            )
+        );
+    }
+
+    #[test]
+    fn port_members() {
+        let mut r = up();
+        if let Some(o) = r.outcome.as_mut() {
+            o.ports = vec![(22, PortState::Open), (80, PortState::Filtered)];
+        }
+        let v = parse(&object(&r, &Fields::default(), true));
+        assert_eq!(
+            shape(&v),
+            format!("{RESULT},ports:[{{port:num,service:str,state:str,rtt_ms:num}}]}}")
+        );
+    }
+
+    #[test]
+    fn document_members() {
+        let results = [up(), cached()];
+        let text = document(
+            &results,
+            &Fields::default(),
+            false,
+            at(1_714_566_896),
+            Duration::from_millis(2500),
+        );
+        let v = parse(&text);
+        assert_eq!(
+            shape(&v),
+            format!(
+                "{{schema_version:num,\
+                 scan:{{tool:str,version:str,started:str,elapsed_ms:num,targets:num,up:num}},\
+                 results:[{RESULT}}}]}}"
+            )
+        );
+        assert_eq!(v.get("schema_version"), Some(&Value::Num("1".into())));
+        let scan = v.get("scan").unwrap();
+        assert_eq!(scan.get("targets"), Some(&Value::Num("2".into())));
+        assert_eq!(scan.get("up"), Some(&Value::Num("1".into())));
+    }
+
+    #[test]
+    fn line_members() {
+        let v = parse(&line(&object(&up(), &Fields::default(), false)));
+        assert_eq!(
+            shape(&v),
+            format!("{{schema_version:num,{}", &RESULT[1..]) + "}"
+        );
+    }
+
+    #[test]
+    fn reads_every_layout() {
+        let object = object(&up(), &Fields::default(), false);
+        let doc = document(&[up()], &Fields::default(), false, at(0), Duration::ZERO);
+        for text in [doc, format!("[{object}]"), line(&object), object.clone()] {
+            let results = results_of(json::parse_all(&text).unwrap()).unwrap();
+            assert_eq!(results.len(), 1, "{text}");
+            assert_eq!(results[0].get("ip"), Some(&Value::Str("10.0.0.1".into())));
+        }
+    }
+
+    #[test]
+    fn refuses_newer_schemas() {
+        for text in [
+            r#"{"schema_version":2,"scan":{},"results":[]}"#,
+            r#"{"schema_version":2,"ip":"10.0.0.1"}"#,
+        ] {
+            assert!(
+                results_of(json::parse_all(text).unwrap()).is_err(),
+                "{text}"
+            );
+        }
+    }
+}
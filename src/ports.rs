@@ -0,0 +1,101 @@
+use std::{collections::HashMap, fs, sync::OnceLock};
+
+/* -------------------- service names for TCP ports (--probe tcp) -------------------- */
+
+// The names people use, where the registry's differ (3389 is
+// "ms-wbt-server" there) or the system has no services file
+const COMMON: &[(u16, &str)] = &[
+    (21, "ftp"),
+    (22, "ssh"),
+    (23, "telnet"),
+    (25, "smtp"),
+    (53, "dns"),
+    (80, "http"),
+    (88, "kerberos"),
+    (110, "pop3"),
+    (111, "rpcbind"),
+    (135, "msrpc"),
+    (139, "netbios-ssn"),
+    (143, "imap"),
+    (389, "ldap"),
+    (443, "https"),
+    (445, "smb"),
+    (465, "smtps"),
+    (514, "shell"),
+    (548, "afp"),
+    (554, "rtsp"),
+    (587, "submission"),
+    (631, "ipp"),
+    (636, "ldaps"),
+    (873, "rsync"),
+    (993, "imaps"),
+    (995, "pop3s"),
+    (1433, "mssql"),
+    (1521, "oracle"),
+    (1723, "pptp"),
+    (2049, "nfs"),
+    (3000, "http-dev"),
+    (3128, "squid"),
+    (3306, "mysql"),
+    (3389, "rdp"),
+    (5060, "sip"),
+    (5432, "postgresql"),
+    (5900, "vnc"),
+    (5985, "winrm"),
+    (6379, "redis"),
+    (8000, "http-alt"),
+    (8080, "http-proxy"),
+    (8443, "https-alt"),
+    (9100, "jetdirect"),
+    (27017, "mongodb"),
+];
+
+#[cfg(windows)]
+fn services_path() -> std::path::PathBuf {
+    let root = std::env::var_os("SystemRoot").unwrap_or_else(|| r"C:\Windows".into());
+    std::path::Path::new(&root).join(r"System32\drivers\etc\services")
+}
+
+#[cfg(not(windows))]
+fn services_path() -> std::path::PathBuf {
+    "/etc/services".into()
+}
+
+// "https   443/tcp   # http protocol over TLS/SSL"; the first name of each
+// TCP port wins
+fn parse(text: &str) -> HashMap<u16, String> {
+    let mut names = HashMap::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let (Some(name), Some(spec)) = (words.next(), words.next()) else {
+            continue;
+        };
+        if let Some(Ok(port)) = spec.strip_suffix("/tcp").map(str::parse::<u16>) {
+            names.entry(port).or_insert_with(|| name.to_string());
+        }
+    }
+    names
+}
+
+// The service usually behind `port`, if known
+pub fn name(port: u16) -> Option<&'static str> {
+    static SYSTEM: OnceLock<HashMap<u16, String>> = OnceLock::new();
+    if let Some(&(_, name)) = COMMON.iter().find(|(p, _)| *p == port) {
+        return Some(name);
+    }
+    let system = SYSTEM.get_or_init(|| {
+        fs::read_to_string(services_path())
+            .map(|text| parse(&text))
+            .unwrap_or_default()
+    });
+    system.get(&port).map(String::as_str)
+}
+
+// "443/https", or just "8123" for an unknown port
+pub fn label(port: u16) -> String {
+    match name(port) {
+        Some(name) => format!("{port}/{name}"),
+        None => port.to_string(),
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    PingResult, http,
+    output::{self, View},
+};
+
+/* -------------------- results to an HTTP collector (--post-url) -------------------- */
+
+// After the sweep, the --output json document in one POST, or with
+// --post-batch <n> its results as JSON Lines, n records to a POST, for
+// collectors that cap the size of a request.
+
+#[derive(Debug)]
+pub struct Collector {
+    pub url: String,
+    pub token: Option<String>, // sent as "Authorization: Bearer <token>"
+    pub batch: Option<usize>,  // records per JSON Lines POST
+}
+
+impl Collector {
+    // Returns how many POSTs it took; the first that fails ends it
+    pub fn send(
+        &self,
+        results: &[PingResult],
+        view: &View,
+        started: SystemTime,
+        elapsed: Duration,
+    ) -> Result<usize, String> {
+        let mut headers = Vec::new();
+        if let Some(token) = &self.token {
+            headers.push(format!("Authorization: Bearer {token}"));
+        }
+        let Some(batch) = self.batch else {
+            let body = output::document(results, view.fields, view.verbose, started, elapsed);
+            http::request("POST", &self.url, &headers, Some(&body))?;
+            return Ok(1);
+        };
+        headers.push("Content-Type: application/x-ndjson".into());
+        let lines = output::lines(results, view.fields, view.verbose);
+        for chunk in lines.chunks(batch) {
+            let mut body = chunk.join("\n");
+            body.push('\n');
+            http::request("POST", &self.url, &headers, Some(&body))?;
+        }
+        Ok(lines.len().div_ceil(batch))
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{Engine, ProbeOptions, ProbeType, Scan, tcp};
+
+/* -------------------- privileges -------------------- */
+
+// What this process may do, found by trying it where that's cheap. The
+// reasons (root, CAP_NET_RAW, ping_group_range) are there to explain the
+// outcome, not to predict it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Privileges {
+    pub root: bool,        // euid 0, or an elevated (administrator) token on Windows
+    pub cap_net_raw: bool, // Linux: CAP_NET_RAW in the effective set
+    pub ping_group: bool,  // Linux: one of our groups is in net.ipv4.ping_group_range
+    pub raw_icmp: bool,    // a raw ICMP socket opens
+    pub dgram_icmp: bool,  // an ICMP datagram socket opens
+    pub ping: bool,        // there's a `ping` on PATH
+}
+
+impl Privileges {
+    #[cfg(unix)]
+    pub fn detect() -> Self {
+        let opens = |kind| {
+            // SAFETY: plain socket(2) call; the fd is closed straight away
+            let fd = unsafe { libc::socket(libc::AF_INET, kind, libc::IPPROTO_ICMP) };
+            if fd >= 0 {
+                unsafe { libc::close(fd) };
+            }
+            fd >= 0
+        };
+        Privileges {
+            root: unsafe { libc::geteuid() } == 0,
+            cap_net_raw: cap_net_raw(),
+            ping_group: ping_group(),
+            raw_icmp: opens(libc::SOCK_RAW),
+            dgram_icmp: opens(libc::SOCK_DGRAM),
+            ping: on_path("ping"),
+        }
+    }
+
+    // IcmpSendEcho needs nothing, so only elevation is worth knowing
+    #[cfg(windows)]
+    pub fn detect() -> Self {
+        Privileges {
+            root: elevated(),
+            ..Privileges::default()
+        }
+    }
+
+    // The native engine when it can open a socket, else the system ping
+    pub fn best_engine(&self) -> Engine {
+        if self.raw_icmp || self.dgram_icmp {
+            Engine::Native
+        } else {
+            Engine::Ping
+        }
+    }
+}
+
+#[cfg(unix)]
+fn on_path(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(name).is_file()))
+}
+
+// "CapEff:\t0000000000003000" in /proc/self/status
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn cap_net_raw() -> bool {
+    const CAP_NET_RAW: u32 = 13;
+    let Ok(status) = std::fs::read_to_string("/proc/self/status") else {
+        return false;
+    };
+    status
+        .lines()
+        .find_map(|l| l.strip_prefix("CapEff:"))
+        .and_then(|hex| u64::from_str_radix(hex.trim(), 16).ok())
+        .is_some_and(|caps| caps & (1 << CAP_NET_RAW) != 0)
+}
+
+// "1	0" (the default) lets no group in
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn ping_group() -> bool {
+    let Ok(range) = std::fs::read_to_string("/proc/sys/net/ipv4/ping_group_range") else {
+        return false;
+    };
+    let mut bounds = range.split_whitespace().map(str::parse::<u32>);
+    let (Some(Ok(lo)), Some(Ok(hi))) = (bounds.next(), bounds.next()) else {
+        return false;
+    };
+    let mut groups = vec![0 as libc::gid_t; 256];
+    let n = unsafe { libc::getgroups(groups.len() as libc::c_int, groups.as_mut_ptr()) };
+    groups.truncate(n.max(0) as usize);
+    groups.push(unsafe { libc::getegid() });
+    groups.iter().any(|g| (lo..=hi).contains(g))
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn cap_net_raw() -> bool {
+    false
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn ping_group() -> bool {
+    false
+}
+
+#[cfg(windows)]
+fn elevated() -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, TOKEN_ELEVATION, TOKEN_QUERY, TokenElevation,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    let mut token: HANDLE = std::ptr::null_mut();
+    if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+        return false;
+    }
+    let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+    let mut len = 0u32;
+    let ok = unsafe {
+        GetTokenInformation(
+            token,
+            TokenElevation,
+            (&mut elevation as *mut TOKEN_ELEVATION).cast(),
+            size_of::<TOKEN_ELEVATION>() as u32,
+            &mut len,
+        )
+    } != 0;
+    unsafe { CloseHandle(token) };
+    ok && elevation.TokenIsElevated != 0
+}
+
+/* -------------------- open files (--concurrency) -------------------- */
+
+// Descriptors kept back for what the process has open besides its probes:
+// stdio, the cache, sockets of its own
+#[cfg(unix)]
+const RESERVED_FILES: libc::rlim_t = 64;
+
+// What one host in flight holds for `opts`: a socket per port tried at once
+// for TCP connects, a child and its pipes for the system ping (which IPv6
+// echoes always use), one socket otherwise
+pub fn descriptors_per_host(opts: &ProbeOptions, v6: bool) -> usize {
+    match opts.probe {
+        ProbeType::Tcp if opts.scan == Scan::Connect => opts.ports.len().clamp(1, tcp::PARALLEL),
+        ProbeType::Echo if opts.engine == Engine::Ping || v6 => 4,
+        _ => 1,
+    }
+}
+
+// How many of `wanted` hosts can be in flight at once when each holds
+// `per_host` descriptors, going by RLIMIT_NOFILE. The soft limit is raised
+// toward the hard one first, as far as the sweep needs. The limit comes
+// back too when it cut `wanted` down.
+#[cfg(unix)]
+pub fn in_flight_limit(wanted: usize, per_host: usize) -> (usize, Option<usize>) {
+    let per_host = per_host.max(1) as libc::rlim_t;
+    let mut lim = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut lim) } != 0 {
+        return (wanted, None);
+    }
+    // RLIM_INFINITY is the largest rlim_t, so it compares as unlimited
+    let need = (wanted as libc::rlim_t)
+        .saturating_mul(per_host)
+        .saturating_add(RESERVED_FILES);
+    if lim.rlim_cur < need {
+        let raised = libc::rlimit {
+            rlim_cur: need.min(lim.rlim_max),
+            rlim_max: lim.rlim_max,
+        };
+        if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &raised) } == 0 {
+            lim = raised;
+        }
+    }
+    if lim.rlim_cur >= need {
+        return (wanted, None);
+    }
+    let fits = (lim.rlim_cur.max(RESERVED_FILES) - RESERVED_FILES) / per_host;
+    ((fits as usize).max(1), Some(lim.rlim_cur as usize))
+}
+
+// Handles run to millions per process, so IcmpSendEcho never runs short
+#[cfg(windows)]
+pub fn in_flight_limit(wanted: usize, _: usize) -> (usize, Option<usize>) {
+    (wanted, None)
+}
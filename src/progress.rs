@@ -0,0 +1,116 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{PingResult, json::Value};
+
+/* -------------------- progress records on stderr (--progress json) -------------------- */
+
+// While a sweep runs, a JSON line on stderr every second for wrappers and
+// GUIs to draw their own progress from, and a last one once every verdict
+// is in: {"completed":120,"total":254,"up":31,"rate":48.2,"eta":2.8}.
+// The rate is verdicts per second so far, the ETA the seconds the rest
+// would take at that rate (null before the first verdict).
+
+const EVERY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown progress format {s:?} (use json)")),
+        }
+    }
+}
+
+// The counts, bumped by the probing threads as their verdicts go out
+#[derive(Debug)]
+pub struct Progress {
+    total: AtomicUsize,
+    completed: AtomicUsize,
+    up: AtomicUsize,
+    started: Instant,
+}
+
+impl Progress {
+    // A name probed at each of its addresses has a verdict per address
+    pub fn expect(&self, more: usize) {
+        self.total.fetch_add(more, Ordering::Relaxed);
+    }
+
+    pub fn record(&self, r: &PingResult) {
+        if r.up == Some(true) {
+            self.up.fetch_add(1, Ordering::Relaxed);
+        }
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn print(&self) {
+        let total = self.total.load(Ordering::Relaxed);
+        let completed = self.completed.load(Ordering::Relaxed).min(total);
+        let up = self.up.load(Ordering::Relaxed);
+        let rate = completed as f64 / self.started.elapsed().as_secs_f64().max(0.001);
+        let eta = if completed == 0 {
+            Value::Null
+        } else {
+            Value::Num(format!("{:.1}", (total - completed) as f64 / rate))
+        };
+        let num = |n: usize| Value::Num(n.to_string());
+        let record = Value::Object(vec![
+            ("completed".into(), num(completed)),
+            ("total".into(), num(total)),
+            ("up".into(), num(up)),
+            ("rate".into(), Value::Num(format!("{rate:.1}"))),
+            ("eta".into(), eta),
+        ]);
+        eprintln!("{}", record.to_json());
+    }
+}
+
+// The thread printing the records; finish() prints the last one
+pub struct Reporter {
+    pub progress: Arc<Progress>,
+    stop: mpsc::Sender<()>,
+    handle: thread::JoinHandle<()>,
+}
+
+pub fn start(total: usize) -> Reporter {
+    let progress = Arc::new(Progress {
+        total: AtomicUsize::new(total),
+        completed: AtomicUsize::new(0),
+        up: AtomicUsize::new(0),
+        started: Instant::now(),
+    });
+    let (stop, stopped) = mpsc::channel();
+    let shared = Arc::clone(&progress);
+    let handle = thread::spawn(move || {
+        while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(EVERY) {
+            shared.print();
+        }
+    });
+    Reporter {
+        progress,
+        stop,
+        handle,
+    }
+}
+
+impl Reporter {
+    pub fn finish(self) {
+        let _ = self.stop.send(());
+        let _ = self.handle.join();
+        self.progress.print();
+    }
+}
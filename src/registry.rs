@@ -0,0 +1,211 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Error, Outcome, ProbeOptions, ProbeType};
+
+/* -------------------- pluggable probes -------------------- */
+
+// Liveness checks ipchk doesn't ship (a vendor keepalive, a serial gateway's
+// status port) implement Probe and are looked up by name, the same way as
+// the built-in probe types.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub addr: Ipv4Addr,
+    pub name: Option<String>, // the hostname it was given as, if any
+}
+
+impl Target {
+    pub fn new(addr: Ipv4Addr) -> Self {
+        Target { addr, name: None }
+    }
+}
+
+// When the verdict on one target is due
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Deadline(pub Instant);
+
+impl Deadline {
+    pub fn after(d: Duration) -> Self {
+        Deadline(Instant::now() + d)
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    pub fn expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
+
+// A host that doesn't answer is Ok with `up == false`; Err is for probes
+// that couldn't run
+pub type ProbeOutcome = Result<Outcome, Error>;
+
+pub trait Probe: Send + Sync {
+    // What --probe calls it
+    fn name(&self) -> &str;
+
+    // Checked once before a sweep, e.g. for privileges; Ok by default
+    fn preflight(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn probe(&self, target: &Target, deadline: Deadline) -> ProbeOutcome;
+}
+
+// A built-in probe type with its ProbeOptions. The options' own timeout and
+// count pace it, so the deadline is only a backstop.
+#[derive(Debug, Clone)]
+pub struct Builtin {
+    opts: ProbeOptions,
+}
+
+impl Builtin {
+    pub fn new(opts: ProbeOptions) -> Self {
+        Builtin { opts }
+    }
+
+    // How long `opts` can take on one target
+    pub fn budget(opts: &ProbeOptions) -> Duration {
+        let n = opts.count.max(1);
+        // A TCP probe waits for the handshake, then for the proxy's answers
+        let each = match opts.probe {
+            ProbeType::Tcp => {
+                let response = opts.response_timeout.unwrap_or(opts.timeout);
+                opts.connect_timeout.unwrap_or(opts.timeout)
+                    + if opts.proxy.is_some() {
+                        response
+                    } else {
+                        Duration::ZERO
+                    }
+            }
+            _ => opts.timeout,
+        };
+        each * n + opts.interval.unwrap_or_default() * (n - 1)
+    }
+}
+
+impl Probe for Builtin {
+    fn name(&self) -> &str {
+        match self.opts.probe {
+            ProbeType::Echo => "echo",
+            ProbeType::Timestamp => "icmp-timestamp",
+            ProbeType::Tcp => "tcp",
+        }
+    }
+
+    fn preflight(&self) -> Result<(), Error> {
+        crate::preflight(&self.opts)
+    }
+
+    fn probe(&self, target: &Target, _deadline: Deadline) -> ProbeOutcome {
+        crate::probe(target.addr, &self.opts)
+    }
+}
+
+// Probes by name. Registering a name again replaces the earlier probe, so
+// a built-in can be swapped for a custom one.
+#[derive(Default, Clone)]
+pub struct Registry {
+    probes: Vec<Arc<dyn Probe>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    // echo, icmp-timestamp and tcp, each with `opts` apart from the type
+    pub fn with_builtins(opts: &ProbeOptions) -> Self {
+        let mut r = Registry::new();
+        for probe in [ProbeType::Echo, ProbeType::Timestamp, ProbeType::Tcp] {
+            r.register(Builtin::new(ProbeOptions {
+                probe,
+                ..opts.clone()
+            }));
+        }
+        r
+    }
+
+    pub fn register(&mut self, probe: impl Probe + 'static) {
+        self.probes.retain(|p| p.name() != probe.name());
+        self.probes.push(Arc::new(probe));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Probe>> {
+        self.probes.iter().find(|p| p.name() == name).cloned()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.probes.iter().map(|p| p.name())
+    }
+}
+
+// Stops a sweep from another thread (a GUI's button, a service's stop
+// request). The batch in flight still finishes, within its budget, and no
+// further one starts. Clones share the one flag.
+#[derive(Debug, Clone, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn new() -> Self {
+        Cancel::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Runs `probe` over `targets`, `concurrency` at a time, each with `budget`
+// to reach its verdict. Outcomes line up with `targets`.
+pub fn sweep(
+    probe: &dyn Probe,
+    targets: &[Target],
+    concurrency: usize,
+    budget: Duration,
+) -> Vec<ProbeOutcome> {
+    sweep_until(probe, targets, concurrency, budget, &Cancel::new())
+}
+
+// sweep() until `cancel` is raised. The outcomes line up with as many of
+// `targets` as were probed by then, from the first.
+pub fn sweep_until(
+    probe: &dyn Probe,
+    targets: &[Target],
+    concurrency: usize,
+    budget: Duration,
+    cancel: &Cancel,
+) -> Vec<ProbeOutcome> {
+    let mut out = Vec::with_capacity(targets.len());
+    for batch in targets.chunks(concurrency.max(1)) {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let deadline = Deadline::after(budget);
+        thread::scope(|s| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|t| s.spawn(move || probe.probe(t, deadline)))
+                .collect();
+            out.extend(handles.into_iter().map(|h| {
+                h.join()
+                    .unwrap_or_else(|_| Err(Error::Io(std::io::Error::other("probe panicked"))))
+            }));
+        });
+    }
+    out
+}
@@ -0,0 +1,296 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    net::Ipv4Addr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    daemon::fmt_secs,
+    json::Value,
+    output::{self, Format},
+    parse_rfc3339, rfc3339_utc,
+};
+
+/* -------------------- ipchk report: availability from daemon logs -------------------- */
+
+// Each host's availability over a period, from the state changes that
+// `ipchk daemon --log` appends: "2024-05-01T12:34:56Z 10.0.0.5 is down".
+// A log holds changes only, so a host is taken to stay as its last line
+// says until the next one (or now), and the time before its first line
+// counts for neither.
+
+pub const USAGE: &str =
+    "Usage: ipchk report [--period 30d] [--output text|csv|json|html] <daemon.log>...";
+
+pub const DEFAULT_PERIOD: Duration = Duration::from_secs(30 * 86_400);
+
+pub struct Request {
+    pub files: Vec<String>, // "-" for stdin
+    pub period: Duration,
+    pub output: Format, // text (the default), csv, json or html
+}
+
+// One host's time within the period, in seconds
+#[derive(Default)]
+struct Availability {
+    up: u64,
+    down: u64,
+    outages: usize,
+    longest: u64, // the longest outage, as far as it falls in the period
+}
+
+impl Availability {
+    fn percent(&self) -> f64 {
+        100.0 * self.up as f64 / (self.up + self.down).max(1) as f64
+    }
+}
+
+// A host's log lines so far, replayed in order
+#[derive(Default)]
+struct Tally {
+    state: Option<(u64, bool)>, // since when, and up or not
+    outage: u64,                // the current outage's time in the period
+    seen: Availability,
+}
+
+impl Tally {
+    // Count the time from the last change up to `t`, inside [start, t]
+    fn until(&mut self, t: u64, start: u64) {
+        let Some((since, up)) = self.state else {
+            return;
+        };
+        let span = t.saturating_sub(since.max(start));
+        if up {
+            self.seen.up += span;
+        } else {
+            self.seen.down += span;
+            self.outage += span;
+        }
+    }
+
+    fn recovered(&mut self) {
+        if self.outage > 0 {
+            self.seen.outages += 1;
+            self.seen.longest = self.seen.longest.max(self.outage);
+            self.outage = 0;
+        }
+    }
+}
+
+pub fn run(req: &Request) -> Result<(), String> {
+    if req.files.is_empty() {
+        return Err(USAGE.into());
+    }
+    if !matches!(
+        req.output,
+        Format::Text | Format::Csv | Format::Json | Format::Html
+    ) {
+        return Err("report: --output must be text, csv, json or html".into());
+    }
+    let mut changes = Vec::new();
+    for file in &req.files {
+        changes.extend(read(file)?);
+    }
+    // Logs of several daemons interleave; the sort is stable, so lines of
+    // the same second keep their order
+    changes.sort_by_key(|&(t, _, _)| t);
+
+    let now = SystemTime::now();
+    let end = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let start = end.saturating_sub(req.period.as_secs());
+    let mut tallies: HashMap<String, Tally> = HashMap::new();
+    for (t, target, up) in changes {
+        let t = t.min(end);
+        let tally = tallies.entry(target).or_default();
+        tally.until(t, start);
+        if up {
+            tally.recovered();
+        }
+        // A host's time only counts from its first line on
+        tally.state = Some((t, up));
+    }
+    let mut hosts: Vec<(String, Availability)> = tallies
+        .into_iter()
+        .map(|(target, mut tally)| {
+            tally.until(end, start);
+            tally.recovered();
+            (target, tally.seen)
+        })
+        .filter(|(_, a)| a.up + a.down > 0)
+        .collect();
+    // IPv4 addresses in order, then names
+    hosts.sort_by(|(a, _), (b, _)| {
+        let key = |s: &str| {
+            s.parse::<Ipv4Addr>()
+                .map_or((1, 0), |ip| (0, u32::from(ip)))
+        };
+        key(a).cmp(&key(b)).then_with(|| a.cmp(b))
+    });
+
+    let started = now - req.period.min(Duration::from_secs(end));
+    let (from, to) = (rfc3339_utc(started), rfc3339_utc(now));
+    match req.output {
+        Format::Csv => {
+            println!(
+                "host,availability,uptime_seconds,downtime_seconds,outages,longest_outage_seconds"
+            );
+            for (host, a) in &hosts {
+                println!(
+                    "{},{:.3},{},{},{},{}",
+                    output::csv_field(host),
+                    a.percent(),
+                    a.up,
+                    a.down,
+                    a.outages,
+                    a.longest
+                );
+            }
+        }
+        Format::Json => {
+            let num = |n: u64| Value::Num(n.to_string());
+            let hosts = hosts
+                .iter()
+                .map(|(host, a)| {
+                    Value::Object(vec![
+                        ("host".into(), Value::Str(host.clone())),
+                        (
+                            "availability".into(),
+                            Value::Num(format!("{:.3}", a.percent())),
+                        ),
+                        ("uptime_seconds".into(), num(a.up)),
+                        ("downtime_seconds".into(), num(a.down)),
+                        ("outages".into(), num(a.outages as u64)),
+                        ("longest_outage_seconds".into(), num(a.longest)),
+                    ])
+                })
+                .collect();
+            let report = Value::Object(vec![
+                ("tool".into(), Value::Str("ipchk".into())),
+                (
+                    "version".into(),
+                    Value::Str(env!("CARGO_PKG_VERSION").into()),
+                ),
+                ("start".into(), Value::Str(from)),
+                ("end".into(), Value::Str(to)),
+            ]);
+            let doc = Value::Object(vec![
+                ("report".into(), report),
+                ("hosts".into(), Value::Array(hosts)),
+            ]);
+            println!("{}", doc.to_json());
+        }
+        Format::Html => print!("{}", html(&hosts, &from, &to, req.period)),
+        _ => {
+            println!(
+                "Availability from {from} to {to} ({}):",
+                fmt_secs(req.period.as_secs())
+            );
+            let width = hosts.iter().map(|(h, _)| h.len()).max().unwrap_or(0);
+            for (host, a) in &hosts {
+                let mut line = format!("{host:<width$}  {:>7.3}%", a.percent());
+                if a.outages > 0 {
+                    line.push_str(&format!(
+                        "  down {} in {} outage{}",
+                        fmt_secs(a.down),
+                        a.outages,
+                        if a.outages == 1 { "" } else { "s" }
+                    ));
+                    if a.outages > 1 {
+                        line.push_str(&format!(", the longest {}", fmt_secs(a.longest)));
+                    }
+                }
+                // Hosts that came (or were first logged) later
+                if a.up + a.down < req.period.as_secs() {
+                    line.push_str(&format!(" (logged for {})", fmt_secs(a.up + a.down)));
+                }
+                println!("{line}");
+            }
+            if hosts.is_empty() {
+                println!("no host was logged in that time");
+            }
+        }
+    }
+    Ok(())
+}
+
+// A page of its own, to mail or attach to an SLA review
+fn html(hosts: &[(String, Availability)], from: &str, to: &str, period: Duration) -> String {
+    let mut page = format!(
+        "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<title>ipchk availability report</title>
+<style>
+body {{ font-family: sans-serif; }}
+table {{ border-collapse: collapse; }}
+th, td {{ padding: 0.3em 0.8em; border-bottom: 1px solid #ddd; text-align: right; }}
+th:first-child, td:first-child {{ text-align: left; }}
+tr.down td {{ color: #d50200; }}
+</style>
+</head>
+<body>
+<h1>Availability</h1>
+<p>From {from} to {to} ({})</p>
+<table>
+<thead><tr><th>Host</th><th>Availability</th><th>Downtime</th><th>Outages</th><th>Longest outage</th></tr></thead>
+<tbody>
+",
+        fmt_secs(period.as_secs())
+    );
+    for (host, a) in hosts {
+        let class = if a.down > 0 { " class=\"down\"" } else { "" };
+        page.push_str(&format!(
+            "<tr{class}><td>{}</td><td>{:.3}%</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape(host),
+            a.percent(),
+            fmt_secs(a.down),
+            a.outages,
+            fmt_secs(a.longest)
+        ));
+    }
+    page.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    page
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// One log's state changes: (unix seconds, target, up)
+fn read(file: &str) -> Result<Vec<(u64, String, bool)>, String> {
+    let (name, text) = if file == "-" {
+        let mut s = String::new();
+        io::stdin()
+            .read_to_string(&mut s)
+            .map_err(|e| format!("stdin: {e}"))?;
+        ("stdin", s)
+    } else {
+        let s = fs::read_to_string(file).map_err(|e| format!("{file}: {e}"))?;
+        (file, s)
+    };
+    let changes: Vec<_> = text.lines().filter_map(change).collect();
+    if changes.is_empty() && !text.trim().is_empty() {
+        return Err(format!("{name}: not an ipchk daemon --log"));
+    }
+    Ok(changes)
+}
+
+// "<time> <target> is up (down for 4s)": the verdict lines; the daemon's
+// other notes (flapping) say nothing about the state
+fn change(line: &str) -> Option<(u64, String, bool)> {
+    let (time, rest) = line.split_once(' ')?;
+    let t = parse_rfc3339(time)?.duration_since(UNIX_EPOCH).ok()?;
+    let (target, verdict) = rest.split_once(" is ")?;
+    let up = match verdict.split_whitespace().next()? {
+        "up" => true,
+        "down" => false,
+        _ => return None,
+    };
+    Some((t.as_secs(), target.to_string(), up))
+}
@@ -0,0 +1,250 @@
+use crate::daemon;
+
+/* -------------------- Windows service wrapper around daemon mode -------------------- */
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Action {
+    Install,   // register with the SCM (forwarding the daemon options) and start
+    Run,       // entry point the SCM launches; not meant to be run by hand
+    Uninstall, // stop and remove
+}
+
+#[cfg(not(windows))]
+pub fn main(_action: Action, _cfg: daemon::Config) -> Result<(), String> {
+    Err("service: Windows only; run `ipchk daemon` under systemd/launchd/rc.d instead".into())
+}
+
+#[cfg(windows)]
+pub use imp::main;
+
+#[cfg(windows)]
+mod imp {
+    use super::{Action, daemon};
+    use std::{
+        env,
+        ffi::c_void,
+        path::PathBuf,
+        ptr,
+        sync::{
+            Mutex,
+            atomic::{AtomicBool, AtomicPtr, Ordering},
+        },
+    };
+    use windows_sys::Win32::System::Services::{
+        CloseServiceHandle, ControlService, CreateServiceW, DeleteService, OpenSCManagerW,
+        OpenServiceW, RegisterServiceCtrlHandlerExW, SC_HANDLE, SC_MANAGER_CREATE_SERVICE,
+        SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_ALL_ACCESS, SERVICE_AUTO_START,
+        SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_ERROR_NORMAL, SERVICE_RUNNING,
+        SERVICE_START_PENDING, SERVICE_STATUS, SERVICE_STATUS_CURRENT_STATE, SERVICE_STOP,
+        SERVICE_STOP_PENDING, SERVICE_STOPPED, SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS,
+        SetServiceStatus, StartServiceCtrlDispatcherW, StartServiceW,
+    };
+
+    const SERVICE_NAME: &str = "ipchk";
+    const DISPLAY_NAME: &str = "ipchk host monitor";
+    const DELETE: u32 = 0x0001_0000; // standard access right, not re-exported by Services
+
+    static STOP: AtomicBool = AtomicBool::new(false);
+    static STATUS: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+    static CONFIG: Mutex<Option<daemon::Config>> = Mutex::new(None);
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    fn last_error(what: &str) -> String {
+        format!("service: {what}: {}", std::io::Error::last_os_error())
+    }
+
+    // Quote like CommandLineToArgvW expects, so the SCM hands the same
+    // arguments back to `service run`.
+    fn quote(arg: &str) -> String {
+        if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+            return arg.to_string();
+        }
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    }
+
+    fn set_state(state: SERVICE_STATUS_CURRENT_STATE) {
+        let status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: if state == SERVICE_RUNNING {
+                SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN
+            } else {
+                0
+            },
+            dwWin32ExitCode: 0,
+            dwServiceSpecificExitCode: 0,
+            dwCheckPoint: 0,
+            dwWaitHint: if state == SERVICE_RUNNING { 0 } else { 10_000 },
+        };
+        unsafe {
+            SetServiceStatus(STATUS.load(Ordering::SeqCst), &status);
+        }
+    }
+
+    unsafe extern "system" fn handler(
+        control: u32,
+        _event: u32,
+        _data: *mut c_void,
+        _ctx: *mut c_void,
+    ) -> u32 {
+        if control == SERVICE_CONTROL_STOP || control == SERVICE_CONTROL_SHUTDOWN {
+            STOP.store(true, Ordering::SeqCst);
+            set_state(SERVICE_STOP_PENDING);
+        }
+        0 // NO_ERROR
+    }
+
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut windows_sys::core::PWSTR) {
+        let name = wide(SERVICE_NAME);
+        let h = unsafe { RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(handler), ptr::null()) };
+        if h.is_null() {
+            return;
+        }
+        STATUS.store(h, Ordering::SeqCst);
+        set_state(SERVICE_START_PENDING);
+
+        let cfg = CONFIG.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(cfg) = cfg {
+            set_state(SERVICE_RUNNING);
+            let _ = daemon::run(cfg, &STOP);
+        }
+        set_state(SERVICE_STOPPED);
+    }
+
+    // Services have no console, so state changes go to a log file unless
+    // the installer configured one explicitly.
+    fn default_log() -> PathBuf {
+        let base = env::var_os("ProgramData")
+            .map(PathBuf::from)
+            .unwrap_or_else(env::temp_dir);
+        base.join("ipchk").join("ipchk.log")
+    }
+
+    fn run(mut cfg: daemon::Config) -> Result<(), String> {
+        if cfg.log.is_none() {
+            let path = default_log();
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            cfg.log = Some(path);
+        }
+        *CONFIG.lock().unwrap_or_else(|e| e.into_inner()) = Some(cfg);
+
+        let mut name = wide(SERVICE_NAME);
+        let table = [
+            SERVICE_TABLE_ENTRYW {
+                lpServiceName: name.as_mut_ptr(),
+                lpServiceProc: Some(service_main),
+            },
+            SERVICE_TABLE_ENTRYW {
+                lpServiceName: ptr::null_mut(),
+                lpServiceProc: None,
+            },
+        ];
+        if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+            return Err(last_error(
+                "not started by the service manager (use `ipchk service install`)",
+            ));
+        }
+        Ok(())
+    }
+
+    fn open_manager(access: u32) -> Result<SC_HANDLE, String> {
+        let scm = unsafe { OpenSCManagerW(ptr::null(), ptr::null(), access) };
+        if scm.is_null() {
+            return Err(last_error(
+                "cannot open the service manager (run as Administrator)",
+            ));
+        }
+        Ok(scm)
+    }
+
+    fn install(cfg: &daemon::Config) -> Result<(), String> {
+        if cfg.targets.is_empty() {
+            return Err("service: no targets to monitor".into());
+        }
+        let exe = env::current_exe().map_err(|e| format!("service: {e}"))?;
+
+        // Everything after `service install` is handed back to `service run`
+        let mut cmdline = format!("{} service run", quote(&exe.to_string_lossy()));
+        for arg in env::args().skip(3) {
+            cmdline.push(' ');
+            cmdline.push_str(&quote(&arg));
+        }
+
+        let scm = open_manager(SC_MANAGER_CREATE_SERVICE)?;
+        let (name, display, path) = (wide(SERVICE_NAME), wide(DISPLAY_NAME), wide(&cmdline));
+        let svc = unsafe {
+            CreateServiceW(
+                scm,
+                name.as_ptr(),
+                display.as_ptr(),
+                SERVICE_ALL_ACCESS,
+                SERVICE_WIN32_OWN_PROCESS,
+                SERVICE_AUTO_START,
+                SERVICE_ERROR_NORMAL,
+                path.as_ptr(),
+                ptr::null(),
+                ptr::null_mut(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+            )
+        };
+        if svc.is_null() {
+            let e = last_error("create failed");
+            unsafe { CloseServiceHandle(scm) };
+            return Err(e);
+        }
+
+        let started = unsafe { StartServiceW(svc, 0, ptr::null()) } != 0;
+        let start_err = last_error("installed but could not start");
+        unsafe {
+            CloseServiceHandle(svc);
+            CloseServiceHandle(scm);
+        }
+        println!("Installed service '{SERVICE_NAME}': {cmdline}");
+        if !started {
+            eprintln!("{start_err}");
+        }
+        Ok(())
+    }
+
+    fn uninstall() -> Result<(), String> {
+        let scm = open_manager(SC_MANAGER_CREATE_SERVICE)?;
+        let name = wide(SERVICE_NAME);
+        let svc = unsafe { OpenServiceW(scm, name.as_ptr(), SERVICE_STOP | DELETE) };
+        if svc.is_null() {
+            let e = last_error("not installed");
+            unsafe { CloseServiceHandle(scm) };
+            return Err(e);
+        }
+
+        let mut status: SERVICE_STATUS = unsafe { std::mem::zeroed() };
+        let deleted = unsafe {
+            ControlService(svc, SERVICE_CONTROL_STOP, &mut status);
+            DeleteService(svc) != 0
+        };
+        let e = last_error("delete failed");
+        unsafe {
+            CloseServiceHandle(svc);
+            CloseServiceHandle(scm);
+        }
+        if !deleted {
+            return Err(e);
+        }
+        println!("Removed service '{SERVICE_NAME}'");
+        Ok(())
+    }
+
+    pub fn main(action: Action, cfg: daemon::Config) -> Result<(), String> {
+        match action {
+            Action::Install => install(&cfg),
+            Action::Run => run(cfg),
+            Action::Uninstall => uninstall(),
+        }
+    }
+}
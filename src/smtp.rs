@@ -0,0 +1,131 @@
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::{Duration, SystemTime},
+};
+
+use crate::civil_utc;
+
+/* -------------------- minimal SMTP relay client (--email-to) -------------------- */
+
+// Plain SMTP to an internal relay: no TLS, no AUTH.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn hostname() -> String {
+    for var in ["HOSTNAME", "COMPUTERNAME"] {
+        if let Ok(h) = env::var(var)
+            && !h.is_empty()
+        {
+            return h;
+        }
+    }
+    fs::read_to_string("/etc/hostname")
+        .ok()
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "localhost".into())
+}
+
+// "Tue, 14 Oct 2026 12:02:40 +0000"
+fn rfc5322_date(t: SystemTime) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (year, month, day, h, m, s) = civil_utc(t);
+    let days = t
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / 86_400);
+    format!(
+        "{}, {day:02} {} {year} {h:02}:{m:02}:{s:02} +0000",
+        DAYS[(days % 7) as usize],
+        MONTHS[(month - 1) as usize]
+    )
+}
+
+struct Session {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Session {
+    // Read a (possibly multi-line) reply and check its class, e.g. 2 or 3
+    fn expect(&mut self, class: u8) -> Result<(), String> {
+        loop {
+            let mut line = String::new();
+            self.reader
+                .read_line(&mut line)
+                .map_err(|e| format!("smtp: {e}"))?;
+            if line.len() < 4 {
+                return Err(format!("smtp: unexpected reply {line:?}"));
+            }
+            if line.as_bytes()[3] == b'-' {
+                continue;
+            }
+            if line.as_bytes()[0] != b'0' + class {
+                return Err(format!("smtp: server said {}", line.trim_end()));
+            }
+            return Ok(());
+        }
+    }
+
+    fn cmd(&mut self, line: &str, class: u8) -> Result<(), String> {
+        write!(self.writer, "{line}\r\n").map_err(|e| format!("smtp: {e}"))?;
+        self.expect(class)
+    }
+}
+
+pub fn send(
+    server: &str,
+    from: &str,
+    to: &[String],
+    subject: &str,
+    body: &str,
+) -> Result<(), String> {
+    let addr = if server.contains(':') && !server.ends_with(']') {
+        server.to_string()
+    } else {
+        format!("{server}:25")
+    };
+    let sock = addr
+        .to_socket_addrs()
+        .map_err(|e| format!("smtp: {addr}: {e}"))?
+        .next()
+        .ok_or_else(|| format!("smtp: {addr}: no address"))?;
+    let stream = TcpStream::connect_timeout(&sock, SMTP_TIMEOUT)
+        .map_err(|e| format!("smtp: {addr}: {e}"))?;
+    let _ = stream.set_read_timeout(Some(SMTP_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(SMTP_TIMEOUT));
+
+    let mut s = Session {
+        reader: BufReader::new(stream.try_clone().map_err(|e| format!("smtp: {e}"))?),
+        writer: stream,
+    };
+    s.expect(2)?;
+    s.cmd(&format!("EHLO {}", hostname()), 2)?;
+    s.cmd(&format!("MAIL FROM:<{from}>"), 2)?;
+    for rcpt in to {
+        s.cmd(&format!("RCPT TO:<{rcpt}>"), 2)?;
+    }
+    s.cmd("DATA", 3)?;
+
+    let mut msg = format!(
+        "From: {from}\r\nTo: {}\r\nSubject: {subject}\r\nDate: {}\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\r\n",
+        to.join(", "),
+        rfc5322_date(SystemTime::now())
+    );
+    for line in body.lines() {
+        // Dot-stuffing so a body line of "." can't end the message early
+        if line.starts_with('.') {
+            msg.push('.');
+        }
+        msg.push_str(line);
+        msg.push_str("\r\n");
+    }
+    msg.push('.');
+    s.cmd(&msg, 2)?;
+    let _ = s.cmd("QUIT", 2);
+    Ok(())
+}
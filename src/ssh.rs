@@ -0,0 +1,227 @@
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    thread,
+};
+
+use crate::agent::{self, Dialect, Link};
+use ipchk::{Engine, PingFlavor, ProbeOptions, ProbeType};
+
+/* -------------------- sweeps through a jump host: --via-ssh -------------------- */
+
+// --via-ssh keeps an `ssh -T` session per host and speaks the agent
+// protocol over its stdin and stdout. What answers at the far end is the
+// first of:
+//
+//   1. an ipchk already there: one copied by an earlier run, else on PATH
+//   2. this binary, copied to ~/.cache/ipchk when the platforms match
+//   3. a shell loop around the host's own ping, for echo probes only
+
+const CACHED: &str = concat!("$HOME/.cache/ipchk/ipchk-", env!("CARGO_PKG_VERSION"));
+
+struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+// Connects to `dest` and finds something there to probe with
+pub fn open(
+    dest: &str,
+    hello: &str,
+    opts: &ProbeOptions,
+    engine: Option<Engine>,
+) -> Result<Link, String> {
+    let installed = format!(
+        "for f in \"{CACHED}\" \"$(command -v ipchk)\"; do \
+         [ -x \"$f\" ] && exec \"$f\" agent --stdio; done; exit 127"
+    );
+    if let Some(link) = agent(dest, &installed, hello)? {
+        return Ok(link);
+    }
+    if same_platform(dest)? && copy(dest)? {
+        let copied = format!("exec \"{CACHED}\" agent --stdio");
+        if let Some(link) = agent(dest, &copied, hello)? {
+            return Ok(link);
+        }
+    }
+    shell(dest, opts, engine)
+}
+
+// `script` under sh on `dest`; stderr stays piped so a failed attempt can
+// say why
+fn run(dest: &str, script: &str) -> Result<Session, String> {
+    let mut child = Command::new("ssh")
+        .args(["-T", "-o", "BatchMode=yes", "-o", "ConnectTimeout=5", "--"])
+        .arg(dest)
+        .arg(format!("sh -c {}", quote(script)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{dest}: ssh: {e}"))?;
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        return Err(format!("{dest}: ssh: no pipes"));
+    };
+    Ok(Session {
+        child,
+        stdin,
+        stdout: BufReader::new(stdout),
+    })
+}
+
+impl Session {
+    // The remote exit status, once the session has ended; 255 is ssh's own
+    // failure, reported with what it printed
+    fn finish(mut self, dest: &str) -> Result<i32, String> {
+        drop(self.stdin);
+        let mut stderr = String::new();
+        if let Some(mut e) = self.child.stderr.take() {
+            let _ = e.read_to_string(&mut stderr);
+        }
+        let status = self.child.wait().map_err(|e| format!("{dest}: ssh: {e}"))?;
+        match status.code() {
+            Some(255) | None => Err(format!(
+                "{dest}: {}",
+                stderr
+                    .lines()
+                    .rfind(|l| !l.trim().is_empty())
+                    .unwrap_or("ssh failed")
+                    .trim()
+            )),
+            Some(code) => Ok(code),
+        }
+    }
+
+    // Past the handshake: what the remote end still has to say on stderr
+    // goes to ours
+    fn link(mut self, dest: &str, dialect: Dialect) -> Link {
+        if let Some(stderr) = self.child.stderr.take() {
+            let dest = dest.to_string();
+            thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    eprintln!("ipchk: {dest}: {line}");
+                }
+            });
+        }
+        Link::start(dest, dialect, self.stdout, self.stdin)
+    }
+}
+
+// An agent started by `script`, or None if it exited without answering
+fn agent(dest: &str, script: &str, hello: &str) -> Result<Option<Link>, String> {
+    let mut session = run(dest, script)?;
+    match agent::greet(&mut session.stdout, &mut session.stdin, hello) {
+        Ok(true) => Ok(Some(session.link(dest, Dialect::Agent))),
+        Ok(false) => session.finish(dest).map(|_| None),
+        Err(e) => Err(format!("{dest}: {e}")),
+    }
+}
+
+// Whether this binary runs on `dest`, going by `uname -sm`
+fn same_platform(dest: &str) -> Result<bool, String> {
+    let mut session = run(dest, "uname -sm")?;
+    let mut uname = String::new();
+    let _ = session.stdout.read_to_string(&mut uname);
+    session.finish(dest)?;
+    let mut words = uname.split_whitespace();
+    let os = match words.next() {
+        Some("Linux") => "linux",
+        Some("Darwin") => "macos",
+        Some("FreeBSD") => "freebsd",
+        Some("OpenBSD") => "openbsd",
+        Some("NetBSD") => "netbsd",
+        _ => return Ok(false),
+    };
+    let arch = match words.next() {
+        Some("amd64") => "x86_64",
+        Some("arm64") => "aarch64",
+        Some(m) => m,
+        None => return Ok(false),
+    };
+    Ok(os == env::consts::OS && arch == env::consts::ARCH)
+}
+
+// Copies this binary to CACHED on `dest`; false if that didn't work out
+fn copy(dest: &str) -> Result<bool, String> {
+    let Ok(binary) = env::current_exe().and_then(fs::read) else {
+        return Ok(false);
+    };
+    let mut session = run(
+        dest,
+        &format!(
+            "mkdir -p \"$HOME/.cache/ipchk\" && cat > \"{CACHED}.tmp\" && \
+             chmod 755 \"{CACHED}.tmp\" && mv \"{CACHED}.tmp\" \"{CACHED}\""
+        ),
+    )?;
+    let sent = session.stdin.write_all(&binary).is_ok();
+    let copied = session.finish(dest)? == 0 && sent;
+    if copied {
+        eprintln!("ipchk: {dest}: copied ipchk to ~/.cache/ipchk");
+    }
+    Ok(copied)
+}
+
+// No ipchk on `dest`: a loop that runs its ping once per target, in the
+// background so targets overlap, with each line of output tagged with the
+// target's id
+fn shell(dest: &str, opts: &ProbeOptions, engine: Option<Engine>) -> Result<Link, String> {
+    if opts.probe != ProbeType::Echo {
+        return Err(format!(
+            "{dest}: no ipchk there, and its ping only sends echo probes"
+        ));
+    }
+    if engine == Some(Engine::Native) {
+        return Err(format!("{dest}: no ipchk there for --engine native"));
+    }
+    let args = |flavor| ipchk::ping_command(flavor, opts).join(" ");
+    let script = format!(
+        "command -v ping >/dev/null 2>&1 || exit 127
+case $(ping -V 2>&1) in
+*iputils*) f=iputils;;
+*BusyBox*) f=busybox;;
+*inetutils*) f=inetutils;;
+*) case $(uname -s) in Linux) f=iputils;; OpenBSD) f=openbsd;; NetBSD) f=netbsd;; *) f=bsd;; esac;;
+esac
+case $f in
+iputils) a='{}';;
+busybox) a='{}';;
+inetutils) a='{}';;
+bsd) a='{}';;
+openbsd) a='{}';;
+netbsd) a='{}';;
+esac
+echo \"ready $f\"
+while read -r id addr; do
+  {{ ping $a \"$addr\" 2>/dev/null; echo \"exit $?\"; }} | sed \"s/^/$id /\" &
+done
+wait",
+        args(PingFlavor::Iputils),
+        args(PingFlavor::Busybox),
+        args(PingFlavor::Inetutils),
+        args(PingFlavor::Bsd),
+        args(PingFlavor::OpenBsd),
+        args(PingFlavor::NetBsd),
+    );
+    let mut session = run(dest, &script)?;
+    let mut line = String::new();
+    let _ = session.stdout.read_line(&mut line);
+    if line.starts_with("ready ") {
+        return Ok(session.link(
+            dest,
+            Dialect::Shell {
+                count: opts.count.max(1),
+            },
+        ));
+    }
+    match session.finish(dest)? {
+        127 => Err(format!("{dest}: no ipchk or ping there")),
+        code => Err(format!("{dest}: the ping loop exited with status {code}")),
+    }
+}
+
+// One word for sh, whatever is in it
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
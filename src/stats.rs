@@ -0,0 +1,104 @@
+use crate::{PingResult, alias};
+use ipchk::percentile;
+use std::time::Duration;
+
+/* -------------------- sweep summary (-s/--stats) -------------------- */
+
+// Bucket edges in ms, 1-2-5 steps so local (<1 ms) and remote (tens of ms)
+// paths land far enough apart to tell apart
+const EDGES_MS: [f64; 15] = [
+    0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0,
+];
+const BAR_WIDTH: usize = 40;
+
+// Printed to stderr, like fping -s, so stdout stays parseable
+pub fn print(results: &[PingResult], elapsed: Duration, raw: bool) {
+    let count = |f: &dyn Fn(&PingResult) -> bool| results.iter().filter(|r| f(r)).count();
+    let up = count(&|r| r.up == Some(true));
+    let down = count(&|r| r.up == Some(false));
+    let errors = count(&|r| r.error.is_some());
+    eprintln!();
+    eprintln!(
+        "--- {} targets, {up} up, {down} down, {errors} errors in {:.2} s ---",
+        results.len(),
+        elapsed.as_secs_f64()
+    );
+    // --aliases
+    let devices = alias::devices(results);
+    if devices > 0 {
+        let known = count(&|r| r.mac.is_some());
+        eprintln!("{known} up addresses with a known MAC belong to {devices} devices");
+    }
+
+    // Each up host's average RTT; cached results weren't timed this run
+    let mut rtts: Vec<f64> = results
+        .iter()
+        .filter_map(|r| r.outcome.as_ref().filter(|o| o.up)?.stats.rtt)
+        .map(|rtt| rtt.avg.as_secs_f64() * 1000.0)
+        .collect();
+    if rtts.is_empty() {
+        return;
+    }
+    rtts.sort_by(f64::total_cmp);
+    let avg = rtts.iter().sum::<f64>() / rtts.len() as f64;
+    eprintln!(
+        "rtt min/avg/max = {:.3}/{avg:.3}/{:.3} ms ({} hosts)",
+        rtts[0],
+        rtts[rtts.len() - 1],
+        rtts.len()
+    );
+
+    // Tails come from every reply, not the per-host averages that hide them
+    let mut replies: Vec<Duration> = results
+        .iter()
+        .filter_map(|r| r.outcome.as_ref().filter(|o| o.up))
+        .flat_map(|o| o.replies.iter().map(|r| r.rtt))
+        .collect();
+    replies.sort();
+    if let [Some(p50), Some(p90), Some(p99)] = [50.0, 90.0, 99.0].map(|p| percentile(&replies, p)) {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        eprintln!(
+            "rtt p50/p90/p99 = {:.3}/{:.3}/{:.3} ms ({} replies)",
+            ms(p50),
+            ms(p90),
+            ms(p99),
+            replies.len()
+        );
+    }
+    eprintln!();
+    histogram(&rtts, raw);
+}
+
+// One bar per bucket between the fastest and slowest host
+fn histogram(rtts: &[f64], raw: bool) {
+    let bucket = |ms: f64| EDGES_MS.iter().take_while(|&&e| ms >= e).count();
+    let mut counts = [0usize; EDGES_MS.len() + 1];
+    for &ms in rtts {
+        counts[bucket(ms)] += 1;
+    }
+    let first = bucket(rtts[0]);
+    let last = bucket(rtts[rtts.len() - 1]);
+    let peak = counts.iter().copied().max().unwrap_or(1);
+    let labels: Vec<String> = (first..=last).map(label).collect();
+    let width = labels.iter().map(String::len).max().unwrap_or(0);
+    for (i, label) in (first..=last).zip(&labels) {
+        let n = counts[i];
+        // Any non-empty bucket gets at least one cell
+        let cells = (n * BAR_WIDTH).div_ceil(peak);
+        let bar = if raw || cells == 0 {
+            "#".repeat(cells)
+        } else {
+            format!("\x1b[36m{}\x1b[0m", "█".repeat(cells))
+        };
+        eprintln!("  {label:>width$} ms | {bar} {n}");
+    }
+}
+
+// "<0.1", "0.5-1", "5000+"
+fn label(i: usize) -> String {
+    match i {
+        0 => format!("<{}", EDGES_MS[0]),
+        i if i == EDGES_MS.len() => format!("{}+", EDGES_MS[i - 1]),
+        i => format!("{}-{}", EDGES_MS[i - 1], EDGES_MS[i]),
+    }
+}
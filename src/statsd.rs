@@ -0,0 +1,148 @@
+use std::{
+    fmt::Display,
+    net::{SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use crate::agent;
+
+/* -------------------- StatsD and DogStatsD metrics (--statsd) -------------------- */
+
+// After each sweep, a gauge per host (up, and the average RTT of those
+// that answered) and counters for the sweep as a whole, over UDP. DogStatsD
+// puts the host and status in tags, which the Datadog agent and Telegraf
+// (datadog_extensions) read; plain StatsD has none, so they go into the
+// metric's name instead: ipchk.host.up.10_0_0_5.
+
+pub const DEFAULT_PORT: u16 = 8125;
+
+// Lines go out together up to this much, which fits any path's MTU
+const MAX_DATAGRAM: usize = 1432;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    DogStatsd, // name:value|type|#tag:value,...
+    Statsd,    // name:value|type, tag values in the name
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dogstatsd" => Ok(Format::DogStatsd),
+            "statsd" => Ok(Format::Statsd),
+            _ => Err(format!("unknown format {s:?} (use dogstatsd or statsd)")),
+        }
+    }
+}
+
+// One target's verdict, as the sweep or daemon had it
+pub struct Host<'a> {
+    pub name: &'a str,
+    pub label: Option<&'a str>,
+    pub up: Option<bool>, // None when it couldn't be probed
+    pub rtt: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct Client {
+    pub addr: SocketAddr,
+    pub format: Format,
+}
+
+impl Client {
+    // "statsd.lan", "127.0.0.1:8125" or "[fd00::5]:9125"
+    pub fn new(spec: &str, format: Format) -> Result<Self, String> {
+        let addr = agent::address_on(spec, DEFAULT_PORT)?;
+        Ok(Client { addr, format })
+    }
+
+    pub fn report(&self, hosts: &[Host], elapsed: Duration) -> Result<(), String> {
+        let mut lines = Vec::new();
+        for h in hosts {
+            let Some(up) = h.up else {
+                continue;
+            };
+            let mut tags = vec![("host", h.name)];
+            // A label says nothing a plain StatsD name needs
+            if let (Some(label), Format::DogStatsd) = (h.label, self.format) {
+                tags.push(("label", label));
+            }
+            lines.push(self.line("host.up", &tags, u8::from(up), "g"));
+            if let Some(rtt) = h.rtt.filter(|_| up) {
+                let ms = format!("{:.3}", rtt.as_secs_f64() * 1000.0);
+                lines.push(self.line("host.rtt", &tags, ms, "g"));
+            }
+        }
+        for (status, n) in [
+            ("up", hosts.iter().filter(|h| h.up == Some(true)).count()),
+            ("down", hosts.iter().filter(|h| h.up == Some(false)).count()),
+            ("error", hosts.iter().filter(|h| h.up.is_none()).count()),
+        ] {
+            lines.push(self.line("scan.hosts", &[("status", status)], n, "c"));
+        }
+        lines.push(self.line("scans", &[], 1, "c"));
+        lines.push(self.line("scan.duration", &[], elapsed.as_millis(), "ms"));
+        self.send(&lines)
+    }
+
+    fn line(&self, name: &str, tags: &[(&str, &str)], value: impl Display, kind: &str) -> String {
+        match self.format {
+            Format::DogStatsd if tags.is_empty() => format!("ipchk.{name}:{value}|{kind}"),
+            Format::DogStatsd => {
+                let tags: Vec<String> = tags
+                    .iter()
+                    .map(|(k, v)| format!("{k}:{}", clean(v, &[',', '|', '#'])))
+                    .collect();
+                format!("ipchk.{name}:{value}|{kind}|#{}", tags.join(","))
+            }
+            Format::Statsd => {
+                let mut name = format!("ipchk.{name}");
+                for (_, v) in tags {
+                    name.push('.');
+                    name.push_str(&clean(v, &['.', ':', '|', '@', '#', '/']));
+                }
+                format!("{name}:{value}|{kind}")
+            }
+        }
+    }
+
+    fn send(&self, lines: &[String]) -> Result<(), String> {
+        let local: SocketAddr = if self.addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let sock = UdpSocket::bind(local).map_err(|e| e.to_string())?;
+        let mut datagram = String::new();
+        for line in lines {
+            if !datagram.is_empty() && datagram.len() + 1 + line.len() > MAX_DATAGRAM {
+                sock.send_to(datagram.as_bytes(), self.addr)
+                    .map_err(|e| format!("{}: {e}", self.addr))?;
+                datagram.clear();
+            }
+            if !datagram.is_empty() {
+                datagram.push('\n');
+            }
+            datagram.push_str(line);
+        }
+        if !datagram.is_empty() {
+            sock.send_to(datagram.as_bytes(), self.addr)
+                .map_err(|e| format!("{}: {e}", self.addr))?;
+        }
+        Ok(())
+    }
+}
+
+// `s` with whitespace and the format's special characters as underscores
+fn clean(s: &str, special: &[char]) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_whitespace() || special.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,181 @@
+use crate::{Error, Outcome, PortState, ProbeOptions, Reply, Stats, icmp};
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    sync::atomic::{AtomicU16, Ordering},
+    time::{Duration, Instant, SystemTime},
+};
+
+/* -------------------- raw SYN scan (--scan syn) -------------------- */
+
+// Only Linux hands incoming TCP segments to raw sockets; the BSDs would need
+// BPF. Answers come back to a source port no socket owns, so the kernel
+// resets every SYN-ACK itself and no handshake is ever completed.
+
+const SYN: u8 = 0x02;
+const RST: u8 = 0x04;
+const ACK: u8 = 0x10;
+
+// Source ports above Linux's default ephemeral range (32768-60999), one per
+// scanned host
+const FIRST_PORT: u16 = 61000;
+static NEXT_PORT: AtomicU16 = AtomicU16::new(0);
+
+// SYNs sent between reads of the answers
+const BURST: usize = 64;
+
+// The address the kernel picks to reach `ip`, needed for the checksum
+fn source_for(ip: Ipv4Addr) -> Result<Ipv4Addr, Error> {
+    let sock = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    sock.connect((ip, 9))?;
+    match sock.local_addr()? {
+        SocketAddr::V4(a) => Ok(*a.ip()),
+        SocketAddr::V6(_) => Ok(Ipv4Addr::UNSPECIFIED),
+    }
+}
+
+// A SYN with an MSS option, the way a real connect would start
+fn segment(src: Ipv4Addr, dst: Ipv4Addr, sport: u16, dport: u16, seq: u32) -> Vec<u8> {
+    let mut seg = Vec::with_capacity(24);
+    seg.extend(sport.to_be_bytes());
+    seg.extend(dport.to_be_bytes());
+    seg.extend(seq.to_be_bytes());
+    seg.extend(0u32.to_be_bytes()); // ack
+    seg.extend([6 << 4, SYN]); // 6 words of header
+    seg.extend(1024u16.to_be_bytes()); // window
+    seg.extend([0, 0, 0, 0]); // checksum, urgent pointer
+    seg.extend([2, 4]); // MSS
+    seg.extend(1460u16.to_be_bytes());
+
+    // Checksummed over the pseudo-header too
+    let mut sum = Vec::with_capacity(12 + seg.len());
+    sum.extend(src.octets());
+    sum.extend(dst.octets());
+    sum.extend([0, libc::IPPROTO_TCP as u8]);
+    sum.extend((seg.len() as u16).to_be_bytes());
+    sum.extend(&seg);
+    let sum = icmp::checksum(&sum);
+    seg[16..18].copy_from_slice(&sum.to_be_bytes());
+    seg
+}
+
+// What the SYNs to one host have drawn so far
+struct Answers<'a> {
+    index: HashMap<u16, usize>, // port -> position in ProbeOptions::ports
+    sent: Vec<(SystemTime, Instant)>,
+    states: Vec<PortState>,
+    replies: Vec<Reply>,
+    from: Ipv4Addr,
+    sport: u16,
+    isn: &'a dyn Fn(u16) -> u32,
+}
+
+impl Answers<'_> {
+    // Records `datagram` if it answers one of our SYNs
+    fn take(&mut self, datagram: &[u8], from: Ipv4Addr) {
+        // Raw sockets deliver the IP header too
+        let ihl = usize::from(datagram.first().map_or(0, |b| b & 0x0f)) * 4;
+        let Some(seg) = datagram.get(ihl..).filter(|s| s.len() >= 14) else {
+            return;
+        };
+        let sp = u16::from_be_bytes([seg[0], seg[1]]);
+        let dp = u16::from_be_bytes([seg[2], seg[3]]);
+        let ack = u32::from_be_bytes([seg[8], seg[9], seg[10], seg[11]]);
+        let flags = seg[13];
+        let Some(&i) = self.index.get(&sp) else {
+            return;
+        };
+        if from != self.from
+            || dp != self.sport
+            || flags & ACK == 0
+            || ack != (self.isn)(sp).wrapping_add(1)
+            || i >= self.sent.len()
+            || self.states[i] != PortState::Filtered
+        // a retransmitted SYN-ACK
+        {
+            return;
+        }
+        self.states[i] = match flags {
+            f if f & SYN != 0 => PortState::Open,
+            f if f & RST != 0 => PortState::Closed,
+            _ => return,
+        };
+        let (sent_at, start) = self.sent[i];
+        self.replies.push(Reply {
+            seq: i as u32,
+            bytes: 0,
+            rtt: start.elapsed(),
+            sent_at: Some(sent_at),
+            clock_offset_ms: None,
+        });
+    }
+}
+
+pub(crate) fn preflight() -> Result<(), Error> {
+    icmp::raw(libc::IPPROTO_TCP).map(drop)
+}
+
+// Every SYN goes out at once; ports still silent a timeout after the last
+// one are filtered
+pub(crate) fn probe(ip: Ipv4Addr, opts: &ProbeOptions) -> Result<Outcome, Error> {
+    let sock = icmp::raw(libc::IPPROTO_TCP)?;
+    let src = source_for(ip)?;
+    let sport = FIRST_PORT
+        + (NEXT_PORT.fetch_add(1, Ordering::Relaxed) ^ std::process::id() as u16)
+            % (u16::MAX - FIRST_PORT);
+    // Initial sequence numbers a stray segment won't guess
+    let base = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let isn = |port: u16| base.wrapping_add((u32::from(port) << 16) | u32::from(port));
+
+    let mut scan = Answers {
+        index: opts
+            .ports
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| (p, i))
+            .collect(),
+        sent: Vec::with_capacity(opts.ports.len()),
+        states: vec![PortState::Filtered; opts.ports.len()],
+        replies: Vec::new(),
+        from: ip,
+        sport,
+        isn: &isn,
+    };
+    let mut buf = [0u8; 256]; // headers are all that's read
+    for (i, &port) in opts.ports.iter().enumerate() {
+        scan.sent.push((SystemTime::now(), Instant::now()));
+        sock.send(ip, &segment(src, ip, sport, port, isn(port)))?;
+        // Read as we go, or a big sweep's answers overflow the socket buffer
+        if i % BURST == BURST - 1 {
+            let drained = Instant::now() + Duration::from_millis(1);
+            while let Some((n, from)) = sock.recv(&mut buf, drained)? {
+                scan.take(&buf[..n], from);
+            }
+        }
+    }
+    let deadline = Instant::now() + opts.connect_timeout.unwrap_or(opts.timeout);
+    while scan.replies.len() < opts.ports.len() {
+        let Some((n, from)) = sock.recv(&mut buf, deadline)? else {
+            break;
+        };
+        scan.take(&buf[..n], from);
+    }
+    let Answers {
+        states, replies, ..
+    } = scan;
+
+    let count = opts.ports.len() as u32;
+    Ok(Outcome {
+        up: !replies.is_empty(),
+        sent: count,
+        stats: Stats::from_replies(count, &replies),
+        replies,
+        icmp: None,
+        corrupted: 0,
+        frag_needed: 0,
+        next_hop_mtu: None,
+        ports: opts.ports.iter().copied().zip(states).collect(),
+    })
+}
@@ -0,0 +1,159 @@
+use crate::{Error, Outcome, PortState, ProbeOptions, Reply, Stats};
+use std::{
+    io::{self, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/* -------------------- TCP connect probe (--probe tcp) -------------------- */
+
+// Ports of one host tried at once
+pub(crate) const PARALLEL: usize = 32;
+
+// A completed handshake is open and a reset closed; either means the host
+// is up. No answer, or an ICMP unreachable, is filtered.
+// Returns when the attempt started and how long the answer took.
+fn connect(ip: Ipv4Addr, port: u16, timeout: Duration) -> io::Result<(PortState, Reply)> {
+    let (sent_at, start) = (SystemTime::now(), Instant::now());
+    let state = match TcpStream::connect_timeout(&SocketAddr::from((ip, port)), timeout) {
+        Ok(_) => PortState::Open,
+        Err(e) => match e.kind() {
+            io::ErrorKind::ConnectionRefused => PortState::Closed,
+            io::ErrorKind::TimedOut
+            | io::ErrorKind::HostUnreachable
+            | io::ErrorKind::NetworkUnreachable => PortState::Filtered,
+            _ => return Err(e),
+        },
+    };
+    let reply = Reply {
+        seq: 0,
+        bytes: 0,
+        rtt: start.elapsed(),
+        sent_at: Some(sent_at),
+        clock_offset_ms: None,
+    };
+    Ok((state, reply))
+}
+
+// The same through a SOCKS5 proxy (RFC 1928, no authentication), whose
+// reply says how its own connection went. A proxy that hangs up instead,
+// as `ssh -D` does when the far end refuses, can't tell closed from
+// filtered and counts as filtered. The RTT includes the proxy's share.
+// `connect` bounds the handshake with the proxy, `response` the exchange
+// after it.
+fn connect_via(
+    proxy: SocketAddr,
+    ip: Ipv4Addr,
+    port: u16,
+    connect: Duration,
+    response: Duration,
+) -> io::Result<(PortState, Reply)> {
+    let (sent_at, start) = (SystemTime::now(), Instant::now());
+    let unusable = |what: &str| io::Error::other(format!("proxy {proxy}: {what}"));
+    let mut s =
+        TcpStream::connect_timeout(&proxy, connect).map_err(|e| unusable(&e.to_string()))?;
+    let connected = Instant::now();
+    let left = || {
+        response
+            .saturating_sub(connected.elapsed())
+            .max(Duration::from_millis(1))
+    };
+    s.set_read_timeout(Some(left()))?;
+    s.set_write_timeout(Some(left()))?;
+    let mut greeting = [0u8; 2];
+    s.write_all(&[5, 1, 0])?;
+    s.read_exact(&mut greeting).map_err(|e| {
+        // It took the connection but keeps quiet
+        if matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ) {
+            unusable("accepted the connection but never answered")
+        } else {
+            unusable(&e.to_string())
+        }
+    })?;
+    if greeting != [5, 0] {
+        return Err(unusable("wants authentication, or isn't SOCKS5"));
+    }
+    let mut request = vec![5, 1, 0, 1];
+    request.extend_from_slice(&ip.octets());
+    request.extend_from_slice(&port.to_be_bytes());
+    s.write_all(&request)?;
+    s.set_read_timeout(Some(left()))?;
+    let mut reply = [0u8; 2];
+    let state = match s.read_exact(&mut reply) {
+        Ok(()) => match reply[1] {
+            0 => PortState::Open,
+            5 => PortState::Closed,
+            // General failure, refused by its rules, net or host unreachable, TTL expired
+            1..=4 | 6 => PortState::Filtered,
+            code => return Err(unusable(&format!("reply code {code}"))),
+        },
+        Err(e) => match e.kind() {
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut => PortState::Filtered,
+            _ => return Err(e),
+        },
+    };
+    let reply = Reply {
+        seq: 0,
+        bytes: 0,
+        rtt: start.elapsed(),
+        sent_at: Some(sent_at),
+        clock_offset_ms: None,
+    };
+    Ok((state, reply))
+}
+
+pub(crate) fn probe(ip: Ipv4Addr, opts: &ProbeOptions) -> Result<Outcome, Error> {
+    let mut ports = Vec::with_capacity(opts.ports.len());
+    let mut replies = Vec::new();
+    for chunk in opts.ports.chunks(PARALLEL) {
+        let states: Vec<io::Result<(PortState, Reply)>> = thread::scope(|s| {
+            let handles: Vec<_> = chunk
+                .iter()
+                .map(|&port| {
+                    let connect_timeout = opts.connect_timeout.unwrap_or(opts.timeout);
+                    let response_timeout = opts.response_timeout.unwrap_or(opts.timeout);
+                    s.spawn(move || match opts.proxy {
+                        Some(proxy) => {
+                            connect_via(proxy, ip, port, connect_timeout, response_timeout)
+                        }
+                        None => connect(ip, port, connect_timeout),
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("connect thread panicked"))
+                .collect()
+        });
+        for (&port, state) in chunk.iter().zip(states) {
+            let (state, reply) = state?;
+            if state != PortState::Filtered {
+                replies.push(Reply {
+                    seq: ports.len() as u32,
+                    ..reply
+                });
+            }
+            ports.push((port, state));
+        }
+    }
+
+    let sent = ports.len() as u32;
+    Ok(Outcome {
+        up: !replies.is_empty(),
+        sent,
+        stats: Stats::from_replies(sent, &replies),
+        replies,
+        icmp: None,
+        corrupted: 0,
+        frag_needed: 0,
+        next_hop_mtu: None,
+        ports,
+    })
+}
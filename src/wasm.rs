@@ -0,0 +1,236 @@
+use std::{
+    fs, io,
+    io::{Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpStream, UdpSocket},
+    path::Path,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use wasmi::{Caller, Config, Engine, Extern, Linker, Module, Store, TypedFunc};
+
+use crate::{Deadline, Error, Outcome, Probe, ProbeOptions, ProbeOutcome, Reply, Stats, Target};
+
+/* -------------------- WASM probes (--probe wasm:<file>) -------------------- */
+
+// The module exports `probe(addr: i32, timeout_ms: i32) -> i32`, called once
+// per attempt with the target's IPv4 address as a big-endian u32. It returns
+// 1 for an answer, 0 for none, and anything negative when it couldn't check.
+// Its only way out is through the imports below, and those only reach the
+// target:
+//
+//   ipchk.udp(port, req, req_len, resp, resp_cap, timeout_ms) -> i32
+//   ipchk.tcp(port, req, req_len, resp, resp_cap, timeout_ms) -> i32
+//
+// udp sends one datagram and waits for one back; tcp connects, sends `req`
+// if there is any, and reads once if `resp_cap` isn't 0. Both return the
+// bytes received, or NO_ANSWER, REFUSED or FAILED. Buffers live in the
+// module's exported `memory`.
+
+const NO_ANSWER: i32 = -1; // timed out
+const REFUSED: i32 = -2; // reset, or an ICMP unreachable
+const FAILED: i32 = -3; // bad buffer or port, or any other socket error
+
+// Instructions per attempt, so a runaway loop traps instead of hanging
+const FUEL: u64 = 50_000_000;
+
+// A module instantiated for one target, and its probe export
+type Instance = (Store<Host>, TypedFunc<(i32, i32), i32>);
+
+struct Host {
+    addr: Ipv4Addr,
+    deadline: Deadline,
+}
+
+pub struct WasmProbe {
+    name: String,
+    engine: Engine,
+    module: Module,
+    opts: ProbeOptions, // timeout, count and interval
+}
+
+impl WasmProbe {
+    pub fn load(path: &Path, opts: &ProbeOptions) -> Result<Self, Error> {
+        let bytes = fs::read(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        // wasmparser's messages can span lines
+        let invalid = |e: &dyn std::fmt::Display| Error::Parse {
+            input: format!(
+                "{}: {}",
+                path.display(),
+                e.to_string()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            expected: "WASM probe module",
+        };
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, bytes).map_err(|e| invalid(&e))?;
+        let probe = WasmProbe {
+            name: format!("wasm:{}", path.display()),
+            engine,
+            module,
+            opts: opts.clone(),
+        };
+        // Missing imports or a mistyped export show up now, not per target
+        probe
+            .instantiate(Ipv4Addr::UNSPECIFIED, Deadline::after(Duration::ZERO))
+            .map_err(|e| invalid(&e))?;
+        Ok(probe)
+    }
+
+    fn instantiate(&self, addr: Ipv4Addr, deadline: Deadline) -> Result<Instance, wasmi::Error> {
+        let mut store = Store::new(&self.engine, Host { addr, deadline });
+        let mut linker = Linker::<Host>::new(&self.engine);
+        linker.func_wrap(
+            "ipchk",
+            "udp",
+            |caller: Caller<'_, Host>, port, req, req_len, resp, cap, ms| {
+                exchange(caller, Transport::Udp, [port, req, req_len, resp, cap, ms])
+            },
+        )?;
+        linker.func_wrap(
+            "ipchk",
+            "tcp",
+            |caller: Caller<'_, Host>, port, req, req_len, resp, cap, ms| {
+                exchange(caller, Transport::Tcp, [port, req, req_len, resp, cap, ms])
+            },
+        )?;
+        let instance = linker.instantiate_and_start(&mut store, &self.module)?;
+        let probe = instance
+            .get_typed_func::<(i32, i32), i32>(&store, "probe")
+            .map_err(|_| wasmi::Error::new("no export probe(i32, i32) -> i32"))?;
+        Ok((store, probe))
+    }
+}
+
+impl Probe for WasmProbe {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn probe(&self, target: &Target, deadline: Deadline) -> ProbeOutcome {
+        let failed =
+            |e: &dyn std::fmt::Display| Error::Io(io::Error::other(format!("{}: {e}", self.name)));
+        let (mut store, probe) = self
+            .instantiate(target.addr, deadline)
+            .map_err(|e| failed(&e))?;
+        let timeout_ms = self.opts.timeout.as_millis().min(i32::MAX as u128) as i32;
+        let sent = self.opts.count.max(1);
+        let mut replies = Vec::new();
+        for seq in 0..sent {
+            if seq > 0
+                && let Some(pause) = self.opts.interval
+            {
+                thread::sleep(pause);
+            }
+            store.set_fuel(FUEL).map_err(|e| failed(&e))?;
+            let (sent_at, start) = (SystemTime::now(), Instant::now());
+            match probe.call(&mut store, (u32::from(target.addr) as i32, timeout_ms)) {
+                Ok(1) => replies.push(Reply {
+                    seq,
+                    bytes: 0,
+                    rtt: start.elapsed(),
+                    sent_at: Some(sent_at),
+                    clock_offset_ms: None,
+                }),
+                Ok(0) => {}
+                Ok(n) => return Err(failed(&format_args!("probe returned {n}"))),
+                Err(e) => return Err(failed(&e)),
+            }
+        }
+        Ok(Outcome {
+            up: !replies.is_empty(),
+            sent,
+            stats: Stats::from_replies(sent, &replies),
+            replies,
+            ..Outcome::default()
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Transport {
+    Udp,
+    Tcp,
+}
+
+// The ipchk.udp and ipchk.tcp imports
+fn exchange(mut caller: Caller<'_, Host>, transport: Transport, args: [i32; 6]) -> i32 {
+    let [port, req, req_len, resp, cap, ms] = args;
+    let (Ok(port), Ok(req), Ok(req_len), Ok(resp), Ok(cap), Ok(ms)) = (
+        u16::try_from(port),
+        usize::try_from(req),
+        usize::try_from(req_len),
+        usize::try_from(resp),
+        usize::try_from(cap),
+        u64::try_from(ms),
+    ) else {
+        return FAILED;
+    };
+    let Some(memory) = caller.get_export("memory").and_then(Extern::into_memory) else {
+        return FAILED;
+    };
+    let mut request = vec![0u8; req_len];
+    if port == 0 || memory.read(&caller, req, &mut request).is_err() {
+        return FAILED;
+    }
+    let host = caller.data();
+    let timeout = Duration::from_millis(ms).min(host.deadline.remaining());
+    if timeout.is_zero() {
+        return NO_ANSWER;
+    }
+    let to = SocketAddr::from((host.addr, port));
+    let mut answer = vec![0u8; cap];
+    let got = match transport {
+        Transport::Udp => udp(to, &request, &mut answer, timeout),
+        Transport::Tcp => tcp(to, &request, &mut answer, timeout),
+    };
+    match got {
+        Ok(n) => match memory.write(&mut caller, resp, &answer[..n]) {
+            Ok(()) => n as i32,
+            Err(_) => FAILED,
+        },
+        Err(e) => match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => NO_ANSWER,
+            io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::HostUnreachable
+            | io::ErrorKind::NetworkUnreachable => REFUSED,
+            _ => FAILED,
+        },
+    }
+}
+
+fn udp(to: SocketAddr, request: &[u8], answer: &mut [u8], timeout: Duration) -> io::Result<usize> {
+    let sock = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    // Connected, so only the target's datagrams (and its ICMP errors) arrive
+    sock.connect(to)?;
+    sock.set_read_timeout(Some(timeout))?;
+    sock.send(request)?;
+    let mut buf = vec![0u8; 65_535];
+    let n = sock.recv(&mut buf)?;
+    let n = n.min(answer.len());
+    answer[..n].copy_from_slice(&buf[..n]);
+    Ok(n)
+}
+
+fn tcp(to: SocketAddr, request: &[u8], answer: &mut [u8], timeout: Duration) -> io::Result<usize> {
+    let started = Instant::now();
+    let mut stream = TcpStream::connect_timeout(&to, timeout)?;
+    let left = timeout
+        .saturating_sub(started.elapsed())
+        .max(Duration::from_millis(1));
+    stream.set_read_timeout(Some(left))?;
+    stream.set_write_timeout(Some(left))?;
+    if !request.is_empty() {
+        stream.write_all(request)?;
+    }
+    if answer.is_empty() {
+        return Ok(0);
+    }
+    stream.read(answer)
+}